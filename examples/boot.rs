@@ -0,0 +1,150 @@
+// boot.rs
+//
+// The example kernel, minus the kernel: what early boot code does with
+// the MB1 mmap the bootloader leaves behind, before there is a heap.
+//
+//   1. parse and sanitize the entries into a fixed-size MemoryMap
+//   2. normalize it
+//   3. print the map over serial
+//   4. bump-allocate the first frames (boot page tables, stacks)
+//
+// This is the host half only. kmain sticks to core and the crate's
+// no-heap API, and the crate builds with `--no-default-features
+// --features fmt` (`just check-no-std`), but no freestanding Multiboot1
+// or UEFI kernel around it exists yet: that needs a bare-metal target,
+// -Zbuild-std and QEMU, none of which the test gates have. Until then
+// `cargo test` runs kmain over the captured vectors, and
+//
+//   cargo run --example boot -- mmap.bin
+//
+// runs it over an mmap dumped from a real boot.
+
+use core::fmt::{self, Write};
+
+use mb1_memmap::frames::BumpAllocator;
+use mb1_memmap::map::MemoryMap;
+use mb1_memmap::raw::{sanitize, Mb1MmapIter};
+use mb1_memmap::table::{MapSummary, MapTable};
+use mb1_memmap::vectors;
+
+/// Frames handed out before the real allocator takes over.
+const BOOT_FRAMES: usize = 16;
+
+/// Everything between the bootloader jumping in and the frame allocator
+/// being up. Bad entries end the map early instead of ending the boot.
+fn kmain(mmap: &[u8], serial: &mut impl Write) -> fmt::Result {
+    let mut map = MemoryMap::<64>::new();
+    for entry in Mb1MmapIter::new(mmap) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                writeln!(serial, "mmap: {e:?}, ignoring the rest")?;
+                break;
+            }
+        };
+        if let Some(region) = sanitize(entry) {
+            if map.push(region).is_err() {
+                writeln!(
+                    serial,
+                    "mmap: over {} entries, ignoring the rest",
+                    map.capacity()
+                )?;
+                break;
+            }
+        }
+    }
+    map.normalize();
+
+    write!(serial, "{}", MapTable::new(map.as_slice()))?;
+    writeln!(serial, "{}", MapSummary::new(map.as_slice()))?;
+
+    let mut bump = BumpAllocator::new(map.as_slice());
+    for _ in 0..BOOT_FRAMES {
+        match bump.allocate() {
+            Some(frame) => writeln!(serial, "frame {:#x}", frame.0)?,
+            None => {
+                writeln!(serial, "out of memory")?;
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    let dumped = std::env::args()
+        .nth(1)
+        .map(|path| match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) => panic!("{path}: {e}"),
+        });
+    let mut serial = String::new();
+    kmain(
+        dumped.as_deref().unwrap_or(vectors::MB1_PC.bytes),
+        &mut serial,
+    )
+    .unwrap();
+    print!("{serial}");
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use mb1_memmap::raw::MemRegion;
+
+    use super::*;
+
+    /// What kmain printed over serial for `mmap`, and the frames it
+    /// handed out.
+    fn boot(mmap: &[u8]) -> (String, Vec<u64>) {
+        let mut serial = String::new();
+        kmain(mmap, &mut serial).unwrap();
+        let frames = serial
+            .lines()
+            .filter_map(|l| l.strip_prefix("frame 0x"))
+            .map(|f| u64::from_str_radix(f, 16).unwrap())
+            .collect();
+        (serial, frames)
+    }
+
+    fn usable(regions: &[MemRegion], frame: u64) -> bool {
+        regions
+            .iter()
+            .any(|r| r.region_kind().is_usable() && r.start <= frame && frame + 0x1000 <= r.end())
+    }
+
+    #[test]
+    fn boots_on_every_captured_mb1_map() {
+        for v in vectors::MB1 {
+            let (serial, frames) = boot(v.bytes);
+            assert!(
+                serial.contains(&MapSummary::new(v.regions).to_string()),
+                "{}: {serial}",
+                v.name
+            );
+            let usable_frames = mb1_memmap::frames::usable_frame_count(v.regions);
+            pretty_assertions::assert_eq!(
+                frames.len() as u64,
+                usable_frames.min(BOOT_FRAMES as u64),
+                "{}",
+                v.name
+            );
+            assert!(
+                frames.iter().all(|&f| usable(v.regions, f)),
+                "{}: {frames:x?}",
+                v.name
+            );
+        }
+    }
+
+    #[test]
+    fn truncated_map_still_boots_on_what_parsed() {
+        let mmap = vectors::MB1_PC.bytes;
+        let (serial, frames) = boot(&mmap[..mmap.len() - 3]);
+        assert!(serial.starts_with("mmap: "), "{serial}");
+        assert!(!frames.is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+// adapters.rs
+//
+// The parse -> sanitize -> keep usable pipeline as iterator adapters, so
+// it reads left to right and never collects:
+//
+//   for region in Mb1MmapIter::new(buf).sanitized(SanitizePolicy::default()).usable() {
+//       let region = region?;
+//       ...
+//   }
+//
+// Works on any parser whose entries convert into RawEntry (MB1, MB2,
+// E820, coreboot, PVH). Framing errors pass through untouched, in the
+// same place, so the parser's "one Err, then stop" still holds.
+
+use crate::raw::{sanitize_with, MemRegion, RawEntry, SanitizePolicy};
+
+/// Adapters for iterators over parser output. Import it and they appear
+/// on every iterator whose items fit.
+pub trait RegionIterExt: Iterator + Sized {
+    /// Sanitize each entry with `policy`, dropping what it rejects.
+    fn sanitized<T, E>(self, policy: SanitizePolicy) -> Sanitized<Self>
+    where
+        Self: Iterator<Item = Result<T, E>>,
+        T: Into<RawEntry>,
+    {
+        Sanitized {
+            inner: self,
+            policy,
+        }
+    }
+
+    /// Keep only usable regions (and errors).
+    fn usable<E>(self) -> Usable<Self>
+    where
+        Self: Iterator<Item = Result<MemRegion, E>>,
+    {
+        Usable { inner: self }
+    }
+}
+
+impl<I: Iterator> RegionIterExt for I {}
+
+/// Iterator returned by [`RegionIterExt::sanitized`].
+#[derive(Clone, Debug)]
+pub struct Sanitized<I> {
+    inner: I,
+    policy: SanitizePolicy,
+}
+
+impl<I, T, E> Iterator for Sanitized<I>
+where
+    I: Iterator<Item = Result<T, E>>,
+    T: Into<RawEntry>,
+{
+    type Item = Result<MemRegion, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok(entry) => {
+                    if let Some(region) = sanitize_with(entry.into(), &self.policy) {
+                        return Some(Ok(region));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Iterator returned by [`RegionIterExt::usable`].
+#[derive(Clone, Debug)]
+pub struct Usable<I> {
+    inner: I,
+}
+
+impl<I, E> Iterator for Usable<I>
+where
+    I: Iterator<Item = Result<MemRegion, E>>,
+{
+    type Item = Result<MemRegion, E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .by_ref()
+            .find(|r| r.as_ref().map_or(true, |r| r.region_kind().is_usable()))
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::kind;
+    use crate::raw::e820::E820Iter;
+    use crate::raw::{push_entry, raw, Mb1MmapIter, MmapError};
+    use crate::tests::common::init;
+    use crate::vectors::{E820_PC, MB1_PC};
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    #[test]
+    fn mb1_pipeline_yields_usable_regions() {
+        init();
+        let usable: Result<Vec<_>, _> = Mb1MmapIter::new(MB1_PC.bytes)
+            .sanitized(SanitizePolicy::default())
+            .usable()
+            .collect();
+        let expected: Vec<_> = MB1_PC
+            .regions
+            .iter()
+            .copied()
+            .filter(|r| r.kind == kind::USABLE)
+            .collect();
+        pretty_assertions::assert_eq!(usable, Ok(expected));
+    }
+
+    #[test]
+    fn policy_applies_and_errors_pass_through() {
+        let mut buf = Vec::new();
+        push_entry(&mut buf, raw(0, 0, 1));
+        push_entry(&mut buf, raw(0x1000, 0x1000, kind::ACPI_RECLAIMABLE));
+        push_entry(&mut buf, raw(0x2000, 0x1000, kind::RESERVED));
+        buf.extend_from_slice(&[0; 4]);
+
+        let policy = SanitizePolicy::default().usable_kind(kind::ACPI_RECLAIMABLE);
+        let got: Vec<_> = Mb1MmapIter::new(&buf).sanitized(policy).usable().collect();
+        pretty_assertions::assert_eq!(
+            got,
+            vec![
+                Ok(region(0x1000, 0x1000, kind::USABLE)),
+                Err(MmapError::SizeTooSmall { size: 0 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn works_on_other_parsers() {
+        let it = E820Iter::new(E820_PC.bytes, 20).unwrap();
+        let n = it.sanitized(SanitizePolicy::default()).usable().count();
+        let expected = E820_PC
+            .regions
+            .iter()
+            .filter(|r| r.kind == kind::USABLE)
+            .count();
+        pretty_assertions::assert_eq!(n, expected);
+    }
+}
@@ -0,0 +1,232 @@
+// blob.rs
+//
+// Every boot-info parser does the same three things with untrusted bytes:
+// read a little-endian field at some offset, carve out a sub-window for a
+// nested structure, and move a cursor forward. Each of those is one
+// missed bounds check away from reading past the table.
+//
+// TableBlob does them once. Reads are relative to the cursor and return
+// None instead of panicking; windows borrow from the original bytes, so
+// what you parse out can outlive the blob itself but not the boot info.
+
+/// Byte order of a firmware table. Boot protocols on x86 and Arm are
+/// little-endian; MIPS and PowerPC firmware may not be.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+impl Endian {
+    pub fn u32_bytes(self, v: u32) -> [u8; 4] {
+        match self {
+            Endian::Little => v.to_le_bytes(),
+            Endian::Big => v.to_be_bytes(),
+        }
+    }
+
+    pub fn u64_bytes(self, v: u64) -> [u8; 8] {
+        match self {
+            Endian::Little => v.to_le_bytes(),
+            Endian::Big => v.to_be_bytes(),
+        }
+    }
+}
+
+/// Borrowed boot-info bytes plus a cursor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TableBlob<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> TableBlob<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        TableBlob { bytes, pos: 0 }
+    }
+
+    /// The kernel-side entry point: boot info arrives as (address, length).
+    ///
+    /// # Safety
+    /// `ptr` must be readable for `len` bytes and those bytes must not be
+    /// written for `'a`. A `len` of 0 accepts any pointer, including null.
+    /// Nothing past `len` is ever read, whatever the table contents claim.
+    pub unsafe fn from_raw_parts(ptr: *const u8, len: usize) -> Self {
+        if len == 0 {
+            return TableBlob::new(&[]);
+        }
+        debug_assert!(!ptr.is_null(), "null boot info pointer with len {len}");
+        // No real allocation is larger; a bigger len is a corrupt length field.
+        assert!(
+            len <= isize::MAX as usize,
+            "boot info length {len} too large"
+        );
+        TableBlob::new(core::slice::from_raw_parts(ptr, len))
+    }
+
+    /// Cursor position from the start of the blob.
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// Bytes from the cursor to the end.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.pos..]
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    /// `len` bytes starting `at` bytes past the cursor.
+    pub fn window(&self, at: usize, len: usize) -> Option<&'a [u8]> {
+        let start = self.pos.checked_add(at)?;
+        let end = start.checked_add(len)?;
+        self.bytes.get(start..end)
+    }
+
+    /// A new blob over [`window`](Self::window), cursor at its start.
+    pub fn sub(&self, at: usize, len: usize) -> Option<TableBlob<'a>> {
+        self.window(at, len).map(TableBlob::new)
+    }
+
+    pub fn u16_at(&self, at: usize) -> Option<u16> {
+        self.array_at(at).map(u16::from_le_bytes)
+    }
+
+    pub fn u32_at(&self, at: usize) -> Option<u32> {
+        self.array_at(at).map(u32::from_le_bytes)
+    }
+
+    pub fn u64_at(&self, at: usize) -> Option<u64> {
+        self.array_at(at).map(u64::from_le_bytes)
+    }
+
+    /// Big-endian read (device tree, ACPI-adjacent firmware tables).
+    pub fn u32_be_at(&self, at: usize) -> Option<u32> {
+        self.array_at(at).map(u32::from_be_bytes)
+    }
+
+    pub fn u64_be_at(&self, at: usize) -> Option<u64> {
+        self.array_at(at).map(u64::from_be_bytes)
+    }
+
+    /// Read in a byte order chosen at runtime.
+    pub fn u32_in(&self, at: usize, endian: Endian) -> Option<u32> {
+        match endian {
+            Endian::Little => self.u32_at(at),
+            Endian::Big => self.u32_be_at(at),
+        }
+    }
+
+    pub fn u64_in(&self, at: usize, endian: Endian) -> Option<u64> {
+        match endian {
+            Endian::Little => self.u64_at(at),
+            Endian::Big => self.u64_be_at(at),
+        }
+    }
+
+    /// Move the cursor forward `n` bytes. Fails (cursor unchanged) if that
+    /// would pass the end.
+    pub fn advance(&mut self, n: usize) -> Option<()> {
+        let pos = self.pos.checked_add(n)?;
+        if pos > self.bytes.len() {
+            return None;
+        }
+        self.pos = pos;
+        Some(())
+    }
+
+    /// Move the cursor to the end; nothing more will be read.
+    pub fn finish(&mut self) {
+        self.pos = self.bytes.len();
+    }
+
+    /// Forget everything more than `n` bytes past the cursor.
+    pub fn limit(self, n: usize) -> Self {
+        let end = self.pos.saturating_add(n).min(self.bytes.len());
+        TableBlob {
+            bytes: &self.bytes[..end],
+            pos: self.pos,
+        }
+    }
+
+    fn array_at<const N: usize>(&self, at: usize) -> Option<[u8; N]> {
+        self.window(at, N)?.try_into().ok()
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    #[test]
+    fn reads_are_little_endian_and_relative_to_cursor() {
+        init();
+        let bytes = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99];
+        let mut blob = TableBlob::new(&bytes);
+        pretty_assertions::assert_eq!(blob.u32_at(0), Some(0x4433_2211));
+        pretty_assertions::assert_eq!(blob.u64_at(1), Some(0x9988_7766_5544_3322));
+        pretty_assertions::assert_eq!(blob.u32_be_at(0), Some(0x1122_3344));
+        pretty_assertions::assert_eq!(blob.u64_be_at(1), Some(0x2233_4455_6677_8899));
+
+        blob.advance(4).unwrap();
+        pretty_assertions::assert_eq!(blob.offset(), 4);
+        pretty_assertions::assert_eq!(blob.u16_at(0), Some(0x6655));
+        pretty_assertions::assert_eq!(blob.remaining(), &bytes[4..]);
+    }
+
+    #[test]
+    fn out_of_bounds_reads_are_none() {
+        let bytes = [0u8; 8];
+        let blob = TableBlob::new(&bytes);
+        pretty_assertions::assert_eq!(blob.u64_at(1), None);
+        pretty_assertions::assert_eq!(blob.u32_at(usize::MAX), None);
+        pretty_assertions::assert_eq!(blob.window(8, 0), Some(&[][..]));
+        pretty_assertions::assert_eq!(blob.window(8, 1), None);
+    }
+
+    #[test]
+    fn advance_past_end_leaves_cursor_alone() {
+        let bytes = [0u8; 8];
+        let mut blob = TableBlob::new(&bytes);
+        blob.advance(6).unwrap();
+        pretty_assertions::assert_eq!(blob.advance(3), None);
+        pretty_assertions::assert_eq!(blob.offset(), 6);
+        blob.finish();
+        assert!(blob.is_exhausted());
+    }
+
+    #[test]
+    fn limit_and_sub_shrink_the_view() {
+        let bytes: Vec<u8> = (0..16).collect();
+        let mut blob = TableBlob::new(&bytes);
+        blob.advance(2).unwrap();
+
+        let limited = blob.limit(4);
+        pretty_assertions::assert_eq!(limited.remaining(), &[2, 3, 4, 5]);
+        pretty_assertions::assert_eq!(limited.u32_at(1), None);
+
+        let sub = blob.sub(4, 2).unwrap();
+        pretty_assertions::assert_eq!(sub.offset(), 0);
+        pretty_assertions::assert_eq!(sub.remaining(), &[6, 7]);
+    }
+
+    #[test]
+    fn from_raw_parts_is_bounded_by_len() {
+        let bytes = [1u8, 0, 0, 0, 2, 0, 0, 0];
+        let blob = unsafe { TableBlob::from_raw_parts(bytes.as_ptr(), 4) };
+        pretty_assertions::assert_eq!(blob.u32_at(0), Some(1));
+        pretty_assertions::assert_eq!(blob.u32_at(4), None);
+
+        let empty = unsafe { TableBlob::from_raw_parts(core::ptr::null(), 0) };
+        assert!(empty.is_exhausted());
+    }
+}
@@ -0,0 +1,156 @@
+// capabilities.rs
+//
+// Which parsers and features this build of the crate has. A loader and
+// a kernel built from different feature sets can disagree about what a
+// handed-over map may contain; comparing capabilities() at boot turns
+// that into one clear error instead of a silently empty map:
+//
+//   let missing = loader_caps.missing(capabilities());
+//   assert!(missing.is_empty(), "kernel lacks {:#x}", missing.bits());
+//
+// Bit positions are part of the public API: they only ever get added.
+// Formats live in the low 16 bits, crate features in the high 16.
+
+use core::ops::BitOr;
+
+/// A set of capability bits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    // Formats.
+    pub const MB1: Capabilities = Capabilities(1 << 0);
+    pub const MB2: Capabilities = Capabilities(1 << 1);
+    pub const E820: Capabilities = Capabilities(1 << 2);
+    pub const UEFI: Capabilities = Capabilities(1 << 3);
+    pub const SRAT: Capabilities = Capabilities(1 << 4);
+    pub const COREBOOT: Capabilities = Capabilities(1 << 5);
+    pub const FDT: Capabilities = Capabilities(1 << 6);
+    pub const PVH: Capabilities = Capabilities(1 << 7);
+    /// /proc/iomem (hosted builds only).
+    pub const PROC_IOMEM: Capabilities = Capabilities(1 << 8);
+
+    // Crate features.
+    pub const STD: Capabilities = Capabilities(1 << 16);
+    pub const ALLOC: Capabilities = Capabilities(1 << 17);
+    pub const FMT: Capabilities = Capabilities(1 << 18);
+    pub const MEMTEST: Capabilities = Capabilities(1 << 19);
+    pub const FFI: Capabilities = Capabilities(1 << 20);
+    pub const TRACING: Capabilities = Capabilities(1 << 21);
+    /// x86_64 crate FrameAllocator impls.
+    pub const X86_64: Capabilities = Capabilities(1 << 22);
+
+    pub const fn empty() -> Self {
+        Capabilities(0)
+    }
+
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Keeps bits this build has no name for, so a newer peer's value
+    /// round-trips.
+    pub const fn from_bits_retain(bits: u32) -> Self {
+        Capabilities(bits)
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(self, other: Capabilities) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: Capabilities) -> Self {
+        Capabilities(self.0 | other.0)
+    }
+
+    /// The bits of `self` that `other` lacks.
+    pub const fn missing(self, other: Capabilities) -> Self {
+        Capabilities(self.0 & !other.0)
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Capabilities;
+
+    fn bitor(self, rhs: Capabilities) -> Capabilities {
+        self.union(rhs)
+    }
+}
+
+// Add `bit` if the feature behind it is compiled in.
+const fn with(caps: Capabilities, on: bool, bit: Capabilities) -> Capabilities {
+    if on {
+        caps.union(bit)
+    } else {
+        caps
+    }
+}
+
+/// What this build of the crate supports.
+pub const fn capabilities() -> Capabilities {
+    let caps = Capabilities::MB1
+        .union(Capabilities::MB2)
+        .union(Capabilities::E820)
+        .union(Capabilities::UEFI)
+        .union(Capabilities::SRAT)
+        .union(Capabilities::COREBOOT);
+    let caps = with(caps, cfg!(feature = "fdt"), Capabilities::FDT);
+    let caps = with(caps, cfg!(feature = "pvh"), Capabilities::PVH);
+    let caps = with(caps, cfg!(feature = "std"), Capabilities::PROC_IOMEM);
+    let caps = with(caps, cfg!(feature = "std"), Capabilities::STD);
+    let caps = with(caps, cfg!(feature = "alloc"), Capabilities::ALLOC);
+    let caps = with(caps, cfg!(feature = "fmt"), Capabilities::FMT);
+    let caps = with(caps, cfg!(feature = "memtest"), Capabilities::MEMTEST);
+    let caps = with(caps, cfg!(feature = "ffi"), Capabilities::FFI);
+    let caps = with(caps, cfg!(feature = "tracing"), Capabilities::TRACING);
+    with(caps, cfg!(feature = "x86_64"), Capabilities::X86_64)
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    #[test]
+    fn default_build_has_every_format() {
+        init();
+        let caps = capabilities();
+        for format in [
+            Capabilities::MB1,
+            Capabilities::MB2,
+            Capabilities::E820,
+            Capabilities::UEFI,
+            Capabilities::SRAT,
+            Capabilities::COREBOOT,
+            Capabilities::FDT,
+            Capabilities::PVH,
+        ] {
+            assert!(caps.contains(format), "{format:?}");
+        }
+        pretty_assertions::assert_eq!(
+            caps.contains(Capabilities::TRACING),
+            cfg!(feature = "tracing")
+        );
+    }
+
+    #[test]
+    fn missing_reports_what_the_peer_lacks() {
+        let loader =
+            Capabilities::MB2 | Capabilities::FDT | Capabilities::from_bits_retain(1 << 15);
+        let kernel = Capabilities::MB1 | Capabilities::MB2;
+        pretty_assertions::assert_eq!(
+            loader.missing(kernel),
+            Capabilities::FDT | Capabilities::from_bits_retain(1 << 15)
+        );
+        assert!(kernel.missing(kernel).is_empty());
+        pretty_assertions::assert_eq!(Capabilities::empty().bits(), 0);
+    }
+}
@@ -0,0 +1,471 @@
+// compose.rs
+//
+// Firmware gives you one opinion about memory. Sometimes you have others:
+//
+//   - the operator passed `memmap=64M$0x10000000` on the command line
+//   - the kernel carves out its own image
+//   - a device tree says something the firmware did not
+//
+// Composing lays those opinions on top of the firmware map, last writer
+// wins, byte by byte. The changelog variant also tells you *what* each
+// override actually changed, so an operator can confirm it took effect.
+//
+// MapSourceChain goes one step further and keeps, for every byte of the
+// final map, which layer decided its kind, so "why is 0x3f000000
+// reserved?" has an answer long after boot.
+
+use alloc::vec::Vec;
+
+use crate::raw::MemRegion;
+use crate::region::canonicalize;
+use crate::rejection::{RegionRejection, RejectionReason};
+
+/// Who said a range has a given kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapSource {
+    Firmware,
+    CommandLine,
+    DeviceTree,
+    KernelCarveOut,
+    /// Frames that failed an early RAM test.
+    Memtest,
+}
+
+/// One region that should replace whatever the base map says about its bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Override {
+    pub region: MemRegion,
+    pub source: MapSource,
+}
+
+impl Override {
+    /// Parse one Linux-style `memmap=` value (without the `memmap=` prefix).
+    ///
+    /// Supported forms (sizes/addresses accept K/M/G suffixes and 0x):
+    /// - `nn@ss`  usable     (kind 1)
+    /// - `nn$ss`  reserved   (kind 2)
+    /// - `nn#ss`  ACPI data  (kind 3)
+    /// - `nn!ss`  protected  (kind 12, persistent memory)
+    pub fn parse_memmap(arg: &str) -> Option<Override> {
+        let (split, kind) = arg.char_indices().find_map(|(i, c)| match c {
+            '@' => Some((i, 1)),
+            '$' => Some((i, 2)),
+            '#' => Some((i, 3)),
+            '!' => Some((i, 12)),
+            _ => None,
+        })?;
+        let len = parse_size(&arg[..split])?;
+        let start = parse_size(&arg[split + 1..])?;
+        Some(Override {
+            region: MemRegion { start, len, kind },
+            source: MapSource::CommandLine,
+        })
+    }
+}
+
+fn parse_size(s: &str) -> Option<u64> {
+    let (digits, shift) = match s.as_bytes().last()? {
+        b'K' | b'k' => (&s[..s.len() - 1], 10),
+        b'M' | b'm' => (&s[..s.len() - 1], 20),
+        b'G' | b'g' => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<u64>().ok()?,
+    };
+    value.checked_mul(1u64 << shift)
+}
+
+/// One range whose kind differs from (or was re-asserted over) the base map.
+///
+/// `from` is what the base map said (`None` if the range was a hole).
+/// `from == Some(to)` means the override matched the firmware already and
+/// had no effect; it is still reported so nothing is silently swallowed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub start: u64,
+    pub len: u64,
+    pub from: Option<u32>,
+    pub to: u32,
+    pub source: MapSource,
+    /// Index into the `overrides` slice of the override that won this range.
+    pub override_index: usize,
+}
+
+impl Change {
+    pub fn took_effect(&self) -> bool {
+        self.from != Some(self.to)
+    }
+
+    /// The base claim this change replaced, in the shared rejection vocabulary.
+    /// `None` if the override filled a hole or changed nothing.
+    pub fn as_rejection(&self) -> Option<RegionRejection> {
+        match self.from {
+            Some(kind) if kind != self.to => Some(RegionRejection {
+                region: MemRegion {
+                    start: self.start,
+                    len: self.len,
+                    kind,
+                },
+                reason: RejectionReason::OverlapLoser,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Lay `overrides` over `base` (later overrides win) and return the result
+/// sorted by start with adjacent same-kind ranges merged.
+pub fn compose(base: &[MemRegion], overrides: &[Override]) -> Vec<MemRegion> {
+    compose_with_changelog(base, overrides).0
+}
+
+/// Like [`compose`], but also report which base ranges each override replaced.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(base = base.len(), overrides = overrides.len())
+    )
+)]
+pub fn compose_with_changelog(
+    base: &[MemRegion],
+    overrides: &[Override],
+) -> (Vec<MemRegion>, Vec<Change>) {
+    // Every start/end is a point where ownership can change.
+    let mut points: Vec<u64> = Vec::new();
+    for r in base.iter().chain(overrides.iter().map(|o| &o.region)) {
+        if r.len > 0 {
+            points.push(r.start);
+            points.push(r.end());
+        }
+    }
+    points.sort_unstable();
+    points.dedup();
+
+    let mut out: Vec<MemRegion> = Vec::new();
+    let mut changes: Vec<Change> = Vec::new();
+
+    for w in points.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        let covers = |r: &MemRegion| r.len > 0 && r.start <= a && r.end() >= b;
+
+        let base_kind = base.iter().rev().find(|r| covers(r)).map(|r| r.kind);
+        let winner = overrides
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, o)| covers(&o.region));
+
+        let kind = match (winner, base_kind) {
+            (Some((_, o)), _) => o.region.kind,
+            (None, Some(k)) => k,
+            (None, None) => continue,
+        };
+
+        match out.last_mut() {
+            Some(last) if last.end() == a && last.kind == kind => last.len += b - a,
+            _ => out.push(MemRegion {
+                start: a,
+                len: b - a,
+                kind,
+            }),
+        }
+
+        if let Some((idx, o)) = winner {
+            match changes.last_mut() {
+                Some(c)
+                    if c.start + c.len == a && c.override_index == idx && c.from == base_kind =>
+                {
+                    c.len += b - a
+                }
+                _ => changes.push(Change {
+                    start: a,
+                    len: b - a,
+                    from: base_kind,
+                    to: o.region.kind,
+                    source: o.source,
+                    override_index: idx,
+                }),
+            }
+        }
+    }
+
+    (out, changes)
+}
+
+// ============================================================
+// PROVENANCE
+// ============================================================
+
+/// A range of the final map and the layer that last decided its kind.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SourceSpan {
+    pub start: u64,
+    pub len: u64,
+    pub kind: u32,
+    pub source: MapSource,
+    /// Index of the layer (in push order) this range came from.
+    pub layer: usize,
+}
+
+impl SourceSpan {
+    pub fn end(&self) -> u64 {
+        self.start + self.len
+    }
+}
+
+/// Layers of opinions about memory, each tagged with where it came from.
+///
+/// Each layer is canonicalized on its own (overlaps inside one source are
+/// settled by kind precedence, partial usable frames dropped), then laid
+/// over the previous layers, last writer wins, as in [`compose`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MapSourceChain {
+    // Sorted, non-overlapping. Adjacent spans from the same layer with the
+    // same kind are merged.
+    spans: Vec<SourceSpan>,
+    layers: usize,
+}
+
+impl MapSourceChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lay `regions` from `source` over everything pushed so far.
+    pub fn push(&mut self, source: MapSource, regions: &[MemRegion]) -> &mut Self {
+        let layer = self.layers;
+        self.layers += 1;
+        let top = canonicalize(regions);
+
+        let mut points: Vec<u64> = Vec::new();
+        for (start, end) in self
+            .spans
+            .iter()
+            .map(|s| (s.start, s.end()))
+            .chain(top.iter().map(|r| (r.start, r.end())))
+        {
+            points.push(start);
+            points.push(end);
+        }
+        points.sort_unstable();
+        points.dedup();
+
+        let mut out: Vec<SourceSpan> = Vec::new();
+        for w in points.windows(2) {
+            let (a, b) = (w[0], w[1]);
+            let piece = match top.iter().find(|r| r.start <= a && r.end() >= b) {
+                Some(r) => (r.kind, source, layer),
+                None => match self.spans.iter().find(|s| s.start <= a && s.end() >= b) {
+                    Some(s) => (s.kind, s.source, s.layer),
+                    None => continue,
+                },
+            };
+            match out.last_mut() {
+                Some(last) if last.end() == a && (last.kind, last.source, last.layer) == piece => {
+                    last.len += b - a
+                }
+                _ => out.push(SourceSpan {
+                    start: a,
+                    len: b - a,
+                    kind: piece.0,
+                    source: piece.1,
+                    layer: piece.2,
+                }),
+            }
+        }
+        self.spans = out;
+        self
+    }
+
+    /// Who decided the kind of the byte at `addr`; `None` for holes.
+    pub fn provenance_at(&self, addr: u64) -> Option<&SourceSpan> {
+        let i = self.spans.partition_point(|s| s.end() <= addr);
+        self.spans.get(i).filter(|s| s.start <= addr)
+    }
+
+    /// Every range of the final map with its provenance, by address.
+    pub fn spans(&self) -> &[SourceSpan] {
+        &self.spans
+    }
+
+    /// The final map, with provenance dropped and same-kind neighbours merged.
+    pub fn to_map(&self) -> Vec<MemRegion> {
+        let mut out: Vec<MemRegion> = Vec::new();
+        for s in &self.spans {
+            match out.last_mut() {
+                Some(last) if last.end() == s.start && last.kind == s.kind => last.len += s.len,
+                _ => out.push(MemRegion {
+                    start: s.start,
+                    len: s.len,
+                    kind: s.kind,
+                }),
+            }
+        }
+        out
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn cmdline(start: u64, len: u64, kind: u32) -> Override {
+        Override {
+            region: region(start, len, kind),
+            source: MapSource::CommandLine,
+        }
+    }
+
+    #[test]
+    fn no_overrides_returns_base_merged() {
+        init();
+        let base = [region(0, 0x1000, 1), region(0x1000, 0x1000, 1)];
+        pretty_assertions::assert_eq!(compose(&base, &[]), vec![region(0, 0x2000, 1)]);
+    }
+
+    #[test]
+    fn override_in_middle_splits_base() {
+        let base = [region(0, 0x10000, 1)];
+        let ov = [cmdline(0x4000, 0x2000, 2)];
+
+        let (map, log) = compose_with_changelog(&base, &ov);
+        pretty_assertions::assert_eq!(
+            map,
+            vec![
+                region(0, 0x4000, 1),
+                region(0x4000, 0x2000, 2),
+                region(0x6000, 0xA000, 1),
+            ]
+        );
+        pretty_assertions::assert_eq!(
+            log,
+            vec![Change {
+                start: 0x4000,
+                len: 0x2000,
+                from: Some(1),
+                to: 2,
+                source: MapSource::CommandLine,
+                override_index: 0,
+            }]
+        );
+        assert!(log[0].took_effect());
+        pretty_assertions::assert_eq!(
+            log[0].as_rejection(),
+            Some(RegionRejection {
+                region: region(0x4000, 0x2000, 1),
+                reason: RejectionReason::OverlapLoser,
+            })
+        );
+    }
+
+    #[test]
+    fn override_spanning_hole_reports_both_parts() {
+        let base = [region(0, 0x1000, 1), region(0x2000, 0x1000, 1)];
+        let ov = [cmdline(0x800, 0x2000, 2)];
+
+        let (map, log) = compose_with_changelog(&base, &ov);
+        pretty_assertions::assert_eq!(
+            map,
+            vec![
+                region(0, 0x800, 1),
+                region(0x800, 0x2000, 2),
+                region(0x2800, 0x800, 1)
+            ]
+        );
+        let froms: Vec<Option<u32>> = log.iter().map(|c| c.from).collect();
+        pretty_assertions::assert_eq!(froms, vec![Some(1), None, Some(1)]);
+    }
+
+    #[test]
+    fn later_override_wins() {
+        let base = [region(0, 0x4000, 1)];
+        let ov = [cmdline(0, 0x4000, 2), cmdline(0x1000, 0x1000, 3)];
+
+        let (map, log) = compose_with_changelog(&base, &ov);
+        pretty_assertions::assert_eq!(map[1], region(0x1000, 0x1000, 3));
+        pretty_assertions::assert_eq!(log[1].override_index, 1);
+    }
+
+    #[test]
+    fn redundant_override_is_reported_as_no_effect() {
+        let base = [region(0, 0x4000, 2)];
+        let (map, log) = compose_with_changelog(&base, &[cmdline(0x1000, 0x1000, 2)]);
+
+        pretty_assertions::assert_eq!(map, vec![region(0, 0x4000, 2)]);
+        pretty_assertions::assert_eq!(log.len(), 1);
+        assert!(!log[0].took_effect());
+        assert!(log[0].as_rejection().is_none());
+    }
+
+    #[test]
+    fn source_chain_tracks_last_writer() {
+        let mut chain = MapSourceChain::new();
+        chain
+            .push(
+                MapSource::Firmware,
+                &[region(0, 0x10_0000, 1), region(0x8000, 0x1000, 2)],
+            )
+            .push(MapSource::DeviceTree, &[region(0x4_0000, 0x1_0000, 2)])
+            .push(MapSource::CommandLine, &[region(0x4_8000, 0x1000, 1)])
+            .push(MapSource::KernelCarveOut, &[region(0x2_0000, 0x800, 2)]);
+
+        let at = |addr| chain.provenance_at(addr).map(|s| (s.source, s.kind));
+        pretty_assertions::assert_eq!(at(0x1000), Some((MapSource::Firmware, 1)));
+        // Inside one source, reserved beat usable during canonicalization.
+        pretty_assertions::assert_eq!(at(0x8800), Some((MapSource::Firmware, 2)));
+        pretty_assertions::assert_eq!(at(0x4_0000), Some((MapSource::DeviceTree, 2)));
+        pretty_assertions::assert_eq!(at(0x4_8FFF), Some((MapSource::CommandLine, 1)));
+        pretty_assertions::assert_eq!(at(0x2_07FF), Some((MapSource::KernelCarveOut, 2)));
+        pretty_assertions::assert_eq!(at(0x10_0000), None);
+
+        let base = canonicalize(&[region(0, 0x10_0000, 1), region(0x8000, 0x1000, 2)]);
+        let ov = [
+            Override {
+                region: region(0x4_0000, 0x1_0000, 2),
+                source: MapSource::DeviceTree,
+            },
+            cmdline(0x4_8000, 0x1000, 1),
+            Override {
+                region: region(0x2_0000, 0x800, 2),
+                source: MapSource::KernelCarveOut,
+            },
+        ];
+        pretty_assertions::assert_eq!(chain.to_map(), compose(&base, &ov));
+    }
+
+    #[test]
+    fn parse_memmap_forms() {
+        pretty_assertions::assert_eq!(
+            Override::parse_memmap("64M$0x10000000"),
+            Some(cmdline(0x1000_0000, 64 << 20, 2))
+        );
+        pretty_assertions::assert_eq!(
+            Override::parse_memmap("4K@1M"),
+            Some(cmdline(1 << 20, 4096, 1))
+        );
+        pretty_assertions::assert_eq!(
+            Override::parse_memmap("1G!4G").map(|o| o.region.kind),
+            Some(12)
+        );
+        pretty_assertions::assert_eq!(Override::parse_memmap("64M"), None);
+        pretty_assertions::assert_eq!(Override::parse_memmap("zz$1M"), None);
+        pretty_assertions::assert_eq!(Override::parse_memmap("99999999999G$0"), None);
+    }
+}
@@ -0,0 +1,319 @@
+// conformance.rs
+//
+// The crate's behaviour as data: a list of inputs and the exact output
+// each one must produce, covering every parser plus canonicalization.
+//
+// vectors.rs answers "do we agree on the wire format?". This answers
+// "do we agree on what to do with it?": which entries are dropped, which
+// inputs are malformed, who wins an overlap. A fork or a rewrite in
+// another language can run the same cases through its own code (see
+// `run`) and know it behaves the same, not just that it parses the same.
+//
+// Cases are only ever added. Changing an expected output is a behaviour
+// change and belongs in the changelog.
+
+use alloc::vec::Vec;
+
+use crate::raw::e820::E820Iter;
+use crate::raw::mb2::Mb2MmapIter;
+use crate::raw::uefi::UefiMmapIter;
+use crate::raw::{sanitize, Mb1MmapIter, MemRegion, RawEntry};
+use crate::region::canonicalize;
+use crate::vectors;
+
+/// How to read a case's input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Multiboot1 mmap buffer.
+    Mb1,
+    /// Complete Multiboot2 mmap tag, header included.
+    Mb2,
+    /// E820 array; entries the BIOS marked "ignore" are skipped.
+    E820 { entry_size: u32 },
+    /// UEFI GetMemoryMap() buffer.
+    Uefi { descriptor_size: u32 },
+    /// Regions in, `canonicalize` of them out.
+    Canonicalize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Input {
+    Bytes(&'static [u8]),
+    Regions(&'static [MemRegion]),
+}
+
+/// What a conforming implementation produces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Expected {
+    /// Every entry that survives sanitizing, in input order.
+    Regions(&'static [MemRegion]),
+    /// The input is rejected as a whole.
+    Malformed,
+}
+
+/// What an implementation under test actually produced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Regions(Vec<MemRegion>),
+    Malformed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Case {
+    pub name: &'static str,
+    pub format: Format,
+    pub input: Input,
+    pub expected: Expected,
+}
+
+impl Case {
+    pub fn accepts(&self, got: &Outcome) -> bool {
+        match (self.expected, got) {
+            (Expected::Regions(want), Outcome::Regions(got)) => want == got.as_slice(),
+            (Expected::Malformed, Outcome::Malformed) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    pub case: &'static str,
+    pub expected: Expected,
+    pub got: Outcome,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    pub passed: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Run every case of `suite` through `implementation` and collect the
+/// ones it gets wrong.
+pub fn run<F: FnMut(&Case) -> Outcome>(suite: &[Case], mut implementation: F) -> Report {
+    let mut report = Report::default();
+    for case in suite {
+        let got = implementation(case);
+        if case.accepts(&got) {
+            report.passed += 1;
+        } else {
+            report.failures.push(Failure {
+                case: case.name,
+                expected: case.expected,
+                got,
+            });
+        }
+    }
+    report
+}
+
+/// This crate, as an implementation: `run(SUITE, reference)` always passes.
+pub fn reference(case: &Case) -> Outcome {
+    let bytes = match case.input {
+        Input::Regions(regions) => return Outcome::Regions(canonicalize(regions)),
+        Input::Bytes(bytes) => bytes,
+    };
+    let entries: Result<Vec<RawEntry>, ()> = match case.format {
+        Format::Mb1 => Mb1MmapIter::new(bytes).map(|e| e.map_err(drop)).collect(),
+        Format::Mb2 => match Mb2MmapIter::new(bytes) {
+            Ok(it) => it.map(|e| e.map(RawEntry::from).map_err(drop)).collect(),
+            Err(_) => Err(()),
+        },
+        Format::E820 { entry_size } => match E820Iter::new(bytes, entry_size) {
+            Ok(it) => it
+                .enabled()
+                .map(|e| e.map(RawEntry::from).map_err(drop))
+                .collect(),
+            Err(_) => Err(()),
+        },
+        Format::Uefi { descriptor_size } => match UefiMmapIter::new(bytes, descriptor_size) {
+            // A page count that overflows is dropped like any other bad entry.
+            Ok(it) => it
+                .filter_map(|d| d.map(|d| d.to_raw()).map_err(drop).transpose())
+                .collect(),
+            Err(_) => Err(()),
+        },
+        Format::Canonicalize => Err(()),
+    };
+    match entries {
+        Ok(entries) => Outcome::Regions(entries.into_iter().filter_map(sanitize).collect()),
+        Err(()) => Outcome::Malformed,
+    }
+}
+
+const fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+    MemRegion { start, len, kind }
+}
+
+const fn from_vector(v: vectors::Vector, format: Format) -> Case {
+    Case {
+        name: v.name,
+        format,
+        input: Input::Bytes(v.bytes),
+        expected: Expected::Regions(v.regions),
+    }
+}
+
+/// The published suite.
+pub const SUITE: &[Case] = &[
+    // Every wire-format vector decodes to its regions.
+    from_vector(vectors::MB1_PC, Format::Mb1),
+    from_vector(vectors::MB1_EXTRA_PAYLOAD, Format::Mb1),
+    from_vector(vectors::MB2_PC, Format::Mb2),
+    from_vector(vectors::E820_PC, Format::E820 { entry_size: 20 }),
+    from_vector(vectors::E820_EXT_ATTRS, Format::E820 { entry_size: 24 }),
+    from_vector(
+        vectors::UEFI_PC,
+        Format::Uefi {
+            descriptor_size: 48,
+        },
+    ),
+    // Sanitizing drops entries, it does not fail the map.
+    Case {
+        name: "mb1_drops_empty_and_wrapping",
+        format: Format::Mb1,
+        input: Input::Bytes(&MB1_DROPS_BYTES),
+        expected: Expected::Regions(&[region(0x1000, 0x1000, 1)]),
+    },
+    // Malformed framing fails the whole map.
+    Case {
+        name: "mb1_zero_size",
+        format: Format::Mb1,
+        input: Input::Bytes(&[0; 24]),
+        expected: Expected::Malformed,
+    },
+    Case {
+        name: "mb1_truncated_entry",
+        format: Format::Mb1,
+        input: Input::Bytes(&MB1_PC_BYTES_TRUNCATED),
+        expected: Expected::Malformed,
+    },
+    Case {
+        name: "mb2_wrong_tag_type",
+        format: Format::Mb2,
+        input: Input::Bytes(&[1, 0, 0, 0, 16, 0, 0, 0, 24, 0, 0, 0, 0, 0, 0, 0]),
+        expected: Expected::Malformed,
+    },
+    Case {
+        name: "uefi_stride_too_small",
+        format: Format::Uefi { descriptor_size: 8 },
+        input: Input::Bytes(&[0; 48]),
+        expected: Expected::Malformed,
+    },
+    // Canonicalization.
+    Case {
+        name: "canon_sorts_and_merges",
+        format: Format::Canonicalize,
+        input: Input::Regions(&[
+            region(0x2000, 0x1000, 1),
+            region(0, 0x1000, 1),
+            region(0x1000, 0x1000, 1),
+        ]),
+        expected: Expected::Regions(&[region(0, 0x3000, 1)]),
+    },
+    Case {
+        name: "canon_reserved_beats_usable",
+        format: Format::Canonicalize,
+        input: Input::Regions(&[region(0, 0x10000, 1), region(0x4000, 0x2000, 2)]),
+        expected: Expected::Regions(&[
+            region(0, 0x4000, 1),
+            region(0x4000, 0x2000, 2),
+            region(0x6000, 0xA000, 1),
+        ]),
+    },
+    Case {
+        name: "canon_bad_ram_beats_everything",
+        format: Format::Canonicalize,
+        input: Input::Regions(&[region(0, 0x3000, 2), region(0x1000, 0x1000, 5)]),
+        expected: Expected::Regions(&[
+            region(0, 0x1000, 2),
+            region(0x1000, 0x1000, 5),
+            region(0x2000, 0x1000, 2),
+        ]),
+    },
+    Case {
+        name: "canon_usable_shrinks_to_frames",
+        format: Format::Canonicalize,
+        input: Input::Regions(&[region(0x800, 0x2000, 1), region(0x5000, 0x800, 1)]),
+        expected: Expected::Regions(&[region(0x1000, 0x1000, 1)]),
+    },
+];
+
+#[rustfmt::skip]
+const MB1_DROPS_BYTES: [u8; 72] = [
+    0x14, 0x00, 0x00, 0x00, // size = 20
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // base_addr = 0x0
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0 (dropped)
+    0x01, 0x00, 0x00, 0x00, // type = 1
+    0x14, 0x00, 0x00, 0x00, // size = 20
+    0x00, 0xf0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, // base_addr = 0xfffffffffffff000
+    0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0x2000 (wraps, dropped)
+    0x01, 0x00, 0x00, 0x00, // type = 1
+    0x14, 0x00, 0x00, 0x00, // size = 20
+    0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // base_addr = 0x1000
+    0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0x1000
+    0x01, 0x00, 0x00, 0x00, // type = 1
+];
+
+/// The first entry of `vectors::MB1_PC` with its type field cut off.
+#[rustfmt::skip]
+const MB1_PC_BYTES_TRUNCATED: [u8; 20] = [
+    0x14, 0x00, 0x00, 0x00, // size = 20
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // base_addr = 0x0
+    0x00, 0xfc, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0x9fc00
+];
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    #[test]
+    fn reference_passes_the_suite() {
+        init();
+        let report = run(SUITE, reference);
+        pretty_assertions::assert_eq!(report.failures, vec![]);
+        pretty_assertions::assert_eq!(report.passed, SUITE.len());
+    }
+
+    #[test]
+    fn runner_reports_what_an_implementation_gets_wrong() {
+        // An implementation that never rejects anything.
+        let lenient = |case: &Case| match reference(case) {
+            Outcome::Malformed => Outcome::Regions(Vec::new()),
+            ok => ok,
+        };
+        let report = run(SUITE, lenient);
+        let failed: Vec<_> = report.failures.iter().map(|f| f.case).collect();
+        pretty_assertions::assert_eq!(
+            failed,
+            vec![
+                "mb1_zero_size",
+                "mb1_truncated_entry",
+                "mb2_wrong_tag_type",
+                "uefi_stride_too_small"
+            ]
+        );
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn case_names_are_unique() {
+        let mut names: Vec<_> = SUITE.iter().map(|c| c.name).collect();
+        names.sort_unstable();
+        names.dedup();
+        pretty_assertions::assert_eq!(names.len(), SUITE.len());
+    }
+}
@@ -0,0 +1,192 @@
+// defrag.rs
+//
+// Late in boot a driver wants 4 MiB of physically contiguous memory for a
+// DMA ring, and the allocator has been handing out single frames for a
+// while. Is there still a hole that big? If not, how close is the
+// nearest one, i.e. which allocated frames sit in the way?
+//
+// This module only answers the question. Moving frames means fixing
+// every mapping that points at them, which only the kernel can do.
+//
+// The allocator's state is passed in as the sorted list of frames it has
+// handed out, so any allocator can be asked, whatever it keeps inside.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::frames::{PhysFrame, UsableRuns, FRAME_SIZE};
+use crate::raw::MemRegion;
+
+/// What it would take to get `size` contiguous bytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// Bytes asked for (rounded up to whole frames).
+    pub size: u64,
+    /// The usable window with the fewest allocated frames in it. None if
+    /// no usable run is that large, allocated or not.
+    pub window: Option<Range<u64>>,
+    /// Allocated frames inside `window` that would have to move.
+    pub relocate: Vec<PhysFrame>,
+    /// Free frames outside `window`, i.e. room to move them to.
+    pub free_elsewhere: u64,
+}
+
+impl CompactionReport {
+    /// A window exists and everything in it fits somewhere else.
+    pub fn is_satisfiable(&self) -> bool {
+        self.window.is_some() && self.relocate.len() as u64 <= self.free_elsewhere
+    }
+
+    /// Satisfiable as things stand, nothing needs to move.
+    pub fn is_free_now(&self) -> bool {
+        self.window.is_some() && self.relocate.is_empty()
+    }
+}
+
+/// Find the `align`-aligned window of `size` bytes inside the usable
+/// memory of `regions` that holds the fewest of the `allocated` frames.
+///
+/// `regions` should be canonical and `allocated` sorted by address.
+/// `align` is a power of two of at least one frame. Ties go to the lowest
+/// address.
+pub fn compaction_report(
+    regions: &[MemRegion],
+    allocated: &[PhysFrame],
+    size: u64,
+    align: u64,
+) -> CompactionReport {
+    debug_assert!(align.is_power_of_two() && align >= FRAME_SIZE);
+    debug_assert!(allocated.windows(2).all(|w| w[0] < w[1]));
+    let size = size.max(1).div_ceil(FRAME_SIZE) * FRAME_SIZE;
+
+    let mut usable_frames = 0u64;
+    let mut allocated_in_usable = 0u64;
+    // (start, allocated frames in the window)
+    let mut best: Option<(u64, usize)> = None;
+
+    for (first, count) in UsableRuns::new(regions) {
+        let run = first.0..first.0 + count * FRAME_SIZE;
+        let taken = frames_in(allocated, &run);
+        usable_frames += count;
+        allocated_in_usable += taken.len() as u64;
+
+        // Moving the window only helps right after it drops an allocated
+        // frame off its low end, so those are the only starts worth trying.
+        let candidates = core::iter::once(run.start)
+            .chain(taken.iter().map(|f| f.0 + FRAME_SIZE))
+            .filter_map(|s| s.checked_next_multiple_of(align));
+        for start in candidates {
+            let Some(end) = start.checked_add(size).filter(|&e| e <= run.end) else {
+                continue;
+            };
+            let n = frames_in(taken, &(start..end)).len();
+            if best.is_none_or(|(b, bn)| n < bn || (n == bn && start < b)) {
+                best = Some((start, n));
+            }
+        }
+    }
+
+    let free = usable_frames - allocated_in_usable;
+    let Some((start, n)) = best else {
+        return CompactionReport {
+            size,
+            window: None,
+            relocate: Vec::new(),
+            free_elsewhere: free,
+        };
+    };
+    let window = start..start + size;
+    let free_in_window = size / FRAME_SIZE - n as u64;
+    CompactionReport {
+        size,
+        relocate: frames_in(allocated, &window).to_vec(),
+        window: Some(window),
+        free_elsewhere: free - free_in_window,
+    }
+}
+
+// The part of sorted `frames` inside `range`.
+fn frames_in<'a>(frames: &'a [PhysFrame], range: &Range<u64>) -> &'a [PhysFrame] {
+    let lo = frames.partition_point(|f| f.0 < range.start);
+    let hi = frames.partition_point(|f| f.0 < range.end);
+    &frames[lo..hi]
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    const MIB: u64 = 1 << 20;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    #[test]
+    fn empty_allocator_is_free_now() {
+        init();
+        let map = [region(0x10_0000, 8 * MIB, 1)];
+        let report = compaction_report(&map, &[], 4 * MIB, 2 * MIB);
+        pretty_assertions::assert_eq!(report.window, Some(2 * MIB..6 * MIB));
+        assert!(report.is_free_now());
+        pretty_assertions::assert_eq!(report.free_elsewhere, (8 * MIB - 4 * MIB) / FRAME_SIZE);
+    }
+
+    #[test]
+    fn picks_the_window_with_fewest_frames_in_the_way() {
+        // 0..8 MiB usable; frames scattered so no 2 MiB-aligned 4 MiB hole
+        // is free, but 4..8 MiB only holds one.
+        let map = [region(0, 8 * MIB, 1)];
+        let allocated = [
+            PhysFrame(0x1000),
+            PhysFrame(2 * MIB),
+            PhysFrame(3 * MIB),
+            PhysFrame(5 * MIB),
+        ];
+        let report = compaction_report(&map, &allocated, 4 * MIB, 2 * MIB);
+        pretty_assertions::assert_eq!(report.window, Some(4 * MIB..8 * MIB));
+        pretty_assertions::assert_eq!(report.relocate, vec![PhysFrame(5 * MIB)]);
+        assert!(report.is_satisfiable());
+        assert!(!report.is_free_now());
+    }
+
+    #[test]
+    fn frame_alignment_finds_the_gap_between_allocations() {
+        let map = [region(0, 0x10000, 1)];
+        let allocated = [PhysFrame(0x3000), PhysFrame(0x8000)];
+        let report = compaction_report(&map, &allocated, 0x4000, FRAME_SIZE);
+        pretty_assertions::assert_eq!(report.window, Some(0x4000..0x8000));
+        assert!(report.is_free_now());
+    }
+
+    #[test]
+    fn too_big_for_any_run() {
+        // Two 1 MiB runs separated by a hole; 2 MiB never fits.
+        let map = [
+            region(0, MIB, 1),
+            region(MIB, 0x1000, 2),
+            region(MIB + 0x1000, MIB, 1),
+        ];
+        let report = compaction_report(&map, &[], 2 * MIB, FRAME_SIZE);
+        pretty_assertions::assert_eq!(report.window, None);
+        assert!(!report.is_satisfiable());
+    }
+
+    #[test]
+    fn not_satisfiable_without_room_to_move() {
+        // Everything is allocated: there is a window, but nowhere to put
+        // what is in it.
+        let map = [region(0, 0x4000, 1)];
+        let allocated: Vec<_> = (0..4).map(|i| PhysFrame(i * FRAME_SIZE)).collect();
+        let report = compaction_report(&map, &allocated, 0x2000, FRAME_SIZE);
+        pretty_assertions::assert_eq!(report.relocate.len(), 2);
+        pretty_assertions::assert_eq!(report.free_elsewhere, 0);
+        assert!(!report.is_satisfiable());
+    }
+}
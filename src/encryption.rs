@@ -0,0 +1,125 @@
+// encryption.rs
+//
+// AMD SEV guests see physical addresses with an extra bit in them: the
+// C-bit (its position comes from CPUID 0x8000001F[EBX] bits 5:0, usually
+// bit 47 or 51). A page table entry with the C-bit set maps the page
+// encrypted.
+//
+// The rule this crate follows:
+//
+//   inside the crate, addresses are ALWAYS canonical (C-bit stripped)
+//   the C-bit is only added back when you ask for encrypted_address()
+//
+// So region math, frame iteration and allocators never see the bit, and
+// a firmware map that leaked it into base addresses gets cleaned on the
+// way in.
+
+use crate::raw::{sanitize, MemRegion, RawEntry};
+
+/// Position of the memory-encryption bit in a physical address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CBit {
+    position: u32,
+}
+
+impl CBit {
+    /// `None` if `position` is not a valid bit index of a u64.
+    pub const fn new(position: u32) -> Option<Self> {
+        if position < 64 {
+            Some(CBit { position })
+        } else {
+            None
+        }
+    }
+
+    pub const fn position(self) -> u32 {
+        self.position
+    }
+
+    pub const fn mask(self) -> u64 {
+        1u64 << self.position
+    }
+
+    /// Canonical (unencrypted) form of `addr`.
+    pub const fn strip(self, addr: u64) -> u64 {
+        addr & !self.mask()
+    }
+
+    /// `addr` with the C-bit set.
+    pub const fn encrypt(self, addr: u64) -> u64 {
+        addr | self.mask()
+    }
+
+    /// Region with its start made canonical.
+    pub fn strip_region(self, r: MemRegion) -> MemRegion {
+        MemRegion {
+            start: self.strip(r.start),
+            ..r
+        }
+    }
+
+    /// Strip the C-bit from a firmware entry, then sanitize it as usual.
+    pub fn sanitize(self, e: RawEntry) -> Option<MemRegion> {
+        let base = e.get_base_addr_unaligned();
+        sanitize(RawEntry {
+            base_addr: self.strip(base),
+            ..e
+        })
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::frames::PhysFrame;
+    use crate::raw::raw;
+    use crate::tests::common::init;
+
+    use super::*;
+
+    const C51: CBit = match CBit::new(51) {
+        Some(c) => c,
+        None => panic!(),
+    };
+
+    #[test]
+    fn strip_and_encrypt_roundtrip() {
+        init();
+        let addr = 0x1234_5000;
+        pretty_assertions::assert_eq!(C51.encrypt(addr), addr | (1 << 51));
+        pretty_assertions::assert_eq!(C51.strip(C51.encrypt(addr)), addr);
+        pretty_assertions::assert_eq!(C51.strip(addr), addr);
+    }
+
+    #[test]
+    fn rejects_out_of_range_position() {
+        assert!(CBit::new(64).is_none());
+        assert!(CBit::new(63).is_some());
+    }
+
+    #[test]
+    fn sanitize_strips_leaked_bit() {
+        let e = raw((1 << 51) | 0x10_0000, 0x1000, 1);
+        let r = C51.sanitize(e).unwrap();
+        pretty_assertions::assert_eq!(r.start, 0x10_0000);
+        pretty_assertions::assert_eq!(r.encrypted_address(C51), (1 << 51) | 0x10_0000);
+    }
+
+    #[test]
+    fn frames_expose_encrypted_address() {
+        let f = PhysFrame(0x20_0000);
+        pretty_assertions::assert_eq!(f.encrypted_address(C51), (1 << 51) | 0x20_0000);
+        pretty_assertions::assert_eq!(
+            C51.strip_region(MemRegion {
+                start: C51.encrypt(0x1000),
+                len: 0x1000,
+                kind: 1
+            })
+            .start,
+            0x1000
+        );
+    }
+}
@@ -0,0 +1,130 @@
+// ffi.rs
+//
+// MemRegion is a Rust struct: its layout is whatever rustc picks. Assembly
+// stubs and C loader stages need a layout written down somewhere, so this
+// is the one place it is: CMemRegion, #[repr(C)], fixed forever.
+//
+//   offset 0   u64 start
+//   offset 8   u64 len
+//   offset 16  u32 kind
+//   offset 20  u32 reserved (write 0, ignore on read)
+//
+// 24 bytes, 8-byte aligned. C_HEADER is the matching declaration; write
+// it out with your build system (e.g. from a build.rs or a test) instead
+// of retyping it.
+
+use crate::raw::MemRegion;
+
+/// Stable-ABI mirror of [`MemRegion`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CMemRegion {
+    pub start: u64,
+    pub len: u64,
+    pub kind: u32,
+    pub reserved: u32,
+}
+
+impl From<MemRegion> for CMemRegion {
+    fn from(r: MemRegion) -> Self {
+        CMemRegion {
+            start: r.start,
+            len: r.len,
+            kind: r.kind,
+            reserved: 0,
+        }
+    }
+}
+
+impl From<CMemRegion> for MemRegion {
+    /// `reserved` is dropped. Nothing is validated: run the result through
+    /// the usual sanitize/canonicalize stages if it came from outside.
+    fn from(r: CMemRegion) -> Self {
+        MemRegion {
+            start: r.start,
+            len: r.len,
+            kind: r.kind,
+        }
+    }
+}
+
+/// Borrow `count` regions written by foreign code at `ptr`.
+///
+/// # Safety
+/// `ptr` must be 8-byte aligned and point to `count` initialized
+/// CMemRegion values that stay unchanged for `'a`. A `count` of 0 accepts
+/// any pointer, including null.
+pub unsafe fn from_raw_parts<'a>(ptr: *const CMemRegion, count: usize) -> &'a [CMemRegion] {
+    if count == 0 {
+        return &[];
+    }
+    core::slice::from_raw_parts(ptr, count)
+}
+
+/// C declaration of [`CMemRegion`], kept next to the struct it describes.
+pub const C_HEADER: &str = "\
+/* Generated from ffi.rs. Do not edit. */
+#ifndef MB1_MEMMAP_H
+#define MB1_MEMMAP_H
+
+#include <stdint.h>
+
+/* Kinds share the MB1 / E820 numbering: 1 usable, 2 reserved,
+ * 3 ACPI reclaimable, 4 ACPI NVS, 5 bad RAM. */
+struct mem_region {
+    uint64_t start;
+    uint64_t len;
+    uint32_t kind;
+    uint32_t reserved; /* write 0 */
+};
+
+_Static_assert(sizeof(struct mem_region) == 24, \"mem_region layout\");
+
+#endif /* MB1_MEMMAP_H */
+";
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use core::mem::{align_of, offset_of, size_of};
+
+    use crate::tests::common::init;
+
+    use super::*;
+
+    #[test]
+    fn layout_matches_the_header() {
+        init();
+        pretty_assertions::assert_eq!(size_of::<CMemRegion>(), 24);
+        pretty_assertions::assert_eq!(align_of::<CMemRegion>(), 8);
+        pretty_assertions::assert_eq!(offset_of!(CMemRegion, len), 8);
+        pretty_assertions::assert_eq!(offset_of!(CMemRegion, kind), 16);
+        assert!(C_HEADER.contains("sizeof(struct mem_region) == 24"));
+    }
+
+    #[test]
+    fn roundtrip_through_foreign_memory() {
+        let regions = [
+            MemRegion {
+                start: 0,
+                len: 0x9_F000,
+                kind: 1,
+            },
+            MemRegion {
+                start: 0xF000_0000,
+                len: 0x1000,
+                kind: 2,
+            },
+        ];
+        let c: Vec<CMemRegion> = regions.iter().map(|&r| r.into()).collect();
+        let back: Vec<MemRegion> = unsafe { from_raw_parts(c.as_ptr(), c.len()) }
+            .iter()
+            .map(|&r| r.into())
+            .collect();
+        pretty_assertions::assert_eq!(back, regions);
+        assert!(unsafe { from_raw_parts(core::ptr::null(), 0) }.is_empty());
+    }
+}
@@ -21,6 +21,26 @@
 
 use core::marker::PhantomData;
 
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+// Regions come from the parse stage (raw::sanitize); frames only consume them.
+pub use crate::raw::MemRegion;
+
+use crate::kind;
+
+pub mod bitmap;
+pub mod buddy;
+pub mod free_list;
+#[cfg(feature = "x86_64")]
+mod paging;
+pub mod zone;
+
+pub use bitmap::BitmapAllocator;
+pub use buddy::BuddyAllocator;
+pub use free_list::FreeListAllocator;
+pub use zone::Zone;
+
 // ============================================================
 // RAW ENTRY (this mirrors the bootloader wire format)
 // ============================================================
@@ -144,6 +164,8 @@ pub fn raw(start: u64, len: u64, kind: u32) -> RawEntry {
 // SERIALIZATION (YOU BECOME THE BOOTLOADER)
 // ============================================================
 
+#[cfg(feature = "alloc")]
+#[allow(clippy::ptr_arg)]
 pub fn push_entry(buf: &mut Vec<u8>, entry: RawEntry) {
     // GOAL:
     // Convert a struct into the exact byte layout GRUB would place in RAM.
@@ -292,98 +314,1640 @@ impl<'a> Iterator for Mb1MmapIter<'a> {
 }
 
 // ============================================================
-// SANITIZATION
+// FRAMES (THIS IS THE REAL GOAL)
 // ============================================================
 //
-// RawEntry describes firmware claims.
-// MemRegion describes safe kernel knowledge.
+// A PhysFrame is a 4KiB physical page.
+// Frames order by address.
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct MemRegion {
-    pub start: u64,
-    pub len: u64,
-    pub kind: u32,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PhysFrame(pub u64);
+
+impl PhysFrame {
+    // Frames hold canonical addresses; the C-bit only goes on when mapping.
+    pub fn encrypted_address(self, cbit: crate::encryption::CBit) -> u64 {
+        cbit.encrypt(self.0)
+    }
+}
+
+// alignment helpers
+// align_up can run off the top of the address space: None means "no such address".
+fn align_up(x: u64, a: u64) -> Option<u64> {
+    x.checked_add(a - 1).map(|v| v & !(a - 1))
+}
+fn align_down(x: u64, a: u64) -> u64 {
+    x & !(a - 1)
+}
+
+/// A physical address known to be aligned to `ALIGN` bytes.
+///
+/// The only ways to get one check the alignment, so code that needs a
+/// 2 MiB-aligned block can say so in its signature instead of trusting
+/// whoever called it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AlignedFrame<const ALIGN: u64>(u64);
+
+impl<const ALIGN: u64> AlignedFrame<ALIGN> {
+    // Evaluated per ALIGN at compile time wherever it is referenced.
+    const VALID: () = assert!(
+        ALIGN.is_power_of_two() && ALIGN >= FRAME_SIZE,
+        "ALIGN must be a power of two of at least one frame"
+    );
+
+    /// `addr` if it is `ALIGN`-aligned.
+    pub fn new(addr: u64) -> Option<Self> {
+        let () = Self::VALID;
+        addr.is_multiple_of(ALIGN).then_some(AlignedFrame(addr))
+    }
+
+    pub fn addr(self) -> u64 {
+        self.0
+    }
+
+    pub fn frame(self) -> PhysFrame {
+        PhysFrame(self.0)
+    }
+
+    /// Forget some of the alignment (a 2 MiB block is also 4 KiB aligned).
+    pub fn weaken<const LESS: u64>(self) -> AlignedFrame<LESS> {
+        const { assert!(LESS <= ALIGN, "can only weaken alignment") };
+        AlignedFrame::<LESS>::new(self.0).expect("weaker alignment always holds")
+    }
+}
+
+/// Which frame `UsableFrames` hands out first.
+///
+/// Regions are taken in slice order (or reversed), never re-sorted, so for
+/// "lowest/highest address first" the map must be sorted by start.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+    /// Regions front to back, frames low to high within each.
+    #[default]
+    LowFirst,
+    /// Regions back to front, frames high to low within each.
+    /// Keeps low memory (trampolines, legacy DMA) for last.
+    HighFirst,
+    /// Regions front to back as given, frames high to low within each.
+    /// For callers that rank regions themselves but fill each from the top.
+    PerRegionHighFirst,
 }
 
-impl MemRegion {
-    // end address of region
-    pub fn end(self) -> u64 {
-        // Decide overflow policy.
-        // Kernel must NEVER wrap addresses.
-        self.start.saturating_add(self.len)
+impl Order {
+    // Frames go high to low within a region.
+    fn descending(self) -> bool {
+        !matches!(self, Order::LowFirst)
     }
 }
 
-pub fn sanitize(e: RawEntry) -> Option<MemRegion> {
-    // Drop zero-length regions.
-    //
-    // Then check:
-    //   start + length overflow
-    //
-    // If overflow occurs:
-    //   region is invalid -> return None
-    //
-    // Otherwise return MemRegion.
+/// Frames of `SIZE` bytes (4 KiB unless asked otherwise) from the usable
+/// regions of a map. Each item is the `SIZE`-aligned start address of a
+/// frame lying wholly inside one region.
+#[derive(Clone, Debug)]
+pub struct UsableFrames<'a, const SIZE: u64 = FRAME_SIZE> {
+    regions: &'a [MemRegion],
+    order: Order,
+    // How many regions each end has taken so far, in walk order.
+    taken: usize,
+    taken_back: usize,
+    // Bytes at the start of every usable region that are never handed out.
+    skip_head: u64,
+    // Nothing below this address is handed out.
+    floor: u64,
+    // Frames still to hand out from the region each end is in.
+    front: Window,
+    back: Window,
+    // Opt-in kinds handed out along with usable memory.
+    soft_reserved: bool,
+    hot_pluggable: bool,
+    // The map lists bad RAM, so every frame has to be checked against it.
+    bad_ram: bool,
+    // Some regions overlap, so a frame may already have been handed out
+    // from an earlier one.
+    overlapping: bool,
+}
 
-    todo!()
+impl<'a> UsableFrames<'a> {
+    pub fn new(regions: &'a [MemRegion]) -> Self {
+        Self::with_order(regions, Order::LowFirst)
+    }
+
+    pub fn with_order(regions: &'a [MemRegion], order: Order) -> Self {
+        Self::sized_with_order(regions, order)
+    }
+}
+
+impl<'a, const SIZE: u64> UsableFrames<'a, SIZE> {
+    /// `SIZE`-byte frames, e.g. `UsableFrames::<{ 2 << 20 }>::sized(map)`
+    /// for 2 MiB pages. Regions without a whole aligned `SIZE` block in
+    /// them yield nothing.
+    pub fn sized(regions: &'a [MemRegion]) -> Self {
+        Self::sized_with_order(regions, Order::LowFirst)
+    }
+
+    pub fn sized_with_order(regions: &'a [MemRegion], order: Order) -> Self {
+        let () = AlignedFrame::<SIZE>::VALID;
+        UsableFrames {
+            regions,
+            order,
+            taken: 0,
+            taken_back: 0,
+            skip_head: 0,
+            floor: 0,
+            front: Window::default(),
+            back: Window::default(),
+            soft_reserved: false,
+            hot_pluggable: false,
+            bad_ram: regions.iter().any(|r| r.kind == kind::BAD_RAM && r.len > 0),
+            overlapping: regions.iter().enumerate().any(|(i, a)| {
+                regions[i + 1..]
+                    .iter()
+                    .any(|b| a.start < b.end() && b.start < a.end())
+            }),
+        }
+    }
+
+    /// Also hand out soft reserved (EFI_MEMORY_SP) memory. Only for the
+    /// one consumer that owns it; the general pool never should.
+    pub fn claim_soft_reserved(mut self) -> Self {
+        self.soft_reserved = true;
+        self
+    }
+
+    /// Also hand out hot-pluggable memory (see `srat::mark_hot_pluggable`),
+    /// for data that can move or be dropped when the memory goes away.
+    pub fn claim_hot_pluggable(mut self) -> Self {
+        self.hot_pluggable = true;
+        self
+    }
+
+    fn takes(&self, region: &MemRegion) -> bool {
+        match region.kind {
+            kind::SOFT_RESERVED => self.soft_reserved,
+            kind::HOT_PLUGGABLE => self.hot_pluggable,
+            _ => region.region_kind().is_usable(),
+        }
+    }
+
+    /// Leave the first `bytes` of each usable region alone, e.g. for
+    /// allocator metadata kept inside the memory it describes. A region
+    /// no larger than the skip yields nothing.
+    pub fn skip_head(mut self, bytes: u64) -> Self {
+        self.skip_head = bytes;
+        self
+    }
+
+    /// Hand out nothing below `addr`, e.g. the end of the kernel image or
+    /// of the boot information the map itself lives in. A frame `addr`
+    /// falls inside is skipped too.
+    pub fn starting_after(mut self, addr: u64) -> Self {
+        self.floor = addr;
+        self
+    }
+
+    /// Hand out a block of `ALIGN` bytes aligned to `ALIGN` (a power of
+    /// two, at least one frame), e.g. 2 MiB for a huge page.
+    ///
+    /// Bump semantics: frames skipped to reach the alignment are not
+    /// handed out later (nor is the rest of a `SIZE` frame a smaller
+    /// block was cut from). Regions too small for a block are passed over.
+    pub fn allocate_aligned<const ALIGN: u64>(&mut self) -> Option<AlignedFrame<ALIGN>> {
+        let () = AlignedFrame::<ALIGN>::VALID;
+        loop {
+            let w = &mut self.front;
+            let block = if self.order.descending() {
+                w.hi.checked_sub(ALIGN)
+                    .map(|top| align_down(top, ALIGN))
+                    .filter(|&a| a >= w.lo && w.lo < w.hi)
+                    .inspect(|&a| w.hi = align_down(a, SIZE))
+            } else {
+                align_up(w.lo, ALIGN)
+                    .filter(|&a| a.checked_add(ALIGN).is_some_and(|end| end <= w.hi))
+                    .inspect(|&a| w.lo = align_up(a + ALIGN, SIZE).map_or(w.hi, |l| l.min(w.hi)))
+            };
+            if let Some(addr) = block {
+                let pos = self.front.pos;
+                if self.clear_of_bad_ram(false, addr, ALIGN)
+                    && !self.claimed_earlier(pos, addr, ALIGN)
+                {
+                    return AlignedFrame::new(addr);
+                }
+                continue;
+            }
+            self.load(false)?;
+        }
+    }
+
+    // A map that was never normalized can list bad RAM inside a usable
+    // region. If [addr, addr + len) touches any, move the cursor of the
+    // `back` or front end past the bad range (in its direction) and say no.
+    fn clear_of_bad_ram(&mut self, back: bool, addr: u64, len: u64) -> bool {
+        if !self.bad_ram {
+            return true;
+        }
+        let end = addr + len;
+        let Some(bad) = self
+            .regions
+            .iter()
+            .find(|r| r.kind == kind::BAD_RAM && r.start < end && addr < r.end())
+        else {
+            return true;
+        };
+        let (start, end) = (bad.start, bad.end());
+        let descending = self.order.descending() != back;
+        let w = if back {
+            &mut self.back
+        } else {
+            &mut self.front
+        };
+        if descending {
+            w.hi = w.hi.min(align_down(start, SIZE)).max(w.lo);
+        } else {
+            let past = align_up(end, SIZE).unwrap_or(w.hi);
+            w.lo = w.lo.max(past).min(w.hi);
+        }
+        false
+    }
+
+    // Whether a region before walk position `pos` covers any of
+    // [addr, addr + len). Overlapping frames belong to the earliest region
+    // in walk order, whichever end reaches them.
+    fn claimed_earlier(&self, pos: usize, addr: u64, len: u64) -> bool {
+        if !self.overlapping {
+            return false;
+        }
+        self.earlier(pos)
+            .iter()
+            .filter_map(|r| self.frames_of(r))
+            .any(|(start, end)| start < addr + len && addr < end)
+    }
+
+    // Regions before walk position `pos`.
+    fn earlier(&self, pos: usize) -> &'a [MemRegion] {
+        match self.order {
+            Order::HighFirst => &self.regions[self.regions.len() - pos..],
+            Order::LowFirst | Order::PerRegionHighFirst => &self.regions[..pos],
+        }
+    }
+
+    fn region_at(&self, pos: usize) -> &'a MemRegion {
+        match self.order {
+            Order::HighFirst => &self.regions[self.regions.len() - 1 - pos],
+            Order::LowFirst | Order::PerRegionHighFirst => &self.regions[pos],
+        }
+    }
+
+    // Frames not handed out yet, by arithmetic on the regions left
+    // rather than by walking them.
+    fn remaining(&self) -> u64 {
+        let windows = [self.front, self.back]
+            .iter()
+            .map(|w| self.count_in(w.pos, w.lo, w.hi))
+            .sum::<u64>();
+        let untaken = (self.taken..self.regions.len() - self.taken_back)
+            .filter_map(|pos| {
+                let (lo, hi) = self.frames_of(self.region_at(pos))?;
+                Some(self.count_in(pos, lo, hi))
+            })
+            .sum::<u64>();
+        windows + untaken
+    }
+
+    // Frames in [lo, hi) of the region at walk position `pos` that the
+    // iterator will hand out: those not touching bad RAM and not claimed
+    // by an earlier region. Sweeps over the excluded ranges, so a clean
+    // map costs one step.
+    fn count_in(&self, pos: usize, lo: u64, hi: u64) -> u64 {
+        let earlier = self
+            .earlier(pos)
+            .iter()
+            .filter(|_| self.overlapping)
+            .filter_map(|r| self.frames_of(r));
+        let bad = self
+            .regions
+            .iter()
+            .filter(|r| self.bad_ram && r.kind == kind::BAD_RAM)
+            .map(|r| {
+                let end = align_up(r.end(), SIZE).unwrap_or(align_down(u64::MAX, SIZE));
+                (align_down(r.start, SIZE), end)
+            });
+        let excluded = earlier.chain(bad);
+        let mut n = 0;
+        let mut x = lo;
+        while x < hi {
+            let covered = excluded.clone().filter(|&(s, e)| s <= x && x < e);
+            if let Some(end) = covered.map(|(_, e)| e).max() {
+                x = end;
+                continue;
+            }
+            let next = excluded
+                .clone()
+                .map(|(s, _)| s)
+                .filter(|&s| s > x)
+                .min()
+                .map_or(hi, |s| s.min(hi));
+            n += (next - x) / SIZE;
+            x = next;
+        }
+        n
+    }
+
+    // The whole frames of `region` this iterator hands out, if any.
+    fn frames_of(&self, region: &MemRegion) -> Option<(u64, u64)> {
+        if !self.takes(region) {
+            return None;
+        }
+        let start = region
+            .start
+            .checked_add(self.skip_head)
+            .and_then(|s| align_up(s.max(self.floor), SIZE))?;
+        let end = align_down(region.end(), SIZE);
+        (start < end).then_some((start, end))
+    }
+
+    // Give the `back` or front end the next usable, non-empty region. Once
+    // no region is left, it takes over what remains of the other end's.
+    // None when that is empty too.
+    fn load(&mut self, back: bool) -> Option<()> {
+        while let Some(pos) = self.next_pos(back) {
+            if let Some((lo, hi)) = self.frames_of(self.region_at(pos)) {
+                *self.window(back) = Window { lo, hi, pos };
+                return Some(());
+            }
+        }
+        let other = core::mem::take(self.window(!back));
+        (other.lo < other.hi).then(|| *self.window(back) = other)
+    }
+
+    // Walk position of the next region from the `back` or front end.
+    fn next_pos(&mut self, back: bool) -> Option<usize> {
+        if self.taken + self.taken_back >= self.regions.len() {
+            return None;
+        }
+        Some(if back {
+            self.taken_back += 1;
+            self.regions.len() - self.taken_back
+        } else {
+            self.taken += 1;
+            self.taken - 1
+        })
+    }
+
+    fn window(&mut self, back: bool) -> &mut Window {
+        if back {
+            &mut self.back
+        } else {
+            &mut self.front
+        }
+    }
+
+    // The next frame from the `back` or front end.
+    fn step(&mut self, back: bool) -> Option<PhysFrame> {
+        let descending = self.order.descending() != back;
+        loop {
+            let w = self.window(back);
+            if w.lo < w.hi {
+                let frame = if descending {
+                    w.hi -= SIZE;
+                    w.hi
+                } else {
+                    w.lo += SIZE;
+                    w.lo - SIZE
+                };
+                debug_assert!(w.lo <= w.hi);
+                let pos = w.pos;
+                if self.clear_of_bad_ram(back, frame, SIZE)
+                    && !self.claimed_earlier(pos, frame, SIZE)
+                {
+                    return Some(PhysFrame(frame));
+                }
+                continue;
+            }
+            self.load(back)?;
+        }
+    }
+}
+
+// Frames [lo, hi) left in the region at walk position `pos`.
+#[derive(Clone, Copy, Debug, Default)]
+struct Window {
+    lo: u64,
+    hi: u64,
+    pos: usize,
+}
+
+impl<'a, const SIZE: u64> Iterator for UsableFrames<'a, SIZE> {
+    type Item = PhysFrame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step(false)
+    }
+
+    /// Exact. Linear in the regions left for a normalized map; bad RAM
+    /// inside usable regions or overlapping regions make it cubic.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = usize::try_from(self.remaining()).unwrap_or(usize::MAX);
+        (n, Some(n))
+    }
+}
+
+impl<'a, const SIZE: u64> ExactSizeIterator for UsableFrames<'a, SIZE> {}
+
+/// From the back: exactly the frames `next` has not reached yet, in the
+/// reverse of the order it would give them.
+impl<'a, const SIZE: u64> DoubleEndedIterator for UsableFrames<'a, SIZE> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.step(true)
+    }
+}
+
+/// How many 4 KiB frames `UsableFrames::new(regions)` hands out,
+/// without walking them: for sizing allocator metadata up front.
+pub fn usable_frame_count(regions: &[MemRegion]) -> u64 {
+    UsableFrames::new(regions).remaining()
 }
 
 // ============================================================
-// FRAMES (THIS IS THE REAL GOAL)
+// ALLOCATOR TRAITS (swap allocators between boot phases)
 // ============================================================
 //
-// A PhysFrame is a 4KiB physical page.
+// Every allocator here hands out 4 KiB frames through FrameAlloc, and
+// the ones that can take frames back implement FrameDealloc. Both are
+// object safe, so a kernel can keep one `&mut dyn FrameAlloc` and point
+// it at the bump allocator, then the bitmap, then the buddy allocator
+// as boot goes on, without every subsystem becoming generic over it.
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub struct PhysFrame(pub u64);
+/// Hands out 4 KiB frames.
+pub trait FrameAlloc {
+    /// A free frame, or None when there are none left.
+    fn alloc_frame(&mut self) -> Option<PhysFrame>;
 
-// alignment helpers
-fn align_up(x: u64, a: u64) -> u64 {
-    (x + (a - 1)) & !(a - 1)
+    /// `count` physically contiguous frames, the first aligned to
+    /// `align` bytes (a power of two). Allocators that cannot promise
+    /// contiguity (bump, free list) always say None.
+    fn alloc_contiguous(&mut self, count: usize, align: u64) -> Option<PhysFrameRange> {
+        let _ = (count, align);
+        None
+    }
 }
-fn align_down(x: u64, a: u64) -> u64 {
-    x & !(a - 1)
+
+/// Takes 4 KiB frames back.
+pub trait FrameDealloc {
+    /// Return `frame` to the free pool.
+    ///
+    /// # Safety
+    /// `frame` must be unused from now on and belong to this allocator's
+    /// memory; allocators that can (bitmap, buddy) panic on a double
+    /// free, the others hand the frame out twice.
+    unsafe fn dealloc_frame(&mut self, frame: PhysFrame);
 }
 
-pub struct UsableFrames<'a> {
-    // You need:
-    //   iterator over regions
-    //   current frame pointer
-    //   end pointer
-    _p: PhantomData<&'a [MemRegion]>,
+impl<A: FrameAlloc + ?Sized> FrameAlloc for &mut A {
+    fn alloc_frame(&mut self) -> Option<PhysFrame> {
+        (**self).alloc_frame()
+    }
+
+    fn alloc_contiguous(&mut self, count: usize, align: u64) -> Option<PhysFrameRange> {
+        (**self).alloc_contiguous(count, align)
+    }
 }
 
-impl<'a> UsableFrames<'a> {
-    pub fn new(_regions: &'a [MemRegion]) -> Self {
-        // Prepare to iterate regions.
+impl<D: FrameDealloc + ?Sized> FrameDealloc for &mut D {
+    unsafe fn dealloc_frame(&mut self, frame: PhysFrame) {
+        (**self).dealloc_frame(frame)
+    }
+}
 
-        todo!()
+impl FrameAlloc for UsableFrames<'_> {
+    fn alloc_frame(&mut self) -> Option<PhysFrame> {
+        self.next()
+    }
+}
+
+impl FrameAlloc for BumpAllocator<'_> {
+    fn alloc_frame(&mut self) -> Option<PhysFrame> {
+        self.allocate()
+    }
+}
+
+impl FrameAlloc for BitmapAllocator<'_> {
+    fn alloc_frame(&mut self) -> Option<PhysFrame> {
+        self.allocate()
+    }
+
+    fn alloc_contiguous(&mut self, count: usize, align: u64) -> Option<PhysFrameRange> {
+        BitmapAllocator::alloc_contiguous(self, count, align)
+    }
+}
+
+impl FrameDealloc for BitmapAllocator<'_> {
+    unsafe fn dealloc_frame(&mut self, frame: PhysFrame) {
+        self.deallocate(frame)
+    }
+}
+
+impl FrameAlloc for BuddyAllocator<'_> {
+    fn alloc_frame(&mut self) -> Option<PhysFrame> {
+        self.allocate(0)
+    }
+
+    fn alloc_contiguous(&mut self, count: usize, align: u64) -> Option<PhysFrameRange> {
+        BuddyAllocator::alloc_contiguous(self, count, align)
+    }
+}
+
+impl FrameDealloc for BuddyAllocator<'_> {
+    unsafe fn dealloc_frame(&mut self, frame: PhysFrame) {
+        self.deallocate(frame, 0)
+    }
+}
+
+impl<M: crate::mapper::PhysMapper> FrameAlloc for FreeListAllocator<M> {
+    fn alloc_frame(&mut self) -> Option<PhysFrame> {
+        self.allocate()
+    }
+}
+
+impl<M: crate::mapper::PhysMapper> FrameDealloc for FreeListAllocator<M> {
+    unsafe fn dealloc_frame(&mut self, frame: PhysFrame) {
+        self.deallocate(frame)
+    }
+}
+
+// ============================================================
+// BUMP ALLOCATOR (the first allocator, before there is a heap)
+// ============================================================
+//
+// Hands out frames in order and never takes one back. No metadata, no
+// heap: all it keeps is a UsableFrames cursor and a count, so it works
+// from the first instruction of the kernel. Later allocators are built
+// from what it has not handed out yet.
+
+#[derive(Clone, Debug)]
+pub struct BumpAllocator<'a> {
+    frames: UsableFrames<'a>,
+    allocated: usize,
+    // Take frames from the back of `frames` instead.
+    from_top: bool,
+}
+
+impl<'a> BumpAllocator<'a> {
+    pub fn new(regions: &'a [MemRegion]) -> Self {
+        Self::from_frames(UsableFrames::new(regions))
+    }
+
+    /// Bump through an already configured iterator (order, skip_head,
+    /// opt-in kinds).
+    pub fn from_frames(frames: UsableFrames<'a>) -> Self {
+        BumpAllocator {
+            frames,
+            allocated: 0,
+            from_top: false,
+        }
+    }
+
+    /// Hand out frames from the far end of the iterator: for the default
+    /// order, highest address first, keeping low memory (SMP trampoline,
+    /// legacy DMA) free for as long as possible.
+    pub fn highest_first(mut self) -> Self {
+        self.from_top = true;
+        self
+    }
+
+    /// The next free frame, or None once usable memory is used up.
+    pub fn allocate(&mut self) -> Option<PhysFrame> {
+        let frame = match self.from_top {
+            false => self.frames.next()?,
+            true => self.frames.next_back()?,
+        };
+        self.allocated += 1;
+        Some(frame)
+    }
+
+    /// Frames handed out so far.
+    pub fn allocated_count(&self) -> usize {
+        self.allocated
+    }
+
+    /// Hand every frame not allocated yet to the allocator that takes
+    /// over, and return how many that was. Start `next` with nothing
+    /// free (e.g. [`BitmapAllocator::new_used`]) so the frames this one
+    /// handed out stay in use there. Consumes the bump allocator, so
+    /// nothing can come out of it afterwards.
+    ///
+    /// Frames inside `in_use` are held back too: memory that was taken
+    /// without going through this allocator (the kernel image, the boot
+    /// info, an initrd) and so would otherwise look free.
+    pub fn drain_into<D: FrameDealloc + ?Sized>(
+        self,
+        next: &mut D,
+        in_use: &[PhysFrameRange],
+    ) -> u64 {
+        let mut moved = 0;
+        for frame in self.frames {
+            if in_use.iter().any(|r| r.contains(frame)) {
+                continue;
+            }
+            // SAFETY: never handed out and not in use, so unused as far as
+            // anyone knows.
+            unsafe { next.dealloc_frame(frame) };
+            moved += 1;
+        }
+        moved
+    }
+}
+
+// ============================================================
+// ALIGNED CHUNKS (buddy init / huge-page mapping input)
+// ============================================================
+//
+// Instead of one frame at a time, cut each usable region into the
+// biggest naturally-aligned power-of-two blocks that fit:
+//
+//   region [0x1000, 0x40_3000)
+//     -> 0x1000   order 0  (4K, not 8K aligned)
+//     -> 0x2000   order 1
+//     -> 0x4000   order 2
+//     ...
+//     -> 0x20_0000 order 9 (2M)
+//     -> 0x40_0000 order 1
+//     -> 0x40_2000 order 0
+//
+// order n means a block of (4096 << n) bytes aligned to its own size.
+// The tricky part is that BOTH limits apply at every step:
+//   alignment of the current address  (trailing zeros)
+//   bytes left before the region ends (log2 of remaining)
+
+pub const FRAME_SIZE: u64 = 4096;
+const FRAME_SHIFT: u32 = 12;
+
+/// Orders that correspond to x86_64 page sizes: 4K, 2M, 1G.
+pub const PAGE_SIZE_ORDERS: u64 = (1 << 0) | (1 << 9) | (1 << 18);
+
+#[derive(Clone, Debug)]
+pub struct AlignedChunks<'a> {
+    regions: &'a [MemRegion],
+    next_region: usize,
+    current: u64,
+    end: u64,
+    // bit n set = order n may be yielded. Bit 0 is always set.
+    allowed: u64,
+}
+
+impl<'a> AlignedChunks<'a> {
+    /// Every order from 0 up to `max_order` is allowed (buddy-style).
+    pub fn new(regions: &'a [MemRegion], max_order: u32) -> Self {
+        let allowed = if max_order >= 63 {
+            u64::MAX
+        } else {
+            (1u64 << (max_order + 1)) - 1
+        };
+        Self::with_orders(regions, allowed)
+    }
+
+    /// Only the 4K/2M/1G orders (huge-page mapping).
+    pub fn page_sizes(regions: &'a [MemRegion]) -> Self {
+        Self::with_orders(regions, PAGE_SIZE_ORDERS)
+    }
+
+    /// Arbitrary set of allowed orders as a bitmask.
+    pub fn with_orders(regions: &'a [MemRegion], allowed: u64) -> Self {
+        AlignedChunks {
+            regions,
+            next_region: 0,
+            current: 0,
+            end: 0,
+            allowed: allowed | 1,
+        }
+    }
+
+    // Largest allowed order <= limit.
+    fn pick_order(&self, limit: u32) -> u32 {
+        let mask = if limit >= 63 {
+            self.allowed
+        } else {
+            self.allowed & ((1u64 << (limit + 1)) - 1)
+        };
+        63 - mask.leading_zeros()
+    }
+}
+
+impl<'a> Iterator for AlignedChunks<'a> {
+    type Item = (PhysFrame, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.current < self.end {
+                let frame_index = self.current >> FRAME_SHIFT;
+                let remaining = (self.end - self.current) >> FRAME_SHIFT;
+
+                let by_alignment = if frame_index == 0 {
+                    63
+                } else {
+                    frame_index.trailing_zeros()
+                };
+                let by_size = 63 - remaining.leading_zeros();
+                let order = self.pick_order(by_alignment.min(by_size));
+
+                let addr = self.current;
+                self.current += FRAME_SIZE << order;
+                debug_assert!(self.current > addr && self.current <= self.end);
+                return Some((PhysFrame(addr), order));
+            }
+
+            let region = *self.regions.get(self.next_region)?;
+            self.next_region += 1;
+            if !region.region_kind().is_usable() {
+                continue;
+            }
+            let Some(start) = align_up(region.start, FRAME_SIZE) else {
+                continue;
+            };
+            let end = align_down(region.end(), FRAME_SIZE);
+            if start >= end {
+                continue;
+            }
+            self.current = start;
+            self.end = end;
+        }
+    }
+}
+
+// ============================================================
+// FRAMES OF ONE REGION
+// ============================================================
+//
+// UsableFrames walks a whole map. Sometimes you hold exactly one region
+// (a block you just allocated, a carve-out) and just want its frames.
+// The kind is not checked: you asked for this region's frames.
+
+#[derive(Clone, Debug)]
+pub struct RegionFrames {
+    current: u64,
+    end: u64,
+    size: u64,
+}
+
+impl RegionFrames {
+    /// Frames of `size` bytes (power of two) fully inside `region`.
+    pub fn new(region: MemRegion, size: u64) -> Self {
+        let end = align_down(region.end(), size);
+        let current = align_up(region.start, size).unwrap_or(end).min(end);
+        RegionFrames { current, end, size }
+    }
+}
+
+impl Iterator for RegionFrames {
+    type Item = PhysFrame;
+
+    fn next(&mut self) -> Option<PhysFrame> {
+        if self.current >= self.end {
+            return None;
+        }
+        let frame = PhysFrame(self.current);
+        self.current += self.size;
+        debug_assert!(self.current > frame.0 && self.current <= self.end);
+        Some(frame)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = ((self.end - self.current) / self.size) as usize;
+        (n, Some(n))
+    }
+}
+
+impl ExactSizeIterator for RegionFrames {}
+
+// ============================================================
+// FRAME RANGES (batches of frames)
+// ============================================================
+//
+// [start, end) in whole 4 KiB frames. Mapping code wants "these 512
+// frames" rather than 512 separate calls, and allocators that hand out
+// contiguous memory return one of these.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PhysFrameRange {
+    pub start: PhysFrame,
+    /// One past the last frame.
+    pub end: PhysFrame,
+}
+
+impl PhysFrameRange {
+    /// The 4 KiB frames fully inside `region` (kind is not checked).
+    pub fn inside(region: MemRegion) -> Self {
+        let frames = RegionFrames::new(region, FRAME_SIZE);
+        PhysFrameRange {
+            start: PhysFrame(frames.current),
+            end: PhysFrame(frames.end),
+        }
+    }
+
+    /// Frames in the range.
+    pub fn len(self) -> u64 {
+        self.end.0.saturating_sub(self.start.0) / FRAME_SIZE
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.start >= self.end
+    }
+
+    pub fn contains(self, frame: PhysFrame) -> bool {
+        self.start <= frame && frame < self.end
+    }
+
+    pub fn iter(self) -> RegionFrames {
+        RegionFrames {
+            current: self.start.0,
+            end: self.end.0.max(self.start.0),
+            size: FRAME_SIZE,
+        }
+    }
+
+    /// The first `n` frames and the rest. `n` past the end gives the
+    /// whole range and an empty one.
+    pub fn split_at(self, n: u64) -> (Self, Self) {
+        let mid = PhysFrame(self.start.0 + n.min(self.len()) * FRAME_SIZE);
+        (
+            PhysFrameRange {
+                start: self.start,
+                end: mid,
+            },
+            PhysFrameRange {
+                start: mid,
+                end: self.end,
+            },
+        )
+    }
+
+    /// Frames in both ranges, if any.
+    pub fn intersect(self, other: Self) -> Option<Self> {
+        let range = PhysFrameRange {
+            start: self.start.max(other.start),
+            end: self.end.min(other.end),
+        };
+        (!range.is_empty()).then_some(range)
     }
 }
 
-impl<'a> Iterator for UsableFrames<'a> {
+impl IntoIterator for PhysFrameRange {
     type Item = PhysFrame;
+    type IntoIter = RegionFrames;
+
+    fn into_iter(self) -> RegionFrames {
+        self.iter()
+    }
+}
+
+// ============================================================
+// CONTIGUOUS RUNS (allocator warm-start input)
+// ============================================================
+//
+// Allocators do not want 2 million individual frames at init.
+// They want "frames 0x100000.. for 30000 frames", so they can
+// fill whole bitmap words / buddy blocks in one go.
+//
+// Adjacent usable regions are joined into one run, and a region that
+// overlaps the previous one only contributes its new frames, so the
+// same frame is never handed out twice. Input should be sorted by start.
+
+#[derive(Clone, Debug)]
+pub struct UsableRuns<'a> {
+    regions: &'a [MemRegion],
+    next_region: usize,
+}
+
+impl<'a> UsableRuns<'a> {
+    pub fn new(regions: &'a [MemRegion]) -> Self {
+        UsableRuns {
+            regions,
+            next_region: 0,
+        }
+    }
+
+    // Next usable region as an aligned [start, end), skipping empty ones.
+    fn next_aligned(&mut self) -> Option<(u64, u64)> {
+        loop {
+            let region = *self.regions.get(self.next_region)?;
+            self.next_region += 1;
+            if !region.region_kind().is_usable() {
+                continue;
+            }
+            let Some(start) = align_up(region.start, FRAME_SIZE) else {
+                continue;
+            };
+            let end = align_down(region.end(), FRAME_SIZE);
+            if start < end {
+                return Some((start, end));
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for UsableRuns<'a> {
+    /// First frame of the run and how many frames it covers.
+    type Item = (PhysFrame, u64);
 
     fn next(&mut self) -> Option<Self::Item> {
-        // Algorithm:
-        //
-        // Loop:
-        //   If current < end:
-        //       return frame and advance by 4096
-        //
-        //   Otherwise:
-        //       load next region
-        //       skip if not type 1 (usable)
-        //
-        //       start = align_up(region.start, 4096)
-        //       end   = align_down(region.end(), 4096)
-        //
-        //       if start >= end:
-        //           continue
-        //
-        //       set current=start, end=end
-        //       repeat
+        let before = self.next_region;
+        let (start, mut end) = self.next_aligned()?;
+        debug_assert!(self.next_region > before);
 
-        todo!()
+        // Absorb following regions that touch or overlap this run.
+        loop {
+            let save = self.next_region;
+            match self.next_aligned() {
+                Some((s, e)) if s <= end => end = end.max(e),
+                _ => {
+                    self.next_region = save;
+                    break;
+                }
+            }
+        }
+
+        Some((PhysFrame(start), (end - start) / FRAME_SIZE))
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+    const GIB: u64 = 1024 * MIB;
+
+    fn usable(start: u64, len: u64) -> MemRegion {
+        MemRegion {
+            start,
+            len,
+            kind: 1,
+        }
+    }
+
+    fn chunks(it: AlignedChunks) -> Vec<(u64, u32)> {
+        it.map(|(f, o)| (f.0, o)).collect()
+    }
+
+    #[test]
+    fn chunks_ramp_up_then_down() {
+        init();
+        let regions = [usable(0x1000, 0x7000)];
+        pretty_assertions::assert_eq!(
+            chunks(AlignedChunks::new(&regions, 10)),
+            vec![(0x1000, 0), (0x2000, 1), (0x4000, 2)]
+        );
+    }
+
+    #[test]
+    fn chunks_cover_region_exactly_without_overlap() {
+        let regions = [usable(0x3000, 5 * MIB + 0x1234), usable(7 * MIB, 0x1000)];
+        let mut expected = 0x3000;
+        let mut total = 0;
+        for (frame, order) in AlignedChunks::new(&regions, 20) {
+            let size = FRAME_SIZE << order;
+            pretty_assertions::assert_eq!(frame.0 % size, 0, "chunk must be naturally aligned");
+            if frame.0 != expected {
+                pretty_assertions::assert_eq!(frame.0, 7 * MIB);
+            }
+            expected = frame.0 + size;
+            total += size;
+        }
+        // [0x3000, 0x504000) after aligning the end down, plus the second region
+        pretty_assertions::assert_eq!(total, (0x50_4000 - 0x3000) + 0x1000);
+    }
+
+    #[test]
+    fn max_order_caps_chunk_size() {
+        let regions = [usable(0, 4 * MIB)];
+        let got = chunks(AlignedChunks::new(&regions, 9));
+        pretty_assertions::assert_eq!(got, vec![(0, 9), (2 * MIB, 9)]);
+    }
+
+    #[test]
+    fn page_sizes_only_yields_4k_2m_1g() {
+        let regions = [usable(GIB - 2 * MIB - 0x1000, GIB + 2 * MIB + 0x2000)];
+        let got = chunks(AlignedChunks::page_sizes(&regions));
+
+        assert!(got.iter().all(|&(_, o)| o == 0 || o == 9 || o == 18));
+        assert!(got.contains(&(GIB - 2 * MIB, 9)));
+        assert!(got.contains(&(GIB, 18)));
+        assert!(got.contains(&(2 * GIB, 0)));
+    }
+
+    #[test]
+    fn unaligned_and_non_usable_regions() {
+        let regions = [
+            MemRegion {
+                start: 0,
+                len: 0x10000,
+                kind: 2,
+            },
+            usable(0x10001, 0xFFF), // collapses to nothing after alignment
+            usable(0x20800, 0x2000),
+        ];
+        pretty_assertions::assert_eq!(chunks(AlignedChunks::new(&regions, 10)), vec![(0x21000, 0)]);
+    }
+
+    #[test]
+    fn region_at_top_of_address_space_does_not_overflow() {
+        let regions = [
+            usable(u64::MAX - 0x2FFF, 0x3000),
+            usable(u64::MAX - 10, 100),
+        ];
+        let got = chunks(AlignedChunks::new(&regions, 63));
+        pretty_assertions::assert_eq!(got, vec![(u64::MAX - 0x2FFF, 0), (u64::MAX - 0x1FFF, 0)]);
+    }
+
+    #[test]
+    fn runs_join_adjacent_regions() {
+        let regions = [
+            usable(0x1000, 0x2000),
+            usable(0x3000, 0x1000),
+            usable(0x10000, 0x1800),
+        ];
+        let runs: Vec<(u64, u64)> = UsableRuns::new(&regions).map(|(f, n)| (f.0, n)).collect();
+        pretty_assertions::assert_eq!(runs, vec![(0x1000, 3), (0x10000, 1)]);
+    }
+
+    #[test]
+    fn runs_do_not_double_count_overlaps() {
+        let regions = [
+            usable(0, 0x4000),
+            usable(0x2000, 0x1000),
+            usable(0x3000, 0x3000),
+        ];
+        let runs: Vec<(u64, u64)> = UsableRuns::new(&regions).map(|(f, n)| (f.0, n)).collect();
+        pretty_assertions::assert_eq!(runs, vec![(0, 6)]);
+    }
+
+    #[test]
+    fn runs_skip_reserved_between_usable() {
+        let regions = [
+            usable(0, 0x1000),
+            MemRegion {
+                start: 0x1000,
+                len: 0x1000,
+                kind: 2,
+            },
+            usable(0x2000, 0x1000),
+        ];
+        let runs: Vec<(u64, u64)> = UsableRuns::new(&regions).map(|(f, n)| (f.0, n)).collect();
+        pretty_assertions::assert_eq!(runs, vec![(0, 1), (0x2000, 1)]);
+    }
+
+    #[test]
+    fn usable_frames_follow_order() {
+        let regions = [
+            usable(0x1000, 0x2000),
+            MemRegion {
+                start: 0x3000,
+                len: 0x1000,
+                kind: 2,
+            },
+            usable(0x10000, 0x2000),
+        ];
+        let walk = |order| -> Vec<u64> {
+            UsableFrames::with_order(&regions, order)
+                .map(|f| f.0)
+                .collect()
+        };
+        pretty_assertions::assert_eq!(
+            walk(Order::LowFirst),
+            vec![0x1000, 0x2000, 0x10000, 0x11000]
+        );
+        pretty_assertions::assert_eq!(
+            walk(Order::HighFirst),
+            vec![0x11000, 0x10000, 0x2000, 0x1000]
+        );
+        pretty_assertions::assert_eq!(
+            walk(Order::PerRegionHighFirst),
+            vec![0x2000, 0x1000, 0x11000, 0x10000]
+        );
+    }
+
+    #[test]
+    fn overlapping_usable_regions_yield_each_frame_once() {
+        let regions = [
+            usable(0, 0x4000),
+            usable(0x1000, 0x1000),
+            usable(0x3000, 0x3000),
+        ];
+        for order in [Order::LowFirst, Order::HighFirst, Order::PerRegionHighFirst] {
+            let mut frames: Vec<u64> = UsableFrames::with_order(&regions, order)
+                .map(|f| f.0)
+                .collect();
+            frames.sort_unstable();
+            pretty_assertions::assert_eq!(
+                frames,
+                vec![0, 0x1000, 0x2000, 0x3000, 0x4000, 0x5000],
+                "{order:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn alignment_boundaries_and_adjacent_partial_frames() {
+        let regions = [
+            // Ends exactly on a frame boundary: last frame included.
+            usable(0, 0x2000),
+            // Shares a partial frame with its neighbour; neither gets it.
+            usable(0x2000, 0x1800),
+            usable(0x3800, 0x1800),
+            // Smaller than a frame, and straddling a boundary.
+            usable(0x6800, 0x1000),
+            // Empty.
+            usable(0x8000, 0),
+        ];
+        let frames: Vec<u64> = UsableFrames::new(&regions).map(|f| f.0).collect();
+        pretty_assertions::assert_eq!(frames, vec![0, 0x1000, 0x2000, 0x4000]);
+    }
+
+    #[test]
+    fn exhausted_and_empty_maps_stay_empty() {
+        assert!(UsableFrames::new(&[]).next().is_none());
+        let regions = [usable(0x1000, 0x1000)];
+        let mut it = UsableFrames::new(&regions);
+        pretty_assertions::assert_eq!(it.next(), Some(PhysFrame(0x1000)));
+        for _ in 0..3 {
+            pretty_assertions::assert_eq!(it.next(), None);
+        }
+        assert!(it.allocate_aligned::<FRAME_SIZE>().is_none());
+    }
+
+    proptest! {
+        #[test]
+        fn frames_are_unique_whole_and_usable(
+            input in proptest::collection::vec((0u64..64, 0u64..16, 1u32..3, 0u64..4), 0..12),
+            high in any::<bool>(),
+        ) {
+            // Page-ish units with some sub-frame jitter on the start.
+            let regions: Vec<MemRegion> = input
+                .iter()
+                .map(|&(s, l, k, j)| MemRegion {
+                    start: s * 0x1000 + j * 0x400,
+                    len: l * 0x1000,
+                    kind: k,
+                })
+                .collect();
+            let order = if high { Order::HighFirst } else { Order::LowFirst };
+            let mut frames: Vec<u64> = UsableFrames::with_order(&regions, order)
+                .map(|f| f.0)
+                .collect();
+            let n = frames.len();
+            frames.sort_unstable();
+            frames.dedup();
+            prop_assert_eq!(frames.len(), n);
+            for f in &frames {
+                prop_assert!(f % FRAME_SIZE == 0);
+                prop_assert!(regions
+                    .iter()
+                    .any(|r| r.kind == 1 && r.start <= *f && f + FRAME_SIZE <= r.end()));
+            }
+        }
+    }
+
+    #[test]
+    fn frames_counted_without_walking() {
+        let clean = [
+            usable(0x1000, 0x7FFF_F000),
+            MemRegion {
+                start: 0x8000_0000,
+                len: 0x1000,
+                kind: 2,
+            },
+        ];
+        pretty_assertions::assert_eq!(usable_frame_count(&clean), 0x7FFFF);
+
+        // Bad RAM inside a usable region, and two usable regions overlapping.
+        let messy = [
+            usable(0x1000, 0x8000),
+            MemRegion {
+                start: 0x3800,
+                len: 0x1000,
+                kind: kind::BAD_RAM,
+            },
+            usable(0x6000, 0x6000),
+        ];
+        let mut frames = UsableFrames::new(&messy);
+        pretty_assertions::assert_eq!(frames.len(), 9);
+        pretty_assertions::assert_eq!(usable_frame_count(&messy), 9);
+        frames.next();
+        frames.next_back();
+        pretty_assertions::assert_eq!(frames.size_hint(), (7, Some(7)));
+        pretty_assertions::assert_eq!(frames.count(), 7);
+    }
+
+    #[test]
+    fn double_ended_and_highest_first() {
+        let regions = [
+            usable(0x1000, 0x2000),
+            MemRegion {
+                start: 0x2000,
+                len: 0x1000,
+                kind: kind::BAD_RAM,
+            },
+            usable(0x8000, 0x3000),
+        ];
+        let back: Vec<u64> = UsableFrames::new(&regions).rev().map(|f| f.0).collect();
+        pretty_assertions::assert_eq!(back, vec![0xA000, 0x9000, 0x8000, 0x1000]);
+
+        // Both ends share the last region without handing a frame out twice.
+        let mut it = UsableFrames::new(&regions);
+        pretty_assertions::assert_eq!(it.next(), Some(PhysFrame(0x1000)));
+        pretty_assertions::assert_eq!(it.next_back(), Some(PhysFrame(0xA000)));
+        pretty_assertions::assert_eq!(it.next(), Some(PhysFrame(0x8000)));
+        pretty_assertions::assert_eq!(it.next_back(), Some(PhysFrame(0x9000)));
+        pretty_assertions::assert_eq!((it.next(), it.next_back()), (None, None));
+
+        let mut bump = BumpAllocator::new(&regions).highest_first();
+        pretty_assertions::assert_eq!(bump.allocate(), Some(PhysFrame(0xA000)));
+        let mut storage = [0u64; 2];
+        let mut bitmap = BitmapAllocator::new(&regions, &mut storage)
+            .unwrap()
+            .highest_first();
+        pretty_assertions::assert_eq!(bitmap.allocate(), Some(PhysFrame(0xA000)));
+        pretty_assertions::assert_eq!(bitmap.allocate(), Some(PhysFrame(0x9000)));
+        bitmap.deallocate(PhysFrame(0xA000));
+        pretty_assertions::assert_eq!(bitmap.allocate(), Some(PhysFrame(0xA000)));
+    }
+
+    proptest! {
+        // Any mix of next and next_back yields exactly the forward
+        // sequence: the front a prefix of it, the back the rest reversed.
+        #[test]
+        fn double_ended_meets_in_the_middle(
+            input in proptest::collection::vec((0u64..64, 0u64..16, 0usize..3, 0u64..4), 0..12),
+            order in prop_oneof![
+                Just(Order::LowFirst),
+                Just(Order::HighFirst),
+                Just(Order::PerRegionHighFirst),
+            ],
+            pattern in proptest::collection::vec(any::<bool>(), 1..64),
+        ) {
+            let regions: Vec<MemRegion> = input
+                .iter()
+                .map(|&(s, l, k, j)| MemRegion {
+                    start: s * 0x1000 + j * 0x400,
+                    len: l * 0x1000,
+                    kind: [kind::USABLE, kind::RESERVED, kind::BAD_RAM][k],
+                })
+                .collect();
+            let forward: Vec<PhysFrame> = UsableFrames::with_order(&regions, order).collect();
+
+            prop_assert_eq!(usable_frame_count(&regions), forward.len() as u64);
+
+            let mut it = UsableFrames::with_order(&regions, order);
+            let (mut front, mut back) = (Vec::new(), Vec::new());
+            for &from_back in pattern.iter().cycle() {
+                prop_assert_eq!(it.len(), forward.len() - front.len() - back.len());
+                let got = if from_back { it.next_back() } else { it.next() };
+                match got {
+                    Some(f) if from_back => back.push(f),
+                    Some(f) => front.push(f),
+                    None => break,
+                }
+            }
+            prop_assert_eq!((it.next(), it.next_back()), (None, None));
+            front.extend(back.into_iter().rev());
+            prop_assert_eq!(front, forward);
+        }
+    }
+
+    #[test]
+    fn skip_head_applies_to_each_usable_region() {
+        let regions = [
+            usable(0x1000, 0x4000),
+            usable(0x10800, 0x3800),
+            usable(0x20000, 0x1000),
+        ];
+        let got: Vec<u64> = UsableFrames::new(&regions)
+            .skip_head(0x2000)
+            .map(|f| f.0)
+            .collect();
+        // 0x10800 + 0x2000 rounds up to 0x13000, the region's last full frame;
+        // the third region is smaller than the skip.
+        pretty_assertions::assert_eq!(got, vec![0x3000, 0x4000, 0x13000]);
+
+        let top = [usable(u64::MAX - 0x3FFF, 0x4000)];
+        pretty_assertions::assert_eq!(UsableFrames::new(&top).skip_head(u64::MAX).count(), 0);
+    }
+
+    #[test]
+    fn starting_after_skips_the_kernel_image() {
+        let regions = [usable(0, 0x3000), usable(0x10_0000, 0x4000)];
+        // Kernel loaded at 1 MiB, ending partway into its second frame.
+        let got: Vec<u64> = UsableFrames::new(&regions)
+            .starting_after(0x10_1800)
+            .map(|f| f.0)
+            .collect();
+        pretty_assertions::assert_eq!(got, vec![0x10_2000, 0x10_3000]);
+
+        let high: Vec<u64> = UsableFrames::with_order(&regions, Order::HighFirst)
+            .starting_after(0x10_3000)
+            .map(|f| f.0)
+            .collect();
+        pretty_assertions::assert_eq!(high, vec![0x10_3000]);
+
+        let mut bump =
+            BumpAllocator::from_frames(UsableFrames::new(&regions).starting_after(0x1000));
+        pretty_assertions::assert_eq!(bump.allocate(), Some(PhysFrame(0x1000)));
+        pretty_assertions::assert_eq!(
+            UsableFrames::new(&regions).starting_after(u64::MAX).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn soft_reserved_and_hot_pluggable_are_opt_in() {
+        let regions = [
+            usable(0, 0x1000),
+            MemRegion {
+                start: 0x1000,
+                len: 0x1000,
+                kind: kind::SOFT_RESERVED,
+            },
+            MemRegion {
+                start: 0x2000,
+                len: 0x1000,
+                kind: kind::HOT_PLUGGABLE,
+            },
+        ];
+        pretty_assertions::assert_eq!(UsableFrames::new(&regions).count(), 1);
+        let sp: Vec<u64> = UsableFrames::new(&regions)
+            .claim_soft_reserved()
+            .map(|f| f.0)
+            .collect();
+        pretty_assertions::assert_eq!(sp, vec![0, 0x1000]);
+        let hot: Vec<u64> = UsableFrames::new(&regions)
+            .claim_hot_pluggable()
+            .map(|f| f.0)
+            .collect();
+        pretty_assertions::assert_eq!(hot, vec![0, 0x2000]);
+    }
+
+    #[test]
+    fn huge_frames_2m_and_1g() {
+        let regions = [
+            usable(0x1000, 4 * MIB),
+            usable(GIB - 2 * MIB, GIB + 4 * MIB),
+        ];
+        let two_m: Vec<u64> = UsableFrames::<{ 2 * MIB }>::sized(&regions)
+            .map(|f| f.0)
+            .collect();
+        pretty_assertions::assert_eq!(two_m.first(), Some(&(2 * MIB)));
+        pretty_assertions::assert_eq!(two_m.len(), 1 + 514);
+        assert!(two_m.iter().all(|f| f % (2 * MIB) == 0));
+
+        let one_g: Vec<u64> = UsableFrames::<GIB>::sized_with_order(&regions, Order::HighFirst)
+            .map(|f| f.0)
+            .collect();
+        pretty_assertions::assert_eq!(one_g, vec![GIB]);
+
+        // A small block cut from a huge frame costs the rest of it.
+        let mut it = UsableFrames::<{ 2 * MIB }>::sized(&regions);
+        pretty_assertions::assert_eq!(it.allocate_aligned::<FRAME_SIZE>().unwrap().addr(), 2 * MIB);
+        pretty_assertions::assert_eq!(it.next(), Some(PhysFrame(GIB - 2 * MIB)));
+    }
+
+    #[test]
+    fn allocate_aligned_blocks() {
+        let regions = [usable(0x1000, 0x1000), usable(0x1F_F000, 0x40_2000)];
+        let mut low = UsableFrames::new(&regions);
+        let a = low.allocate_aligned::<{ 2 * MIB }>().unwrap();
+        pretty_assertions::assert_eq!(a.addr(), 2 * MIB);
+        pretty_assertions::assert_eq!(
+            low.allocate_aligned::<{ 2 * MIB }>().unwrap().addr(),
+            4 * MIB
+        );
+        assert!(low.allocate_aligned::<{ 2 * MIB }>().is_none());
+        // What remains after the last block is still handed out frame by frame.
+        pretty_assertions::assert_eq!(low.next(), Some(PhysFrame(0x60_0000)));
+
+        let mut high = UsableFrames::with_order(&regions, Order::HighFirst);
+        pretty_assertions::assert_eq!(
+            high.allocate_aligned::<{ 64 * 1024 }>().unwrap().addr(),
+            0x5F_0000
+        );
+
+        let small: AlignedFrame<FRAME_SIZE> = a.weaken();
+        pretty_assertions::assert_eq!(small.frame(), PhysFrame(2 * MIB));
+        pretty_assertions::assert_eq!(AlignedFrame::<{ 2 * MIB }>::new(0x1000), None);
+    }
+
+    #[test]
+    fn bad_ram_inside_usable_is_never_handed_out() {
+        // Raw, un-normalized map: bad RAM overlapping the start of one
+        // usable region, the end of another, and the middle of a third.
+        let bad = |start, len| MemRegion {
+            start,
+            len,
+            kind: kind::BAD_RAM,
+        };
+        let regions = [
+            usable(0, 0x4000),
+            bad(0x3800, 0x1800),
+            usable(0x5000, 0x3000),
+            bad(0x6000, 0x800),
+            usable(0x9000, 0x2000),
+            bad(0x8800, 0x1000),
+        ];
+        let low: Vec<u64> = UsableFrames::new(&regions).map(|f| f.0).collect();
+        pretty_assertions::assert_eq!(low, vec![0, 0x1000, 0x2000, 0x5000, 0x7000, 0xA000]);
+        let mut high: Vec<u64> = UsableFrames::with_order(&regions, Order::HighFirst)
+            .map(|f| f.0)
+            .collect();
+        high.reverse();
+        pretty_assertions::assert_eq!(high, low);
+
+        // Aligned blocks step over it too.
+        let regions = [usable(0, 0x4_0000), bad(0x1_8000, 0x1000)];
+        let mut blocks = UsableFrames::new(&regions);
+        let got: Vec<u64> = core::iter::from_fn(|| blocks.allocate_aligned::<0x1_0000>())
+            .map(|b| b.addr())
+            .collect();
+        pretty_assertions::assert_eq!(got, vec![0, 0x2_0000, 0x3_0000]);
+    }
+
+    #[test]
+    fn phys_frames_order_by_address() {
+        assert!(PhysFrame(0x1000) < PhysFrame(0x2000));
+        pretty_assertions::assert_eq!(usable(0, 0x3000).frames().max(), Some(PhysFrame(0x2000)));
+    }
+
+    #[test]
+    fn region_frames_4k_and_2m() {
+        let r = usable(0x1800, 0x40_0000);
+        let frames: Vec<u64> = r.frames().map(|f| f.0).take(3).collect();
+        pretty_assertions::assert_eq!(frames, vec![0x2000, 0x3000, 0x4000]);
+        pretty_assertions::assert_eq!(r.frames().len(), 0x3FF);
+
+        let huge: Vec<u64> = r.frames_with_size(2 * MIB).map(|f| f.0).collect();
+        pretty_assertions::assert_eq!(huge, vec![0x20_0000]);
+    }
+
+    #[test]
+    fn frame_ranges_split_intersect_and_iterate() {
+        let r = usable(0x1800, 0x5000).frame_range();
+        pretty_assertions::assert_eq!((r.start, r.end), (PhysFrame(0x2000), PhysFrame(0x6000)));
+        pretty_assertions::assert_eq!(r.len(), 4);
+        assert!(r.contains(PhysFrame(0x5000)) && !r.contains(PhysFrame(0x6000)));
+        pretty_assertions::assert_eq!(
+            r.iter().collect::<Vec<_>>(),
+            usable(0x1800, 0x5000).frames().collect::<Vec<_>>()
+        );
+
+        let (a, b) = r.split_at(1);
+        pretty_assertions::assert_eq!((a.len(), b.start), (1, PhysFrame(0x3000)));
+        pretty_assertions::assert_eq!(
+            r.split_at(9),
+            (
+                r,
+                PhysFrameRange {
+                    start: r.end,
+                    end: r.end
+                }
+            )
+        );
+
+        let other = usable(0x5000, 0x8000).frame_range();
+        pretty_assertions::assert_eq!(
+            r.intersect(other),
+            Some(PhysFrameRange {
+                start: PhysFrame(0x5000),
+                end: PhysFrame(0x6000)
+            })
+        );
+        pretty_assertions::assert_eq!(a.intersect(b), None);
+
+        // Too small for a whole frame: empty, and nothing to iterate.
+        let empty = usable(0x1100, 0x10).frame_range();
+        assert!(empty.is_empty());
+        pretty_assertions::assert_eq!((empty.len(), empty.into_iter().count()), (0, 0));
+    }
+
+    #[test]
+    fn region_frames_ignore_kind_and_handle_degenerate_regions() {
+        let reserved = MemRegion {
+            start: 0x1000,
+            len: 0x2000,
+            kind: 2,
+        };
+        pretty_assertions::assert_eq!(reserved.frames().count(), 2);
+        pretty_assertions::assert_eq!(usable(0x1001, 0x1000).frames().count(), 0);
+        pretty_assertions::assert_eq!(usable(u64::MAX - 0x800, 0x800).frames().count(), 0);
+    }
+
+    // A subsystem that only knows there is some frame allocator.
+    fn page_table_frames(frames: &mut dyn FrameAlloc, n: usize) -> Vec<u64> {
+        (0..n)
+            .filter_map(|_| frames.alloc_frame())
+            .map(|f| f.0)
+            .collect()
+    }
+
+    #[test]
+    fn one_dyn_slot_points_at_each_phase_in_turn() {
+        let regions = [usable(0, 0x40_0000)];
+        let mut bump = BumpAllocator::new(&regions);
+        let mut bitmap_storage = vec![0; BitmapAllocator::storage_words(&regions)];
+        let mut bitmap = BitmapAllocator::new_used(&regions, &mut bitmap_storage).unwrap();
+        let mut buddy_storage = vec![0; BuddyAllocator::storage_words(&regions)];
+        let mut buddy = BuddyAllocator::new_used(&regions, &mut buddy_storage).unwrap();
+        unsafe {
+            bitmap.dealloc_frame(PhysFrame(0x10_0000));
+            buddy.dealloc_frame(PhysFrame(0x20_0000));
+        }
+
+        let mut got = Vec::new();
+        let mut contiguous = Vec::new();
+        for current in [&mut bump as &mut dyn FrameAlloc, &mut bitmap, &mut buddy] {
+            got.extend(page_table_frames(current, 1));
+            contiguous.push(current.alloc_contiguous(1, 0).map(|r| r.start.0));
+        }
+        pretty_assertions::assert_eq!(got, vec![0, 0x10_0000, 0x20_0000]);
+        // Bump cannot promise contiguity; the others had nothing left.
+        pretty_assertions::assert_eq!(contiguous, vec![None, None, None]);
+    }
+
+    #[test]
+    fn boot_phases_behind_dyn_frame_alloc() {
+        let regions = [usable(0, 0x8000)];
+        let mut bump = BumpAllocator::new(&regions);
+        let current: &mut dyn FrameAlloc = &mut bump;
+        let early = [current.alloc_frame(), current.alloc_frame()];
+        pretty_assertions::assert_eq!(early, [Some(PhysFrame(0)), Some(PhysFrame(0x1000))]);
+
+        // Hand over: the bitmap starts full and gets what bump never gave out.
+        let mut storage = [0u64; 2];
+        let mut bitmap = BitmapAllocator::new_used(&regions, &mut storage).unwrap();
+        pretty_assertions::assert_eq!(bump.drain_into(&mut bitmap, &[]), 6);
+        assert!(bitmap.is_allocated(PhysFrame(0x1000)));
+        pretty_assertions::assert_eq!(bitmap.free_count(), 6);
+
+        let current: &mut dyn FrameDealloc = &mut bitmap;
+        unsafe { current.dealloc_frame(PhysFrame(0)) };
+        let current: &mut dyn FrameAlloc = &mut bitmap;
+        pretty_assertions::assert_eq!(current.alloc_frame(), Some(PhysFrame(0)));
+
+        // Same handover into a buddy allocator coalesces as it goes.
+        let mut storage = vec![0; BuddyAllocator::storage_words(&regions)];
+        let mut buddy = BuddyAllocator::new_used(&regions, &mut storage).unwrap();
+        let mut bump = BumpAllocator::new(&regions);
+        bump.allocate();
+        pretty_assertions::assert_eq!(bump.drain_into(&mut buddy, &[]), 7);
+        pretty_assertions::assert_eq!((buddy.free_blocks(2), buddy.free_count()), (1, 7));
+    }
+
+    #[test]
+    fn bump_allocator_counts_and_runs_out() {
+        let regions = [
+            usable(0x1000, 0x2000),
+            MemRegion {
+                start: 0x3000,
+                len: 0x1000,
+                kind: 2,
+            },
+            usable(0x4800, 0x1800),
+        ];
+        let mut bump = BumpAllocator::new(&regions);
+        pretty_assertions::assert_eq!(bump.allocated_count(), 0);
+        let got: Vec<u64> = core::iter::from_fn(|| bump.allocate())
+            .map(|f| f.0)
+            .collect();
+        pretty_assertions::assert_eq!(got, vec![0x1000, 0x2000, 0x5000]);
+        pretty_assertions::assert_eq!(bump.allocated_count(), 3);
+        pretty_assertions::assert_eq!(bump.allocate(), None);
+        pretty_assertions::assert_eq!(bump.allocated_count(), 3);
+
+        let mut high =
+            BumpAllocator::from_frames(UsableFrames::with_order(&regions, Order::HighFirst));
+        pretty_assertions::assert_eq!(high.allocate(), Some(PhysFrame(0x5000)));
+    }
+
+    #[test]
+    fn drain_holds_back_frames_in_use_elsewhere() {
+        let regions = [usable(0, 0x8000)];
+        let mut bump = BumpAllocator::new(&regions);
+        bump.allocate();
+        // The kernel image sits at 0x3000..0x5000 without bump knowing.
+        let image = PhysFrameRange {
+            start: PhysFrame(0x3000),
+            end: PhysFrame(0x5000),
+        };
+        let mut storage = vec![0; BitmapAllocator::storage_words(&regions)];
+        let mut bitmap = BitmapAllocator::new_used(&regions, &mut storage).unwrap();
+        pretty_assertions::assert_eq!(bump.drain_into(&mut bitmap, &[image]), 5);
+        let used: Vec<u64> = (0..8)
+            .map(|i| PhysFrame(i * FRAME_SIZE))
+            .filter(|&f| bitmap.is_allocated(f))
+            .map(|f| f.0)
+            .collect();
+        pretty_assertions::assert_eq!(used, vec![0, 0x3000, 0x4000]);
     }
 }
@@ -0,0 +1,488 @@
+// bitmap.rs
+//
+// One bit per frame, from the lowest usable frame to the highest:
+//
+//   bit set   = in use (allocated, or not usable memory at all)
+//   bit clear = free
+//
+// Unlike the bump allocator, frames can be given back. A second bitmap
+// of the same size remembers which frames are usable memory at all, so
+// giving back a frame of a hole or of reserved memory is caught instead
+// of putting it into circulation. The cost is the two bitmaps, 64 KiB
+// per GiB covered, which have to live somewhere before there is a heap:
+// either the caller hands in a slice, or init() takes frames out of the
+// usable memory it is about to manage.
+//
+// Holes in the map still cost bits (they are just never clear), so a
+// map with a few bytes of RAM at 64 GiB pays for the whole 64 GiB.
+// Input should be normalized: bad RAM inside a usable region is not
+// looked for here.
+
+use core::slice;
+
+use super::{align_up, PhysFrame, PhysFrameRange, UsableRuns, Zone, FRAME_SIZE};
+use crate::mapper::PhysMapper;
+use crate::raw::MemRegion;
+
+const BITS: u64 = u64::BITS as u64;
+
+#[derive(Debug)]
+pub struct BitmapAllocator<'a> {
+    bits: &'a mut [u64],
+    /// Set for frames this allocator hands out and takes back.
+    managed: &'a mut [u64],
+    /// Address of the frame bit 0 stands for.
+    base: u64,
+    /// Frames the bitmap covers, usable or not.
+    frames: u64,
+    free: u64,
+    /// Word to start the next search at.
+    next: usize,
+    /// Search downwards from the top of memory.
+    top_down: bool,
+}
+
+impl<'a> BitmapAllocator<'a> {
+    /// Words of storage `regions` needs, for both bitmaps.
+    pub fn storage_words(regions: &[MemRegion]) -> usize {
+        2 * span(regions).map_or(0, |(base, end)| {
+            ((end - base) / FRAME_SIZE).div_ceil(BITS) as usize
+        })
+    }
+
+    /// Frames of memory the bitmap for `regions` takes up.
+    pub fn storage_frames(regions: &[MemRegion]) -> u64 {
+        (Self::storage_words(regions) as u64 * 8).div_ceil(FRAME_SIZE)
+    }
+
+    /// An allocator over `regions` keeping its bitmap in `storage`, e.g.
+    /// a static array. None if `storage` is shorter than
+    /// [`storage_words`](Self::storage_words).
+    pub fn new(regions: &[MemRegion], storage: &'a mut [u64]) -> Option<Self> {
+        let words = Self::storage_words(regions);
+        let storage = storage.get_mut(..words)?;
+        Some(Self::fill(regions, storage))
+    }
+
+    /// [`new`](Self::new), but with every frame allocated. For taking
+    /// over from an allocator that hands its free frames back through
+    /// [`FrameDealloc`](super::FrameDealloc).
+    pub fn new_used(regions: &[MemRegion], storage: &'a mut [u64]) -> Option<Self> {
+        let mut this = Self::new(regions, storage)?;
+        this.bits.fill(u64::MAX);
+        this.free = 0;
+        Some(this)
+    }
+
+    /// An allocator over `regions` keeping its bitmap in the first
+    /// usable run big enough for it. Those frames show up as allocated.
+    /// None if no run is big enough.
+    ///
+    /// # Safety
+    /// Every usable frame in `regions` must be unused, and the mapping
+    /// `mapper` returns for the bitmap must stay valid, and alias nothing
+    /// else, for `'a`. It is never unmapped.
+    pub unsafe fn init<M: PhysMapper>(regions: &[MemRegion], mapper: &mut M) -> Option<Self> {
+        let words = Self::storage_words(regions);
+        let need = Self::storage_frames(regions);
+        let (at, _) = UsableRuns::new(regions).find(|&(_, count)| count >= need)?;
+        let virt = mapper.map(at.0, words * 8) as *mut u64;
+        let mut this = Self::fill(regions, slice::from_raw_parts_mut(virt, words));
+        for i in 0..need {
+            let frame = PhysFrame(at.0 + i * FRAME_SIZE);
+            this.take(frame);
+            // Giving back the bitmap's own frames is a bug, not a free.
+            let i = this.index(frame).expect("frame inside the bitmap");
+            this.managed[(i / BITS) as usize] &= !(1 << (i % BITS));
+        }
+        Some(this)
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(regions = regions.len()))
+    )]
+    fn fill(regions: &[MemRegion], storage: &'a mut [u64]) -> Self {
+        let (base, end) = span(regions).unwrap_or((0, 0));
+        let (bits, managed) = storage.split_at_mut(storage.len() / 2);
+        bits.fill(u64::MAX);
+        managed.fill(0);
+        let mut this = BitmapAllocator {
+            bits,
+            managed,
+            base,
+            frames: (end - base) / FRAME_SIZE,
+            free: 0,
+            next: 0,
+            top_down: false,
+        };
+        // Runs never overlap, so every bit is cleared at most once and
+        // whole words go in one store.
+        for (first, count) in UsableRuns::new(regions) {
+            let mut i = (first.0 - base) / FRAME_SIZE;
+            let end = i + count;
+            while i < end {
+                let (word, bit) = ((i / BITS) as usize, i % BITS);
+                let n = (BITS - bit).min(end - i);
+                let mask = if n == BITS {
+                    u64::MAX
+                } else {
+                    ((1 << n) - 1) << bit
+                };
+                this.bits[word] &= !mask;
+                this.managed[word] |= mask;
+                i += n;
+            }
+            this.free += count;
+        }
+        this
+    }
+
+    /// Hand out the highest free frame first, keeping low memory (SMP
+    /// trampoline, legacy DMA) free for as long as possible.
+    pub fn highest_first(mut self) -> Self {
+        self.top_down = true;
+        self.next = self.bits.len().saturating_sub(1);
+        self
+    }
+
+    /// A free frame, lowest first after the last one handed out (highest
+    /// first, below it, with [`highest_first`](Self::highest_first)).
+    /// None once every frame is in use.
+    pub fn allocate(&mut self) -> Option<PhysFrame> {
+        let words = self.bits.len();
+        let word = (0..words)
+            .map(|i| match self.top_down {
+                false => (self.next + i) % words,
+                true => (self.next + words - i) % words,
+            })
+            .find(|&w| self.bits[w] != u64::MAX)?;
+        let bit = match self.top_down {
+            false => self.bits[word].trailing_ones(),
+            true => BITS as u32 - 1 - (!self.bits[word]).leading_zeros(),
+        } as u64;
+        self.bits[word] |= 1 << bit;
+        self.free -= 1;
+        self.next = word;
+        Some(self.frame(word as u64 * BITS + bit))
+    }
+
+    /// The lowest free frame in `zone`, or failing that in the zones
+    /// below it (see [`Zone::fallbacks`]).
+    pub fn alloc_in_zone(&mut self, zone: Zone) -> Option<PhysFrame> {
+        let frame = zone
+            .fallbacks()
+            .find_map(|z| self.first_free_in(z.range()))?;
+        self.take(frame);
+        Some(frame)
+    }
+
+    /// `count` free frames in a row, the first aligned to `align` bytes
+    /// (a power of two; 0 or anything up to a frame means frame aligned).
+    /// None if there is no such run. Give them back one by one.
+    ///
+    /// First fit from the bottom of memory, checking each candidate bit
+    /// by bit: fine for the occasional DMA buffer, slow for hot paths.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self)))]
+    pub fn alloc_contiguous(&mut self, count: usize, align: u64) -> Option<PhysFrameRange> {
+        let (count, align) = (count as u64, align.max(FRAME_SIZE));
+        if count == 0 || !align.is_power_of_two() {
+            return None;
+        }
+        let step = align / FRAME_SIZE;
+        // Bit numbers count from `base`, alignment from address 0.
+        let first = (align_up(self.base, align)? - self.base) / FRAME_SIZE;
+        let mut i = first;
+        while i + count <= self.frames {
+            match (i..i + count).find(|&j| self.used(j)) {
+                // Restart at the first aligned frame past the used one.
+                Some(j) => i = first + (j + 1 - first).next_multiple_of(step),
+                None => {
+                    for j in i..i + count {
+                        self.bits[(j / BITS) as usize] |= 1 << (j % BITS);
+                    }
+                    self.free -= count;
+                    return Some(PhysFrameRange {
+                        start: self.frame(i),
+                        end: self.frame(i + count),
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Give `frame` back.
+    ///
+    /// # Panics
+    /// If `frame` is not an allocated frame of usable memory in this map
+    /// (a double free, or a frame it never handed out).
+    pub fn deallocate(&mut self, frame: PhysFrame) {
+        let i = self
+            .index(frame)
+            .filter(|&i| self.managed[(i / BITS) as usize] & (1 << (i % BITS)) != 0)
+            .expect("frame not managed by this allocator");
+        let (word, bit) = ((i / BITS) as usize, i % BITS);
+        assert!(
+            self.bits[word] & (1 << bit) != 0,
+            "double free of {frame:?}"
+        );
+        self.bits[word] &= !(1 << bit);
+        self.free += 1;
+        self.next = match self.top_down {
+            false => self.next.min(word),
+            true => self.next.max(word),
+        };
+    }
+
+    /// Whether `frame` is in use. Frames the map has no usable memory
+    /// for always are.
+    pub fn is_allocated(&self, frame: PhysFrame) -> bool {
+        self.index(frame).is_none_or(|i| self.used(i))
+    }
+
+    /// Frames free right now.
+    pub fn free_count(&self) -> u64 {
+        self.free
+    }
+
+    // Lowest free frame in [lo, hi), a word at a time.
+    fn first_free_in(&self, (lo, hi): (u64, u64)) -> Option<PhysFrame> {
+        let to = (hi.saturating_sub(self.base) / FRAME_SIZE).min(self.frames);
+        let mut i = lo.saturating_sub(self.base).div_ceil(FRAME_SIZE);
+        while i < to {
+            // Bits below i count as used.
+            let word = self.bits[(i / BITS) as usize] | ((1 << (i % BITS)) - 1);
+            if word != u64::MAX {
+                let j = i - i % BITS + word.trailing_ones() as u64;
+                return (j < to).then(|| self.frame(j));
+            }
+            i = i - i % BITS + BITS;
+        }
+        None
+    }
+
+    fn used(&self, i: u64) -> bool {
+        self.bits[(i / BITS) as usize] & (1 << (i % BITS)) != 0
+    }
+
+    // Mark a free frame allocated.
+    fn take(&mut self, frame: PhysFrame) {
+        let i = self.index(frame).expect("frame inside the bitmap");
+        self.bits[(i / BITS) as usize] |= 1 << (i % BITS);
+        self.free -= 1;
+    }
+
+    fn frame(&self, i: u64) -> PhysFrame {
+        PhysFrame(self.base + i * FRAME_SIZE)
+    }
+
+    // Bit number of `frame`, if it is a frame the bitmap covers.
+    fn index(&self, frame: PhysFrame) -> Option<u64> {
+        let off = frame.0.checked_sub(self.base)?;
+        let i = off / FRAME_SIZE;
+        (off % FRAME_SIZE == 0 && i < self.frames).then_some(i)
+    }
+}
+
+// [lowest usable frame, end of the highest usable frame), if any.
+fn span(regions: &[MemRegion]) -> Option<(u64, u64)> {
+    UsableRuns::new(regions)
+        .map(|(first, count)| (first.0, first.0 + count * FRAME_SIZE))
+        .reduce(|(a, b), (c, d)| (a.min(c), b.max(d)))
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    /// Fake RAM starting at `base`.
+    struct Ram {
+        base: u64,
+        mem: Vec<u64>,
+    }
+
+    impl PhysMapper for Ram {
+        unsafe fn map(&mut self, phys: u64, len: usize) -> *mut u8 {
+            let off = ((phys - self.base) / 8) as usize;
+            assert!(
+                off * 8 + len <= self.mem.len() * 8,
+                "mapped outside fake RAM"
+            );
+            self.mem.as_mut_ptr().add(off) as *mut u8
+        }
+    }
+
+    #[test]
+    fn allocates_usable_frames_and_takes_them_back() {
+        init();
+        let map = [
+            region(0x1000, 0x2000, 1),
+            region(0x3000, 0x1000, 2),
+            region(0x4000, 0x1000, 1),
+        ];
+        pretty_assertions::assert_eq!(BitmapAllocator::storage_words(&map), 2);
+        let mut storage = [0u64; 2];
+        let mut bitmap = BitmapAllocator::new(&map, &mut storage).unwrap();
+        pretty_assertions::assert_eq!(bitmap.free_count(), 3);
+        assert!(bitmap.is_allocated(PhysFrame(0x3000)));
+        assert!(bitmap.is_allocated(PhysFrame(0x10_0000)));
+
+        let got: Vec<u64> = core::iter::from_fn(|| bitmap.allocate())
+            .map(|f| f.0)
+            .collect();
+        pretty_assertions::assert_eq!(got, vec![0x1000, 0x2000, 0x4000]);
+        pretty_assertions::assert_eq!(bitmap.free_count(), 0);
+
+        bitmap.deallocate(PhysFrame(0x2000));
+        assert!(!bitmap.is_allocated(PhysFrame(0x2000)));
+        pretty_assertions::assert_eq!(bitmap.allocate(), Some(PhysFrame(0x2000)));
+        pretty_assertions::assert_eq!(bitmap.allocate(), None);
+    }
+
+    #[test]
+    fn storage_too_small_or_empty_map() {
+        let map = [region(0, 65 * FRAME_SIZE, 1)];
+        pretty_assertions::assert_eq!(BitmapAllocator::storage_words(&map), 4);
+        assert!(BitmapAllocator::new(&map, &mut [0; 3]).is_none());
+
+        let mut none = BitmapAllocator::new(&[], &mut []).unwrap();
+        pretty_assertions::assert_eq!(none.allocate(), None);
+        assert!(none.is_allocated(PhysFrame(0)));
+    }
+
+    #[test]
+    fn contiguous_first_fit_respects_alignment() {
+        let map = [region(0x1000, 0x1_F000, 1), region(0x20_0000, 0x1_0000, 1)];
+        let mut storage = vec![0u64; BitmapAllocator::storage_words(&map)];
+        let mut bitmap = BitmapAllocator::new(&map, &mut storage).unwrap();
+        // A used frame inside the first candidate pushes it on.
+        bitmap.allocate();
+        pretty_assertions::assert_eq!(bitmap.allocate(), Some(PhysFrame(0x2000)));
+        bitmap.deallocate(PhysFrame(0x1000));
+
+        let three = bitmap.alloc_contiguous(3, 0).unwrap();
+        pretty_assertions::assert_eq!((three.start.0, three.end.0), (0x3000, 0x6000));
+        let dma = bitmap.alloc_contiguous(16, 0x1_0000).unwrap();
+        pretty_assertions::assert_eq!((dma.start.0, dma.len()), (0x1_0000, 16));
+        let next = bitmap.alloc_contiguous(16, 0x1_0000).unwrap();
+        pretty_assertions::assert_eq!(next.start.0, 0x20_0000);
+        pretty_assertions::assert_eq!(bitmap.alloc_contiguous(16, 0x1_0000), None);
+        pretty_assertions::assert_eq!(bitmap.alloc_contiguous(0, 0), None);
+        pretty_assertions::assert_eq!(bitmap.alloc_contiguous(1, 0x3000), None);
+        pretty_assertions::assert_eq!(bitmap.free_count(), 0x2F - 1 - 3 - 16 - 16);
+    }
+
+    #[test]
+    fn zone_allocation_falls_back_downwards() {
+        const MIB: u64 = 1 << 20;
+        let map = [
+            region(0x1000, 0x1000, 1),
+            region(16 * MIB, 0x2000, 1),
+            region(4096 * MIB, 0x1000, 1),
+        ];
+        let mut storage = vec![0u64; BitmapAllocator::storage_words(&map)];
+        let mut bitmap = BitmapAllocator::new(&map, &mut storage).unwrap();
+        let got: Vec<Option<u64>> = [
+            Zone::Normal,
+            Zone::Normal,
+            Zone::Dma32,
+            Zone::Dma32,
+            Zone::Dma,
+        ]
+        .map(|z| bitmap.alloc_in_zone(z).map(|f| f.0))
+        .to_vec();
+        pretty_assertions::assert_eq!(
+            got,
+            vec![
+                Some(4096 * MIB),
+                Some(16 * MIB),
+                Some(16 * MIB + 0x1000),
+                Some(0x1000),
+                None
+            ]
+        );
+        pretty_assertions::assert_eq!(bitmap.free_count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "double free")]
+    fn double_free_panics() {
+        let map = [region(0, 0x2000, 1)];
+        let mut storage = [0u64; 2];
+        let mut bitmap = BitmapAllocator::new(&map, &mut storage).unwrap();
+        let f = bitmap.allocate().unwrap();
+        bitmap.deallocate(f);
+        bitmap.deallocate(f);
+    }
+
+    #[test]
+    #[should_panic(expected = "not managed")]
+    fn freeing_reserved_memory_panics() {
+        let map = [
+            region(0x1000, 0x1000, 1),
+            region(0x2000, 0x1000, 2),
+            region(0x3000, 0x1000, 1),
+        ];
+        let mut storage = [0u64; 2];
+        let mut bitmap = BitmapAllocator::new(&map, &mut storage).unwrap();
+        bitmap.deallocate(PhysFrame(0x2000));
+    }
+
+    #[test]
+    fn init_steals_its_storage_from_usable_memory() {
+        // 0x20 frames of RAM above a tiny 1-frame region: one word of
+        // bitmap, one frame of storage, which the small region can hold.
+        let base = 0x10_0000;
+        let map = [
+            region(base, 0x1000, 1),
+            region(base + 0x1000, 0x1000, 2),
+            region(base + 0x2000, 0x1E000, 1),
+        ];
+        let mut ram = Ram {
+            base,
+            mem: vec![0; 0x20 * 512],
+        };
+        let mut bitmap = unsafe { BitmapAllocator::init(&map, &mut ram) }.unwrap();
+        pretty_assertions::assert_eq!(BitmapAllocator::storage_frames(&map), 1);
+        assert!(bitmap.is_allocated(PhysFrame(base)));
+        pretty_assertions::assert_eq!(bitmap.free_count(), 0x1E);
+        pretty_assertions::assert_eq!(bitmap.allocate(), Some(PhysFrame(base + 0x2000)));
+        // The bitmaps really live in the stolen frame, and it cannot be
+        // given back.
+        pretty_assertions::assert_eq!(ram.mem[0] as u32, 0b111);
+        pretty_assertions::assert_eq!(ram.mem[1] as u32, !0b11);
+        let stolen = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            bitmap.deallocate(PhysFrame(base))
+        }));
+        assert!(stolen.is_err());
+    }
+
+    #[test]
+    fn word_fills_match_frame_by_frame() {
+        let map = [
+            region(0x1000, 70 * FRAME_SIZE, 1),
+            region(0x20_0000, 200 * FRAME_SIZE, 1),
+        ];
+        let words = BitmapAllocator::storage_words(&map);
+        let mut storage = vec![0u64; words];
+        let bitmap = BitmapAllocator::new(&map, &mut storage).unwrap();
+        let expected: Vec<_> = super::super::UsableFrames::new(&map).collect();
+        let free: Vec<_> = (0..bitmap.frames)
+            .map(|i| bitmap.frame(i))
+            .filter(|&f| !bitmap.is_allocated(f))
+            .collect();
+        pretty_assertions::assert_eq!(free, expected);
+        pretty_assertions::assert_eq!(bitmap.free_count(), 270);
+    }
+}
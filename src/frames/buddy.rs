@@ -0,0 +1,546 @@
+// buddy.rs
+//
+// Blocks of 2^order frames, naturally aligned, for callers that need
+// physically contiguous memory (DMA buffers, page table batches):
+//
+//   order 0   one 4 KiB frame
+//   order 9   2 MiB
+//   order 10  4 MiB, the largest block (MAX_ORDER)
+//
+// A block of order k at address a has a buddy at a ^ (4 KiB << k). When
+// both are free they merge into one block of order k + 1, so freeing
+// everything always gets the big blocks back.
+//
+// Free blocks are tracked with one bitmap per order (bit set = this
+// block is free and not part of a bigger free block), plus one bit per
+// frame saying whether it is usable memory this allocator manages, so a
+// reserved frame or a hole given back is caught. All in storage the
+// caller provides or init() takes from usable memory, as for the
+// BitmapAllocator, and about one and a half times its size.
+// Single frames cost more than with the bitmap: every allocation
+// searches an order's bitmap from the bottom, where the bitmap resumes
+// after the last frame it handed out. benches/allocators.rs has the
+// numbers.
+// Input should be normalized: bad RAM inside a usable region is not
+// looked for here.
+
+use core::slice;
+
+use super::{align_down, PhysFrame, PhysFrameRange, UsableRuns, Zone, FRAME_SIZE};
+use crate::mapper::PhysMapper;
+use crate::raw::MemRegion;
+
+/// Largest block order: 2^10 frames, 4 MiB.
+pub const MAX_ORDER: u32 = 10;
+
+const ORDERS: usize = MAX_ORDER as usize + 1;
+const BITS: u64 = u64::BITS as u64;
+
+#[derive(Debug)]
+pub struct BuddyAllocator<'a> {
+    /// The bitmaps of every order, back to back.
+    bits: &'a mut [u64],
+    /// One bit per frame, set for frames handed out and taken back here.
+    managed: &'a mut [u64],
+    /// Where each order's bitmap starts in `bits`.
+    offset: [usize; ORDERS],
+    /// Free blocks of each order.
+    count: [u64; ORDERS],
+    /// Address of the first frame, aligned to a MAX_ORDER block.
+    base: u64,
+    /// Frames covered, usable or not.
+    frames: u64,
+}
+
+impl<'a> BuddyAllocator<'a> {
+    /// Words of bitmap `regions` needs.
+    pub fn storage_words(regions: &[MemRegion]) -> usize {
+        let frames = span(regions).map_or(0, |(base, end)| (end - base) / FRAME_SIZE);
+        layout(frames).1 + frames.div_ceil(BITS) as usize
+    }
+
+    /// Frames of memory the bitmaps for `regions` take up.
+    pub fn storage_frames(regions: &[MemRegion]) -> u64 {
+        (Self::storage_words(regions) as u64 * 8).div_ceil(FRAME_SIZE)
+    }
+
+    /// An allocator over `regions` keeping its bitmaps in `storage`. None
+    /// if `storage` is shorter than [`storage_words`](Self::storage_words).
+    pub fn new(regions: &[MemRegion], storage: &'a mut [u64]) -> Option<Self> {
+        let words = Self::storage_words(regions);
+        let storage = storage.get_mut(..words)?;
+        Some(Self::fill(regions, storage, (0, 0)))
+    }
+
+    /// [`new`](Self::new), but with every frame allocated. For taking
+    /// over from an allocator that hands its free frames back through
+    /// [`FrameDealloc`](super::FrameDealloc); they merge as they come in.
+    pub fn new_used(regions: &[MemRegion], storage: &'a mut [u64]) -> Option<Self> {
+        let words = Self::storage_words(regions);
+        let storage = storage.get_mut(..words)?;
+        Some(Self::fill(regions, storage, (0, u64::MAX)))
+    }
+
+    /// An allocator over `regions` keeping its bitmaps in the first
+    /// usable run big enough for them. Those frames are never handed out.
+    /// None if no run is big enough.
+    ///
+    /// # Safety
+    /// Every usable frame in `regions` must be unused, and the mapping
+    /// `mapper` returns for the bitmaps must stay valid, and alias nothing
+    /// else, for `'a`. It is never unmapped.
+    pub unsafe fn init<M: PhysMapper>(regions: &[MemRegion], mapper: &mut M) -> Option<Self> {
+        let words = Self::storage_words(regions);
+        let need = Self::storage_frames(regions);
+        let (at, _) = UsableRuns::new(regions).find(|&(_, count)| count >= need)?;
+        let virt = mapper.map(at.0, words * 8) as *mut u64;
+        let storage = slice::from_raw_parts_mut(virt, words);
+        let mut this = Self::fill(regions, storage, (at.0, at.0 + need * FRAME_SIZE));
+        // Giving back the bitmaps' own frames is a bug, not a free.
+        this.set_managed((at.0 - this.base) / FRAME_SIZE, need, false);
+        Some(this)
+    }
+
+    // Free every usable frame outside `keep`, in the biggest blocks that fit.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(regions = regions.len()))
+    )]
+    fn fill(regions: &[MemRegion], storage: &'a mut [u64], keep: (u64, u64)) -> Self {
+        let (base, end) = span(regions).unwrap_or((0, 0));
+        let frames = (end - base) / FRAME_SIZE;
+        let (offset, words) = layout(frames);
+        storage.fill(0);
+        let (bits, managed) = storage.split_at_mut(words);
+        let mut this = BuddyAllocator {
+            bits,
+            managed,
+            offset,
+            count: [0; ORDERS],
+            base,
+            frames,
+        };
+        for (first, count) in UsableRuns::new(regions) {
+            let (start, end) = (first.0, first.0 + count * FRAME_SIZE);
+            this.set_managed((start - base) / FRAME_SIZE, count, true);
+            this.free_range(start, end.min(keep.0).max(start));
+            this.free_range(start.max(keep.1).min(end), end);
+        }
+        this
+    }
+
+    // Set or clear the managed bits of `count` frames from frame `i`,
+    // whole words in one store where the run covers them.
+    fn set_managed(&mut self, mut i: u64, count: u64, on: bool) {
+        let end = i + count;
+        while i < end {
+            let (word, bit) = ((i / BITS) as usize, i % BITS);
+            let n = (BITS - bit).min(end - i);
+            let mask = if n == BITS {
+                u64::MAX
+            } else {
+                ((1 << n) - 1) << bit
+            };
+            match on {
+                true => self.managed[word] |= mask,
+                false => self.managed[word] &= !mask,
+            }
+            i += n;
+        }
+    }
+
+    // Mark [start, end) free as a run of maximal aligned blocks.
+    fn free_range(&mut self, start: u64, end: u64) {
+        let mut i = (start - self.base) / FRAME_SIZE;
+        let end = (end - self.base) / FRAME_SIZE;
+        while i < end {
+            // Order 0 always fits.
+            let order = (0..=i.trailing_zeros().min(MAX_ORDER))
+                .rev()
+                .find(|&k| i + (1 << k) <= end)
+                .unwrap_or(0);
+            self.set(order, i >> order, true);
+            self.count[order as usize] += 1;
+            i += 1 << order;
+        }
+    }
+
+    /// A free block of `2^order` frames, aligned to its size. None if
+    /// `order` is above [`MAX_ORDER`] or no block that big is free.
+    pub fn allocate(&mut self, order: u32) -> Option<PhysFrame> {
+        self.allocate_in(order, 0, u64::MAX)
+    }
+
+    /// [`allocate`](Self::allocate) from `zone`, or failing that from the
+    /// zones below it (see [`Zone::fallbacks`]). Zone boundaries are
+    /// multiples of the largest block, so a block is always wholly in one.
+    pub fn alloc_in_zone(&mut self, zone: Zone, order: u32) -> Option<PhysFrame> {
+        zone.fallbacks().find_map(|z| {
+            let (lo, hi) = z.range();
+            let index = |a: u64| a.saturating_sub(self.base) / FRAME_SIZE;
+            self.allocate_in(order, index(lo), index(hi))
+        })
+    }
+
+    // Allocate a block of `order` from frames [lo, hi) (counted from base).
+    fn allocate_in(&mut self, order: u32, lo: u64, hi: u64) -> Option<PhysFrame> {
+        let (from, mut index) = (order..=MAX_ORDER)
+            .filter(|&k| self.count[k as usize] > 0)
+            .find_map(|k| Some((k, self.first_free(k, lo.div_ceil(1 << k), hi >> k)?)))?;
+        self.set(from, index, false);
+        self.count[from as usize] -= 1;
+        // Split down, freeing the upper half each time.
+        for k in (order..from).rev() {
+            index *= 2;
+            self.set(k, index + 1, true);
+            self.count[k as usize] += 1;
+        }
+        Some(PhysFrame(self.base + (index << order) * FRAME_SIZE))
+    }
+
+    /// `count` contiguous frames, the first aligned to `align` bytes (a
+    /// power of two; 0 or anything up to a frame means frame aligned).
+    /// Takes the smallest block big and aligned enough and frees the
+    /// frames past `count` again. None if that block would be above
+    /// [`MAX_ORDER`] or none is free. Give the frames back one by one;
+    /// they merge again as they come in.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self)))]
+    pub fn alloc_contiguous(&mut self, count: usize, align: u64) -> Option<PhysFrameRange> {
+        let (count, align) = (count as u64, align.max(FRAME_SIZE));
+        if count == 0 || !align.is_power_of_two() {
+            return None;
+        }
+        let frames = count.max(align / FRAME_SIZE).checked_next_power_of_two()?;
+        let order = frames.trailing_zeros();
+        let start = self.allocate(order)?;
+        let range = PhysFrameRange {
+            start,
+            end: PhysFrame(start.0 + count * FRAME_SIZE),
+        };
+        let mut i = (range.end.0 - self.base) / FRAME_SIZE;
+        let end = (start.0 - self.base) / FRAME_SIZE + frames;
+        while i < end {
+            let k = (0..=i.trailing_zeros().min(order))
+                .rev()
+                .find(|&k| i + (1 << k) <= end)
+                .unwrap_or(0);
+            self.deallocate(PhysFrame(self.base + i * FRAME_SIZE), k);
+            i += 1 << k;
+        }
+        Some(range)
+    }
+
+    /// Give back a block from [`allocate`](Self::allocate)`(order)`,
+    /// merging it with its buddy for as long as the buddy is free.
+    ///
+    /// # Panics
+    /// If `frame` is not a block of this order, any frame of the block is
+    /// not usable memory this allocator manages, or any of it is already
+    /// free.
+    pub fn deallocate(&mut self, frame: PhysFrame, order: u32) {
+        assert!(order <= MAX_ORDER, "order {order} above MAX_ORDER");
+        let i = frame
+            .0
+            .checked_sub(self.base)
+            .map(|off| off / FRAME_SIZE)
+            .filter(|&i| frame.0.is_multiple_of(FRAME_SIZE) && i + (1 << order) <= self.frames)
+            .expect("frame not managed by this allocator");
+        assert!(
+            i.is_multiple_of(1 << order),
+            "{frame:?} is not an order {order} block"
+        );
+        let frames = i..i + (1 << order);
+        assert!(
+            frames
+                .clone()
+                .all(|j| self.managed[(j / BITS) as usize] & (1 << (j % BITS)) != 0),
+            "frame not managed by this allocator"
+        );
+        let mut index = i >> order;
+        let mut k = order;
+        // Already free, on its own, merged into a bigger block, or in
+        // part as a smaller one.
+        let free = (order..=MAX_ORDER).any(|j| self.get(j, index >> (j - order)))
+            || (0..order).any(|j| (frames.start >> j..frames.end >> j).any(|b| self.get(j, b)));
+        assert!(!free, "double free of {frame:?}");
+        while k < MAX_ORDER && self.get(k, index ^ 1) {
+            self.set(k, index ^ 1, false);
+            self.count[k as usize] -= 1;
+            index /= 2;
+            k += 1;
+        }
+        self.set(k, index, true);
+        self.count[k as usize] += 1;
+    }
+
+    /// Free blocks of exactly `order` (not counting those inside bigger
+    /// free blocks).
+    pub fn free_blocks(&self, order: u32) -> u64 {
+        self.count.get(order as usize).copied().unwrap_or(0)
+    }
+
+    /// Frames free right now, in blocks of any order.
+    pub fn free_count(&self) -> u64 {
+        (0..ORDERS).map(|k| self.count[k] << k).sum()
+    }
+
+    // Lowest free block of `order` with index in [from, to).
+    fn first_free(&self, order: u32, from: u64, to: u64) -> Option<u64> {
+        let k = order as usize;
+        let words =
+            &self.bits[self.offset[k]..self.offset.get(k + 1).copied().unwrap_or(self.bits.len())];
+        let start = usize::try_from(from / BITS).ok()?;
+        let mut mask = !0 << (from % BITS);
+        for (w, &word) in words.iter().enumerate().skip(start) {
+            if word & mask != 0 {
+                let i = w as u64 * BITS + (word & mask).trailing_zeros() as u64;
+                return (i < to).then_some(i);
+            }
+            mask = !0;
+        }
+        None
+    }
+
+    fn get(&self, order: u32, index: u64) -> bool {
+        let word = self.offset[order as usize] + (index / BITS) as usize;
+        self.bits
+            .get(word)
+            .is_some_and(|w| w & (1 << (index % BITS)) != 0)
+    }
+
+    fn set(&mut self, order: u32, index: u64, free: bool) {
+        let word = &mut self.bits[self.offset[order as usize] + (index / BITS) as usize];
+        if free {
+            *word |= 1 << (index % BITS);
+        } else {
+            *word &= !(1 << (index % BITS));
+        }
+    }
+}
+
+// Where each order's bitmap starts, and the total words, for `frames`
+// frames from the base.
+fn layout(frames: u64) -> ([usize; ORDERS], usize) {
+    let mut offset = [0; ORDERS];
+    let mut words = 0;
+    if frames == 0 {
+        return (offset, 0);
+    }
+    for (k, off) in offset.iter_mut().enumerate() {
+        *off = words;
+        // Whole words, so a buddy past the last block still has a bit
+        // (always clear) in this order's bitmap.
+        words += (frames.div_ceil(1 << k).div_ceil(BITS)) as usize;
+    }
+    (offset, words)
+}
+
+// [base, end of the highest usable frame) with base aligned down to a
+// MAX_ORDER block, so buddies are found by flipping one bit.
+fn span(regions: &[MemRegion]) -> Option<(u64, u64)> {
+    UsableRuns::new(regions)
+        .map(|(first, count)| (first.0, first.0 + count * FRAME_SIZE))
+        .reduce(|(a, b), (c, d)| (a.min(c), b.max(d)))
+        .map(|(start, end)| (align_down(start, FRAME_SIZE << MAX_ORDER), end))
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn buddy<'a>(map: &[MemRegion], storage: &'a mut Vec<u64>) -> BuddyAllocator<'a> {
+        storage.resize(BuddyAllocator::storage_words(map), 0);
+        BuddyAllocator::new(map, storage).unwrap()
+    }
+
+    #[test]
+    fn warm_start_uses_the_biggest_aligned_blocks() {
+        init();
+        // [0x1000, 0x80_3000): order 0, 1, 2, ... up to 4 MiB, then down.
+        let map = [region(0x1000, 8 * MIB + 0x2000, 1)];
+        let mut storage = Vec::new();
+        let b = buddy(&map, &mut storage);
+        pretty_assertions::assert_eq!(b.free_count(), 8 * MIB / FRAME_SIZE + 2);
+        pretty_assertions::assert_eq!(b.free_blocks(MAX_ORDER), 1);
+        pretty_assertions::assert_eq!(b.free_blocks(0), 2);
+        pretty_assertions::assert_eq!(b.free_blocks(1), 2);
+    }
+
+    #[test]
+    fn split_and_coalesce() {
+        let map = [region(0, 4 * MIB, 1)];
+        let mut storage = Vec::new();
+        let mut b = buddy(&map, &mut storage);
+
+        let a = b.allocate(0).unwrap();
+        let c = b.allocate(0).unwrap();
+        let d = b.allocate(2).unwrap();
+        pretty_assertions::assert_eq!((a.0, c.0, d.0), (0, 0x1000, 0x4000));
+        pretty_assertions::assert_eq!(b.free_blocks(MAX_ORDER), 0);
+
+        b.deallocate(a, 0);
+        b.deallocate(d, 2);
+        b.deallocate(c, 0);
+        pretty_assertions::assert_eq!(b.free_blocks(MAX_ORDER), 1);
+        pretty_assertions::assert_eq!(b.free_count(), 1024);
+    }
+
+    #[test]
+    fn respects_holes_and_runs_out() {
+        let map = [
+            region(0, 0x4000, 1),
+            region(0x4000, 0x4000, 2),
+            region(0x8000, 0x8000, 1),
+        ];
+        let mut storage = Vec::new();
+        let mut b = buddy(&map, &mut storage);
+        pretty_assertions::assert_eq!(b.allocate(3), Some(PhysFrame(0x8000)));
+        pretty_assertions::assert_eq!(b.allocate(3), None);
+        pretty_assertions::assert_eq!(b.allocate(2), Some(PhysFrame(0)));
+        pretty_assertions::assert_eq!(b.allocate(0), None);
+        pretty_assertions::assert_eq!(b.allocate(MAX_ORDER + 1), None);
+
+        // The reserved block in the middle never merges in.
+        b.deallocate(PhysFrame(0), 2);
+        b.deallocate(PhysFrame(0x8000), 3);
+        pretty_assertions::assert_eq!((b.free_blocks(2), b.free_blocks(3)), (1, 1));
+    }
+
+    #[test]
+    fn contiguous_takes_a_block_and_frees_the_tail() {
+        let map = [region(0, 4 * MIB, 1)];
+        let mut storage = Vec::new();
+        let mut b = buddy(&map, &mut storage);
+
+        let three = b.alloc_contiguous(3, 0).unwrap();
+        pretty_assertions::assert_eq!((three.start.0, three.len()), (0, 3));
+        pretty_assertions::assert_eq!(b.free_count(), 1024 - 3);
+        pretty_assertions::assert_eq!(b.allocate(0), Some(PhysFrame(0x3000)));
+
+        // 64 KiB aligned to 64 KiB skips the partly used first 64 KiB.
+        let dma = b.alloc_contiguous(16, 0x1_0000).unwrap();
+        pretty_assertions::assert_eq!((dma.start.0, dma.end.0), (0x1_0000, 0x2_0000));
+
+        pretty_assertions::assert_eq!(b.alloc_contiguous(0, 0), None);
+        pretty_assertions::assert_eq!(b.alloc_contiguous(1, 0x3000), None);
+        pretty_assertions::assert_eq!(b.alloc_contiguous(2048, 0), None);
+        pretty_assertions::assert_eq!(b.alloc_contiguous(usize::MAX, 0), None);
+
+        for f in three.iter().chain(dma).chain([PhysFrame(0x3000)]) {
+            b.deallocate(f, 0);
+        }
+        pretty_assertions::assert_eq!(b.free_blocks(MAX_ORDER), 1);
+    }
+
+    #[test]
+    fn zone_allocation_falls_back_downwards() {
+        let map = [region(0, 16 * MIB, 1), region(4096 * MIB, 4 * MIB, 1)];
+        let mut storage = Vec::new();
+        let mut b = buddy(&map, &mut storage);
+        pretty_assertions::assert_eq!(b.alloc_in_zone(Zone::Dma32, 0), Some(PhysFrame(0)));
+        pretty_assertions::assert_eq!(
+            b.alloc_in_zone(Zone::Normal, MAX_ORDER),
+            Some(PhysFrame(4096 * MIB))
+        );
+        pretty_assertions::assert_eq!(
+            b.alloc_in_zone(Zone::Normal, MAX_ORDER),
+            Some(PhysFrame(4 * MIB))
+        );
+        // 64 KiB for an ISA DMA buffer, next to the frame taken above.
+        pretty_assertions::assert_eq!(b.alloc_in_zone(Zone::Dma, 4), Some(PhysFrame(0x1_0000)));
+        pretty_assertions::assert_eq!(b.alloc_in_zone(Zone::Dma, MAX_ORDER + 1), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "double free")]
+    fn double_free_panics() {
+        let map = [region(0, 0x2000, 1)];
+        let mut storage = Vec::new();
+        let mut b = buddy(&map, &mut storage);
+        let f = b.allocate(0).unwrap();
+        b.deallocate(f, 0);
+        b.deallocate(f, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "not managed")]
+    fn freeing_reserved_memory_panics() {
+        let map = [
+            region(0x1000, 0x1000, 1),
+            region(0x2000, 0x1000, 2),
+            region(0x3000, 0x1000, 1),
+        ];
+        let mut storage = Vec::new();
+        let mut b = buddy(&map, &mut storage);
+        b.deallocate(PhysFrame(0x2000), 0);
+    }
+
+    #[test]
+    fn freeing_a_block_that_is_partly_free_panics() {
+        let map = [region(0, 0x4000, 1)];
+        let mut storage = Vec::new();
+        let mut b = buddy(&map, &mut storage);
+        let f = b.allocate(0).unwrap();
+        // The hole below the map is not managed either.
+        let hole = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            b.deallocate(PhysFrame(0x4000), 0)
+        }));
+        assert!(hole.is_err());
+        // Its buddy is still free, so f cannot come back as order 1.
+        let partly = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| b.deallocate(f, 1)));
+        assert!(partly.is_err());
+        b.deallocate(f, 0);
+        pretty_assertions::assert_eq!(b.free_blocks(2), 1);
+    }
+
+    #[test]
+    fn init_keeps_its_storage_out_of_the_pool() {
+        struct Ram(Vec<u64>);
+        impl PhysMapper for Ram {
+            unsafe fn map(&mut self, phys: u64, len: usize) -> *mut u8 {
+                assert!(phys as usize + len <= self.0.len() * 8);
+                self.0.as_mut_ptr().add(phys as usize / 8) as *mut u8
+            }
+        }
+        let map = [region(0, 0x10_0000, 1)];
+        let mut ram = Ram(vec![0; 0x10_0000 / 8]);
+        let mut b = unsafe { BuddyAllocator::init(&map, &mut ram) }.unwrap();
+        pretty_assertions::assert_eq!(BuddyAllocator::storage_frames(&map), 1);
+        pretty_assertions::assert_eq!(b.free_count(), 255);
+        pretty_assertions::assert_eq!(b.allocate(0), Some(PhysFrame(0x1000)));
+        let stolen = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            b.deallocate(PhysFrame(0), 0)
+        }));
+        assert!(stolen.is_err());
+    }
+
+    #[test]
+    fn word_fills_match_frame_by_frame() {
+        let map = [
+            region(0x1000, 70 * FRAME_SIZE, 1),
+            region(0x4_8000, 3 * FRAME_SIZE, 2),
+            region(0x20_0000, 200 * FRAME_SIZE, 1),
+        ];
+        let mut storage = Vec::new();
+        let b = buddy(&map, &mut storage);
+        let expected: Vec<_> = super::super::UsableFrames::new(&map).collect();
+        let managed: Vec<_> = (0..b.frames)
+            .filter(|&j| b.managed[(j / BITS) as usize] & (1 << (j % BITS)) != 0)
+            .map(|j| PhysFrame(b.base + j * FRAME_SIZE))
+            .collect();
+        pretty_assertions::assert_eq!(managed, expected);
+        pretty_assertions::assert_eq!(b.free_count(), 270);
+    }
+}
@@ -0,0 +1,194 @@
+// free_list.rs
+//
+// The allocator from every teaching kernel: free frames form a singly
+// linked list, and each free frame holds the physical address of the
+// next one in its first 8 bytes.
+//
+//   head -> [0x1000: next=0x2000] -> [0x2000: next=0x5000] -> [0x5000: END]
+//
+// No metadata anywhere else, O(1) allocate and free. The price: building
+// the list writes to every usable frame, and a stray write into a free
+// frame corrupts the list. Pointers go through a PhysMapper, so the list
+// lives in physical memory whatever the kernel's mappings are.
+
+use core::ptr;
+
+use super::{Order, PhysFrame, UsableFrames};
+use crate::mapper::PhysMapper;
+use crate::raw::MemRegion;
+
+// Stored in the last frame. Not 0, since frame 0 can be free.
+const END: u64 = u64::MAX;
+
+#[derive(Debug)]
+pub struct FreeListAllocator<M> {
+    head: u64,
+    free: u64,
+    mapper: M,
+}
+
+impl<M: PhysMapper> FreeListAllocator<M> {
+    /// A list with nothing on it yet, to be filled by deallocating (see
+    /// [`BumpAllocator::drain_into`](super::BumpAllocator::drain_into)).
+    pub fn empty(mapper: M) -> Self {
+        FreeListAllocator {
+            head: END,
+            free: 0,
+            mapper,
+        }
+    }
+
+    /// Thread every usable frame of `regions` onto the list, lowest
+    /// address at the head.
+    ///
+    /// # Safety
+    /// Every usable frame in `regions` must be unused for as long as the
+    /// allocator owns it (its first 8 bytes are overwritten), and
+    /// `mapper` must map frames writable.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(regions = regions.len()))
+    )]
+    pub unsafe fn init(regions: &[MemRegion], mapper: M) -> Self {
+        let mut this = Self::empty(mapper);
+        // Push high to low so the list comes out low to high.
+        for frame in UsableFrames::with_order(regions, Order::HighFirst) {
+            this.push(frame);
+        }
+        this
+    }
+
+    /// The frame at the head of the list: the most recently freed one,
+    /// or the lowest never handed out.
+    pub fn allocate(&mut self) -> Option<PhysFrame> {
+        if self.head == END {
+            return None;
+        }
+        let frame = self.head;
+        // SAFETY: `frame` is on the list, so init's contract says it is
+        // ours and holds the next pointer.
+        self.head = unsafe { self.with_link(frame, |link| ptr::read(link)) };
+        self.free -= 1;
+        Some(PhysFrame(frame))
+    }
+
+    /// Put `frame` back at the head of the list.
+    ///
+    /// # Safety
+    /// `frame` must have come from [`allocate`](Self::allocate) and be
+    /// unused from now on; a double free makes the list hand it out
+    /// twice.
+    pub unsafe fn deallocate(&mut self, frame: PhysFrame) {
+        self.push(frame);
+    }
+
+    /// Frames on the list.
+    pub fn free_count(&self) -> u64 {
+        self.free
+    }
+
+    unsafe fn push(&mut self, frame: PhysFrame) {
+        let next = self.head;
+        self.with_link(frame.0, |link| ptr::write(link, next));
+        self.head = frame.0;
+        self.free += 1;
+    }
+
+    unsafe fn with_link<T>(&mut self, frame: u64, f: impl FnOnce(*mut u64) -> T) -> T {
+        let virt = self.mapper.map(frame, 8);
+        let out = f(virt as *mut u64);
+        self.mapper.unmap(virt, 8);
+        out
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::frames::FRAME_SIZE;
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    /// Fake RAM from physical 0.
+    struct Ram(Vec<u64>);
+
+    impl PhysMapper for Ram {
+        unsafe fn map(&mut self, phys: u64, len: usize) -> *mut u8 {
+            assert!(
+                phys as usize + len <= self.0.len() * 8,
+                "mapped outside fake RAM"
+            );
+            self.0.as_mut_ptr().add(phys as usize / 8) as *mut u8
+        }
+    }
+
+    fn word(list: &FreeListAllocator<Ram>, phys: u64) -> u64 {
+        list.mapper.0[phys as usize / 8]
+    }
+
+    #[test]
+    fn links_live_in_the_free_frames() {
+        init();
+        let map = [
+            region(0, 0x2000, 1),
+            region(0x2000, 0x3000, 2),
+            region(0x5000, 0x1000, 1),
+        ];
+        let ram = Ram(vec![0; 6 * FRAME_SIZE as usize / 8]);
+        let mut list = unsafe { FreeListAllocator::init(&map, ram) };
+        pretty_assertions::assert_eq!(list.free_count(), 3);
+        pretty_assertions::assert_eq!(
+            (word(&list, 0), word(&list, 0x1000), word(&list, 0x5000)),
+            (0x1000, 0x5000, END)
+        );
+
+        let got: Vec<u64> = core::iter::from_fn(|| list.allocate())
+            .map(|f| f.0)
+            .collect();
+        pretty_assertions::assert_eq!(got, vec![0, 0x1000, 0x5000]);
+        pretty_assertions::assert_eq!(list.free_count(), 0);
+    }
+
+    #[test]
+    fn freed_frames_come_back_first() {
+        let map = [region(0, 0x4000, 1)];
+        let ram = Ram(vec![0; 4 * FRAME_SIZE as usize / 8]);
+        let mut list = unsafe { FreeListAllocator::init(&map, ram) };
+        let a = list.allocate().unwrap();
+        let b = list.allocate().unwrap();
+        unsafe {
+            list.deallocate(a);
+            list.deallocate(b);
+        }
+        pretty_assertions::assert_eq!(list.allocate(), Some(b));
+        pretty_assertions::assert_eq!(list.allocate(), Some(a));
+        pretty_assertions::assert_eq!(list.allocate(), Some(PhysFrame(0x2000)));
+        pretty_assertions::assert_eq!(list.free_count(), 1);
+    }
+
+    #[test]
+    fn takes_over_from_the_bump_allocator() {
+        let map = [region(0, 0x3000, 1)];
+        let mut bump = crate::frames::BumpAllocator::new(&map);
+        bump.allocate();
+        let mut list = FreeListAllocator::empty(Ram(vec![0; 3 * FRAME_SIZE as usize / 8]));
+        pretty_assertions::assert_eq!(bump.drain_into(&mut list, &[]), 2);
+        pretty_assertions::assert_eq!(list.allocate(), Some(PhysFrame(0x2000)));
+        pretty_assertions::assert_eq!(list.allocate(), Some(PhysFrame(0x1000)));
+        pretty_assertions::assert_eq!(list.allocate(), None);
+    }
+
+    #[test]
+    fn empty_map_gives_nothing() {
+        let mut list = unsafe { FreeListAllocator::init(&[], Ram(Vec::new())) };
+        pretty_assertions::assert_eq!(list.allocate(), None);
+    }
+}
@@ -0,0 +1,221 @@
+// paging.rs
+//
+// Glue for the x86_64 crate: its Mapper::map_to takes any
+// FrameAllocator<Size4KiB> for page table frames, so every allocator
+// here is one.
+//
+//   let mut frames = BumpAllocator::new(&map);
+//   mapper.map_to(page, frame, flags, &mut frames)?.flush();
+//
+// Frames convert both ways, but only frames below 2^52 fit the x86_64
+// crate's PhysAddr, and nothing in sanitize or normalize cuts the map
+// off there. So conversion into its frames is TryFrom, and an allocator
+// that hands out a frame above that treats it as running out: the frame
+// goes back where the allocator can take it, and allocate_frame says
+// None. Clamp the map first (region::clamp_max_addr) to avoid this.
+
+use ::x86_64::addr::PhysAddrNotValid;
+use ::x86_64::structures::paging::{
+    FrameAllocator, FrameDeallocator, PhysFrame as X86Frame, Size2MiB, Size4KiB,
+};
+use ::x86_64::PhysAddr;
+
+use super::buddy::BuddyAllocator;
+use super::{BitmapAllocator, BumpAllocator, FreeListAllocator, PhysFrame, UsableFrames};
+use crate::mapper::PhysMapper;
+
+const HUGE: u64 = 2 << 20;
+/// Buddy order of a 2 MiB block.
+const HUGE_ORDER: u32 = 9;
+
+impl TryFrom<PhysFrame> for X86Frame<Size4KiB> {
+    type Error = PhysAddrNotValid;
+
+    fn try_from(frame: PhysFrame) -> Result<Self, PhysAddrNotValid> {
+        PhysAddr::try_new(frame.0).map(X86Frame::containing_address)
+    }
+}
+
+impl From<X86Frame<Size4KiB>> for PhysFrame {
+    fn from(frame: X86Frame<Size4KiB>) -> Self {
+        PhysFrame(frame.start_address().as_u64())
+    }
+}
+
+fn small(frame: PhysFrame) -> Option<X86Frame<Size4KiB>> {
+    frame.try_into().ok()
+}
+
+fn huge(frame: PhysFrame) -> Option<X86Frame<Size2MiB>> {
+    PhysAddr::try_new(frame.0)
+        .ok()
+        .map(X86Frame::containing_address)
+}
+
+// SAFETY (all impls below): each allocator hands a frame out once until
+// it is given back, and only frames of usable memory.
+
+// The iterators and the bump allocator cannot take a frame back; theirs
+// is lost, which only wastes memory nobody here can address.
+
+unsafe impl FrameAllocator<Size4KiB> for UsableFrames<'_> {
+    fn allocate_frame(&mut self) -> Option<X86Frame<Size4KiB>> {
+        self.next().and_then(small)
+    }
+}
+
+unsafe impl FrameAllocator<Size2MiB> for UsableFrames<'_, HUGE> {
+    fn allocate_frame(&mut self) -> Option<X86Frame<Size2MiB>> {
+        self.next().and_then(huge)
+    }
+}
+
+unsafe impl FrameAllocator<Size4KiB> for BumpAllocator<'_> {
+    fn allocate_frame(&mut self) -> Option<X86Frame<Size4KiB>> {
+        self.allocate().and_then(small)
+    }
+}
+
+unsafe impl FrameAllocator<Size4KiB> for BitmapAllocator<'_> {
+    fn allocate_frame(&mut self) -> Option<X86Frame<Size4KiB>> {
+        let frame = self.allocate()?;
+        small(frame).or_else(|| {
+            self.deallocate(frame);
+            None
+        })
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for BitmapAllocator<'_> {
+    unsafe fn deallocate_frame(&mut self, frame: X86Frame<Size4KiB>) {
+        self.deallocate(frame.into());
+    }
+}
+
+unsafe impl FrameAllocator<Size4KiB> for BuddyAllocator<'_> {
+    fn allocate_frame(&mut self) -> Option<X86Frame<Size4KiB>> {
+        let frame = self.allocate(0)?;
+        small(frame).or_else(|| {
+            self.deallocate(frame, 0);
+            None
+        })
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for BuddyAllocator<'_> {
+    unsafe fn deallocate_frame(&mut self, frame: X86Frame<Size4KiB>) {
+        self.deallocate(frame.into(), 0);
+    }
+}
+
+unsafe impl FrameAllocator<Size2MiB> for BuddyAllocator<'_> {
+    fn allocate_frame(&mut self) -> Option<X86Frame<Size2MiB>> {
+        let frame = self.allocate(HUGE_ORDER)?;
+        huge(frame).or_else(|| {
+            self.deallocate(frame, HUGE_ORDER);
+            None
+        })
+    }
+}
+
+impl FrameDeallocator<Size2MiB> for BuddyAllocator<'_> {
+    unsafe fn deallocate_frame(&mut self, frame: X86Frame<Size2MiB>) {
+        let start = PhysFrame(frame.start_address().as_u64());
+        self.deallocate(start, HUGE_ORDER);
+    }
+}
+
+unsafe impl<M: PhysMapper> FrameAllocator<Size4KiB> for FreeListAllocator<M> {
+    fn allocate_frame(&mut self) -> Option<X86Frame<Size4KiB>> {
+        let frame = self.allocate()?;
+        small(frame).or_else(|| {
+            // SAFETY: it came off the list just now and nobody has it.
+            unsafe { self.deallocate(frame) };
+            None
+        })
+    }
+}
+
+impl<M: PhysMapper> FrameDeallocator<Size4KiB> for FreeListAllocator<M> {
+    unsafe fn deallocate_frame(&mut self, frame: X86Frame<Size4KiB>) {
+        self.deallocate(frame.into());
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::MemRegion;
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn usable(start: u64, len: u64) -> MemRegion {
+        MemRegion {
+            start,
+            len,
+            kind: 1,
+        }
+    }
+
+    // What Mapper::map_to asks of its frame allocator.
+    fn take<A: FrameAllocator<Size4KiB>>(alloc: &mut A) -> Option<u64> {
+        alloc.allocate_frame().map(|f| f.start_address().as_u64())
+    }
+
+    #[test]
+    fn allocators_plug_into_the_paging_api() {
+        init();
+        let map = [usable(0x1000, 0x40_0000)];
+        pretty_assertions::assert_eq!(take(&mut UsableFrames::new(&map)), Some(0x1000));
+        pretty_assertions::assert_eq!(take(&mut BumpAllocator::new(&map)), Some(0x1000));
+
+        let mut storage = vec![0; BitmapAllocator::storage_words(&map)];
+        let mut bitmap = BitmapAllocator::new(&map, &mut storage).unwrap();
+        let f = bitmap.allocate_frame().unwrap();
+        unsafe { bitmap.deallocate_frame(f) };
+        pretty_assertions::assert_eq!(take(&mut bitmap), Some(0x1000));
+
+        let mut storage = vec![0; BuddyAllocator::storage_words(&map)];
+        let mut buddy = BuddyAllocator::new(&map, &mut storage).unwrap();
+        let big: X86Frame<Size2MiB> = buddy.allocate_frame().unwrap();
+        pretty_assertions::assert_eq!(big.start_address().as_u64(), HUGE);
+        unsafe { buddy.deallocate_frame(big) };
+        pretty_assertions::assert_eq!(buddy.free_count(), 0x400);
+    }
+
+    #[test]
+    fn frames_convert_both_ways() {
+        let f = PhysFrame(0x20_3000);
+        let x = X86Frame::<Size4KiB>::try_from(f).unwrap();
+        pretty_assertions::assert_eq!(PhysFrame::from(x), f);
+        assert!(X86Frame::<Size4KiB>::try_from(PhysFrame(1 << 52)).is_err());
+
+        let map = [usable(0x1000, 0x60_0000)];
+        let mut huge_frames = UsableFrames::<HUGE>::sized(&map);
+        let got: Option<X86Frame<Size2MiB>> = huge_frames.allocate_frame();
+        pretty_assertions::assert_eq!(got.map(|f| f.start_address().as_u64()), Some(HUGE));
+    }
+
+    #[test]
+    fn frames_above_52_bits_run_out_instead_of_panicking() {
+        let map = [usable(1 << 60, 0x40_0000)];
+        pretty_assertions::assert_eq!(take(&mut UsableFrames::new(&map)), None);
+        pretty_assertions::assert_eq!(take(&mut BumpAllocator::new(&map)), None);
+
+        let mut storage = vec![0; BitmapAllocator::storage_words(&map)];
+        let mut bitmap = BitmapAllocator::new(&map, &mut storage).unwrap();
+        pretty_assertions::assert_eq!(take(&mut bitmap), None);
+        pretty_assertions::assert_eq!(bitmap.free_count(), 0x400);
+
+        let mut storage = vec![0; BuddyAllocator::storage_words(&map)];
+        let mut buddy = BuddyAllocator::new(&map, &mut storage).unwrap();
+        pretty_assertions::assert_eq!(take(&mut buddy), None);
+        let big: Option<X86Frame<Size2MiB>> = buddy.allocate_frame();
+        pretty_assertions::assert_eq!(big, None);
+        pretty_assertions::assert_eq!(buddy.free_count(), 0x400);
+    }
+}
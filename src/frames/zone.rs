@@ -0,0 +1,104 @@
+// zone.rs
+//
+// Devices that cannot address all of physical memory:
+//
+//   ZONE_DMA     [0, 16 MiB)     ISA DMA, 24 address bits
+//   ZONE_DMA32   [16 MiB, 4 GiB) 32-bit PCI devices
+//   ZONE_NORMAL  [4 GiB, ...)    everyone else
+//
+// Zones are fixed address ranges; what the memory map decides is how
+// much usable memory each holds. Allocating "in" a zone falls back to
+// lower zones, never higher ones: a DMA32 device is happy with a frame
+// below 16 MiB, an ISA device is not happy with one above it.
+
+use super::{PhysFrame, UsableRuns, FRAME_SIZE};
+use crate::raw::MemRegion;
+
+const MIB: u64 = 1 << 20;
+const GIB: u64 = 1 << 30;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Zone {
+    Dma,
+    Dma32,
+    Normal,
+}
+
+impl Zone {
+    /// Lowest first.
+    pub const ALL: [Zone; 3] = [Zone::Dma, Zone::Dma32, Zone::Normal];
+
+    /// `[start, end)` of the zone.
+    pub fn range(self) -> (u64, u64) {
+        match self {
+            Zone::Dma => (0, 16 * MIB),
+            Zone::Dma32 => (16 * MIB, 4 * GIB),
+            Zone::Normal => (4 * GIB, u64::MAX),
+        }
+    }
+
+    pub fn of(frame: PhysFrame) -> Zone {
+        match frame.0 {
+            a if a < 16 * MIB => Zone::Dma,
+            a if a < 4 * GIB => Zone::Dma32,
+            _ => Zone::Normal,
+        }
+    }
+
+    /// This zone, then the lower ones, highest first: where an
+    /// allocation for this zone may be placed, in order of preference.
+    pub fn fallbacks(self) -> impl Iterator<Item = Zone> {
+        Zone::ALL.into_iter().rev().filter(move |&z| z <= self)
+    }
+
+    /// Bytes of whole usable frames `map` has in this zone.
+    pub fn usable_bytes(self, map: &[MemRegion]) -> u64 {
+        let (lo, hi) = self.range();
+        UsableRuns::new(map)
+            .map(|(first, count)| {
+                let end = first.0 + count * FRAME_SIZE;
+                end.min(hi).saturating_sub(first.0.max(lo))
+            })
+            .sum()
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    #[test]
+    fn map_sizes_each_zone() {
+        init();
+        let map = [
+            region(0, 0x9_F000, 1),
+            region(0x10_0000, 3 * GIB - 0x10_0000, 1),
+            region(3 * GIB, GIB, 2),
+            region(4 * GIB, 4 * GIB, 1),
+        ];
+        let sizes = Zone::ALL.map(|z| z.usable_bytes(&map));
+        pretty_assertions::assert_eq!(sizes, [0x9_F000 + 15 * MIB, 3 * GIB - 16 * MIB, 4 * GIB]);
+    }
+
+    #[test]
+    fn zones_and_fallbacks() {
+        pretty_assertions::assert_eq!(Zone::of(PhysFrame(16 * MIB - 0x1000)), Zone::Dma);
+        pretty_assertions::assert_eq!(Zone::of(PhysFrame(16 * MIB)), Zone::Dma32);
+        pretty_assertions::assert_eq!(Zone::of(PhysFrame(4 * GIB)), Zone::Normal);
+        pretty_assertions::assert_eq!(
+            Zone::Normal.fallbacks().collect::<Vec<_>>(),
+            vec![Zone::Normal, Zone::Dma32, Zone::Dma]
+        );
+        pretty_assertions::assert_eq!(Zone::Dma.fallbacks().collect::<Vec<_>>(), vec![Zone::Dma]);
+    }
+}
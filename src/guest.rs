@@ -0,0 +1,370 @@
+// guest.rs
+//
+// The inverse of parsing: instead of reading a map some firmware made,
+// a VMM has to *make* one for its guest.
+//
+// Given how much RAM the guest gets and where the device windows sit,
+// this builds the canonical region list and can emit it in the wire
+// formats a guest kernel expects (MB1 mmap, E820 table, MB2 mmap tag).
+//
+// Layout produced (x86 PC conventions, same shape Firecracker and
+// cloud-hypervisor hand out):
+//
+//   [0, EBDA)                 usable   (conventional memory)
+//   [EBDA, 1 MiB)             reserved (EBDA, VGA window, BIOS ROM)
+//   [1 MiB, low_end)          usable
+//   [pci_hole_base, hole_end) reserved (32-bit PCI MMIO window)
+//   [high_base, ...)          usable   (whatever RAM did not fit below)
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::kind;
+use crate::raw::MemRegion;
+#[cfg(feature = "alloc")]
+use crate::raw::{e820, mb2, push_entry, raw};
+
+/// Start of the Extended BIOS Data Area; conventional memory ends here.
+pub const EBDA_START: u64 = 0x9_FC00;
+/// First byte above the legacy BIOS/VGA area.
+pub const HIGH_MEMORY_START: u64 = 0x10_0000;
+/// Guest RAM that does not fit below the PCI hole is relocated here (or higher).
+pub const FOUR_GIB: u64 = 0x1_0000_0000;
+
+/// Most regions a generated layout can contain (see the table at the top).
+pub const MAX_GUEST_REGIONS: usize = 5;
+
+/// What the VMM wants the guest to see.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GuestLayoutConfig {
+    /// Total guest RAM in bytes (including the legacy area below 1 MiB).
+    pub ram_size: u64,
+    /// Start of the 32-bit PCI MMIO window.
+    pub pci_hole_base: u64,
+    /// Size of the 32-bit PCI MMIO window. Zero means no hole.
+    pub pci_hole_size: u64,
+    /// Highest address low RAM may reach; RAM beyond this moves above 4 GiB.
+    /// Effective split is `min(below_4g_split, pci_hole_base)`.
+    pub below_4g_split: u64,
+}
+
+impl GuestLayoutConfig {
+    /// Typical PC layout: a 1 GiB PCI hole at 3 GiB, low RAM split at the hole.
+    pub fn new(ram_size: u64) -> Self {
+        GuestLayoutConfig {
+            ram_size,
+            pci_hole_base: 0xC000_0000,
+            pci_hole_size: 0x4000_0000,
+            below_4g_split: 0xC000_0000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GuestLayoutError {
+    // Not even enough RAM to cover the legacy area below 1 MiB.
+    RamTooSmall { ram_size: u64 },
+
+    // The PCI hole (or the split) would swallow the legacy area.
+    HoleOverlapsLegacy { pci_hole_base: u64 },
+    SplitTooLow { below_4g_split: u64 },
+
+    // base + size (or the relocated high RAM) does not fit in u64.
+    Overflow,
+}
+
+/// A generated guest layout: canonical regions, sorted and non-overlapping.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GuestLayout {
+    regions: [MemRegion; MAX_GUEST_REGIONS],
+    len: usize,
+}
+
+impl GuestLayout {
+    /// Build the layout described by `cfg`.
+    pub fn generate(cfg: &GuestLayoutConfig) -> Result<Self, GuestLayoutError> {
+        if cfg.ram_size < HIGH_MEMORY_START {
+            return Err(GuestLayoutError::RamTooSmall {
+                ram_size: cfg.ram_size,
+            });
+        }
+        if cfg.pci_hole_size > 0 && cfg.pci_hole_base < HIGH_MEMORY_START {
+            return Err(GuestLayoutError::HoleOverlapsLegacy {
+                pci_hole_base: cfg.pci_hole_base,
+            });
+        }
+        if cfg.below_4g_split < HIGH_MEMORY_START {
+            return Err(GuestLayoutError::SplitTooLow {
+                below_4g_split: cfg.below_4g_split,
+            });
+        }
+        let hole_end = cfg
+            .pci_hole_base
+            .checked_add(cfg.pci_hole_size)
+            .ok_or(GuestLayoutError::Overflow)?;
+
+        let low_limit = if cfg.pci_hole_size > 0 {
+            cfg.below_4g_split.min(cfg.pci_hole_base)
+        } else {
+            cfg.below_4g_split
+        };
+        let low_end = cfg.ram_size.min(low_limit);
+
+        let mut layout = GuestLayout {
+            regions: [MemRegion {
+                start: 0,
+                len: 0,
+                kind: 0,
+            }; MAX_GUEST_REGIONS],
+            len: 0,
+        };
+
+        layout.push(0, EBDA_START, kind::USABLE);
+        layout.push(EBDA_START, HIGH_MEMORY_START - EBDA_START, kind::RESERVED);
+        if low_end > HIGH_MEMORY_START {
+            layout.push(HIGH_MEMORY_START, low_end - HIGH_MEMORY_START, kind::USABLE);
+        }
+        if cfg.pci_hole_size > 0 {
+            layout.push(cfg.pci_hole_base, cfg.pci_hole_size, kind::RESERVED);
+        }
+
+        // Everything that did not fit below the split is relocated high.
+        let remaining = cfg.ram_size - low_end;
+        if remaining > 0 {
+            let high_base = if cfg.pci_hole_size > 0 {
+                FOUR_GIB.max(hole_end)
+            } else {
+                FOUR_GIB.max(low_limit)
+            };
+            high_base
+                .checked_add(remaining)
+                .ok_or(GuestLayoutError::Overflow)?;
+            layout.push(high_base, remaining, kind::USABLE);
+        }
+
+        Ok(layout)
+    }
+
+    fn push(&mut self, start: u64, len: u64, kind: u32) {
+        self.regions[self.len] = MemRegion { start, len, kind };
+        self.len += 1;
+    }
+
+    /// Canonical regions, sorted by start.
+    pub fn regions(&self) -> &[MemRegion] {
+        &self.regions[..self.len]
+    }
+
+    /// Bytes the guest may use as general RAM.
+    pub fn usable_bytes(&self) -> u64 {
+        self.regions()
+            .iter()
+            .filter(|r| r.kind == kind::USABLE)
+            .map(|r| r.len)
+            .sum()
+    }
+
+    /// Emit the layout as an MB1 mmap blob (minimal 20-byte payloads).
+    #[cfg(feature = "alloc")]
+    pub fn push_mb1(&self, buf: &mut Vec<u8>) {
+        for r in self.regions() {
+            push_entry(buf, raw(r.start, r.len, r.kind));
+        }
+    }
+
+    /// Emit the layout as a BIOS E820 table (20-byte entries, no size prefix).
+    #[cfg(feature = "alloc")]
+    pub fn push_e820(&self, buf: &mut Vec<u8>) {
+        for r in self.regions() {
+            let entry = e820::E820Entry {
+                base: r.start,
+                length: r.len,
+                typ: r.kind,
+                ext_attrs: None,
+            };
+            e820::push_e820_entry(buf, entry);
+        }
+    }
+
+    /// Emit the layout as a complete Multiboot2 mmap tag.
+    ///
+    /// Tag header (type, size), then entry_size/entry_version, then one
+    /// 24-byte entry per region. The tag is already a multiple of 8 bytes.
+    #[cfg(feature = "alloc")]
+    pub fn push_mb2_tag(&self, buf: &mut Vec<u8>) {
+        let entries: Vec<mb2::Mb2Entry> = self
+            .regions()
+            .iter()
+            .map(|r| mb2::Mb2Entry {
+                base_addr: r.start,
+                length: r.len,
+                typ: r.kind,
+                reserved: 0,
+            })
+            .collect();
+        mb2::push_mmap_tag(buf, &entries, mb2::ENTRY_SIZE);
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::Mb1MmapIter;
+    use crate::tests::common::init;
+
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+    const GIB: u64 = 1024 * MIB;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    #[test]
+    fn small_guest_fits_below_hole() {
+        init();
+        let layout = GuestLayout::generate(&GuestLayoutConfig::new(512 * MIB)).unwrap();
+
+        pretty_assertions::assert_eq!(
+            layout.regions(),
+            &[
+                region(0, EBDA_START, 1),
+                region(EBDA_START, HIGH_MEMORY_START - EBDA_START, 2),
+                region(HIGH_MEMORY_START, 512 * MIB - HIGH_MEMORY_START, 1),
+                region(0xC000_0000, GIB, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn large_guest_relocates_above_4g() {
+        init();
+        let layout = GuestLayout::generate(&GuestLayoutConfig::new(6 * GIB)).unwrap();
+        let regions = layout.regions();
+
+        pretty_assertions::assert_eq!(regions.len(), 5);
+        pretty_assertions::assert_eq!(regions[2], region(MIB, 3 * GIB - MIB, 1));
+        pretty_assertions::assert_eq!(regions[4], region(FOUR_GIB, 3 * GIB, 1));
+    }
+
+    #[test]
+    fn usable_bytes_is_ram_minus_legacy_window() {
+        for ram in [MIB, 512 * MIB, 3 * GIB, 6 * GIB] {
+            let layout = GuestLayout::generate(&GuestLayoutConfig::new(ram)).unwrap();
+            pretty_assertions::assert_eq!(
+                layout.usable_bytes(),
+                ram - (HIGH_MEMORY_START - EBDA_START)
+            );
+        }
+    }
+
+    #[test]
+    fn regions_are_sorted_and_disjoint() {
+        let cfg = GuestLayoutConfig {
+            ram_size: 8 * GIB,
+            pci_hole_base: 0xE000_0000,
+            pci_hole_size: 0x2000_0000,
+            below_4g_split: 0x8000_0000,
+        };
+        let layout = GuestLayout::generate(&cfg).unwrap();
+        for pair in layout.regions().windows(2) {
+            assert!(pair[0].start + pair[0].len <= pair[1].start);
+        }
+        // split below the hole leaves RAM end at 2 GiB
+        pretty_assertions::assert_eq!(layout.regions()[2].start + layout.regions()[2].len, 2 * GIB);
+    }
+
+    #[test]
+    fn hole_ending_above_4g_pushes_high_ram_past_it() {
+        let cfg = GuestLayoutConfig {
+            ram_size: 4 * GIB,
+            pci_hole_base: 0xC000_0000,
+            pci_hole_size: 2 * GIB,
+            below_4g_split: 0xC000_0000,
+        };
+        let layout = GuestLayout::generate(&cfg).unwrap();
+        let last = layout.regions()[4];
+        pretty_assertions::assert_eq!(last.start, 0xC000_0000 + 2 * GIB);
+    }
+
+    #[test]
+    fn mb1_output_parses_back_to_regions() {
+        let layout = GuestLayout::generate(&GuestLayoutConfig::new(6 * GIB)).unwrap();
+        let mut buf = Vec::new();
+        layout.push_mb1(&mut buf);
+
+        let parsed: Vec<MemRegion> = Mb1MmapIter::new(&buf)
+            .map(|e| {
+                let e = e.unwrap();
+                region(
+                    e.get_base_addr_unaligned(),
+                    e.get_length_unaligned(),
+                    e.get_type_unaligned(),
+                )
+            })
+            .collect();
+        pretty_assertions::assert_eq!(parsed.as_slice(), layout.regions());
+    }
+
+    #[test]
+    fn e820_output_is_20_bytes_per_region() {
+        let layout = GuestLayout::generate(&GuestLayoutConfig::new(512 * MIB)).unwrap();
+        let mut buf = Vec::new();
+        layout.push_e820(&mut buf);
+
+        pretty_assertions::assert_eq!(buf.len(), 20 * layout.regions().len());
+        pretty_assertions::assert_eq!(buf[20..28], EBDA_START.to_le_bytes());
+        pretty_assertions::assert_eq!(buf[36..40], 2u32.to_le_bytes());
+    }
+
+    #[test]
+    fn mb2_tag_header_and_size() {
+        let layout = GuestLayout::generate(&GuestLayoutConfig::new(512 * MIB)).unwrap();
+        let mut buf = Vec::new();
+        layout.push_mb2_tag(&mut buf);
+
+        pretty_assertions::assert_eq!(buf.len(), 16 + 24 * 4);
+        pretty_assertions::assert_eq!(buf[0..4], 6u32.to_le_bytes());
+        pretty_assertions::assert_eq!(buf[4..8], (buf.len() as u32).to_le_bytes());
+        pretty_assertions::assert_eq!(buf[8..12], 24u32.to_le_bytes());
+        pretty_assertions::assert_eq!(buf.len() % 8, 0);
+
+        let parsed: Vec<MemRegion> = mb2::Mb2MmapIter::new(&buf)
+            .unwrap()
+            .map(|e| {
+                let e = e.unwrap();
+                MemRegion {
+                    start: e.base_addr,
+                    len: e.length,
+                    kind: e.typ,
+                }
+            })
+            .collect();
+        pretty_assertions::assert_eq!(parsed, layout.regions());
+    }
+
+    #[test]
+    fn rejects_bad_configs() {
+        pretty_assertions::assert_eq!(
+            GuestLayout::generate(&GuestLayoutConfig::new(0x8_0000)),
+            Err(GuestLayoutError::RamTooSmall { ram_size: 0x8_0000 })
+        );
+
+        let mut cfg = GuestLayoutConfig::new(GIB);
+        cfg.pci_hole_base = 0xA_0000;
+        pretty_assertions::assert_eq!(
+            GuestLayout::generate(&cfg),
+            Err(GuestLayoutError::HoleOverlapsLegacy {
+                pci_hole_base: 0xA_0000
+            })
+        );
+
+        let mut cfg = GuestLayoutConfig::new(GIB);
+        cfg.pci_hole_base = u64::MAX;
+        pretty_assertions::assert_eq!(GuestLayout::generate(&cfg), Err(GuestLayoutError::Overflow));
+    }
+}
@@ -0,0 +1,200 @@
+// handoff.rs
+//
+// A Rust bootloader and a Rust kernel are two separate builds, maybe of
+// two different versions of this crate. Nothing Rust-layout may cross
+// between them. HandoffV1 is what does: #[repr(C)], every field a fixed
+// width integer (addresses too, so a 32-bit loader can hand off to a
+// 64-bit kernel), layout checked at compile time below.
+//
+// Rules for the future: never change V1. New fields go into a HandoffV2
+// that starts with the same magic/version/size header, so a kernel can
+// read the header first and then decide which struct it is looking at.
+
+use core::mem::{offset_of, size_of};
+
+use crate::ffi::{self, CMemRegion};
+use crate::raw::MemRegion;
+
+pub const HANDOFF_MAGIC: u32 = u32::from_le_bytes(*b"MMHO");
+pub const HANDOFF_VERSION_1: u32 = 1;
+
+/// Numbers the loader already computed, so the kernel need not redo them.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HandoffStats {
+    pub usable_bytes: u64,
+    pub reserved_bytes: u64,
+    /// Frames the loader's allocator handed out (page tables, kernel image).
+    pub frames_allocated: u64,
+}
+
+impl HandoffStats {
+    /// Usable vs everything-else totals for `regions`.
+    pub fn from_regions(regions: &[MemRegion], frames_allocated: u64) -> Self {
+        let (usable, other): (u64, u64) = regions.iter().fold((0, 0), |(u, o), r| {
+            if r.region_kind().is_usable() {
+                (u + r.len, o)
+            } else {
+                (u, o + r.len)
+            }
+        });
+        HandoffStats {
+            usable_bytes: usable,
+            reserved_bytes: other,
+            frames_allocated,
+        }
+    }
+}
+
+/// Version 1 of the loader -> kernel handoff.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HandoffV1 {
+    pub magic: u32,
+    pub version: u32,
+    /// `size_of::<HandoffV1>()` as written by the loader.
+    pub size: u32,
+    pub reserved: u32,
+    /// Physical address of `map_len` CMemRegion values (the canonical map).
+    pub map_addr: u64,
+    pub map_len: u64,
+    /// Lowest address the loader's allocator has not handed out yet; the
+    /// kernel's allocator must start at or above it.
+    pub alloc_cursor: u64,
+    pub stats: HandoffStats,
+}
+
+const _: () = {
+    assert!(size_of::<CMemRegion>() == 24);
+    assert!(size_of::<HandoffStats>() == 24);
+    assert!(size_of::<HandoffV1>() == 64);
+    assert!(offset_of!(HandoffV1, version) == 4);
+    assert!(offset_of!(HandoffV1, size) == 8);
+    assert!(offset_of!(HandoffV1, map_addr) == 16);
+    assert!(offset_of!(HandoffV1, map_len) == 24);
+    assert!(offset_of!(HandoffV1, alloc_cursor) == 32);
+    assert!(offset_of!(HandoffV1, stats) == 40);
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HandoffError {
+    BadMagic {
+        magic: u32,
+    },
+    UnsupportedVersion {
+        version: u32,
+    },
+    /// The loader's struct is smaller than the version it claims.
+    BadSize {
+        size: u32,
+    },
+}
+
+impl HandoffV1 {
+    pub fn new(map: &[CMemRegion], alloc_cursor: u64, stats: HandoffStats) -> Self {
+        HandoffV1 {
+            magic: HANDOFF_MAGIC,
+            version: HANDOFF_VERSION_1,
+            size: size_of::<HandoffV1>() as u32,
+            reserved: 0,
+            map_addr: map.as_ptr() as usize as u64,
+            map_len: map.len() as u64,
+            alloc_cursor,
+            stats,
+        }
+    }
+
+    /// Check the header. A larger `size` is accepted (a newer loader
+    /// appended fields this kernel does not know about).
+    pub fn validate(&self) -> Result<(), HandoffError> {
+        if self.magic != HANDOFF_MAGIC {
+            return Err(HandoffError::BadMagic { magic: self.magic });
+        }
+        if self.version != HANDOFF_VERSION_1 {
+            return Err(HandoffError::UnsupportedVersion {
+                version: self.version,
+            });
+        }
+        if (self.size as usize) < size_of::<HandoffV1>() {
+            return Err(HandoffError::BadSize { size: self.size });
+        }
+        Ok(())
+    }
+
+    /// Read and validate the handoff at physical address `addr`.
+    ///
+    /// # Safety
+    /// `addr` must be identity-mapped, 8-byte aligned and readable for
+    /// `size_of::<HandoffV1>()` bytes that stay unchanged for `'a`.
+    pub unsafe fn from_addr<'a>(addr: u64) -> Result<&'a HandoffV1, HandoffError> {
+        let h = &*(addr as usize as *const HandoffV1);
+        h.validate()?;
+        Ok(h)
+    }
+
+    /// The canonical map the loader passed.
+    ///
+    /// # Safety
+    /// As for [`ffi::from_raw_parts`], at `map_addr`.
+    pub unsafe fn regions<'a>(&self) -> &'a [CMemRegion] {
+        ffi::from_raw_parts(
+            self.map_addr as usize as *const CMemRegion,
+            self.map_len as usize,
+        )
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    #[test]
+    fn handoff_roundtrip() {
+        init();
+        let map = [
+            region(0, 0x9_F000, 1),
+            region(0x10_0000, 0x100_0000, 1),
+            region(0xF000_0000, 0x1000, 2),
+        ];
+        let c: Vec<CMemRegion> = map.iter().map(|&r| r.into()).collect();
+        let stats = HandoffStats::from_regions(&map, 12);
+        let handoff = HandoffV1::new(&c, 0x40_0000, stats);
+
+        let got = unsafe { HandoffV1::from_addr(&handoff as *const HandoffV1 as u64) }.unwrap();
+        pretty_assertions::assert_eq!(got.stats.usable_bytes, 0x10_9F000);
+        pretty_assertions::assert_eq!(got.stats.reserved_bytes, 0x1000);
+        let back: Vec<MemRegion> = unsafe { got.regions() }.iter().map(|&r| r.into()).collect();
+        pretty_assertions::assert_eq!(back, map);
+    }
+
+    #[test]
+    fn header_validation() {
+        let good = HandoffV1::new(&[], 0, HandoffStats::default());
+        pretty_assertions::assert_eq!(good.validate(), Ok(()));
+
+        let newer = HandoffV1 { size: 128, ..good };
+        pretty_assertions::assert_eq!(newer.validate(), Ok(()));
+        pretty_assertions::assert_eq!(
+            HandoffV1 { magic: 0, ..good }.validate(),
+            Err(HandoffError::BadMagic { magic: 0 })
+        );
+        pretty_assertions::assert_eq!(
+            HandoffV1 { version: 2, ..good }.validate(),
+            Err(HandoffError::UnsupportedVersion { version: 2 })
+        );
+        pretty_assertions::assert_eq!(
+            HandoffV1 { size: 32, ..good }.validate(),
+            Err(HandoffError::BadSize { size: 32 })
+        );
+    }
+}
@@ -0,0 +1,193 @@
+// integrity.rs
+//
+// Tamper detection across boot stages. Before a handoff, record a CRC of
+// the *contents* of the regions the next stage must leave alone (initrd,
+// ACPI tables, the map itself). After the handoff, recompute and compare.
+// A mismatch means some loader stage scribbled over data it did not own.
+//
+// CRC-32 (IEEE, the zlib/Ethernet one) is not a security measure: it
+// catches bugs, not attackers. For that, see measure.rs.
+
+use alloc::vec::Vec;
+
+use crate::mapper::PhysMapper;
+use crate::raw::MemRegion;
+
+/// Bytes mapped and hashed per step.
+pub const CHECKSUM_CHUNK: u64 = 64 * 1024;
+
+const CRC32_POLY: u32 = 0xEDB8_8320;
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            c = if c & 1 != 0 {
+                CRC32_POLY ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            bit += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+/// Feed `bytes` into a running CRC (start from 0).
+pub fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    let mut c = !crc;
+    for &b in bytes {
+        c = CRC32_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+/// A region and the CRC of its contents at the time it was recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegionChecksum {
+    pub region: MemRegion,
+    pub crc: u32,
+}
+
+/// A region whose contents changed since its checksum was recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    pub region: MemRegion,
+    pub expected: u32,
+    pub found: u32,
+}
+
+/// CRC-32 of the bytes of `region`, read through `mapper` in chunks.
+///
+/// # Safety
+/// `mapper` must map `region` readable, and nothing may write to it
+/// while it is being read.
+pub unsafe fn checksum_region<M: PhysMapper>(region: MemRegion, mapper: &mut M) -> u32 {
+    let end = region.end();
+    let mut phys = region.start;
+    let mut crc = 0;
+
+    while phys < end {
+        let len = (end - phys).min(CHECKSUM_CHUNK) as usize;
+        let virt = mapper.map(phys, len);
+        crc = crc32_update(crc, core::slice::from_raw_parts(virt, len));
+        mapper.unmap(virt, len);
+        phys += len as u64;
+    }
+    crc
+}
+
+/// Record checksums for `regions`, in order.
+///
+/// # Safety
+/// As for `checksum_region`, for every region.
+pub unsafe fn checksum_regions<M: PhysMapper>(
+    regions: &[MemRegion],
+    mapper: &mut M,
+) -> Vec<RegionChecksum> {
+    regions
+        .iter()
+        .map(|&region| RegionChecksum {
+            region,
+            crc: checksum_region(region, mapper),
+        })
+        .collect()
+}
+
+/// Recompute every recorded checksum and report the regions that changed.
+/// Returns how many did.
+///
+/// # Safety
+/// As for `checksum_region`, for every recorded region.
+pub unsafe fn verify<M, F>(recorded: &[RegionChecksum], mapper: &mut M, mut on_mismatch: F) -> usize
+where
+    M: PhysMapper,
+    F: FnMut(ChecksumMismatch),
+{
+    let mut mismatches = 0;
+    for r in recorded {
+        let found = checksum_region(r.region, mapper);
+        if found != r.crc {
+            mismatches += 1;
+            on_mismatch(ChecksumMismatch {
+                region: r.region,
+                expected: r.crc,
+                found,
+            });
+        }
+    }
+    mismatches
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    /// Fake physical memory starting at address 0.
+    struct VecMapper {
+        mem: Vec<u8>,
+        max_chunk: usize,
+    }
+
+    impl PhysMapper for VecMapper {
+        unsafe fn map(&mut self, phys: u64, len: usize) -> *mut u8 {
+            assert!(
+                phys as usize + len <= self.mem.len(),
+                "mapped outside fake RAM"
+            );
+            self.max_chunk = self.max_chunk.max(len);
+            self.mem.as_mut_ptr().add(phys as usize)
+        }
+    }
+
+    #[test]
+    fn crc32_matches_reference() {
+        init();
+        // The standard check value for CRC-32/ISO-HDLC.
+        pretty_assertions::assert_eq!(crc32_update(0, b"123456789"), 0xCBF4_3926);
+        // Incremental == one shot.
+        pretty_assertions::assert_eq!(
+            crc32_update(crc32_update(0, b"1234"), b"56789"),
+            0xCBF4_3926
+        );
+    }
+
+    #[test]
+    fn verify_reports_only_modified_regions() {
+        let size = (CHECKSUM_CHUNK * 2 + 0x100) as usize;
+        let mut m = VecMapper {
+            mem: (0..size).map(|i| i as u8).collect(),
+            max_chunk: 0,
+        };
+        let initrd = region(0, CHECKSUM_CHUNK * 2 + 0x80, 2);
+        let acpi = region(CHECKSUM_CHUNK * 2 + 0x80, 0x80, 3);
+
+        let recorded = unsafe { checksum_regions(&[initrd, acpi], &mut m) };
+        pretty_assertions::assert_eq!(m.max_chunk, CHECKSUM_CHUNK as usize);
+        pretty_assertions::assert_eq!(unsafe { verify(&recorded, &mut m, |_| panic!()) }, 0);
+
+        // A later stage flips one byte of the initrd.
+        m.mem[CHECKSUM_CHUNK as usize + 7] ^= 1;
+        let mut bad = Vec::new();
+        let n = unsafe { verify(&recorded, &mut m, |x| bad.push(x)) };
+        pretty_assertions::assert_eq!(n, 1);
+        pretty_assertions::assert_eq!(bad[0].region, initrd);
+        pretty_assertions::assert_eq!(bad[0].expected, recorded[0].crc);
+        assert_ne!(bad[0].found, bad[0].expected);
+    }
+}
@@ -0,0 +1,161 @@
+// kind.rs
+//
+// Region type values. MB1 and E820 share the same numbering (MB1 just
+// forwards what the BIOS E820 call returned), so these are the values
+// you will see in MemRegion::kind.
+//
+// Only USABLE is general-purpose RAM. Everything else is either never
+// yours (RESERVED, BAD_RAM), yours later (ACPI_RECLAIMABLE), or memory
+// that exists but must not be mixed into the general pool (the special
+// purpose kinds below).
+
+pub const USABLE: u32 = 1;
+pub const RESERVED: u32 = 2;
+pub const ACPI_RECLAIMABLE: u32 = 3;
+pub const ACPI_NVS: u32 = 4;
+pub const BAD_RAM: u32 = 5;
+
+/// Which kind wins when two claims overlap: higher beats lower.
+///
+/// Being wrong about "usable" corrupts memory; being wrong about
+/// "reserved" only wastes it. So everything beats usable, and bad RAM
+/// beats everything. Kinds nobody has assigned count as reserved.
+pub fn precedence(kind: u32) -> u8 {
+    match kind {
+        USABLE => 0,
+        ACPI_RECLAIMABLE => 1,
+        ACPI_NVS => 2,
+        BAD_RAM => 4,
+        _ => 3,
+    }
+}
+
+/// The numbered kinds above as an enum, for code that wants `match`
+/// instead of comparing against magic numbers.
+///
+/// `MemRegion::kind` stays a `u32`: firmware sends values nobody has
+/// assigned yet and those must survive a round trip. Build one with
+/// `RegionKind::from(u32)` (or `MemRegion::region_kind`) so that
+/// `Unknown` only ever holds values without a variant of their own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RegionKind {
+    Usable,
+    Reserved,
+    AcpiReclaimable,
+    AcpiNvs,
+    BadRam,
+    Unknown(u32),
+}
+
+impl RegionKind {
+    pub fn is_usable(self) -> bool {
+        self == RegionKind::Usable
+    }
+}
+
+impl From<u32> for RegionKind {
+    fn from(kind: u32) -> Self {
+        match kind {
+            USABLE => RegionKind::Usable,
+            RESERVED => RegionKind::Reserved,
+            ACPI_RECLAIMABLE => RegionKind::AcpiReclaimable,
+            ACPI_NVS => RegionKind::AcpiNvs,
+            BAD_RAM => RegionKind::BadRam,
+            other => RegionKind::Unknown(other),
+        }
+    }
+}
+
+impl From<RegionKind> for u32 {
+    fn from(kind: RegionKind) -> u32 {
+        match kind {
+            RegionKind::Usable => USABLE,
+            RegionKind::Reserved => RESERVED,
+            RegionKind::AcpiReclaimable => ACPI_RECLAIMABLE,
+            RegionKind::AcpiNvs => ACPI_NVS,
+            RegionKind::BadRam => BAD_RAM,
+            RegionKind::Unknown(other) => other,
+        }
+    }
+}
+
+// ------------------------------------------------------------
+// Special purpose memory
+// ------------------------------------------------------------
+//
+// Newer platforms ship RAM that is real, fast enough, and still not
+// meant for the general allocator:
+//
+//   - EFI_MEMORY_SP ("specific purpose") conventional memory, usually
+//     HBM or CXL-attached DRAM reserved for a particular driver/app.
+//     Linux reports it as E820 type 0xEFFFFFFF (soft reserved).
+//   - ACPI persistent memory (type 7) and the legacy pre-ACPI-6 PRAM
+//     type (12): NVDIMMs and CXL persistent devices.
+//
+// Handing these to the frame allocator either wastes scarce memory or
+// puts kernel data on media with different durability/latency.
+
+/// ACPI 6 AddressRangePersistentMemory.
+pub const PERSISTENT: u32 = 7;
+/// Legacy (pre-ACPI 6) persistent RAM type.
+pub const PERSISTENT_LEGACY: u32 = 12;
+/// EFI_MEMORY_SP conventional memory (Linux E820_TYPE_SOFT_RESERVED).
+pub const SOFT_RESERVED: u32 = 0xEFFF_FFFF;
+
+/// Usable RAM that SRAT marks hot-pluggable (see `srat::mark_hot_pluggable`).
+///
+/// Not a firmware value: this crate assigns it, next to SOFT_RESERVED, so
+/// memory that may be unplugged later stays out of the general pool
+/// unless a caller claims it on purpose.
+pub const HOT_PLUGGABLE: u32 = 0xEFFF_FFFE;
+
+/// UEFI memory descriptor attribute bit for specific-purpose memory.
+pub const EFI_MEMORY_SP: u64 = 0x4_0000;
+
+/// RAM that exists but must stay out of general allocation.
+pub fn is_special_purpose(kind: u32) -> bool {
+    matches!(
+        kind,
+        PERSISTENT | PERSISTENT_LEGACY | SOFT_RESERVED | HOT_PLUGGABLE
+    )
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    #[test]
+    fn region_kind_round_trips_every_value() {
+        init();
+        for k in [
+            0,
+            USABLE,
+            RESERVED,
+            ACPI_RECLAIMABLE,
+            ACPI_NVS,
+            BAD_RAM,
+            PERSISTENT,
+            SOFT_RESERVED,
+        ] {
+            pretty_assertions::assert_eq!(u32::from(RegionKind::from(k)), k);
+        }
+        pretty_assertions::assert_eq!(RegionKind::from(2), RegionKind::Reserved);
+        pretty_assertions::assert_eq!(RegionKind::from(7), RegionKind::Unknown(7));
+        assert!(RegionKind::from(1).is_usable());
+    }
+
+    #[test]
+    fn precedence_orders_usable_lowest_and_bad_ram_highest() {
+        let order = [USABLE, ACPI_RECLAIMABLE, ACPI_NVS, RESERVED, BAD_RAM];
+        assert!(order
+            .windows(2)
+            .all(|w| precedence(w[0]) < precedence(w[1])));
+        pretty_assertions::assert_eq!(precedence(SOFT_RESERVED), precedence(RESERVED));
+    }
+}
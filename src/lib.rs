@@ -3,15 +3,113 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 #![cfg_attr(not(feature = "std"), no_std)]
+pub mod adapters;
+pub mod blob;
+pub mod capabilities;
+#[cfg(feature = "alloc")]
+pub mod compose;
+#[cfg(feature = "alloc")]
+pub mod conformance;
+#[cfg(feature = "alloc")]
+pub mod defrag;
+pub mod encryption;
+pub mod entropy;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod frames;
+pub mod guest;
+#[cfg(feature = "ffi")]
+pub mod handoff;
+#[cfg(feature = "alloc")]
+pub mod integrity;
+pub mod kind;
+pub mod map;
+pub mod mapper;
+pub mod measure;
+#[cfg(feature = "memtest")]
+pub mod memtest;
+#[cfg(feature = "fmt")]
+pub mod numfmt;
+#[cfg(feature = "alloc")]
+pub mod owned;
+pub mod persist;
 pub mod raw;
 pub mod region;
+#[cfg(feature = "alloc")]
+pub mod relocate;
+pub mod rejection;
+pub mod scrub;
+pub mod source;
+pub mod stats;
+#[cfg(feature = "fmt")]
+pub mod table;
 pub mod tests;
+#[cfg(feature = "alloc")]
+pub mod testing;
+#[cfg(feature = "alloc")]
+pub mod tier;
+#[cfg(feature = "alloc")]
+pub mod tree;
+#[cfg(feature = "alloc")]
+pub mod validate;
+pub mod vectors;
+#[cfg(all(feature = "std", feature = "fmt"))]
+pub mod viz;
 
 // Your code goes here.
 // Don’t depend on Vec in the core parsing path unless you have alloc in the kernel.
 // Prefer: read_one(&[u8]), iter over &[u8], sanitize, usable frames iterator.
 
+// Thread safety and cloning
+//
+// Every iterator and map view in this crate borrows plain bytes or
+// regions and holds nothing but integers besides, so all of them are
+// Clone, Send and Sync. A clone is a checkpoint: it resumes exactly where
+// the original was. The list below is checked at compile time; anything
+// added to it is a promise, and dropping something from it is a breaking
+// change.
+const _: () = {
+    fn clone_send_sync<T: Clone + Send + Sync>() {}
+    fn check() {
+        clone_send_sync::<blob::TableBlob<'static>>();
+        clone_send_sync::<raw::Mb1MmapIter<'static>>();
+        clone_send_sync::<raw::mb2::Mb2MmapIter<'static>>();
+        clone_send_sync::<raw::e820::E820Iter<'static>>();
+        clone_send_sync::<raw::uefi::UefiMmapIter<'static>>();
+        clone_send_sync::<raw::srat::SratIter<'static>>();
+        clone_send_sync::<raw::coreboot::CorebootTable<'static>>();
+        clone_send_sync::<raw::coreboot::Records<'static>>();
+        clone_send_sync::<raw::coreboot::CorebootMemIter<'static>>();
+        #[cfg(feature = "pvh")]
+        clone_send_sync::<raw::pvh::PvhMemmapIter<'static>>();
+        #[cfg(feature = "fdt")]
+        clone_send_sync::<raw::fdt::Fdt<'static>>();
+        clone_send_sync::<adapters::Usable<adapters::Sanitized<raw::Mb1MmapIter<'static>>>>();
+        clone_send_sync::<frames::UsableFrames<'static>>();
+        clone_send_sync::<frames::BumpAllocator<'static>>();
+        clone_send_sync::<frames::UsableRuns<'static>>();
+        clone_send_sync::<frames::AlignedChunks<'static>>();
+        clone_send_sync::<frames::RegionFrames>();
+        clone_send_sync::<region::Candidates<'static>>();
+        clone_send_sync::<region::Coverage<'static>>();
+        clone_send_sync::<region::Gaps<'static>>();
+        clone_send_sync::<region::Stripe<'static>>();
+        #[cfg(feature = "alloc")]
+        clone_send_sync::<region::RegionSet>();
+        clone_send_sync::<map::MemoryMap<1>>();
+        #[cfg(feature = "alloc")]
+        clone_send_sync::<owned::MemoryMap>();
+        clone_send_sync::<persist::Snapshot<'static>>();
+        #[cfg(feature = "alloc")]
+        clone_send_sync::<tree::RegionTree>();
+        #[cfg(feature = "alloc")]
+        clone_send_sync::<tier::TieredMap>();
+        #[cfg(feature = "alloc")]
+        clone_send_sync::<tier::TieredFrames<'static>>();
+    }
+};
+
+#[cfg(feature = "alloc")]
 extern crate alloc;
 #[cfg(test)]
 extern crate std; // allows tests to use Vec, etc.
@@ -0,0 +1,312 @@
+// map.rs
+//
+// Somewhere to keep the map before there is a heap. MemoryMap<N> is an
+// array of N regions plus a length, so it can live on the stack or in a
+// static and be filled straight from a parser:
+//
+//   static mut MAP: MemoryMap<128> = MemoryMap::new();
+//   for r in Mb1MmapIter::new(buf).filter_map(...) { MAP.push(r)?; }
+//   MAP.normalize();
+//
+// Nothing here allocates. Operations that may need more entries
+// (normalize and carve_out can split regions) use the spare capacity,
+// so size N for the firmware's entry count plus some headroom.
+
+use core::ops::{Index, Range};
+
+use crate::frames::UsableFrames;
+use crate::kind;
+use crate::raw::MemRegion;
+use crate::region::{self, CarveOutError};
+use crate::stats::MemoryStats;
+
+const EMPTY: MemRegion = MemRegion {
+    start: 0,
+    len: 0,
+    kind: 0,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MapError {
+    /// All `capacity` entries are in use.
+    Full { capacity: usize },
+}
+
+/// Up to `N` regions, no heap.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryMap<const N: usize> {
+    regions: [MemRegion; N],
+    len: usize,
+}
+
+impl<const N: usize> Default for MemoryMap<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> MemoryMap<N> {
+    pub const fn new() -> Self {
+        MemoryMap {
+            regions: [EMPTY; N],
+            len: 0,
+        }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_slice(&self) -> &[MemRegion] {
+        &self.regions[..self.len]
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, MemRegion> {
+        self.as_slice().iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn push(&mut self, region: MemRegion) -> Result<(), MapError> {
+        let slot = self
+            .regions
+            .get_mut(self.len)
+            .ok_or(MapError::Full { capacity: N })?;
+        *slot = region;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Push every region of `regions`, stopping at the first that does
+    /// not fit (the ones before it stay).
+    pub fn extend<I: IntoIterator<Item = MemRegion>>(
+        &mut self,
+        regions: I,
+    ) -> Result<(), MapError> {
+        regions.into_iter().try_for_each(|r| self.push(r))
+    }
+
+    /// Sort by start (then length, then kind).
+    pub fn sort(&mut self) {
+        self.regions[..self.len].sort_unstable();
+    }
+
+    /// Sort, then join regions of the same kind that touch or overlap.
+    /// Overlaps between different kinds are left alone; see `normalize`.
+    pub fn merge(&mut self) {
+        self.sort();
+        let mut w = 0usize;
+        for i in 0..self.len {
+            let r = self.regions[i];
+            match w.checked_sub(1).map(|l| &mut self.regions[l]) {
+                Some(last) if last.kind == r.kind && r.start <= last.end() => {
+                    last.len = last.end().max(r.end()) - last.start;
+                }
+                _ => {
+                    self.regions[w] = r;
+                    w += 1;
+                }
+            }
+        }
+        self.len = w;
+    }
+
+    /// [`region::normalize`]: sorted, merged, overlaps resolved by
+    /// precedence. Splits use the spare capacity; without enough of it the
+    /// losing pieces are dropped (see `region::normalize`).
+    pub fn normalize(&mut self) {
+        // Unused slots are the room normalize splits into.
+        self.regions[self.len..].fill(EMPTY);
+        self.len = region::normalize(&mut self.regions);
+    }
+
+    /// [`region::carve_out`] `range` from the usable regions.
+    pub fn carve_out(&mut self, range: Range<u64>) -> Result<(), MapError> {
+        match region::carve_out(&mut self.regions, self.len, range) {
+            Ok(len) => {
+                self.len = len;
+                Ok(())
+            }
+            Err(CarveOutError::NoRoom { .. }) => Err(MapError::Full { capacity: N }),
+        }
+    }
+
+    /// After the ACPI tables are copied out: make ACPI reclaimable memory
+    /// usable and normalize. See [`region::reclaim_acpi`].
+    pub fn reclaim_acpi(&mut self) {
+        if region::reclaim_acpi(&mut self.regions[..self.len]) > 0 {
+            self.normalize();
+        }
+    }
+
+    /// Reserve everything below 1 MiB and normalize. See
+    /// [`region::reserve_low_memory`].
+    pub fn reserve_low_memory(&mut self) -> Result<(), MapError> {
+        self.push(MemRegion {
+            start: 0,
+            len: region::LOW_MEMORY_END,
+            kind: kind::RESERVED,
+        })?;
+        self.normalize();
+        Ok(())
+    }
+
+    /// Cut the map off at `limit`, like Linux's `mem=`. See
+    /// [`region::clamp_max_addr`].
+    pub fn clamp_max_addr(&mut self, limit: u64) {
+        self.len = region::clamp_max_addr(&mut self.regions, self.len, limit);
+    }
+
+    /// Holes between regions, lowest first; normalize first. See
+    /// [`region::gaps`].
+    pub fn gaps(&self) -> region::Gaps<'_> {
+        region::gaps(self.as_slice())
+    }
+
+    /// The region containing `addr`; normalize first. See
+    /// [`region::region_containing`].
+    pub fn region_containing(&self, addr: u64) -> Option<&MemRegion> {
+        region::region_containing(self.as_slice(), addr)
+    }
+
+    /// Every byte of `start..start + len` is usable; normalize first. See
+    /// [`region::is_range_usable`].
+    pub fn is_range_usable(&self, start: u64, len: u64) -> bool {
+        region::is_range_usable(self.as_slice(), start, len)
+    }
+
+    /// Boot-time capacity numbers; see [`MemoryStats`].
+    pub fn stats(&self) -> MemoryStats {
+        MemoryStats::from_regions(self.as_slice())
+    }
+
+    /// Frames of the usable regions, lowest first.
+    pub fn usable_frames(&self) -> UsableFrames<'_> {
+        UsableFrames::new(self.as_slice())
+    }
+}
+
+/// Only the first `len()` entries; past that panics like a slice would.
+impl<const N: usize> Index<usize> for MemoryMap<N> {
+    type Output = MemRegion;
+
+    fn index(&self, i: usize) -> &MemRegion {
+        &self.as_slice()[i]
+    }
+}
+
+impl<'a, const N: usize> IntoIterator for &'a MemoryMap<N> {
+    type Item = &'a MemRegion;
+    type IntoIter = core::slice::Iter<'a, MemRegion>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::frames::PhysFrame;
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    #[test]
+    fn push_until_full() {
+        init();
+        let mut map = MemoryMap::<2>::new();
+        map.push(region(0, 0x1000, 1)).unwrap();
+        map.push(region(0x1000, 0x1000, 2)).unwrap();
+        pretty_assertions::assert_eq!(
+            map.push(region(0x2000, 0x1000, 1)),
+            Err(MapError::Full { capacity: 2 })
+        );
+        pretty_assertions::assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn merge_joins_same_kind_neighbours() {
+        let mut map = MemoryMap::<8>::new();
+        map.extend([
+            region(0x2000, 0x1000, 1),
+            region(0, 0x1000, 1),
+            region(0x1000, 0x1800, 1),
+            region(0x4000, 0x1000, 2),
+        ])
+        .unwrap();
+        map.merge();
+        pretty_assertions::assert_eq!(
+            map.as_slice(),
+            &[region(0, 0x3000, 1), region(0x4000, 0x1000, 2)]
+        );
+        pretty_assertions::assert_eq!(map[1], region(0x4000, 0x1000, 2));
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_stops_at_len() {
+        let mut map = MemoryMap::<4>::new();
+        map.push(region(0, 0x1000, 1)).unwrap();
+        let _ = map[1];
+    }
+
+    #[test]
+    fn normalize_and_carve_out_use_spare_capacity() {
+        let mut map = MemoryMap::<4>::new();
+        map.extend([region(0, 0x10000, 1), region(0x4000, 0x2000, 2)])
+            .unwrap();
+        map.normalize();
+        pretty_assertions::assert_eq!(
+            map.as_slice(),
+            &[
+                region(0, 0x4000, 1),
+                region(0x4000, 0x2000, 2),
+                region(0x6000, 0xA000, 1),
+            ]
+        );
+
+        map.carve_out(0x8000..0x9000).unwrap();
+        pretty_assertions::assert_eq!(map.len(), 4);
+        pretty_assertions::assert_eq!(
+            map.carve_out(0x1000..0x2000),
+            Err(MapError::Full { capacity: 4 })
+        );
+        pretty_assertions::assert_eq!(map.usable_frames().next(), Some(PhysFrame(0)));
+    }
+
+    #[test]
+    fn reserve_low_memory_needs_a_free_slot() {
+        let mut map = MemoryMap::<3>::new();
+        map.extend([region(0, 0x9_F000, 1), region(0x10_0000, 0x10_0000, 1)])
+            .unwrap();
+        map.reserve_low_memory().unwrap();
+        pretty_assertions::assert_eq!(
+            map.as_slice(),
+            &[region(0, 0x10_0000, 2), region(0x10_0000, 0x10_0000, 1)]
+        );
+
+        map.extend([region(0x30_0000, 0x1000, 1)]).unwrap();
+        pretty_assertions::assert_eq!(
+            map.reserve_low_memory(),
+            Err(MapError::Full { capacity: 3 })
+        );
+    }
+}
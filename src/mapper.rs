@@ -0,0 +1,42 @@
+// mapper.rs
+//
+// This crate only ever deals in physical addresses. Touching the memory
+// behind one needs a virtual mapping, and how you get that is kernel
+// policy: a direct-map offset, a temporary window, identity mapping in a
+// bootloader, a Vec in tests.
+//
+// PhysMapper is that policy. Anything here that reads or writes RAM
+// contents (scrubbing, memory tests, checksums) goes through it.
+
+/// Maps physical ranges into the current address space.
+pub trait PhysMapper {
+    /// Make `[phys, phys + len)` accessible and return a pointer to its
+    /// first byte. Callers never ask for more than one chunk at a time
+    /// and always `unmap` before the next `map`.
+    ///
+    /// # Safety
+    /// The returned pointer must be valid for reads and writes of `len`
+    /// bytes until `unmap` is called, and must alias nothing Rust owns.
+    unsafe fn map(&mut self, phys: u64, len: usize) -> *mut u8;
+
+    /// Release a mapping returned by `map`. Default: nothing to do
+    /// (direct maps, identity maps).
+    ///
+    /// # Safety
+    /// `virt`/`len` must come from the matching `map` call.
+    unsafe fn unmap(&mut self, virt: *mut u8, len: usize) {
+        let _ = (virt, len);
+    }
+}
+
+/// Direct map: physical address `p` lives at virtual `offset + p`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OffsetMapper {
+    pub offset: u64,
+}
+
+impl PhysMapper for OffsetMapper {
+    unsafe fn map(&mut self, phys: u64, _len: usize) -> *mut u8 {
+        self.offset.wrapping_add(phys) as usize as *mut u8
+    }
+}
@@ -0,0 +1,112 @@
+// measure.rs
+//
+// Measured boot: every stage hashes what it is about to trust into a TPM
+// PCR, so a remote verifier can tell exactly what booted. The memory
+// layout belongs in that chain too (a hostile loader could hide memory
+// from the kernel or overlap it with its own code).
+//
+// Hashing only works if the same layout always produces the same bytes.
+// So the map is canonicalized first (order, overlaps, merging and
+// alignment no longer matter) and then written in a fixed format:
+//
+//   offset  size  field
+//   0       4     magic   "MMAP"
+//   4       4     version 1 (u32 LE)
+//   8       4     count   number of records (u32 LE)
+//   12      20*n  records: start u64 LE, len u64 LE, kind u32 LE
+//
+// This crate does not hash. You pass the bytes to whatever your TPM
+// stack uses (SHA-256 for PCR extend, usually).
+
+use crate::raw::MemRegion;
+#[cfg(feature = "alloc")]
+use crate::region::canonicalize;
+
+pub const MEASURE_MAGIC: [u8; 4] = *b"MMAP";
+pub const MEASURE_VERSION: u32 = 1;
+/// Bytes per region record.
+pub const MEASURE_RECORD_LEN: usize = 20;
+
+/// Stream the byte-stable serialization of an already canonical map into
+/// `update`. No allocation; the caller guarantees canonical input.
+pub fn write_canonical<F: FnMut(&[u8])>(canonical: &[MemRegion], mut update: F) {
+    update(&MEASURE_MAGIC);
+    update(&MEASURE_VERSION.to_le_bytes());
+    update(&(canonical.len() as u32).to_le_bytes());
+    for r in canonical {
+        let mut rec = [0u8; MEASURE_RECORD_LEN];
+        rec[0..8].copy_from_slice(&r.start.to_le_bytes());
+        rec[8..16].copy_from_slice(&r.len.to_le_bytes());
+        rec[16..20].copy_from_slice(&r.kind.to_le_bytes());
+        update(&rec);
+    }
+}
+
+/// Canonicalize `regions` and stream the result into `update` (e.g. a
+/// hasher's update method). Equivalent maps always feed identical bytes.
+#[cfg(feature = "alloc")]
+pub fn measure<F: FnMut(&[u8])>(regions: &[MemRegion], update: F) {
+    write_canonical(&canonicalize(regions), update);
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+    use crate::tests::prelude::hexdump;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn measured(regions: &[MemRegion]) -> Vec<u8> {
+        let mut out = Vec::new();
+        measure(regions, |b| out.extend_from_slice(b));
+        out
+    }
+
+    #[test]
+    fn wire_format_is_stable() {
+        init();
+        let bytes = measured(&[region(0, 0x9F000, 1), region(0x9F000, 0x61000, 2)]);
+        insta::assert_snapshot!(hexdump(&bytes), @r"
+        00000000: 4d 4d 41 50 01 00 00 00 02 00 00 00 00 00 00 00 
+        00000010: 00 00 00 00 00 f0 09 00 00 00 00 00 01 00 00 00 
+        00000020: 00 f0 09 00 00 00 00 00 00 10 06 00 00 00 00 00 
+        00000030: 02 00 00 00 
+        ");
+    }
+
+    #[test]
+    fn equivalent_maps_measure_identically() {
+        let a = [
+            region(0x1000, 0x1000, 1),
+            region(0, 0x1000, 1),
+            region(0x4000, 0x1000, 2),
+        ];
+        let b = [
+            region(0x4000, 0x1000, 2),
+            region(0, 0x2000, 1),
+            region(0x4000, 0x800, 1), // loses to reserved anyway
+        ];
+        pretty_assertions::assert_eq!(measured(&a), measured(&b));
+    }
+
+    #[test]
+    fn different_maps_measure_differently() {
+        let a = [region(0, 0x2000, 1)];
+        let b = [region(0, 0x2000, 2)];
+        pretty_assertions::assert_ne!(measured(&a), measured(&b));
+    }
+
+    #[test]
+    fn length_matches_header_and_records() {
+        let bytes = measured(&[region(0, 0x1000, 1), region(0x2000, 0x1000, 1)]);
+        pretty_assertions::assert_eq!(bytes.len(), 12 + 2 * MEASURE_RECORD_LEN);
+    }
+}
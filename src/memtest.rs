@@ -0,0 +1,269 @@
+// memtest.rs
+//
+// Early RAM testing, memtest86-style but tiny: walk every usable frame
+// through a PhysMapper, write patterns, read them back.
+//
+// Two classic patterns catch most real failures:
+//
+//   - walking ones: each bit of each word set on its own; finds stuck
+//     and shorted data lines
+//   - address-in-address: every word holds its own physical address,
+//     written for a whole run before anything is read back; finds
+//     address lines that alias two frames onto the same cells
+//
+// Failing frames go back into the map as BAD_RAM (mark_defective), so
+// everything built from the map afterwards (allocators included) never
+// sees them.
+//
+// Destructive: whatever was in the tested frames is gone.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::compose::{compose, MapSource, Override};
+use crate::frames::{PhysFrame, UsableRuns, FRAME_SIZE};
+#[cfg(feature = "alloc")]
+use crate::kind;
+use crate::mapper::PhysMapper;
+use crate::raw::MemRegion;
+
+const WORDS_PER_FRAME: usize = (FRAME_SIZE / 8) as usize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    WalkingOnes,
+    AddressInAddress,
+}
+
+/// Both patterns, address test first (it is the cheaper one).
+pub const DEFAULT_PATTERNS: [Pattern; 2] = [Pattern::AddressInAddress, Pattern::WalkingOnes];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemtestStats {
+    pub frames_tested: u64,
+    /// Failure reports; a frame failing several patterns counts once per pattern.
+    pub failures: u64,
+}
+
+/// Run `patterns` over every usable frame of `regions`, calling `on_bad`
+/// for each frame that fails a pattern.
+///
+/// # Safety
+/// Every usable frame in `regions` must be unused (no kernel image, no
+/// page tables, no DMA) and `mapper` must map frames writable. Contents
+/// are destroyed.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "info", skip_all, fields(regions = regions.len()))
+)]
+pub unsafe fn memtest<M, F>(
+    regions: &[MemRegion],
+    patterns: &[Pattern],
+    mapper: &mut M,
+    mut on_bad: F,
+) -> MemtestStats
+where
+    M: PhysMapper,
+    F: FnMut(PhysFrame),
+{
+    let mut stats = MemtestStats::default();
+    for (first, count) in UsableRuns::new(regions) {
+        let frames = (0..count).map(|i| PhysFrame(first.0 + i * FRAME_SIZE));
+        for pattern in patterns {
+            match pattern {
+                Pattern::WalkingOnes => {
+                    for frame in frames.clone() {
+                        if !with_frame(mapper, frame, |words| walking_ones(words)) {
+                            stats.failures += 1;
+                            on_bad(frame);
+                        }
+                    }
+                }
+                Pattern::AddressInAddress => {
+                    // Fill the whole run before checking, so aliasing
+                    // frames overwrite each other and show up.
+                    for frame in frames.clone() {
+                        with_frame(mapper, frame, |words| {
+                            fill_addresses(words, frame.0);
+                            true
+                        });
+                    }
+                    for frame in frames.clone() {
+                        if !with_frame(mapper, frame, |words| check_addresses(words, frame.0)) {
+                            stats.failures += 1;
+                            on_bad(frame);
+                        }
+                    }
+                }
+            }
+        }
+        stats.frames_tested += count;
+    }
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+        frames = stats.frames_tested,
+        failures = stats.failures,
+        "memtest done"
+    );
+    stats
+}
+
+/// Mark `bad` frames as BAD_RAM in `map`. Bad frames outside the map
+/// become BAD_RAM regions of their own.
+#[cfg(feature = "alloc")]
+pub fn mark_defective(map: &[MemRegion], bad: &[PhysFrame]) -> Vec<MemRegion> {
+    let overrides: Vec<Override> = bad
+        .iter()
+        .map(|f| Override {
+            region: MemRegion {
+                start: f.0,
+                len: FRAME_SIZE,
+                kind: kind::BAD_RAM,
+            },
+            source: MapSource::Memtest,
+        })
+        .collect();
+    compose(map, &overrides)
+}
+
+unsafe fn with_frame<M: PhysMapper>(
+    mapper: &mut M,
+    frame: PhysFrame,
+    f: impl FnOnce(*mut u64) -> bool,
+) -> bool {
+    let virt = mapper.map(frame.0, FRAME_SIZE as usize);
+    let ok = f(virt as *mut u64);
+    mapper.unmap(virt, FRAME_SIZE as usize);
+    ok
+}
+
+unsafe fn walking_ones(words: *mut u64) -> bool {
+    for bit in 0..64 {
+        let pattern = 1u64 << bit;
+        for i in 0..WORDS_PER_FRAME {
+            words.add(i).write_volatile(pattern);
+        }
+        for i in 0..WORDS_PER_FRAME {
+            if words.add(i).read_volatile() != pattern {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+unsafe fn fill_addresses(words: *mut u64, phys: u64) {
+    for i in 0..WORDS_PER_FRAME {
+        words.add(i).write_volatile(phys + 8 * i as u64);
+    }
+}
+
+unsafe fn check_addresses(words: *mut u64, phys: u64) -> bool {
+    (0..WORDS_PER_FRAME).all(|i| words.add(i).read_volatile() == phys + 8 * i as u64)
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    /// Fake RAM where frame `alias` is wired to the same cells as `target`,
+    /// like a broken address line.
+    struct AliasingMapper {
+        base: u64,
+        mem: Vec<u64>,
+        alias: Option<(u64, u64)>,
+    }
+
+    impl PhysMapper for AliasingMapper {
+        unsafe fn map(&mut self, phys: u64, len: usize) -> *mut u8 {
+            let phys = match self.alias {
+                Some((alias, target)) if phys == alias => target,
+                _ => phys,
+            };
+            let off = ((phys - self.base) / 8) as usize;
+            assert!(
+                off * 8 + len <= self.mem.len() * 8,
+                "mapped outside fake RAM"
+            );
+            self.mem.as_mut_ptr().add(off) as *mut u8
+        }
+    }
+
+    fn mapper(base: u64, frames: usize, alias: Option<(u64, u64)>) -> AliasingMapper {
+        AliasingMapper {
+            base,
+            mem: vec![0; frames * WORDS_PER_FRAME],
+            alias,
+        }
+    }
+
+    #[test]
+    fn healthy_ram_passes_every_pattern() {
+        init();
+        let map = [region(0x10_0000, 0x4000, 1), region(0x10_4000, 0x1000, 2)];
+        let mut m = mapper(0x10_0000, 5, None);
+        let mut bad = Vec::new();
+
+        let stats = unsafe { memtest(&map, &DEFAULT_PATTERNS, &mut m, |f| bad.push(f)) };
+
+        pretty_assertions::assert_eq!(
+            stats,
+            MemtestStats {
+                frames_tested: 4,
+                failures: 0
+            }
+        );
+        assert!(bad.is_empty());
+        // Reserved frame was never touched.
+        assert!(m.mem[4 * WORDS_PER_FRAME..].iter().all(|&w| w == 0));
+    }
+
+    #[test]
+    fn address_in_address_catches_aliasing() {
+        let map = [region(0x10_0000, 0x4000, 1)];
+        let mut m = mapper(0x10_0000, 4, Some((0x10_3000, 0x10_1000)));
+        let mut bad = Vec::new();
+
+        let stats = unsafe { memtest(&map, &[Pattern::AddressInAddress], &mut m, |f| bad.push(f)) };
+
+        // Frame 3's writes landed in frame 1's cells. Frame 3 reads its own
+        // pattern back, so only the overwritten frame is caught.
+        pretty_assertions::assert_eq!(bad, vec![PhysFrame(0x10_1000)]);
+        pretty_assertions::assert_eq!(stats.failures, 1);
+    }
+
+    #[test]
+    fn walking_ones_alone_misses_aliasing() {
+        let map = [region(0x10_0000, 0x2000, 1)];
+        let mut m = mapper(0x10_0000, 2, Some((0x10_1000, 0x10_0000)));
+        let stats = unsafe { memtest(&map, &[Pattern::WalkingOnes], &mut m, |_| {}) };
+        pretty_assertions::assert_eq!(stats.failures, 0);
+    }
+
+    #[test]
+    fn mark_defective_turns_frames_into_bad_ram() {
+        let map = [region(0, 0x10000, 1)];
+        let bad = [PhysFrame(0x2000), PhysFrame(0x3000), PhysFrame(0x8000)];
+        pretty_assertions::assert_eq!(
+            mark_defective(&map, &bad),
+            vec![
+                region(0, 0x2000, 1),
+                region(0x2000, 0x2000, kind::BAD_RAM),
+                region(0x4000, 0x4000, 1),
+                region(0x8000, 0x1000, kind::BAD_RAM),
+                region(0x9000, 0x7000, 1),
+            ]
+        );
+    }
+}
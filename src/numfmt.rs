@@ -0,0 +1,133 @@
+// numfmt.rs
+//
+// Integer -> text without core::fmt.
+//
+// core::fmt is big (it pulls in the whole Formatter machinery and its
+// trait objects). Some early-boot environments cannot afford it, but still
+// want to print "0x9fc00" or "639.0 KiB" on a serial port.
+//
+// Every helper writes ASCII into a caller-provided fixed buffer and hands
+// back the written part as &str. No allocation, no panics, no fmt.
+
+/// Enough for `0x` + 16 hex digits.
+pub const HEX_BUF_LEN: usize = 18;
+/// Enough for u64::MAX in decimal.
+pub const DEC_BUF_LEN: usize = 20;
+/// Enough for `1023.9 KiB` and friends.
+pub const SIZE_BUF_LEN: usize = 12;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+const UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+fn ascii(bytes: &[u8]) -> &str {
+    // SAFETY: every helper in this file writes only ASCII digits/letters.
+    unsafe { core::str::from_utf8_unchecked(bytes) }
+}
+
+/// `0x` followed by lowercase hex digits, no leading zeros (`0x0` for zero).
+pub fn hex(value: u64, buf: &mut [u8; HEX_BUF_LEN]) -> &str {
+    hex_padded(value, 1, buf)
+}
+
+/// Like [`hex`](hex()), zero-padded to at least `digits` digits (capped at 16).
+pub fn hex_padded(value: u64, digits: usize, buf: &mut [u8; HEX_BUF_LEN]) -> &str {
+    let used = (64 - value.leading_zeros() as usize).div_ceil(4);
+    let n = used.max(digits).clamp(1, 16);
+
+    buf[0] = b'0';
+    buf[1] = b'x';
+    for i in 0..n {
+        let nibble = (value >> (4 * (n - 1 - i))) & 0xF;
+        buf[2 + i] = HEX_DIGITS[nibble as usize];
+    }
+    ascii(&buf[..2 + n])
+}
+
+/// Plain decimal.
+pub fn decimal(value: u64, buf: &mut [u8; DEC_BUF_LEN]) -> &str {
+    let mut v = value;
+    let mut i = DEC_BUF_LEN;
+    loop {
+        i -= 1;
+        buf[i] = b'0' + (v % 10) as u8;
+        v /= 10;
+        if v == 0 {
+            break;
+        }
+    }
+    ascii(&buf[i..])
+}
+
+/// Largest binary unit with one decimal, rounded down: `639.0 KiB`.
+pub fn human_size(bytes: u64, buf: &mut [u8; SIZE_BUF_LEN]) -> &str {
+    let mut unit = 0;
+    while unit + 1 < UNITS.len() && bytes >> (10 * (unit + 1)) > 0 {
+        unit += 1;
+    }
+    let scale = 1u64 << (10 * unit);
+    let whole = bytes / scale;
+    let tenths = (bytes % scale) * 10 / scale;
+
+    let mut digits = [0u8; DEC_BUF_LEN];
+    let whole = decimal(whole, &mut digits).as_bytes();
+
+    // whole is at most 4 digits ("1023"), so this always fits.
+    let mut n = 0;
+    for part in [
+        whole,
+        b".",
+        &[b'0' + tenths as u8],
+        b" ",
+        UNITS[unit].as_bytes(),
+    ] {
+        buf[n..n + part.len()].copy_from_slice(part);
+        n += part.len();
+    }
+    ascii(&buf[..n])
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    #[test]
+    fn hex_matches_core_fmt() {
+        init();
+        for v in [0, 1, 0xF, 0x10, 0x9_FC00, 0x23_FFFF_FFFF, u64::MAX] {
+            let mut buf = [0; HEX_BUF_LEN];
+            pretty_assertions::assert_eq!(hex(v, &mut buf), format!("{v:#x}"));
+        }
+    }
+
+    #[test]
+    fn hex_padded_pads_but_never_truncates() {
+        let mut buf = [0; HEX_BUF_LEN];
+        pretty_assertions::assert_eq!(hex_padded(0x9_FC00, 8, &mut buf), "0x0009fc00");
+        pretty_assertions::assert_eq!(hex_padded(0x1_0000_0000, 8, &mut buf), "0x100000000");
+        pretty_assertions::assert_eq!(hex_padded(0, 99, &mut buf), "0x0000000000000000");
+    }
+
+    #[test]
+    fn decimal_matches_core_fmt() {
+        for v in [0, 7, 10, 654_336, u64::MAX] {
+            let mut buf = [0; DEC_BUF_LEN];
+            pretty_assertions::assert_eq!(decimal(v, &mut buf), v.to_string());
+        }
+    }
+
+    #[test]
+    fn human_size_units() {
+        let mut buf = [0; SIZE_BUF_LEN];
+        pretty_assertions::assert_eq!(human_size(0, &mut buf), "0.0 B");
+        pretty_assertions::assert_eq!(human_size(1023, &mut buf), "1023.0 B");
+        pretty_assertions::assert_eq!(human_size(0x9_FC00, &mut buf), "639.0 KiB");
+        pretty_assertions::assert_eq!(human_size((1 << 30) - 1, &mut buf), "1023.9 MiB");
+        pretty_assertions::assert_eq!(human_size(u64::MAX, &mut buf), "15.9 EiB");
+    }
+}
@@ -0,0 +1,258 @@
+// owned.rs
+//
+// The whole pipeline behind one type, for hosted tools and tests that
+// have a heap and don't want to stitch parser, sanitize, normalize and
+// carve-outs together by hand:
+//
+//   let mut map = MemoryMap::from_mb1(buf)?;
+//   map.normalize();
+//   map.reserve(kernel_start..kernel_end);
+//   for frame in map.usable_frames() { ... }
+//
+// Kernels without a heap want map::MemoryMap<N> instead; it does the
+// same in a fixed array.
+
+use alloc::vec::Vec;
+use core::ops::{Index, Range};
+
+use crate::frames::UsableFrames;
+use crate::raw::{Mb1MmapIter, MemRegion, MmapError};
+use crate::region;
+use crate::source::MemoryMapSource;
+use crate::stats::MemoryStats;
+use crate::tree::RegionTree;
+
+/// A memory map that owns its regions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryMap {
+    regions: Vec<MemRegion>,
+}
+
+impl MemoryMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take `regions` as they are; nothing is sorted or merged.
+    pub fn from_regions(regions: Vec<MemRegion>) -> Self {
+        MemoryMap { regions }
+    }
+
+    /// Every region of `source`, stopping at the first error.
+    pub fn from_source<S: MemoryMapSource + ?Sized>(source: &S) -> Result<Self, S::Error> {
+        source
+            .regions()
+            .collect::<Result<_, _>>()
+            .map(Self::from_regions)
+    }
+
+    /// Parse and sanitize a Multiboot1 mmap buffer. Not normalized yet.
+    pub fn from_mb1(buf: &[u8]) -> Result<Self, MmapError> {
+        Self::from_source(&Mb1MmapIter::new(buf))
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    pub fn as_slice(&self) -> &[MemRegion] {
+        &self.regions
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, MemRegion> {
+        self.regions.iter()
+    }
+
+    pub fn push(&mut self, region: MemRegion) {
+        self.regions.push(region);
+    }
+
+    /// [`region::normalize_vec`]: sorted, merged, overlaps resolved by
+    /// precedence.
+    pub fn normalize(&mut self) {
+        region::normalize_vec(&mut self.regions);
+    }
+
+    /// Take `range` out of the usable regions (kernel image, initrd, ...),
+    /// splitting where needed. See [`region::carve_out`].
+    pub fn reserve(&mut self, range: Range<u64>) {
+        region::carve_out_vec(&mut self.regions, range);
+    }
+
+    /// After the ACPI tables are copied out: make ACPI reclaimable memory
+    /// usable and normalize. See [`region::reclaim_acpi`].
+    pub fn reclaim_acpi(&mut self) {
+        if region::reclaim_acpi(&mut self.regions) > 0 {
+            self.normalize();
+        }
+    }
+
+    /// Reserve everything below 1 MiB, whatever the firmware said, and
+    /// normalize. See [`region::reserve_low_memory`].
+    pub fn reserve_low_memory(&mut self) {
+        region::reserve_low_memory(&mut self.regions);
+    }
+
+    /// Cut the map off at `limit`, like Linux's `mem=`: for trying a
+    /// low-memory configuration, or a 32-bit kernel that can't reach high
+    /// RAM. See [`region::clamp_max_addr`].
+    pub fn clamp_max_addr(&mut self, limit: u64) {
+        let len = self.regions.len();
+        let len = region::clamp_max_addr(&mut self.regions, len, limit);
+        self.regions.truncate(len);
+    }
+
+    /// Holes between regions, lowest first; normalize first. See
+    /// [`region::gaps`].
+    pub fn gaps(&self) -> region::Gaps<'_> {
+        region::gaps(self.as_slice())
+    }
+
+    /// The region containing `addr`; normalize first. See
+    /// [`region::region_containing`].
+    pub fn region_containing(&self, addr: u64) -> Option<&MemRegion> {
+        region::region_containing(self.as_slice(), addr)
+    }
+
+    /// Every byte of `start..start + len` is usable; normalize first. See
+    /// [`region::is_range_usable`].
+    pub fn is_range_usable(&self, start: u64, len: u64) -> bool {
+        region::is_range_usable(self.as_slice(), start, len)
+    }
+
+    /// Boot-time capacity numbers; see [`MemoryStats`].
+    pub fn stats(&self) -> MemoryStats {
+        MemoryStats::from_regions(self.as_slice())
+    }
+
+    /// Frames of the usable regions, lowest first.
+    pub fn usable_frames(&self) -> UsableFrames<'_> {
+        UsableFrames::new(&self.regions)
+    }
+
+    /// For maps that keep changing after boot (hotplug, ballooning).
+    pub fn into_interval_tree(self) -> RegionTree {
+        RegionTree::from_regions(&self.regions)
+    }
+
+    pub fn into_vec(self) -> Vec<MemRegion> {
+        self.regions
+    }
+}
+
+impl From<Vec<MemRegion>> for MemoryMap {
+    fn from(regions: Vec<MemRegion>) -> Self {
+        Self::from_regions(regions)
+    }
+}
+
+impl FromIterator<MemRegion> for MemoryMap {
+    fn from_iter<I: IntoIterator<Item = MemRegion>>(iter: I) -> Self {
+        Self::from_regions(iter.into_iter().collect())
+    }
+}
+
+impl Index<usize> for MemoryMap {
+    type Output = MemRegion;
+
+    fn index(&self, i: usize) -> &MemRegion {
+        &self.regions[i]
+    }
+}
+
+impl IntoIterator for MemoryMap {
+    type Item = MemRegion;
+    type IntoIter = alloc::vec::IntoIter<MemRegion>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.regions.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a MemoryMap {
+    type Item = &'a MemRegion;
+    type IntoIter = core::slice::Iter<'a, MemRegion>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::frames::PhysFrame;
+    use crate::raw::{push_entry, raw};
+    use crate::tests::common::init;
+    use crate::vectors::MB1_PC;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    #[test]
+    fn from_mb1_matches_the_vector() {
+        init();
+        let map = MemoryMap::from_mb1(MB1_PC.bytes).unwrap();
+        pretty_assertions::assert_eq!(map.as_slice(), MB1_PC.regions);
+        pretty_assertions::assert_eq!(map[0], MB1_PC.regions[0]);
+    }
+
+    #[test]
+    fn from_mb1_surfaces_framing_errors() {
+        let mut buf = Vec::new();
+        push_entry(&mut buf, raw(0, 0x1000, 1));
+        buf.truncate(buf.len() - 4);
+        pretty_assertions::assert_eq!(
+            MemoryMap::from_mb1(&buf),
+            Err(MmapError::TruncatedEntry {
+                needed: 24,
+                have: 20
+            })
+        );
+    }
+
+    #[test]
+    fn normalize_reserve_then_allocate() {
+        let mut map: MemoryMap = [region(0x4000, 0x2000, 2), region(0, 0x10000, 1)]
+            .into_iter()
+            .collect();
+        map.normalize();
+        map.reserve(0..0x2000);
+        pretty_assertions::assert_eq!(
+            map.as_slice(),
+            &[
+                region(0x2000, 0x2000, 1),
+                region(0x4000, 0x2000, 2),
+                region(0x6000, 0xA000, 1),
+            ]
+        );
+        pretty_assertions::assert_eq!(map.usable_frames().next(), Some(PhysFrame(0x2000)));
+        pretty_assertions::assert_eq!(map.usable_frames().count(), 12);
+        pretty_assertions::assert_eq!(map.stats().usable_bytes, 12 * 0x1000);
+
+        let mut acpi =
+            MemoryMap::from_regions(vec![region(0, 0x1000, 1), region(0x1000, 0x1000, 3)]);
+        acpi.reclaim_acpi();
+        pretty_assertions::assert_eq!(acpi.as_slice(), &[region(0, 0x2000, 1)]);
+
+        let mut low = map.clone();
+        low.clamp_max_addr(0x5000);
+        pretty_assertions::assert_eq!(
+            low.as_slice(),
+            &[region(0x2000, 0x2000, 1), region(0x4000, 0x1000, 2)]
+        );
+
+        let tree = map.clone().into_interval_tree();
+        pretty_assertions::assert_eq!(tree.to_vec(), map.into_vec());
+    }
+}
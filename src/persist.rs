@@ -0,0 +1,287 @@
+// persist.rs
+//
+// A sanitized map, written down so the next stage does not have to
+// sanitize again. A bootloader canonicalizes once and leaves the result
+// in memory; the kernel (or a kexec'd successor) decodes it in place,
+// without allocating.
+//
+//   offset  size  field
+//   0       4     magic   "MMSN"
+//   4       4     version 1 (u32 LE)
+//   8       4     count   number of records (u32 LE)
+//   12      20*n  records: start u64 LE, len u64 LE, kind u32 LE
+//
+// Records are the same as in measure.rs; the magic differs so a snapshot
+// is never mistaken for measured bytes or the other way round.
+//
+// The writer only accepts a map that is already sorted and
+// non-overlapping, and the reader checks the same, so whatever decodes
+// is safe to build allocators from. Bytes after the last record are
+// ignored (the snapshot usually sits in a page of its own).
+
+use crate::blob::TableBlob;
+use crate::raw::MemRegion;
+
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"MMSN";
+pub const SNAPSHOT_VERSION: u32 = 1;
+pub const SNAPSHOT_HEADER_LEN: usize = 12;
+/// Bytes per region record.
+pub const SNAPSHOT_RECORD_LEN: usize = 20;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    BufferTooSmall {
+        needed: usize,
+        got: usize,
+    },
+    BadMagic {
+        found: [u8; 4],
+    },
+    UnsupportedVersion {
+        version: u32,
+    },
+    /// Region `index` is empty, wraps, or starts before the previous one ends.
+    NotSanitized {
+        index: usize,
+    },
+}
+
+/// Bytes needed to encode `count` regions.
+pub fn encoded_len(count: usize) -> usize {
+    SNAPSHOT_HEADER_LEN + count * SNAPSHOT_RECORD_LEN
+}
+
+/// Write `regions` into the front of `buf`. Returns the bytes written.
+///
+/// `regions` must be sanitized: sorted, non-empty, non-overlapping
+/// (what `canonicalize` returns). Nothing is written on error.
+pub fn encode_into(regions: &[MemRegion], buf: &mut [u8]) -> Result<usize, SnapshotError> {
+    check_sanitized(regions.iter().copied())?;
+    let needed = encoded_len(regions.len());
+    let count = u32::try_from(regions.len()).map_err(|_| SnapshotError::BufferTooSmall {
+        needed,
+        got: buf.len(),
+    })?;
+    let Some(out) = buf.get_mut(..needed) else {
+        return Err(SnapshotError::BufferTooSmall {
+            needed,
+            got: buf.len(),
+        });
+    };
+
+    out[0..4].copy_from_slice(&SNAPSHOT_MAGIC);
+    out[4..8].copy_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+    out[8..12].copy_from_slice(&count.to_le_bytes());
+    for (r, rec) in regions
+        .iter()
+        .zip(out[SNAPSHOT_HEADER_LEN..].chunks_exact_mut(SNAPSHOT_RECORD_LEN))
+    {
+        rec[0..8].copy_from_slice(&r.start.to_le_bytes());
+        rec[8..16].copy_from_slice(&r.len.to_le_bytes());
+        rec[16..20].copy_from_slice(&r.kind.to_le_bytes());
+    }
+    Ok(needed)
+}
+
+/// Check the header and every record of a snapshot, then hand back a view
+/// over it.
+pub fn decode(bytes: &[u8]) -> Result<Snapshot<'_>, SnapshotError> {
+    let blob = TableBlob::new(bytes);
+    let too_small = |needed| SnapshotError::BufferTooSmall {
+        needed,
+        got: bytes.len(),
+    };
+    let magic = blob.window(0, 4).ok_or(too_small(SNAPSHOT_HEADER_LEN))?;
+    if magic != SNAPSHOT_MAGIC {
+        return Err(SnapshotError::BadMagic {
+            found: magic.try_into().unwrap(),
+        });
+    }
+    let version = blob.u32_at(4).ok_or(too_small(SNAPSHOT_HEADER_LEN))?;
+    if version != SNAPSHOT_VERSION {
+        return Err(SnapshotError::UnsupportedVersion { version });
+    }
+    let count = blob.u32_at(8).ok_or(too_small(SNAPSHOT_HEADER_LEN))? as usize;
+    let records = count
+        .checked_mul(SNAPSHOT_RECORD_LEN)
+        .and_then(|n| blob.window(SNAPSHOT_HEADER_LEN, n))
+        .ok_or(too_small(encoded_len(count)))?;
+
+    let snapshot = Snapshot { records };
+    check_sanitized(snapshot.iter())?;
+    Ok(snapshot)
+}
+
+/// A decoded snapshot, borrowed from the bytes it was decoded from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Snapshot<'a> {
+    records: &'a [u8],
+}
+
+impl<'a> Snapshot<'a> {
+    pub fn len(&self) -> usize {
+        self.records.len() / SNAPSHOT_RECORD_LEN
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// The regions, in address order.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = MemRegion> + Clone + 'a {
+        self.records.chunks_exact(SNAPSHOT_RECORD_LEN).map(|rec| {
+            let rec = TableBlob::new(rec);
+            // Lengths were checked in decode.
+            MemRegion {
+                start: rec.u64_at(0).unwrap(),
+                len: rec.u64_at(8).unwrap(),
+                kind: rec.u32_at(16).unwrap(),
+            }
+        })
+    }
+}
+
+fn check_sanitized(regions: impl Iterator<Item = MemRegion>) -> Result<(), SnapshotError> {
+    let mut prev_end = None;
+    for (index, r) in regions.enumerate() {
+        let end = r.start.checked_add(r.len).filter(|_| r.len != 0);
+        let after_prev = prev_end.is_none_or(|p| r.start >= p);
+        match end {
+            Some(end) if after_prev => prev_end = Some(end),
+            _ => return Err(SnapshotError::NotSanitized { index }),
+        }
+    }
+    Ok(())
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::region::canonicalize;
+    use crate::tests::common::init;
+    use crate::tests::prelude::hexdump;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn encoded(regions: &[MemRegion]) -> Vec<u8> {
+        let mut buf = vec![0u8; encoded_len(regions.len())];
+        let n = encode_into(regions, &mut buf).unwrap();
+        pretty_assertions::assert_eq!(n, buf.len());
+        buf
+    }
+
+    #[test]
+    fn wire_format_is_stable() {
+        init();
+        let bytes = encoded(&[region(0, 0x9F000, 1), region(0x9F000, 0x61000, 2)]);
+        insta::assert_snapshot!(hexdump(&bytes), @r"
+        00000000: 4d 4d 53 4e 01 00 00 00 02 00 00 00 00 00 00 00 
+        00000010: 00 00 00 00 00 f0 09 00 00 00 00 00 01 00 00 00 
+        00000020: 00 f0 09 00 00 00 00 00 00 10 06 00 00 00 00 00 
+        00000030: 02 00 00 00 
+        ");
+    }
+
+    #[test]
+    fn round_trips_a_canonical_map() {
+        let map = canonicalize(&[
+            region(0x10_0000, 0x7FF0_0000, 1),
+            region(0, 0x9F000, 1),
+            region(0xE0000, 0x20000, 2),
+            region(0xFEC0_0000, 0x1000, 2),
+        ]);
+        let mut bytes = encoded(&map);
+        // Trailing space after the records is fine.
+        bytes.extend_from_slice(&[0xAA; 7]);
+
+        let snap = decode(&bytes).unwrap();
+        pretty_assertions::assert_eq!(snap.len(), map.len());
+        pretty_assertions::assert_eq!(snap.iter().collect::<Vec<_>>(), map);
+    }
+
+    #[test]
+    fn encode_rejects_unsanitized_maps_and_short_buffers() {
+        let mut buf = [0u8; 64];
+        pretty_assertions::assert_eq!(
+            encode_into(&[region(0x2000, 0x1000, 1), region(0, 0x1000, 1)], &mut buf),
+            Err(SnapshotError::NotSanitized { index: 1 })
+        );
+        pretty_assertions::assert_eq!(
+            encode_into(&[region(0, 0x2000, 1), region(0x1000, 0x1000, 2)], &mut buf),
+            Err(SnapshotError::NotSanitized { index: 1 })
+        );
+        pretty_assertions::assert_eq!(
+            encode_into(&[region(0, 0, 1)], &mut buf),
+            Err(SnapshotError::NotSanitized { index: 0 })
+        );
+        let three = [
+            region(0, 0x1000, 1),
+            region(0x1000, 0x1000, 2),
+            region(0x2000, 0x1000, 1),
+        ];
+        pretty_assertions::assert_eq!(
+            encode_into(&three, &mut buf),
+            Err(SnapshotError::BufferTooSmall {
+                needed: 72,
+                got: 64
+            })
+        );
+        assert!(buf.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn decode_rejects_damaged_headers() {
+        let good = encoded(&[region(0, 0x1000, 1)]);
+
+        pretty_assertions::assert_eq!(
+            decode(&good[..8]),
+            Err(SnapshotError::BufferTooSmall { needed: 12, got: 8 })
+        );
+        pretty_assertions::assert_eq!(
+            decode(&good[..20]),
+            Err(SnapshotError::BufferTooSmall {
+                needed: 32,
+                got: 20
+            })
+        );
+
+        let mut bad = good.clone();
+        bad[0..4].copy_from_slice(b"MMAP");
+        pretty_assertions::assert_eq!(
+            decode(&bad),
+            Err(SnapshotError::BadMagic { found: *b"MMAP" })
+        );
+
+        let mut bad = good.clone();
+        bad[4] = 2;
+        pretty_assertions::assert_eq!(
+            decode(&bad),
+            Err(SnapshotError::UnsupportedVersion { version: 2 })
+        );
+
+        let mut bad = good.clone();
+        bad[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            decode(&bad),
+            Err(SnapshotError::BufferTooSmall { .. })
+        ));
+    }
+
+    #[test]
+    fn decode_rejects_overlapping_records() {
+        let mut bytes = encoded(&[region(0, 0x1000, 1), region(0x1000, 0x1000, 2)]);
+        // Pull the second region's start back into the first.
+        bytes[32..40].copy_from_slice(&0x800u64.to_le_bytes());
+        pretty_assertions::assert_eq!(
+            decode(&bytes),
+            Err(SnapshotError::NotSanitized { index: 1 })
+        );
+    }
+}
@@ -3,7 +3,25 @@
 #![allow(unused_variables)]
 #![allow(unused_imports)]
 
-use std::marker::PhantomData;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::blob::{Endian, TableBlob};
+pub use crate::rejection::RejectionReason;
+pub use mbi::{Mb1Info, Mb1InfoError, Mb1Memory};
+
+pub mod coreboot;
+pub mod e820;
+#[cfg(feature = "fdt")]
+pub mod fdt;
+#[cfg(feature = "std")]
+pub mod linux;
+pub mod mb2;
+pub mod mbi;
+#[cfg(feature = "pvh")]
+pub mod pvh;
+pub mod srat;
+pub mod uefi;
 
 #[repr(C, packed)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -17,24 +35,20 @@ pub struct RawEntry {
 impl RawEntry {
     pub fn get_size_unaligned(self) -> u32 {
         let pointer = core::ptr::addr_of!(self.size);
-        let val = unsafe { pointer.read_unaligned() };
-        return val;
+        unsafe { pointer.read_unaligned() }
     }
 
     pub fn get_base_addr_unaligned(self) -> u64 {
         let pointer = core::ptr::addr_of!(self.base_addr);
-        let val = unsafe { pointer.read_unaligned() };
-        return val;
+        unsafe { pointer.read_unaligned() }
     }
     pub fn get_length_unaligned(self) -> u64 {
         let pointer = core::ptr::addr_of!(self.length);
-        let val = unsafe { pointer.read_unaligned() };
-        return val;
+        unsafe { pointer.read_unaligned() }
     }
     pub fn get_type_unaligned(self) -> u32 {
         let pointer = core::ptr::addr_of!(self.typ);
-        let val = unsafe { pointer.read_unaligned() };
-        return val;
+        unsafe { pointer.read_unaligned() }
     }
 }
 
@@ -49,12 +63,12 @@ impl RawEntry {
 /// Create a minimal MB1 entry (payload size = 20).
 pub fn raw(start: u64, len: u64, kind: u32) -> RawEntry {
     // TODO: return a RawEntry with size=20 and fields set
-    return RawEntry {
+    RawEntry {
         size: 20,
         base_addr: start,
         length: len,
         typ: kind,
-    };
+    }
 }
 
 /// Append an entry in MB1 mmap wire format (little-endian).
@@ -66,6 +80,7 @@ pub fn raw(start: u64, len: u64, kind: u32) -> RawEntry {
 /// - u32 typ
 /// - (optional extra payload bytes if size > 20)
 // @doc: memlayout
+#[cfg(feature = "alloc")]
 pub fn push_entry(buf: &mut Vec<u8>, entry: RawEntry) {
     // TODO:
     // - append entry.size (LE)
@@ -77,39 +92,22 @@ pub fn push_entry(buf: &mut Vec<u8>, entry: RawEntry) {
     // - This function should append bytes into `buf` (not print a pointer).
     // - Tests will also use size>20 and expect you to append (size-20) extra bytes.
     //   Pick a fill pattern for those extra bytes (e.g., 0xEE) and keep consistent.
-    let tipo = entry.get_type_unaligned();
-    let base_addr = entry.get_base_addr_unaligned();
-    let length = entry.get_length_unaligned();
-    let size = entry.get_size_unaligned();
-
-    let size_bytes = size.to_le_bytes();
-    let base_addr_bytes = base_addr.to_le_bytes();
-    let length_bytes = length.to_le_bytes();
-    let tipo_bytes = tipo.to_le_bytes();
-
-    for byte in size_bytes {
-        buf.push(byte);
-    }
-    for byte in base_addr_bytes {
-        buf.push(byte);
-    }
-
-    for byte in length_bytes {
-        buf.push(byte);
-    }
-    for byte in tipo_bytes {
-        buf.push(byte);
-    }
-
-    if size > 20 as u32 {
-        let num: u32 = 0;
-        let diff = size.saturating_sub(20) as usize;
-        // @doc: saturating_sub
+    push_entry_with(buf, entry, Endian::Little)
+}
 
-        if diff > 0 {
-            buf.resize(buf.len() + diff, 0xEE);
-        }
-    }
+/// [`push_entry`] in either byte order.
+#[cfg(feature = "alloc")]
+pub fn push_entry_with(buf: &mut Vec<u8>, entry: RawEntry, endian: Endian) {
+    let size = entry.get_size_unaligned();
+    buf.extend_from_slice(&endian.u32_bytes(size));
+    buf.extend_from_slice(&endian.u64_bytes(entry.get_base_addr_unaligned()));
+    buf.extend_from_slice(&endian.u64_bytes(entry.get_length_unaligned()));
+    buf.extend_from_slice(&endian.u32_bytes(entry.get_type_unaligned()));
+
+    // Extra payload bytes, if the entry claims more than 20.
+    // @doc: saturating_sub
+    let diff = size.saturating_sub(20) as usize;
+    buf.resize(buf.len() + diff, 0xEE);
 }
 
 /// Parse ONE entry from a byte slice.
@@ -125,38 +123,38 @@ pub fn read_one(buf: &[u8]) -> Result<(RawEntry, usize), MmapError> {
     // - read base_addr, length, typ from first 20 bytes of payload
     // - ignore extra payload bytes (size-20)
     // - return entry with that size field preserved (even if >20)
-    let size = buf.len();
-    if size < 4 {
-        return Err(MmapError::TruncatedHeader { have: buf.len() });
-    } else if size < 20 {
-        return Err(MmapError::SizeTooSmall {
-            size: buf.len() as u32,
-        });
-    } else {
-        let base_addr = read_u64(buf, 4);
-        let length = read_u64(buf, 8);
-        let typ = read_u32(buf, 12);
-        let entry = RawEntry {
-            size: size as u32,
-            base_addr: base_addr,
-            length: length,
-            typ: typ,
-        };
-
-        Ok((entry, size))
-    }
+    read_one_with(buf, Endian::Little)
 }
 
-fn read_u32(bytes: &[u8], offset: usize) -> u32 {
-    let mut arr = [0u8; 4];
-    arr.copy_from_slice(&bytes[offset..offset + 4]);
-    u32::from_le_bytes(arr)
-}
+/// [`read_one`] for a table in either byte order. Every field, including
+/// the size prefix, is read in `endian`.
+pub fn read_one_with(buf: &[u8], endian: Endian) -> Result<(RawEntry, usize), MmapError> {
+    let blob = TableBlob::new(buf);
+    let size = blob
+        .u32_in(0, endian)
+        .ok_or(MmapError::TruncatedHeader { have: buf.len() })?;
+    if size < 20 {
+        return Err(MmapError::SizeTooSmall { size });
+    }
+    // On a 32-bit host `4 + size` can wrap usize and sneak past the
+    // length check below, so it has to be checked.
+    let needed = usize::try_from(size)
+        .ok()
+        .and_then(|s| s.checked_add(4))
+        .ok_or(MmapError::SizeTooLarge { size })?;
+    let entry = blob.sub(0, needed).ok_or(MmapError::TruncatedEntry {
+        needed,
+        have: buf.len(),
+    })?;
+    // The window is at least 24 bytes, so these reads cannot fail.
+    let entry = RawEntry {
+        size,
+        base_addr: entry.u64_in(4, endian).unwrap_or_default(),
+        length: entry.u64_in(12, endian).unwrap_or_default(),
+        typ: entry.u32_in(20, endian).unwrap_or_default(),
+    };
 
-fn read_u64(bytes: &[u8], offset: usize) -> u64 {
-    let mut arr = [0u8; 8];
-    arr.copy_from_slice(&bytes[offset..offset + 8]);
-    u64::from_le_bytes(arr)
+    Ok((entry, needed))
 }
 
 /*
@@ -167,10 +165,10 @@ fn read_u64(bytes: &[u8], offset: usize) -> u64 {
 /// Iterator over a full MB1 mmap blob.
 /// Stops at end, or yields Err for invalid entries.
 /// Must not infinite-loop (especially size==0).
+#[derive(Clone, Debug)]
 pub struct Mb1MmapIter<'a> {
-    // TODO: store buf and current offset
-    buffer: &'a [u8],
-    offset: usize,
+    blob: TableBlob<'a>,
+    endian: Endian,
 }
 
 impl<'a> Mb1MmapIter<'a> {
@@ -201,11 +199,42 @@ impl<'a> Mb1MmapIter<'a> {
      *
      */
     pub fn new(buf: &'a [u8]) -> Self {
+        Self::with_endian(buf, Endian::Little)
+    }
+
+    /// Walk the mmap at `ptr` (e.g. `mmap_addr`/`mmap_length` from the MBI).
+    ///
+    /// # Safety
+    /// See [`TableBlob::from_raw_parts`].
+    pub unsafe fn from_raw_parts(ptr: *const u8, len: usize) -> Self {
+        Mb1MmapIter {
+            blob: TableBlob::from_raw_parts(ptr, len),
+            endian: Endian::Little,
+        }
+    }
+
+    /// Walk a table written in `endian` (e.g. big-endian MIPS/PowerPC firmware).
+    pub fn with_endian(buf: &'a [u8], endian: Endian) -> Self {
         Mb1MmapIter {
-            buffer: buf,
-            offset: 0,
+            blob: TableBlob::new(buf),
+            endian,
         }
     }
+
+    /// Never read more than `n` further bytes, whatever the entries claim.
+    ///
+    /// For when the buffer length (e.g. `mmap_length`) is trusted less than
+    /// a known upper bound. An entry that would run past the limit is
+    /// reported as `TruncatedEntry` instead of being read.
+    pub fn take_bytes(mut self, n: usize) -> Self {
+        self.blob = self.blob.limit(n);
+        self
+    }
+
+    /// Bytes consumed so far.
+    pub fn offset(&self) -> usize {
+        self.blob.offset()
+    }
 }
 
 impl<'a> Iterator for Mb1MmapIter<'a> {
@@ -222,12 +251,40 @@ impl<'a> Iterator for Mb1MmapIter<'a> {
         //     advance offset in a way that guarantees progress OR end iteration
         //     (common policy: return Some(Err(e)) and then set offset = buf.len())
         //     so you don't yield the same error forever.
+        if self.blob.is_exhausted() {
+            return None;
+        }
+        match read_one_with(self.blob.remaining(), self.endian) {
+            Ok((entry, consumed)) => {
+                // read_one never accepts less than a header plus 20 bytes,
+                // so every Ok strictly advances.
+                debug_assert!(consumed >= 24, "mb1 iterator stalled at {}", self.offset());
+                // consumed fit in remaining(), so this cannot fail.
+                let _ = self.blob.advance(consumed);
+                Some(Ok(entry))
+            }
+            Err(e) => {
+                self.blob.finish();
+                Some(Err(e))
+            }
+        }
     }
 }
 
-/// Optional: your “sanitize” stage for later phases.
-/// For now, leaving it TODO; tests for sanitize can be ignored until you implement.
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Validate the framing of a whole MB1 mmap blob and count its entries,
+/// without keeping any of them. Lets no-alloc callers size a fixed buffer
+/// before the real parse; the first framing error is returned as-is.
+pub fn count_entries(buf: &[u8]) -> Result<usize, MmapError> {
+    Mb1MmapIter::new(buf).try_fold(0, |n, entry| entry.map(|_| n + 1))
+}
+
+/// Sanitized view of an entry: what the kernel is willing to believe.
+///
+/// Ordering is total and lexicographic: by `start`, then `len`, then
+/// `kind`. It is derived, so it follows the field order below; do not
+/// reorder the fields. All sorting in this crate uses it, so prefer
+/// `regions.sort()` over hand-written comparators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct MemRegion {
     pub start: u64,
     pub len: u64,
@@ -235,18 +292,149 @@ pub struct MemRegion {
 }
 
 impl MemRegion {
+    /// One past the last byte. Saturates instead of wrapping; sanitize
+    /// decides whether an overflowing region is kept at all.
     pub fn end(self) -> u64 {
-        // TODO: return start + len (choose overflow policy in sanitize)
-        todo!()
+        self.start.saturating_add(self.len)
+    }
+
+    /// `kind` classified, for matching.
+    pub fn region_kind(self) -> crate::kind::RegionKind {
+        self.kind.into()
+    }
+
+    /// 4 KiB frames fully inside this region (kind is not checked).
+    pub fn frames(self) -> crate::frames::RegionFrames {
+        self.frames_with_size(4096)
+    }
+
+    /// Frames of `size` bytes (power of two, e.g. 2 MiB) fully inside this region.
+    pub fn frames_with_size(self, size: u64) -> crate::frames::RegionFrames {
+        crate::frames::RegionFrames::new(self, size)
+    }
+
+    /// The 4 KiB frames fully inside this region, as one range.
+    pub fn frame_range(self) -> crate::frames::PhysFrameRange {
+        crate::frames::PhysFrameRange::inside(self)
+    }
+
+    /// Start address with the SEV C-bit set (addresses here are always canonical).
+    pub fn encrypted_address(self, cbit: crate::encryption::CBit) -> u64 {
+        cbit.encrypt(self.start)
     }
 }
 
-pub fn sanitize(_e: RawEntry) -> Option<MemRegion> {
-    // TODO (phase 2):
-    // - drop len==0
-    // - handle overflow start+len (reject or clamp)
-    // - decide what to do with kinds (maybe only typ==1 is usable)
-    todo!()
+/// Turn a firmware claim into a region, or drop it.
+///
+/// Policy:
+/// - len == 0 is dropped
+/// - start + len overflowing u64 is rejected (not clamped)
+/// - the kind is kept as-is (see `MemRegion::region_kind` to classify
+///   it); deciding what is usable happens later
+pub fn sanitize(e: RawEntry) -> Option<MemRegion> {
+    check(e, &SanitizePolicy::default()).ok()
+}
+
+/// `sanitize` with the choices spelled out. The default policy is
+/// exactly what `sanitize` does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SanitizePolicy {
+    /// Cut overflowing regions off at the top of the address space
+    /// (losing the last byte) instead of rejecting them.
+    pub clamp_overflow: bool,
+    /// Keep zero-length entries instead of dropping them.
+    pub keep_zero_length: bool,
+    /// Bit `k` set: kind `k` is rewritten to `kind::USABLE`. Only kinds
+    /// below 64 can be listed, which covers everything firmware means as
+    /// RAM; soft reserved and other high values always keep their kind.
+    pub usable_kinds: u64,
+}
+
+impl Default for SanitizePolicy {
+    fn default() -> Self {
+        SanitizePolicy {
+            clamp_overflow: false,
+            keep_zero_length: false,
+            usable_kinds: 1 << crate::kind::USABLE,
+        }
+    }
+}
+
+impl SanitizePolicy {
+    pub fn clamp_overflow(mut self) -> Self {
+        self.clamp_overflow = true;
+        self
+    }
+
+    pub fn keep_zero_length(mut self) -> Self {
+        self.keep_zero_length = true;
+        self
+    }
+
+    /// Also count `kind` as usable, e.g. ACPI reclaimable once the tables
+    /// have been copied out.
+    pub fn usable_kind(mut self, kind: u32) -> Self {
+        assert!(kind < 64, "kind {kind} cannot be made usable");
+        self.usable_kinds |= 1 << kind;
+        self
+    }
+
+    fn is_usable(&self, kind: u32) -> bool {
+        kind < 64 && self.usable_kinds & (1 << kind) != 0
+    }
+}
+
+/// Turn a firmware claim into a region under `policy`, or drop it.
+pub fn sanitize_with(e: RawEntry, policy: &SanitizePolicy) -> Option<MemRegion> {
+    check(e, policy).ok()
+}
+
+fn check(e: RawEntry, policy: &SanitizePolicy) -> Result<MemRegion, RejectionReason> {
+    let start = e.get_base_addr_unaligned();
+    let mut len = e.get_length_unaligned();
+    if len == 0 && !policy.keep_zero_length {
+        return Err(RejectionReason::ZeroLength);
+    }
+    if start.checked_add(len).is_none() {
+        if !policy.clamp_overflow {
+            return Err(RejectionReason::Overflow);
+        }
+        len = u64::MAX - start;
+    }
+    let kind = e.get_type_unaligned();
+    Ok(MemRegion {
+        start,
+        len,
+        kind: if policy.is_usable(kind) {
+            crate::kind::USABLE
+        } else {
+            kind
+        },
+    })
+}
+
+/// An entry sanitize dropped, kept around so it can be logged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rejection {
+    pub entry: RawEntry,
+    pub reason: RejectionReason,
+}
+
+/// Sanitize every entry, keeping both what survived and what did not (and why).
+#[cfg(feature = "alloc")]
+pub fn sanitize_all<I>(entries: I) -> (Vec<MemRegion>, Vec<Rejection>)
+where
+    I: IntoIterator<Item = RawEntry>,
+{
+    let mut kept = Vec::new();
+    let mut rejected = Vec::new();
+    for entry in entries {
+        match check(entry, &SanitizePolicy::default()) {
+            Ok(region) => kept.push(region),
+            Err(reason) => rejected.push(Rejection { entry, reason }),
+        }
+    }
+    (kept, rejected)
 }
 
 // -------------------------
@@ -258,6 +446,8 @@ pub enum MmapError {
     TruncatedHeader { have: usize },
     SizeTooSmall { size: u32 },
     TruncatedEntry { needed: usize, have: usize },
+    /// `4 + size` does not fit in usize (32-bit hosts only).
+    SizeTooLarge { size: u32 },
 }
 
 // -------------------------
@@ -361,6 +551,50 @@ mod tests {
         pretty_assertions::assert_eq!(&buf[24..32], &[0xEE; 8]);
     }
 
+    #[test]
+    fn from_raw_parts_reads_in_place() {
+        let mut buf = Vec::new();
+        push_entry(&mut buf, raw(0x10_0000, 0x1000, 1));
+        push_entry(&mut buf, raw(0x20_0000, 0x1000, 2));
+        // The second entry is outside `len` and must not be read.
+        let it = unsafe { Mb1MmapIter::from_raw_parts(buf.as_ptr(), 24) };
+        pretty_assertions::assert_eq!(it.count(), 1);
+    }
+
+    #[test]
+    fn big_endian_roundtrip_and_byte_orders_differ() {
+        let e = RawEntry {
+            size: 24,
+            base_addr: 0x1_0000_0000,
+            length: 0x2000,
+            typ: 1,
+        };
+        let mut le = Vec::new();
+        let mut be = Vec::new();
+        push_entry_with(&mut le, e, Endian::Little);
+        push_entry_with(&mut be, e, Endian::Big);
+
+        pretty_assertions::assert_eq!(be[0..4], [0, 0, 0, 24]);
+        pretty_assertions::assert_eq!(be[4..12], 0x1_0000_0000u64.to_be_bytes());
+        pretty_assertions::assert_eq!(&be[24..28], &[0xEE; 4]);
+
+        for (buf, endian) in [(&le, Endian::Little), (&be, Endian::Big)] {
+            let got: Vec<RawEntry> = Mb1MmapIter::with_endian(buf, endian)
+                .map(Result::unwrap)
+                .collect();
+            pretty_assertions::assert_eq!(got, vec![e]);
+        }
+
+        // Read in the wrong order, a size of 24 becomes 0x1800_0000.
+        pretty_assertions::assert_eq!(
+            read_one_with(&be, Endian::Little),
+            Err(MmapError::TruncatedEntry {
+                needed: 0x1800_0004,
+                have: 28
+            })
+        );
+    }
+
     // -------------------------
     // read_one behavior
     // -------------------------
@@ -422,6 +656,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn read_one_huge_size_never_wraps() {
+        let mut buf = u32::MAX.to_le_bytes().to_vec();
+        buf.extend_from_slice(&[0; 20]);
+
+        let err = read_one(&buf).unwrap_err();
+        // 64-bit: 4 + u32::MAX fits and is just too long. 32-bit: it would
+        // wrap to 3, so it must be refused before the length check.
+        #[cfg(target_pointer_width = "64")]
+        pretty_assertions::assert_eq!(
+            err,
+            MmapError::TruncatedEntry {
+                needed: 4 + u32::MAX as usize,
+                have: 24
+            }
+        );
+        #[cfg(not(target_pointer_width = "64"))]
+        pretty_assertions::assert_eq!(err, MmapError::SizeTooLarge { size: u32::MAX });
+    }
+
     #[test]
     fn read_one_parses_minimal_ok() {
         let mut buf = Vec::new();
@@ -503,12 +757,61 @@ mod tests {
         assert!(it.next().is_none(), "must not repeat same error forever");
     }
 
+    #[test]
+    fn take_bytes_stops_at_limit() {
+        let mut buf = Vec::new();
+        push_mb1_entry(&mut buf, 20, 0x1000, 0x1000, 1);
+        push_mb1_entry(&mut buf, 20, 0x3000, 0x1000, 1);
+
+        let mut it = Mb1MmapIter::new(&buf).take_bytes(24);
+        assert!(it.next().unwrap().is_ok());
+        assert!(it.next().is_none());
+        pretty_assertions::assert_eq!(it.offset(), 24);
+    }
+
+    #[test]
+    fn take_bytes_reports_entry_running_past_limit() {
+        let mut buf = Vec::new();
+        push_mb1_entry(&mut buf, 20, 0x1000, 0x1000, 1);
+        push_mb1_entry(&mut buf, 60, 0x3000, 0x1000, 1);
+
+        let mut it = Mb1MmapIter::new(&buf);
+        it.next();
+        let mut it = it.take_bytes(40);
+        pretty_assertions::assert_eq!(
+            it.next(),
+            Some(Err(MmapError::TruncatedEntry {
+                needed: 64,
+                have: 40
+            }))
+        );
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn count_entries_counts_valid_framing() {
+        let mut buf = Vec::new();
+        pretty_assertions::assert_eq!(count_entries(&buf), Ok(0));
+        push_mb1_entry(&mut buf, 20, 0x1000, 0x1000, 1);
+        push_mb1_entry(&mut buf, 28, 0x3000, 0x2000, 2);
+        pretty_assertions::assert_eq!(count_entries(&buf), Ok(2));
+    }
+
+    #[test]
+    fn count_entries_reports_first_framing_error() {
+        let mut buf = Vec::new();
+        push_mb1_entry(&mut buf, 20, 0x1000, 0x1000, 1);
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        pretty_assertions::assert_eq!(
+            count_entries(&buf),
+            Err(MmapError::SizeTooSmall { size: 0 })
+        );
+    }
+
     // -------------------------
     // sanitize tests (phase 2)
     // -------------------------
-    // Uncomment when you implement sanitize.
 
-    /*
     #[test]
     fn sanitize_drops_zero_length() {
         let e = raw(0x2000, 0, 1);
@@ -520,12 +823,102 @@ mod tests {
         let e = raw(u64::MAX - 0xF, 0x200, 1);
         let region = sanitize(e);
 
-        // Choose one policy:
-        // assert!(region.is_none()); // reject overflow
-        if let Some(r) = region {
-            assert!(r.end() >= r.start, "end must not wrap");
-            pretty_assertions::assert_eq!(r.end(), u64::MAX, "if clamping, end saturates");
+        // Policy: reject overflow.
+        assert!(region.is_none());
+    }
+
+    #[test]
+    fn sanitize_keeps_kind_and_bounds() {
+        let region = sanitize(raw(0x1000, 0x2000, 3)).unwrap();
+        pretty_assertions::assert_eq!(
+            region,
+            MemRegion {
+                start: 0x1000,
+                len: 0x2000,
+                kind: 3
+            }
+        );
+    }
+
+    #[test]
+    fn sanitize_with_default_policy_matches_sanitize() {
+        let policy = SanitizePolicy::default();
+        for e in [
+            raw(0x2000, 0, 1),
+            raw(u64::MAX - 0xF, 0x200, 1),
+            raw(0x1000, 0x2000, 3),
+        ] {
+            pretty_assertions::assert_eq!(sanitize_with(e, &policy), sanitize(e));
         }
     }
-    */
+
+    #[test]
+    fn sanitize_with_clamps_keeps_and_reclassifies() {
+        let policy = SanitizePolicy::default()
+            .clamp_overflow()
+            .keep_zero_length()
+            .usable_kind(crate::kind::ACPI_RECLAIMABLE);
+        let r = |start, len, kind| Some(MemRegion { start, len, kind });
+
+        pretty_assertions::assert_eq!(
+            sanitize_with(raw(u64::MAX - 0xF, 0x200, 1), &policy),
+            r(u64::MAX - 0xF, 0xF, 1)
+        );
+        pretty_assertions::assert_eq!(sanitize_with(raw(0x2000, 0, 1), &policy), r(0x2000, 0, 1));
+        pretty_assertions::assert_eq!(
+            sanitize_with(raw(0x1000, 0x1000, 3), &policy),
+            r(0x1000, 0x1000, 1)
+        );
+        pretty_assertions::assert_eq!(
+            sanitize_with(raw(0x1000, 0x1000, 4), &policy),
+            r(0x1000, 0x1000, 4)
+        );
+    }
+
+    #[test]
+    fn memregion_orders_by_start_then_len_then_kind() {
+        let r = |start, len, kind| MemRegion { start, len, kind };
+        let mut regions = vec![
+            r(0x2000, 1, 1),
+            r(0x1000, 2, 1),
+            r(0x1000, 1, 2),
+            r(0x1000, 1, 1),
+        ];
+        regions.sort();
+        pretty_assertions::assert_eq!(
+            regions,
+            vec![
+                r(0x1000, 1, 1),
+                r(0x1000, 1, 2),
+                r(0x1000, 2, 1),
+                r(0x2000, 1, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitize_all_reports_rejections_with_reasons() {
+        let entries = [
+            raw(0x1000, 0x1000, 1),
+            raw(0x2000, 0, 1),
+            raw(u64::MAX - 0xF, 0x200, 2),
+            raw(0x3000, 0x1000, 2),
+        ];
+        let (kept, rejected) = sanitize_all(entries);
+
+        pretty_assertions::assert_eq!(kept.len(), 2);
+        pretty_assertions::assert_eq!(
+            rejected,
+            vec![
+                Rejection {
+                    entry: entries[1],
+                    reason: RejectionReason::ZeroLength
+                },
+                Rejection {
+                    entry: entries[2],
+                    reason: RejectionReason::Overflow
+                },
+            ]
+        );
+    }
 }
@@ -0,0 +1,381 @@
+// coreboot.rs
+//
+// coreboot tables: what a coreboot payload gets instead of E820 or UEFI.
+// A 24-byte header followed by `table_bytes` of records:
+//
+//   header:  "LBIO"  u32 header_bytes  u32 header_checksum
+//                    u32 table_bytes   u32 table_checksum  u32 table_entries
+//   record:  u32 tag  u32 size (including these 8 bytes)  payload...
+//
+// The LB_MEM record (tag 1) is the memory map. Its payload is an array of
+//
+//   u64 start   u64 size   u32 type
+//
+// 20 bytes each, packed, and only 4-byte aligned (coreboot stores the
+// u64s as two u32 halves for exactly that reason).
+//
+// Both checksums are the 16-bit ones' complement "IP checksum". Forward
+// records (tag 0x11, pointing at a second table elsewhere) are not
+// followed: mapping that address is the caller's business.
+
+use crate::blob::TableBlob;
+use crate::kind;
+use crate::raw::{MemRegion, RawEntry};
+
+pub const LB_SIGNATURE: [u8; 4] = *b"LBIO";
+pub const LB_HEADER_LEN: usize = 24;
+pub const LB_TAG_MEMORY: u32 = 0x01;
+pub const LB_TAG_FORWARD: u32 = 0x11;
+pub const LB_RECORD_HEADER_LEN: usize = 8;
+pub const LB_MEMORY_RANGE_LEN: usize = 20;
+
+pub const LB_MEM_RAM: u32 = 1;
+pub const LB_MEM_RESERVED: u32 = 2;
+pub const LB_MEM_ACPI: u32 = 3;
+pub const LB_MEM_NVS: u32 = 4;
+pub const LB_MEM_UNUSABLE: u32 = 5;
+pub const LB_MEM_VENDOR_RSVD: u32 = 6;
+/// The coreboot tables themselves (and CBMEM). Must not be allocated over.
+pub const LB_MEM_TABLE: u32 = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CorebootError {
+    TruncatedHeader {
+        have: usize,
+    },
+    BadSignature {
+        signature: [u8; 4],
+    },
+    BadHeaderChecksum,
+    /// header_bytes + table_bytes runs past the buffer.
+    TruncatedTable {
+        needed: usize,
+        have: usize,
+    },
+    BadTableChecksum {
+        expected: u16,
+        found: u16,
+    },
+    /// Record size smaller than its own header, or past the table end.
+    BadRecord {
+        offset: usize,
+        size: u32,
+    },
+    NoMemoryRecord,
+    /// LB_MEM payload is not a whole number of 20-byte ranges.
+    TruncatedRange {
+        offset: usize,
+    },
+}
+
+/// 16-bit ones' complement sum, as coreboot's compute_ip_checksum().
+pub fn ip_checksum(bytes: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    for (i, &b) in bytes.iter().enumerate() {
+        let value = if i & 1 == 1 {
+            (b as u32) << 8
+        } else {
+            b as u32
+        };
+        sum += value;
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// One lb_memory_range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CorebootMemRange {
+    pub start: u64,
+    pub size: u64,
+    pub typ: u32,
+}
+
+impl CorebootMemRange {
+    /// The MB1/E820-style kind. Types 1..=5 already match; everything
+    /// else (vendor reserved, the coreboot tables) is reserved.
+    pub fn kind(&self) -> u32 {
+        match self.typ {
+            LB_MEM_RAM => kind::USABLE,
+            LB_MEM_ACPI => kind::ACPI_RECLAIMABLE,
+            LB_MEM_NVS => kind::ACPI_NVS,
+            LB_MEM_UNUSABLE => kind::BAD_RAM,
+            _ => kind::RESERVED,
+        }
+    }
+}
+
+impl From<CorebootMemRange> for RawEntry {
+    fn from(r: CorebootMemRange) -> Self {
+        crate::raw::raw(r.start, r.size, r.kind())
+    }
+}
+
+/// A validated coreboot table.
+#[derive(Clone, Copy, Debug)]
+pub struct CorebootTable<'a> {
+    records: TableBlob<'a>,
+    entries: u32,
+}
+
+impl<'a> CorebootTable<'a> {
+    /// Validate the header and both checksums. `bytes` starts at "LBIO".
+    pub fn new(bytes: &'a [u8]) -> Result<Self, CorebootError> {
+        let blob = TableBlob::new(bytes);
+        let Some(header) = blob.window(0, LB_HEADER_LEN) else {
+            return Err(CorebootError::TruncatedHeader { have: bytes.len() });
+        };
+        if header[..4] != LB_SIGNATURE {
+            return Err(CorebootError::BadSignature {
+                signature: [header[0], header[1], header[2], header[3]],
+            });
+        }
+        let field = |at| blob.u32_at(at).unwrap_or_default() as usize;
+        let (header_bytes, table_bytes) = (field(4), field(12));
+        let table_checksum = field(16) as u16;
+        let entries = field(20) as u32;
+
+        if header_bytes < LB_HEADER_LEN {
+            return Err(CorebootError::TruncatedHeader { have: header_bytes });
+        }
+        let truncated = CorebootError::TruncatedTable {
+            needed: header_bytes.saturating_add(table_bytes),
+            have: bytes.len(),
+        };
+        let full_header = blob.window(0, header_bytes).ok_or(truncated.clone())?;
+        // The stored checksum is part of the summed bytes, so a good
+        // header sums to zero.
+        if ip_checksum(full_header) != 0 {
+            return Err(CorebootError::BadHeaderChecksum);
+        }
+        let table = blob.sub(header_bytes, table_bytes).ok_or(truncated)?;
+        let found = ip_checksum(table.remaining());
+        if found != table_checksum {
+            return Err(CorebootError::BadTableChecksum {
+                expected: table_checksum,
+                found,
+            });
+        }
+        Ok(CorebootTable {
+            records: table,
+            entries,
+        })
+    }
+
+    /// Every record as (tag, payload), at most `table_entries` of them.
+    /// Yields Err at most once, then stops.
+    pub fn records(&self) -> Records<'a> {
+        Records {
+            blob: self.records,
+            left: self.entries,
+        }
+    }
+
+    /// The memory ranges of the (first) LB_MEM record.
+    pub fn memory(&self) -> Result<CorebootMemIter<'a>, CorebootError> {
+        for record in self.records() {
+            let (tag, payload) = record?;
+            if tag == LB_TAG_MEMORY {
+                return Ok(CorebootMemIter {
+                    ranges: TableBlob::new(payload),
+                });
+            }
+        }
+        Err(CorebootError::NoMemoryRecord)
+    }
+}
+
+/// Iterator returned by [`CorebootTable::records`].
+#[derive(Clone, Debug)]
+pub struct Records<'a> {
+    blob: TableBlob<'a>,
+    left: u32,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<(u32, &'a [u8]), CorebootError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.left == 0 || self.blob.is_exhausted() {
+            return None;
+        }
+        let offset = self.blob.offset();
+        let tag = self.blob.u32_at(0);
+        let size = self.blob.u32_at(4).unwrap_or_default();
+        let record = (size as usize >= LB_RECORD_HEADER_LEN)
+            .then(|| self.blob.window(0, size as usize))
+            .flatten();
+        let (Some(tag), Some(record)) = (tag, record) else {
+            self.blob.finish();
+            return Some(Err(CorebootError::BadRecord { offset, size }));
+        };
+        // The window above proved `size` bytes remain.
+        let _ = self.blob.advance(size as usize);
+        self.left -= 1;
+        Some(Ok((tag, &record[LB_RECORD_HEADER_LEN..])))
+    }
+}
+
+/// Iterator over the ranges of one LB_MEM record. Yields Err at most once.
+#[derive(Clone, Debug)]
+pub struct CorebootMemIter<'a> {
+    ranges: TableBlob<'a>,
+}
+
+impl<'a> Iterator for CorebootMemIter<'a> {
+    type Item = Result<CorebootMemRange, CorebootError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ranges.is_exhausted() {
+            return None;
+        }
+        let b = self.ranges;
+        let range = b.window(0, LB_MEMORY_RANGE_LEN).map(|_| CorebootMemRange {
+            start: b.u64_at(0).unwrap_or_default(),
+            size: b.u64_at(8).unwrap_or_default(),
+            typ: b.u32_at(16).unwrap_or_default(),
+        });
+        match range {
+            Some(r) => {
+                let _ = self.ranges.advance(LB_MEMORY_RANGE_LEN);
+                Some(Ok(r))
+            }
+            None => {
+                self.ranges.finish();
+                Some(Err(CorebootError::TruncatedRange { offset: b.offset() }))
+            }
+        }
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::sanitize;
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn record(tag: u32, payload: &[u8]) -> Vec<u8> {
+        let mut r = Vec::new();
+        r.extend_from_slice(&tag.to_le_bytes());
+        r.extend_from_slice(&((LB_RECORD_HEADER_LEN + payload.len()) as u32).to_le_bytes());
+        r.extend_from_slice(payload);
+        r
+    }
+
+    fn mem_payload(ranges: &[(u64, u64, u32)]) -> Vec<u8> {
+        let mut p = Vec::new();
+        for &(start, size, typ) in ranges {
+            p.extend_from_slice(&start.to_le_bytes());
+            p.extend_from_slice(&size.to_le_bytes());
+            p.extend_from_slice(&typ.to_le_bytes());
+        }
+        p
+    }
+
+    /// Header + records with correct checksums.
+    fn table(records: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = records.concat();
+        let mut t = Vec::new();
+        t.extend_from_slice(&LB_SIGNATURE);
+        for v in [
+            LB_HEADER_LEN as u32,
+            0,
+            body.len() as u32,
+            ip_checksum(&body) as u32,
+            records.len() as u32,
+        ] {
+            t.extend_from_slice(&v.to_le_bytes());
+        }
+        let header_sum = ip_checksum(&t) as u32;
+        t[8..12].copy_from_slice(&header_sum.to_le_bytes());
+        t.extend_from_slice(&body);
+        t
+    }
+
+    #[test]
+    fn lb_mem_ranges_become_regions() {
+        init();
+        let t = table(&[
+            record(0x10, b"1.0\0"), // LB_TAG_VERSION, skipped
+            record(
+                LB_TAG_MEMORY,
+                &mem_payload(&[
+                    (0, 0x1000, LB_MEM_TABLE),
+                    (0x1000, 0x9_F000, LB_MEM_RAM),
+                    (0x7FF0_0000, 0x10_0000, LB_MEM_TABLE),
+                ]),
+            ),
+        ]);
+        let cb = CorebootTable::new(&t).unwrap();
+        pretty_assertions::assert_eq!(cb.records().count(), 2);
+
+        let regions: Vec<MemRegion> = cb
+            .memory()
+            .unwrap()
+            .map(|r| sanitize(r.unwrap().into()).unwrap())
+            .collect();
+        pretty_assertions::assert_eq!(
+            regions,
+            vec![
+                region(0, 0x1000, kind::RESERVED),
+                region(0x1000, 0x9_F000, kind::USABLE),
+                region(0x7FF0_0000, 0x10_0000, kind::RESERVED),
+            ]
+        );
+    }
+
+    #[test]
+    fn checksums_are_enforced() {
+        let mut t = table(&[record(LB_TAG_MEMORY, &mem_payload(&[(0, 0x1000, 1)]))]);
+        t[LB_HEADER_LEN + 9] ^= 0x40;
+        assert!(matches!(
+            CorebootTable::new(&t),
+            Err(CorebootError::BadTableChecksum { .. })
+        ));
+        t[20] ^= 1; // table_entries
+        pretty_assertions::assert_eq!(
+            CorebootTable::new(&t).err(),
+            Some(CorebootError::BadHeaderChecksum)
+        );
+    }
+
+    #[test]
+    fn malformed_tables() {
+        pretty_assertions::assert_eq!(
+            CorebootTable::new(b"LBIO").err(),
+            Some(CorebootError::TruncatedHeader { have: 4 })
+        );
+        let t = table(&[record(0x10, b"")]);
+        pretty_assertions::assert_eq!(
+            CorebootTable::new(&t).unwrap().memory().err(),
+            Some(CorebootError::NoMemoryRecord)
+        );
+
+        // A record claiming 4 bytes cannot even hold its own header.
+        let mut bad = record(0x10, b"");
+        bad[4..8].copy_from_slice(&4u32.to_le_bytes());
+        let t = table(&[bad]);
+        let mut records = CorebootTable::new(&t).unwrap().records();
+        pretty_assertions::assert_eq!(
+            records.next(),
+            Some(Err(CorebootError::BadRecord { offset: 0, size: 4 }))
+        );
+        assert!(records.next().is_none());
+
+        let mut payload = mem_payload(&[(0, 0x1000, 1)]);
+        payload.extend_from_slice(&[0; 4]);
+        let t = table(&[record(LB_TAG_MEMORY, &payload)]);
+        let items: Vec<_> = CorebootTable::new(&t).unwrap().memory().unwrap().collect();
+        pretty_assertions::assert_eq!(items.len(), 2);
+        pretty_assertions::assert_eq!(items[1], Err(CorebootError::TruncatedRange { offset: 20 }));
+    }
+}
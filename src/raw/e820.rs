@@ -0,0 +1,305 @@
+// e820.rs
+//
+// BIOS E820 memory map (INT 15h, EAX=E820h), the source of truth on
+// legacy PCs. A real-mode stub calls it in a loop and stores what comes
+// back in a flat array; Linux's boot_params.e820_table is the same thing.
+//
+// Each entry is:
+//
+//   u64 base
+//   u64 length
+//   u32 type          (same numbering as MB1)
+//   u32 ext_attrs     only if the BIOS returned 24 bytes (ACPI 3.0)
+//
+// There is no size prefix per entry: the caller knows the entry size (the
+// BIOS reports it in ECX). With ACPI 3.0 attributes, an entry whose
+// "enabled" bit is clear must be ignored entirely.
+//
+// QEMU exposes the same 20-byte layout as the fw_cfg file "etc/e820", so
+// guests booted without a bootloader (microvm, direct kernel boot) can
+// read the map from there instead.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::blob::TableBlob;
+use crate::raw::{MemRegion, RawEntry};
+
+/// fw_cfg file name QEMU stores its E820 table under.
+pub const FW_CFG_FILE: &str = "etc/e820";
+
+/// Entry without extended attributes.
+pub const ENTRY_SIZE: u32 = 20;
+/// Entry with the ACPI 3.0 extended attributes dword.
+pub const ENTRY_SIZE_EXT: u32 = 24;
+
+/// ACPI 3.0: entry is valid. Clear means "ignore this entry".
+pub const EXT_ATTR_ENABLED: u32 = 1 << 0;
+/// ACPI 3.0: memory is non-volatile.
+pub const EXT_ATTR_NON_VOLATILE: u32 = 1 << 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct E820Entry {
+    pub base: u64,
+    pub length: u64,
+    pub typ: u32,
+    /// Present only for 24-byte entries.
+    pub ext_attrs: Option<u32>,
+}
+
+impl E820Entry {
+    /// False only when ACPI 3.0 attributes are present and say "ignore".
+    pub fn is_enabled(&self) -> bool {
+        self.ext_attrs.is_none_or(|a| a & EXT_ATTR_ENABLED != 0)
+    }
+}
+
+impl From<MemRegion> for E820Entry {
+    /// For writing a map back out (kinds share the E820 numbering).
+    fn from(r: MemRegion) -> Self {
+        E820Entry {
+            base: r.start,
+            length: r.len,
+            typ: r.kind,
+            ext_attrs: None,
+        }
+    }
+}
+
+impl From<E820Entry> for RawEntry {
+    /// As a minimal MB1 entry. Check `is_enabled` first: the conversion
+    /// keeps entries the BIOS asked you to ignore.
+    fn from(e: E820Entry) -> RawEntry {
+        crate::raw::raw(e.base, e.length, e.typ)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum E820Error {
+    /// entry_size below 20 bytes.
+    EntrySizeTooSmall { entry_size: u32 },
+    /// Trailing bytes that do not make a whole entry.
+    TruncatedEntry { needed: usize, have: usize },
+}
+
+/// Parse ONE entry of `entry_size` bytes from the start of `buf`.
+/// Returns the entry and bytes consumed (always `entry_size`).
+pub fn read_one_e820(buf: &[u8], entry_size: u32) -> Result<(E820Entry, usize), E820Error> {
+    if entry_size < ENTRY_SIZE {
+        return Err(E820Error::EntrySizeTooSmall { entry_size });
+    }
+    let needed = entry_size as usize;
+    let entry = TableBlob::new(buf)
+        .sub(0, needed)
+        .ok_or(E820Error::TruncatedEntry {
+            needed,
+            have: buf.len(),
+        })?;
+    // The window is at least 20 bytes, so these reads cannot fail.
+    let entry = E820Entry {
+        base: entry.u64_at(0).unwrap_or_default(),
+        length: entry.u64_at(8).unwrap_or_default(),
+        typ: entry.u32_at(16).unwrap_or_default(),
+        ext_attrs: entry.u32_at(20),
+    };
+    Ok((entry, needed))
+}
+
+/// Append one entry: 24 bytes if it has extended attributes, else 20.
+#[cfg(feature = "alloc")]
+pub fn push_e820_entry(buf: &mut Vec<u8>, entry: E820Entry) {
+    buf.extend_from_slice(&entry.base.to_le_bytes());
+    buf.extend_from_slice(&entry.length.to_le_bytes());
+    buf.extend_from_slice(&entry.typ.to_le_bytes());
+    if let Some(attrs) = entry.ext_attrs {
+        buf.extend_from_slice(&attrs.to_le_bytes());
+    }
+}
+
+/// Iterator over a flat E820 array. Yields Err at most once, then stops.
+#[derive(Clone, Debug)]
+pub struct E820Iter<'a> {
+    entries: TableBlob<'a>,
+    entry_size: u32,
+}
+
+impl<'a> E820Iter<'a> {
+    /// Entries are `entry_size` bytes apart (20 or 24 in practice; larger
+    /// sizes are accepted and the extra bytes skipped).
+    pub fn new(buf: &'a [u8], entry_size: u32) -> Result<Self, E820Error> {
+        if entry_size < ENTRY_SIZE {
+            return Err(E820Error::EntrySizeTooSmall { entry_size });
+        }
+        Ok(E820Iter {
+            entries: TableBlob::new(buf),
+            entry_size,
+        })
+    }
+
+    /// [`new`](Self::new) over `len` bytes at `ptr` (e.g. boot_params.e820_table).
+    ///
+    /// # Safety
+    /// See [`TableBlob::from_raw_parts`].
+    pub unsafe fn from_raw_parts(
+        ptr: *const u8,
+        len: usize,
+        entry_size: u32,
+    ) -> Result<Self, E820Error> {
+        Self::new(TableBlob::from_raw_parts(ptr, len).remaining(), entry_size)
+    }
+
+    /// The contents of QEMU's fw_cfg "etc/e820" file: packed 20-byte
+    /// entries, no header, no count (the file size says how many).
+    pub fn fw_cfg(file: &'a [u8]) -> Self {
+        E820Iter {
+            entries: TableBlob::new(file),
+            entry_size: ENTRY_SIZE,
+        }
+    }
+
+    /// Only entries the BIOS did not mark "ignore", errors included.
+    pub fn enabled(self) -> impl Iterator<Item = Result<E820Entry, E820Error>> + 'a {
+        self.filter(|r| r.as_ref().map_or(true, E820Entry::is_enabled))
+    }
+}
+
+impl<'a> Iterator for E820Iter<'a> {
+    type Item = Result<E820Entry, E820Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.entries.is_exhausted() {
+            return None;
+        }
+        match read_one_e820(self.entries.remaining(), self.entry_size) {
+            Ok((entry, consumed)) => {
+                debug_assert!(consumed >= ENTRY_SIZE as usize);
+                // consumed fit in remaining(), so this cannot fail.
+                let _ = self.entries.advance(consumed);
+                Some(Ok(entry))
+            }
+            Err(e) => {
+                self.entries.finish();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::sanitize;
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn entry(base: u64, length: u64, typ: u32, ext_attrs: Option<u32>) -> E820Entry {
+        E820Entry {
+            base,
+            length,
+            typ,
+            ext_attrs,
+        }
+    }
+
+    #[test]
+    fn roundtrip_20_byte_entries_into_sanitize() {
+        init();
+        let mut buf = Vec::new();
+        push_e820_entry(&mut buf, entry(0, 0x9_FC00, 1, None));
+        push_e820_entry(&mut buf, entry(0x10_0000, 0x7FF0_0000, 1, None));
+        pretty_assertions::assert_eq!(buf.len(), 40);
+
+        let regions: Vec<MemRegion> = E820Iter::new(&buf, ENTRY_SIZE)
+            .unwrap()
+            .map(|e| sanitize(e.unwrap().into()).unwrap())
+            .collect();
+        pretty_assertions::assert_eq!(
+            regions[1],
+            MemRegion {
+                start: 0x10_0000,
+                len: 0x7FF0_0000,
+                kind: 1
+            }
+        );
+    }
+
+    #[test]
+    fn extended_attributes_disable_entries() {
+        let mut buf = Vec::new();
+        push_e820_entry(&mut buf, entry(0, 0x1000, 1, Some(EXT_ATTR_ENABLED)));
+        push_e820_entry(&mut buf, entry(0x1000, 0x1000, 1, Some(0)));
+        push_e820_entry(
+            &mut buf,
+            entry(
+                0x2000,
+                0x1000,
+                7,
+                Some(EXT_ATTR_ENABLED | EXT_ATTR_NON_VOLATILE),
+            ),
+        );
+
+        let all: Vec<E820Entry> = E820Iter::new(&buf, ENTRY_SIZE_EXT)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        pretty_assertions::assert_eq!(all[1], entry(0x1000, 0x1000, 1, Some(0)));
+        assert!(!all[1].is_enabled());
+
+        let bases: Vec<u64> = E820Iter::new(&buf, ENTRY_SIZE_EXT)
+            .unwrap()
+            .enabled()
+            .map(|e| e.unwrap().base)
+            .collect();
+        pretty_assertions::assert_eq!(bases, vec![0, 0x2000]);
+    }
+
+    #[test]
+    fn fw_cfg_file_is_packed_20_byte_entries() {
+        // What QEMU writes for a 512MiB microvm guest.
+        #[rustfmt::skip]
+        let file: [u8; 40] = [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // base 0
+            0x00, 0xFC, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // length 0x9FC00
+            0x01, 0x00, 0x00, 0x00,                         // RAM
+            0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, // base 0x100000
+            0x00, 0x00, 0xF0, 0x1F, 0x00, 0x00, 0x00, 0x00, // length 0x1FF00000
+            0x01, 0x00, 0x00, 0x00,                         // RAM
+        ];
+        let got: Vec<E820Entry> = E820Iter::fw_cfg(&file).map(Result::unwrap).collect();
+        pretty_assertions::assert_eq!(
+            got,
+            vec![
+                entry(0, 0x9_FC00, 1, None),
+                entry(0x10_0000, 0x1FF0_0000, 1, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn entry_size_errors() {
+        assert!(E820Iter::new(&[], 19).is_err());
+        pretty_assertions::assert_eq!(
+            read_one_e820(&[0; 20], 24),
+            Err(E820Error::TruncatedEntry {
+                needed: 24,
+                have: 20
+            })
+        );
+    }
+
+    #[test]
+    fn trailing_partial_entry_errors_once() {
+        let mut buf = Vec::new();
+        push_e820_entry(&mut buf, entry(0, 0x1000, 1, None));
+        buf.extend_from_slice(&[0; 7]);
+
+        let mut it = E820Iter::new(&buf, ENTRY_SIZE).unwrap();
+        assert!(it.next().unwrap().is_ok());
+        assert!(it.next().unwrap().is_err());
+        assert!(it.next().is_none());
+    }
+}
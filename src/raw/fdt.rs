@@ -0,0 +1,543 @@
+// fdt.rs
+//
+// Flattened device tree (DTB), the memory map on ARM and RISC-V boards.
+//
+// There is no single table of regions. RAM is described by nodes:
+//
+//   / {
+//       #address-cells = <2>; #size-cells = <2>;
+//       memory@80000000 { device_type = "memory"; reg = <base size ...>; };
+//       reserved-memory {
+//           #address-cells = <2>; #size-cells = <2>;
+//           optee@fe000000 { reg = <...>; no-map; };
+//       };
+//   };
+//
+// plus the memory reservation block in the header (bootloader/firmware
+// carve-outs such as the DTB itself). Everything is big-endian.
+//
+// This reads only what the memory map needs: memory nodes become usable
+// regions, reserved-memory children and the reservation block become
+// reserved ones. Run the result through canonicalize (reserved wins
+// over usable) before building frames.
+
+use alloc::vec::Vec;
+
+use crate::blob::TableBlob;
+use crate::kind;
+use crate::raw::MemRegion;
+
+pub const FDT_MAGIC: u32 = 0xD00D_FEED;
+/// Header fields this parser reads (up to and including size_dt_struct).
+pub const FDT_HEADER_LEN: usize = 40;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FdtError {
+    TruncatedHeader {
+        have: usize,
+    },
+    BadMagic {
+        magic: u32,
+    },
+    /// A header offset/size points outside the blob.
+    BadBlockBounds,
+    /// Structure block ended in the middle of a token.
+    Truncated {
+        offset: usize,
+    },
+    UnknownToken {
+        token: u32,
+        offset: usize,
+    },
+    /// More END_NODE than BEGIN_NODE tokens.
+    UnbalancedNodes {
+        offset: usize,
+    },
+    /// #address-cells / #size-cells this parser cannot represent in u64.
+    UnsupportedCells {
+        address_cells: u32,
+        size_cells: u32,
+    },
+}
+
+/// A validated device tree blob.
+#[derive(Clone, Copy, Debug)]
+pub struct Fdt<'a> {
+    structs: TableBlob<'a>,
+    strings: TableBlob<'a>,
+    rsvmap: TableBlob<'a>,
+}
+
+impl<'a> Fdt<'a> {
+    /// Check the header and locate the blocks. Bytes past `totalsize` are
+    /// ignored.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, FdtError> {
+        let blob = TableBlob::new(bytes);
+        let header = blob
+            .window(0, FDT_HEADER_LEN)
+            .map(TableBlob::new)
+            .ok_or(FdtError::TruncatedHeader { have: bytes.len() })?;
+        // The window is FDT_HEADER_LEN bytes, so these reads cannot fail.
+        let field = |i: usize| header.u32_be_at(4 * i).unwrap_or_default() as usize;
+
+        let magic = field(0) as u32;
+        if magic != FDT_MAGIC {
+            return Err(FdtError::BadMagic { magic });
+        }
+        let total = blob.sub(0, field(1)).ok_or(FdtError::BadBlockBounds)?;
+        let (off_struct, off_strings, off_rsvmap) = (field(2), field(3), field(4));
+        let (size_strings, size_struct) = (field(8), field(9));
+
+        let block = |off: usize, len: usize| total.sub(off, len).ok_or(FdtError::BadBlockBounds);
+        let rsv_len = field(1)
+            .checked_sub(off_rsvmap)
+            .ok_or(FdtError::BadBlockBounds)?;
+        Ok(Fdt {
+            structs: block(off_struct, size_struct)?,
+            strings: block(off_strings, size_strings)?,
+            rsvmap: block(off_rsvmap, rsv_len)?,
+        })
+    }
+
+    /// Memory nodes as usable regions, reserved-memory children and the
+    /// reservation block as reserved regions. Zero-size entries are dropped.
+    pub fn regions(&self) -> Result<Vec<MemRegion>, FdtError> {
+        let mut out = Vec::new();
+        self.push_rsvmap(&mut out);
+        self.walk(&mut out)?;
+        Ok(out)
+    }
+
+    // (address, size) u64 pairs, terminated by a (0, 0) entry.
+    fn push_rsvmap(&self, out: &mut Vec<MemRegion>) {
+        let mut at = 0;
+        while let (Some(start), Some(len)) =
+            (self.rsvmap.u64_be_at(at), self.rsvmap.u64_be_at(at + 8))
+        {
+            if start == 0 && len == 0 {
+                break;
+            }
+            push_region(out, start, len, kind::RESERVED);
+            at += 16;
+        }
+    }
+
+    fn walk(&self, out: &mut Vec<MemRegion>) -> Result<(), FdtError> {
+        let s = &self.structs;
+        let mut at = 0;
+        // Open nodes: 1 inside the root, 2 in a top-level node, 3 below that.
+        let mut depth = 0usize;
+        let mut root_cells = Cells::default();
+        let mut rsv_cells = Cells::default();
+        let mut in_reserved = false;
+        let mut node1 = Node::default();
+        let mut node2 = Node::default();
+
+        loop {
+            let token = s.u32_be_at(at).ok_or(FdtError::Truncated { offset: at })?;
+            let token_at = at;
+            at += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name =
+                        cstr(s.remaining().get(at..)).ok_or(FdtError::Truncated { offset: at })?;
+                    at += align4(name.len() + 1);
+                    depth += 1;
+                    match depth {
+                        2 => {
+                            node1 = Node::named(name);
+                            in_reserved = name == b"reserved-memory";
+                            if in_reserved {
+                                rsv_cells = root_cells;
+                            }
+                        }
+                        3 => node2 = Node::default(),
+                        _ => {}
+                    }
+                }
+                FDT_END_NODE => {
+                    match depth {
+                        0 => return Err(FdtError::UnbalancedNodes { offset: token_at }),
+                        2 if node1.is_memory() => {
+                            push_reg(out, node1.reg, root_cells, kind::USABLE)?
+                        }
+                        3 if in_reserved => push_reg(out, node2.reg, rsv_cells, kind::RESERVED)?,
+                        _ => {}
+                    }
+                    if depth == 2 {
+                        in_reserved = false;
+                    }
+                    depth -= 1;
+                }
+                FDT_PROP => {
+                    let (Some(len), Some(nameoff)) = (s.u32_be_at(at), s.u32_be_at(at + 4)) else {
+                        return Err(FdtError::Truncated { offset: at });
+                    };
+                    let value = s
+                        .window(at + 8, len as usize)
+                        .ok_or(FdtError::Truncated { offset: at })?;
+                    at += 8 + align4(len as usize);
+                    let name = self.prop_name(nameoff).unwrap_or_default();
+
+                    match depth {
+                        1 => root_cells.set(name, value),
+                        2 => {
+                            if in_reserved {
+                                rsv_cells.set(name, value);
+                            }
+                            match name {
+                                b"device_type" => {
+                                    node1.device_type_memory = cstr(Some(value)) == Some(b"memory")
+                                }
+                                b"reg" => node1.reg = Some(value),
+                                _ => {}
+                            }
+                        }
+                        3 if in_reserved && name == b"reg" => node2.reg = Some(value),
+                        _ => {}
+                    }
+                }
+                FDT_NOP => {}
+                FDT_END => return Ok(()),
+                token => {
+                    return Err(FdtError::UnknownToken {
+                        token,
+                        offset: token_at,
+                    })
+                }
+            }
+        }
+    }
+
+    fn prop_name(&self, nameoff: u32) -> Option<&'a [u8]> {
+        cstr(self.strings.remaining().get(nameoff as usize..))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Cells {
+    address: u32,
+    size: u32,
+}
+
+impl Default for Cells {
+    // Devicetree spec defaults when a node does not say.
+    fn default() -> Self {
+        Cells {
+            address: 2,
+            size: 1,
+        }
+    }
+}
+
+impl Cells {
+    fn set(&mut self, name: &[u8], value: &[u8]) {
+        let Some(v) = TableBlob::new(value).u32_be_at(0) else {
+            return;
+        };
+        match name {
+            b"#address-cells" => self.address = v,
+            b"#size-cells" => self.size = v,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Node<'a> {
+    memory_name: bool,
+    device_type_memory: bool,
+    reg: Option<&'a [u8]>,
+}
+
+impl<'a> Node<'a> {
+    fn named(name: &[u8]) -> Self {
+        Node {
+            memory_name: name == b"memory" || name.starts_with(b"memory@"),
+            ..Node::default()
+        }
+    }
+
+    fn is_memory(&self) -> bool {
+        self.memory_name || self.device_type_memory
+    }
+}
+
+fn push_reg(
+    out: &mut Vec<MemRegion>,
+    reg: Option<&[u8]>,
+    cells: Cells,
+    kind: u32,
+) -> Result<(), FdtError> {
+    let Some(reg) = reg else {
+        return Ok(());
+    };
+    if cells.address > 2 || cells.size > 2 || cells.address == 0 {
+        return Err(FdtError::UnsupportedCells {
+            address_cells: cells.address,
+            size_cells: cells.size,
+        });
+    }
+    let stride = 4 * (cells.address + cells.size) as usize;
+    for entry in reg.chunks_exact(stride) {
+        let blob = TableBlob::new(entry);
+        let start = read_cells(&blob, 0, cells.address);
+        let len = read_cells(&blob, 4 * cells.address as usize, cells.size);
+        push_region(out, start, len, kind);
+    }
+    Ok(())
+}
+
+// One or two big-endian cells as a u64 (caller checked count <= 2).
+fn read_cells(blob: &TableBlob<'_>, at: usize, count: u32) -> u64 {
+    match count {
+        1 => blob.u32_be_at(at).unwrap_or_default() as u64,
+        2 => blob.u64_be_at(at).unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+fn push_region(out: &mut Vec<MemRegion>, start: u64, len: u64, kind: u32) {
+    if len > 0 {
+        out.push(MemRegion { start, len, kind });
+    }
+}
+
+// Bytes up to the first NUL, if there is one.
+fn cstr(bytes: Option<&[u8]>) -> Option<&[u8]> {
+    let bytes = bytes?;
+    bytes.iter().position(|&b| b == 0).map(|n| &bytes[..n])
+}
+
+fn align4(n: usize) -> usize {
+    n.div_ceil(4) * 4
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::region::canonicalize;
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    /// Just enough of a DTB writer to build test trees.
+    #[derive(Default)]
+    struct DtbBuilder {
+        structs: Vec<u8>,
+        strings: Vec<u8>,
+        rsvmap: Vec<(u64, u64)>,
+    }
+
+    impl DtbBuilder {
+        fn begin(&mut self, name: &str) -> &mut Self {
+            self.token(FDT_BEGIN_NODE);
+            self.structs.extend_from_slice(name.as_bytes());
+            self.structs.push(0);
+            self.pad();
+            self
+        }
+
+        fn end(&mut self) -> &mut Self {
+            self.token(FDT_END_NODE);
+            self
+        }
+
+        fn prop(&mut self, name: &str, value: &[u8]) -> &mut Self {
+            let nameoff = self.strings.len() as u32;
+            self.strings.extend_from_slice(name.as_bytes());
+            self.strings.push(0);
+            self.token(FDT_PROP);
+            self.structs
+                .extend_from_slice(&(value.len() as u32).to_be_bytes());
+            self.structs.extend_from_slice(&nameoff.to_be_bytes());
+            self.structs.extend_from_slice(value);
+            self.pad();
+            self
+        }
+
+        fn cells(&mut self, name: &str, cells: &[u32]) -> &mut Self {
+            let value: Vec<u8> = cells.iter().flat_map(|c| c.to_be_bytes()).collect();
+            self.prop(name, &value)
+        }
+
+        fn token(&mut self, t: u32) {
+            self.structs.extend_from_slice(&t.to_be_bytes());
+        }
+
+        fn pad(&mut self) {
+            while !self.structs.len().is_multiple_of(4) {
+                self.structs.push(0);
+            }
+        }
+
+        fn build(&mut self) -> Vec<u8> {
+            self.token(FDT_END);
+            let off_rsvmap = FDT_HEADER_LEN;
+            let rsv_len = 16 * (self.rsvmap.len() + 1);
+            let off_struct = off_rsvmap + rsv_len;
+            let off_strings = off_struct + self.structs.len();
+            let total = off_strings + self.strings.len();
+
+            let mut out = Vec::new();
+            for v in [
+                FDT_MAGIC,
+                total as u32,
+                off_struct as u32,
+                off_strings as u32,
+                off_rsvmap as u32,
+                17,
+                16,
+                0,
+                self.strings.len() as u32,
+                self.structs.len() as u32,
+            ] {
+                out.extend_from_slice(&v.to_be_bytes());
+            }
+            for &(a, s) in self.rsvmap.iter().chain([(0, 0)].iter()) {
+                out.extend_from_slice(&a.to_be_bytes());
+                out.extend_from_slice(&s.to_be_bytes());
+            }
+            out.extend_from_slice(&self.structs);
+            out.extend_from_slice(&self.strings);
+            out
+        }
+    }
+
+    fn board() -> Vec<u8> {
+        let mut b = DtbBuilder {
+            rsvmap: vec![(0x8800_0000, 0x1_0000)],
+            ..Default::default()
+        };
+        b.begin("")
+            .cells("#address-cells", &[2])
+            .cells("#size-cells", &[2])
+            .begin("cpus")
+            .end()
+            .begin("memory@80000000")
+            .prop("device_type", b"memory\0")
+            .cells(
+                "reg",
+                &[0, 0x8000_0000, 0, 0x4000_0000, 1, 0, 0, 0x4000_0000],
+            )
+            .end()
+            .begin("reserved-memory")
+            .cells("#address-cells", &[2])
+            .cells("#size-cells", &[1])
+            .begin("optee@be000000")
+            .cells("reg", &[0, 0xBE00_0000, 0x0200_0000])
+            .prop("no-map", &[])
+            .end()
+            .begin("cma")
+            .cells("size", &[0x0400_0000])
+            .end()
+            .end()
+            .end();
+        b.build()
+    }
+
+    #[test]
+    fn memory_and_reserved_nodes() {
+        init();
+        let dtb = board();
+        let regions = Fdt::new(&dtb).unwrap().regions().unwrap();
+        pretty_assertions::assert_eq!(
+            regions,
+            vec![
+                region(0x8800_0000, 0x1_0000, kind::RESERVED),
+                region(0x8000_0000, 0x4000_0000, kind::USABLE),
+                region(0x1_0000_0000, 0x4000_0000, kind::USABLE),
+                region(0xBE00_0000, 0x0200_0000, kind::RESERVED),
+            ]
+        );
+
+        // Reserved carve-outs punch holes in RAM once canonicalized.
+        let canonical = canonicalize(&regions);
+        pretty_assertions::assert_eq!(canonical[1], region(0x8800_0000, 0x1_0000, kind::RESERVED));
+    }
+
+    #[test]
+    fn device_type_alone_marks_memory() {
+        let mut b = DtbBuilder::default();
+        b.begin("")
+            .cells("#address-cells", &[1])
+            .cells("#size-cells", &[1])
+            .begin("ram")
+            .prop("device_type", b"memory\0")
+            .cells("reg", &[0x4000_0000, 0x1000_0000])
+            .end()
+            .begin("soc")
+            .cells("reg", &[0x1000_0000, 0x1000])
+            .end()
+            .end();
+        let dtb = b.build();
+        pretty_assertions::assert_eq!(
+            Fdt::new(&dtb).unwrap().regions().unwrap(),
+            vec![region(0x4000_0000, 0x1000_0000, kind::USABLE)]
+        );
+    }
+
+    #[test]
+    fn header_errors() {
+        pretty_assertions::assert_eq!(
+            Fdt::new(&[0; 8]).err(),
+            Some(FdtError::TruncatedHeader { have: 8 })
+        );
+        let mut dtb = board();
+        dtb[0] = 0;
+        pretty_assertions::assert_eq!(
+            Fdt::new(&dtb).err(),
+            Some(FdtError::BadMagic { magic: 0x000D_FEED })
+        );
+        let mut dtb = board();
+        dtb[4..8].copy_from_slice(&u32::MAX.to_be_bytes());
+        pretty_assertions::assert_eq!(Fdt::new(&dtb).err(), Some(FdtError::BadBlockBounds));
+    }
+
+    #[test]
+    fn structure_errors() {
+        let mut b = DtbBuilder::default();
+        b.begin("").end().end();
+        let dtb = b.build();
+        assert!(matches!(
+            Fdt::new(&dtb).unwrap().regions(),
+            Err(FdtError::UnbalancedNodes { .. })
+        ));
+
+        let mut b = DtbBuilder::default();
+        b.begin("").cells("#address-cells", &[3]);
+        b.begin("memory").cells("reg", &[0, 0, 0, 1]).end().end();
+        let dtb = b.build();
+        pretty_assertions::assert_eq!(
+            Fdt::new(&dtb).unwrap().regions(),
+            Err(FdtError::UnsupportedCells {
+                address_cells: 3,
+                size_cells: 1
+            })
+        );
+
+        // Structure block ends before FDT_END.
+        let mut b = DtbBuilder::default();
+        b.begin("");
+        let mut dtb = b.build();
+        let size_struct = u32::from_be_bytes(dtb[36..40].try_into().unwrap());
+        dtb[36..40].copy_from_slice(&(size_struct - 4).to_be_bytes());
+        assert!(matches!(
+            Fdt::new(&dtb).unwrap().regions(),
+            Err(FdtError::Truncated { .. })
+        ));
+    }
+}
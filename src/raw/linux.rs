@@ -0,0 +1,120 @@
+// linux.rs
+//
+// /proc/iomem as a memory map source, so the sanitize / frames pipeline
+// can run against a real machine's layout from userland.
+//
+//   00000000-00000fff : Reserved
+//   00001000-0009ffff : System RAM
+//   000a0000-000fffff : Reserved
+//     000a0000-000bffff : PCI Bus 0000:00
+//
+// Ranges are inclusive. Only top-level lines (no indentation) describe
+// the physical map; indented lines are children (kernel code, devices).
+// Top-level names that are not memory (PCI windows, APIC, ...) are
+// skipped. Unprivileged readers see every address as zero; those lines
+// are skipped too, so the result is simply empty.
+
+use crate::kind;
+use crate::raw::MemRegion;
+
+/// Kind for a top-level /proc/iomem name, or None if it is not memory.
+fn iomem_kind(name: &str) -> Option<u32> {
+    match name {
+        "System RAM" => Some(kind::USABLE),
+        "Reserved" => Some(kind::RESERVED),
+        "ACPI Tables" => Some(kind::ACPI_RECLAIMABLE),
+        "ACPI Non-volatile Storage" => Some(kind::ACPI_NVS),
+        "Persistent Memory" => Some(kind::PERSISTENT),
+        "Persistent Memory (legacy)" => Some(kind::PERSISTENT_LEGACY),
+        "Soft Reserved" => Some(kind::SOFT_RESERVED),
+        "Unusable memory" => Some(kind::BAD_RAM),
+        _ => None,
+    }
+}
+
+fn parse_line(line: &str) -> Option<MemRegion> {
+    if line.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let (range, name) = line.split_once(" : ")?;
+    let kind = iomem_kind(name.trim())?;
+    let (start, last) = range.trim().split_once('-')?;
+    let start = u64::from_str_radix(start, 16).ok()?;
+    let last = u64::from_str_radix(last, 16).ok()?;
+    if last < start || (start == 0 && last == 0) {
+        return None;
+    }
+    // Inclusive end; a range ending at u64::MAX loses its last byte.
+    let len = (last - start).saturating_add(1);
+    Some(MemRegion { start, len, kind })
+}
+
+/// Memory regions from the text of /proc/iomem, in file order.
+/// Malformed lines are skipped; feed the result through `sanitize`.
+pub fn parse_iomem(text: &str) -> impl Iterator<Item = MemRegion> + '_ {
+    text.lines().filter_map(parse_line)
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    const QEMU_IOMEM: &str = "\
+00000000-00000fff : Reserved
+00001000-0009fbff : System RAM
+0009fc00-0009ffff : Reserved
+000a0000-000bffff : PCI Bus 0000:00
+000f0000-000fffff : Reserved
+  000f0000-000fffff : System ROM
+00100000-7ffdffff : System RAM
+  01000000-01e00cd6 : Kernel code
+7ffe0000-7fffffff : Reserved
+fee00000-fee00fff : Local APIC
+100000000-17fffffff : System RAM
+";
+
+    #[test]
+    fn parses_top_level_memory_lines() {
+        init();
+        let got: Vec<MemRegion> = parse_iomem(QEMU_IOMEM).collect();
+        pretty_assertions::assert_eq!(
+            got,
+            vec![
+                region(0, 0x1000, kind::RESERVED),
+                region(0x1000, 0x9_EC00, kind::USABLE),
+                region(0x9_FC00, 0x400, kind::RESERVED),
+                region(0xF_0000, 0x1_0000, kind::RESERVED),
+                region(0x10_0000, 0x7FEE_0000, kind::USABLE),
+                region(0x7FFE_0000, 0x2_0000, kind::RESERVED),
+                region(0x1_0000_0000, 0x8000_0000, kind::USABLE),
+            ]
+        );
+    }
+
+    #[test]
+    fn unprivileged_and_malformed_lines_are_skipped() {
+        let text = "\
+00000000-00000000 : System RAM
+00000000-00000000 : Reserved
+zzzz-0fff : System RAM
+00002000-00001000 : System RAM
+00003000 : System RAM
+ffffffffffff0000-ffffffffffffffff : Reserved
+";
+        let got: Vec<MemRegion> = parse_iomem(text).collect();
+        pretty_assertions::assert_eq!(
+            got,
+            vec![region(0xFFFF_FFFF_FFFF_0000, 0x1_0000, kind::RESERVED)]
+        );
+    }
+}
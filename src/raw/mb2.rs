@@ -0,0 +1,406 @@
+// mb2.rs
+//
+// Multiboot2 memory map tag (GRUB's `multiboot2` command).
+//
+// Same information as MB1, different framing. Instead of a size field in
+// front of every entry, the tag says once how big each entry is:
+//
+//   u32 type          = 6
+//   u32 size          whole tag, header included
+//   u32 entry_size    bytes per entry (24 today; may grow)
+//   u32 entry_version 0
+//   entries...        u64 base_addr, u64 length, u32 type, u32 reserved
+//
+// Type values are the same as MB1/E820, so entries convert straight into
+// RawEntry and go through sanitize like everything else.
+//
+// Going the other way (fabricating boot info for tests and emulators):
+// every tag starts on an 8-byte boundary, its `size` does NOT include the
+// padding after it, and the whole info block ends with an end tag
+// (type 0, size 8) after a u32 total_size, u32 reserved header.
+
+#[cfg(feature = "alloc")]
+use alloc::vec;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::blob::TableBlob;
+use crate::raw::RawEntry;
+
+/// Multiboot2 tag type for the memory map.
+pub const TAG_TYPE_MMAP: u32 = 6;
+/// type + size + entry_size + entry_version.
+pub const TAG_HEADER_LEN: usize = 16;
+/// Size of one entry as currently defined (base, length, type, reserved).
+pub const ENTRY_SIZE: u32 = 24;
+/// Tag type that terminates the boot information.
+pub const TAG_TYPE_END: u32 = 0;
+/// Tags start on this boundary.
+pub const TAG_ALIGN: usize = 8;
+
+/// One Multiboot2 mmap entry, as found on the wire.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mb2Entry {
+    pub base_addr: u64,
+    pub length: u64,
+    pub typ: u32,
+    /// Must be zero per spec; kept so nothing is silently dropped.
+    pub reserved: u32,
+}
+
+impl From<Mb2Entry> for RawEntry {
+    /// As a minimal MB1 entry, so the MB1 pipeline applies unchanged.
+    fn from(e: Mb2Entry) -> RawEntry {
+        crate::raw::raw(e.base_addr, e.length, e.typ)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mb2Error {
+    /// Fewer than 16 bytes for the tag header.
+    TruncatedTag { have: usize },
+    /// Not a memory map tag.
+    WrongTagType { typ: u32 },
+    /// The tag claims to be bigger than the buffer, or smaller than its header.
+    BadTagSize { size: u32, have: usize },
+    /// entry_size too small to hold an entry.
+    EntrySizeTooSmall { entry_size: u32 },
+    /// Trailing bytes that do not make a whole entry.
+    TruncatedEntry { needed: usize, have: usize },
+}
+
+/// Parse ONE entry from the start of `buf`, where entries are
+/// `entry_size` bytes apart. Returns the entry and bytes consumed
+/// (always `entry_size`). Extra bytes past the known 24 are ignored.
+pub fn read_one_mb2(buf: &[u8], entry_size: u32) -> Result<(Mb2Entry, usize), Mb2Error> {
+    if entry_size < ENTRY_SIZE {
+        return Err(Mb2Error::EntrySizeTooSmall { entry_size });
+    }
+    let needed = entry_size as usize;
+    let entry = TableBlob::new(buf)
+        .sub(0, needed)
+        .ok_or(Mb2Error::TruncatedEntry {
+            needed,
+            have: buf.len(),
+        })?;
+    // The window is at least 24 bytes, so these reads cannot fail.
+    let entry = Mb2Entry {
+        base_addr: entry.u64_at(0).unwrap_or_default(),
+        length: entry.u64_at(8).unwrap_or_default(),
+        typ: entry.u32_at(16).unwrap_or_default(),
+        reserved: entry.u32_at(20).unwrap_or_default(),
+    };
+    Ok((entry, needed))
+}
+
+/// Iterator over the entries of one Multiboot2 mmap tag.
+/// Yields Err at most once, then stops.
+#[derive(Clone, Debug)]
+pub struct Mb2MmapIter<'a> {
+    entries: TableBlob<'a>,
+    entry_size: u32,
+    entry_version: u32,
+}
+
+impl<'a> Mb2MmapIter<'a> {
+    /// Validate the tag header at the start of `tag` and iterate its entries.
+    /// Bytes past the tag's own `size` are ignored (the next tag).
+    pub fn new(tag: &'a [u8]) -> Result<Self, Mb2Error> {
+        let blob = TableBlob::new(tag);
+        let (Some(typ), Some(size), Some(entry_size), Some(entry_version)) = (
+            blob.u32_at(0),
+            blob.u32_at(4),
+            blob.u32_at(8),
+            blob.u32_at(12),
+        ) else {
+            return Err(Mb2Error::TruncatedTag { have: tag.len() });
+        };
+        if typ != TAG_TYPE_MMAP {
+            return Err(Mb2Error::WrongTagType { typ });
+        }
+        let entries = (size as usize)
+            .checked_sub(TAG_HEADER_LEN)
+            .and_then(|len| blob.sub(TAG_HEADER_LEN, len))
+            .ok_or(Mb2Error::BadTagSize {
+                size,
+                have: tag.len(),
+            })?;
+        if entry_size < ENTRY_SIZE {
+            return Err(Mb2Error::EntrySizeTooSmall { entry_size });
+        }
+        Ok(Mb2MmapIter {
+            entries,
+            entry_size,
+            entry_version,
+        })
+    }
+
+    /// [`new`](Self::new) over `len` bytes at `ptr`, the start of the tag.
+    /// `len` bounds the read even if the tag's size field claims more.
+    ///
+    /// # Safety
+    /// See [`TableBlob::from_raw_parts`].
+    pub unsafe fn from_raw_parts(ptr: *const u8, len: usize) -> Result<Self, Mb2Error> {
+        Self::new(TableBlob::from_raw_parts(ptr, len).remaining())
+    }
+
+    pub fn entry_size(&self) -> u32 {
+        self.entry_size
+    }
+
+    pub fn entry_version(&self) -> u32 {
+        self.entry_version
+    }
+}
+
+impl<'a> Iterator for Mb2MmapIter<'a> {
+    type Item = Result<Mb2Entry, Mb2Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.entries.is_exhausted() {
+            return None;
+        }
+        match read_one_mb2(self.entries.remaining(), self.entry_size) {
+            Ok((entry, consumed)) => {
+                debug_assert!(consumed >= ENTRY_SIZE as usize);
+                // consumed fit in remaining(), so this cannot fail.
+                let _ = self.entries.advance(consumed);
+                Some(Ok(entry))
+            }
+            Err(e) => {
+                self.entries.finish();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+// ============================================================
+// BUILDER (YOU BECOME THE BOOTLOADER)
+// ============================================================
+
+/// Append a complete mmap tag with entries `entry_size` bytes apart
+/// (bytes past the first 24 are zero), then pad `buf` to the next tag
+/// boundary. The tag's `size` excludes the padding, as on real hardware.
+///
+/// Panics if `entry_size` is smaller than [`ENTRY_SIZE`].
+#[cfg(feature = "alloc")]
+pub fn push_mmap_tag(buf: &mut Vec<u8>, entries: &[Mb2Entry], entry_size: u32) {
+    assert!(
+        entry_size >= ENTRY_SIZE,
+        "entry_size {entry_size} < {ENTRY_SIZE}"
+    );
+    let size = TAG_HEADER_LEN + entry_size as usize * entries.len();
+    buf.extend_from_slice(&TAG_TYPE_MMAP.to_le_bytes());
+    buf.extend_from_slice(&(size as u32).to_le_bytes());
+    buf.extend_from_slice(&entry_size.to_le_bytes());
+    buf.extend_from_slice(&0u32.to_le_bytes()); // entry_version
+    for e in entries {
+        buf.extend_from_slice(&e.base_addr.to_le_bytes());
+        buf.extend_from_slice(&e.length.to_le_bytes());
+        buf.extend_from_slice(&e.typ.to_le_bytes());
+        buf.extend_from_slice(&e.reserved.to_le_bytes());
+        buf.resize(buf.len() + (entry_size - ENTRY_SIZE) as usize, 0);
+    }
+    pad_to_tag(buf);
+}
+
+#[cfg(feature = "alloc")]
+fn pad_to_tag(buf: &mut Vec<u8>) {
+    let padded = buf.len().next_multiple_of(TAG_ALIGN);
+    buf.resize(padded, 0);
+}
+
+/// A whole Multiboot2 boot information block: header, tags, end tag.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub struct Mb2InfoBuilder {
+    buf: Vec<u8>,
+}
+
+#[cfg(feature = "alloc")]
+impl Default for Mb2InfoBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Mb2InfoBuilder {
+    pub fn new() -> Self {
+        // total_size (patched in finish) + reserved.
+        Mb2InfoBuilder { buf: vec![0; 8] }
+    }
+
+    /// Add an mmap tag with the standard 24-byte entries.
+    pub fn mmap(self, entries: &[Mb2Entry]) -> Self {
+        self.mmap_with_entry_size(entries, ENTRY_SIZE)
+    }
+
+    /// Add an mmap tag with a larger stride, as a future spec revision might.
+    pub fn mmap_with_entry_size(mut self, entries: &[Mb2Entry], entry_size: u32) -> Self {
+        push_mmap_tag(&mut self.buf, entries, entry_size);
+        self
+    }
+
+    /// Add an arbitrary tag (e.g. a command line), padded like the rest.
+    pub fn tag(mut self, typ: u32, payload: &[u8]) -> Self {
+        let size = 8 + payload.len();
+        self.buf.extend_from_slice(&typ.to_le_bytes());
+        self.buf.extend_from_slice(&(size as u32).to_le_bytes());
+        self.buf.extend_from_slice(payload);
+        pad_to_tag(&mut self.buf);
+        self
+    }
+
+    /// Append the end tag and fill in total_size.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.buf.extend_from_slice(&TAG_TYPE_END.to_le_bytes());
+        self.buf.extend_from_slice(&8u32.to_le_bytes());
+        let total = self.buf.len() as u32;
+        self.buf[0..4].copy_from_slice(&total.to_le_bytes());
+        self.buf
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::{sanitize, MemRegion};
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn tag(entry_size: u32, entries: &[(u64, u64, u32)]) -> Vec<u8> {
+        let size = TAG_HEADER_LEN as u32 + entry_size * entries.len() as u32;
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&TAG_TYPE_MMAP.to_le_bytes());
+        buf.extend_from_slice(&size.to_le_bytes());
+        buf.extend_from_slice(&entry_size.to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+        for &(start, len, kind) in entries {
+            buf.extend_from_slice(&start.to_le_bytes());
+            buf.extend_from_slice(&len.to_le_bytes());
+            buf.extend_from_slice(&kind.to_le_bytes());
+            buf.extend_from_slice(&0u32.to_le_bytes());
+            buf.resize(buf.len() + (entry_size - ENTRY_SIZE) as usize, 0xEE);
+        }
+        buf
+    }
+
+    #[test]
+    fn builder_emits_aligned_tags_and_end_tag() {
+        let ram = Mb2Entry {
+            base_addr: 0x10_0000,
+            length: 0x100_0000,
+            typ: 1,
+            reserved: 0,
+        };
+        let info = Mb2InfoBuilder::new()
+            .tag(1, b"console=ttyS0\0") // 14-byte payload: padded
+            .mmap_with_entry_size(&[ram, ram], 28)
+            .finish();
+
+        // header 8 + cmdline tag 22 -> 24 + mmap tag 16 + 56 = 72 + end 8
+        pretty_assertions::assert_eq!(info.len(), 8 + 24 + 72 + 8);
+        pretty_assertions::assert_eq!(info[0..4], (info.len() as u32).to_le_bytes());
+        pretty_assertions::assert_eq!(info[8 + 4..8 + 8], 22u32.to_le_bytes());
+        pretty_assertions::assert_eq!(info[info.len() - 8..], [0, 0, 0, 0, 8, 0, 0, 0]);
+
+        let it = Mb2MmapIter::new(&info[32..]).unwrap();
+        pretty_assertions::assert_eq!(it.entry_size(), 28);
+        let got: Vec<Mb2Entry> = it.map(Result::unwrap).collect();
+        pretty_assertions::assert_eq!(got, vec![ram, ram]);
+    }
+
+    #[test]
+    fn parses_entries_into_the_mb1_pipeline() {
+        init();
+        let buf = tag(24, &[(0, 0x9_FC00, 1), (0x10_0000, 0x7FF0_0000, 1)]);
+        let regions: Vec<MemRegion> = Mb2MmapIter::new(&buf)
+            .unwrap()
+            .map(|e| sanitize(e.unwrap().into()).unwrap())
+            .collect();
+        pretty_assertions::assert_eq!(
+            regions,
+            vec![
+                MemRegion {
+                    start: 0,
+                    len: 0x9_FC00,
+                    kind: 1
+                },
+                MemRegion {
+                    start: 0x10_0000,
+                    len: 0x7FF0_0000,
+                    kind: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn larger_entry_size_skips_unknown_fields() {
+        let buf = tag(32, &[(0x1000, 0x1000, 1), (0x3000, 0x1000, 2)]);
+        let it = Mb2MmapIter::new(&buf).unwrap();
+        pretty_assertions::assert_eq!(it.entry_size(), 32);
+        let starts: Vec<u64> = it.map(|e| e.unwrap().base_addr).collect();
+        pretty_assertions::assert_eq!(starts, vec![0x1000, 0x3000]);
+    }
+
+    #[test]
+    fn bytes_past_the_tag_are_ignored() {
+        let mut buf = tag(24, &[(0x1000, 0x1000, 1)]);
+        buf.extend_from_slice(&[0xFF; 24]); // next tag
+        pretty_assertions::assert_eq!(Mb2MmapIter::new(&buf).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn header_errors() {
+        pretty_assertions::assert_eq!(
+            Mb2MmapIter::new(&[0; 15]).err(),
+            Some(Mb2Error::TruncatedTag { have: 15 })
+        );
+
+        let mut buf = tag(24, &[]);
+        buf[0] = 4;
+        pretty_assertions::assert_eq!(
+            Mb2MmapIter::new(&buf).err(),
+            Some(Mb2Error::WrongTagType { typ: 4 })
+        );
+
+        let mut buf = tag(24, &[(0, 0x1000, 1)]);
+        buf[4..8].copy_from_slice(&64u32.to_le_bytes());
+        pretty_assertions::assert_eq!(
+            Mb2MmapIter::new(&buf).err(),
+            Some(Mb2Error::BadTagSize { size: 64, have: 40 })
+        );
+
+        let buf = tag(0, &[]);
+        pretty_assertions::assert_eq!(
+            Mb2MmapIter::new(&buf).err(),
+            Some(Mb2Error::EntrySizeTooSmall { entry_size: 0 })
+        );
+    }
+
+    #[test]
+    fn trailing_partial_entry_errors_once() {
+        let mut buf = tag(24, &[(0x1000, 0x1000, 1)]);
+        buf.extend_from_slice(&[0; 8]);
+        let size = buf.len() as u32;
+        buf[4..8].copy_from_slice(&size.to_le_bytes());
+
+        let mut it = Mb2MmapIter::new(&buf).unwrap();
+        assert!(it.next().unwrap().is_ok());
+        pretty_assertions::assert_eq!(
+            it.next(),
+            Some(Err(Mb2Error::TruncatedEntry {
+                needed: 24,
+                have: 8
+            }))
+        );
+        assert!(it.next().is_none());
+    }
+}
@@ -0,0 +1,253 @@
+// mbi.rs
+//
+// The Multiboot1 information structure (MBI): what EBX points at when a
+// Multiboot1 loader jumps to the kernel. The mmap blob parsed by
+// Mb1MmapIter is only reachable through it.
+//
+//   +0   u32 flags
+//   +4   u32 mem_lower     KiB below 1MiB          (flags bit 0)
+//   +8   u32 mem_upper     KiB from 1MiB up         (flags bit 0)
+//   ...
+//   +44  u32 mmap_length   bytes                    (flags bit 6)
+//   +48  u32 mmap_addr     physical address          (flags bit 6)
+//   ...
+//
+// A field whose flag bit is clear is garbage, not zero. Old loaders set
+// only bit 0; then mem_lower/mem_upper are all there is, and they describe
+// two usable ranges: [0, mem_lower KiB) and [1MiB, 1MiB + mem_upper KiB).
+
+use crate::blob::TableBlob;
+use crate::kind;
+use crate::raw::MemRegion;
+
+pub const FLAG_MEM: u32 = 1 << 0;
+/// a.out symbol table. Mutually exclusive with FLAG_ELF_SHDR.
+pub const FLAG_AOUT_SYMS: u32 = 1 << 4;
+pub const FLAG_ELF_SHDR: u32 = 1 << 5;
+pub const FLAG_MMAP: u32 = 1 << 6;
+
+/// Bytes of the MBI this module reads (through mmap_addr).
+pub const MBI_READ_LEN: usize = 52;
+
+const MEM_UPPER_BASE: u64 = 0x10_0000;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mb1InfoError {
+    /// The flags promise a field that lies past the end of the buffer.
+    TruncatedInfo { needed: usize, have: usize },
+    /// Bits 4 and 5 both set: the loader is confused about everything.
+    ConflictingSymbolFlags { flags: u32 },
+    /// mmap_addr + mmap_length does not fit in 32-bit physical memory.
+    MmapOutOfRange { addr: u32, length: u32 },
+}
+
+/// Where the memory layout comes from, best source first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mb1Memory {
+    /// Full map at `addr`, `length` bytes: feed it to Mb1MmapIter.
+    Mmap { addr: u32, length: u32 },
+    /// Only mem_lower/mem_upper: the two usable ranges they describe.
+    /// Either may be empty (len 0).
+    Basic([MemRegion; 2]),
+    /// The loader gave no memory information at all.
+    None,
+}
+
+/// The memory-related part of an MBI, validated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mb1Info {
+    pub flags: u32,
+    /// KiB below 1MiB, if flags bit 0 is set.
+    pub mem_lower: Option<u32>,
+    /// KiB from 1MiB up, if flags bit 0 is set.
+    pub mem_upper: Option<u32>,
+    /// (mmap_addr, mmap_length), if flags bit 6 is set.
+    pub mmap: Option<(u32, u32)>,
+}
+
+impl Mb1Info {
+    /// Parse from the MBI bytes. Only as many bytes as the set flags need
+    /// must be present.
+    pub fn parse(bytes: &[u8]) -> Result<Self, Mb1InfoError> {
+        let blob = TableBlob::new(bytes);
+        let need = |needed: usize| {
+            if bytes.len() < needed {
+                Err(Mb1InfoError::TruncatedInfo {
+                    needed,
+                    have: bytes.len(),
+                })
+            } else {
+                Ok(())
+            }
+        };
+
+        need(4)?;
+        let flags = blob.u32_at(0).unwrap_or_default();
+        if flags & FLAG_AOUT_SYMS != 0 && flags & FLAG_ELF_SHDR != 0 {
+            return Err(Mb1InfoError::ConflictingSymbolFlags { flags });
+        }
+
+        let (mut mem_lower, mut mem_upper) = (None, None);
+        if flags & FLAG_MEM != 0 {
+            need(12)?;
+            mem_lower = blob.u32_at(4);
+            mem_upper = blob.u32_at(8);
+        }
+
+        let mut mmap = None;
+        if flags & FLAG_MMAP != 0 {
+            need(MBI_READ_LEN)?;
+            let length = blob.u32_at(44).unwrap_or_default();
+            let addr = blob.u32_at(48).unwrap_or_default();
+            if addr.checked_add(length).is_none() {
+                return Err(Mb1InfoError::MmapOutOfRange { addr, length });
+            }
+            mmap = Some((addr, length));
+        }
+
+        Ok(Mb1Info {
+            flags,
+            mem_lower,
+            mem_upper,
+            mmap,
+        })
+    }
+
+    /// Parse the MBI at physical address `addr` (the value of EBX).
+    ///
+    /// # Safety
+    /// `addr` must be identity-mapped and readable for MBI_READ_LEN bytes.
+    pub unsafe fn from_ptr(addr: u64) -> Result<Self, Mb1InfoError> {
+        let bytes = core::slice::from_raw_parts(addr as usize as *const u8, MBI_READ_LEN);
+        Self::parse(bytes)
+    }
+
+    /// The mmap blob as a slice, for Mb1MmapIter.
+    ///
+    /// # Safety
+    /// The mmap range must be identity-mapped, readable, and stay
+    /// untouched for `'a`.
+    pub unsafe fn mmap_bytes<'a>(&self) -> Option<&'a [u8]> {
+        let (addr, length) = self.mmap?;
+        Some(core::slice::from_raw_parts(
+            addr as usize as *const u8,
+            length as usize,
+        ))
+    }
+
+    /// Best available memory information: the full map if present,
+    /// otherwise the mem_lower/mem_upper fallback.
+    pub fn memory(&self) -> Mb1Memory {
+        if let Some((addr, length)) = self.mmap {
+            return Mb1Memory::Mmap { addr, length };
+        }
+        match (self.mem_lower, self.mem_upper) {
+            (Some(lower), Some(upper)) => Mb1Memory::Basic([
+                MemRegion {
+                    start: 0,
+                    len: lower as u64 * 1024,
+                    kind: kind::USABLE,
+                },
+                MemRegion {
+                    start: MEM_UPPER_BASE,
+                    len: upper as u64 * 1024,
+                    kind: kind::USABLE,
+                },
+            ]),
+            _ => Mb1Memory::None,
+        }
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn mbi(
+        flags: u32,
+        mem_lower: u32,
+        mem_upper: u32,
+        mmap_length: u32,
+        mmap_addr: u32,
+    ) -> Vec<u8> {
+        let mut b = vec![0u8; MBI_READ_LEN];
+        b[0..4].copy_from_slice(&flags.to_le_bytes());
+        b[4..8].copy_from_slice(&mem_lower.to_le_bytes());
+        b[8..12].copy_from_slice(&mem_upper.to_le_bytes());
+        b[44..48].copy_from_slice(&mmap_length.to_le_bytes());
+        b[48..52].copy_from_slice(&mmap_addr.to_le_bytes());
+        b
+    }
+
+    #[test]
+    fn mmap_preferred_when_present() {
+        init();
+        let info = Mb1Info::parse(&mbi(FLAG_MEM | FLAG_MMAP, 639, 130048, 144, 0x9000)).unwrap();
+        pretty_assertions::assert_eq!(info.mem_lower, Some(639));
+        pretty_assertions::assert_eq!(
+            info.memory(),
+            Mb1Memory::Mmap {
+                addr: 0x9000,
+                length: 144
+            }
+        );
+    }
+
+    #[test]
+    fn falls_back_to_mem_lower_upper() {
+        // Only the first 12 bytes are needed when bit 6 is clear.
+        let bytes = mbi(FLAG_MEM, 639, 130048, 0xDEAD, 0xBEEF);
+        let info = Mb1Info::parse(&bytes[..12]).unwrap();
+        pretty_assertions::assert_eq!(info.mmap, None);
+        pretty_assertions::assert_eq!(
+            info.memory(),
+            Mb1Memory::Basic([
+                region(0, 639 * 1024, kind::USABLE),
+                region(0x10_0000, 130048 * 1024, kind::USABLE),
+            ])
+        );
+
+        let info = Mb1Info::parse(&mbi(0, 1, 2, 3, 4)).unwrap();
+        pretty_assertions::assert_eq!(info.memory(), Mb1Memory::None);
+    }
+
+    #[test]
+    fn flag_errors() {
+        let bytes = mbi(FLAG_MEM | FLAG_MMAP, 0, 0, 24, 0x9000);
+        pretty_assertions::assert_eq!(
+            Mb1Info::parse(&bytes[..48]),
+            Err(Mb1InfoError::TruncatedInfo {
+                needed: MBI_READ_LEN,
+                have: 48
+            })
+        );
+        pretty_assertions::assert_eq!(
+            Mb1Info::parse(&mbi(FLAG_AOUT_SYMS | FLAG_ELF_SHDR, 0, 0, 0, 0)),
+            Err(Mb1InfoError::ConflictingSymbolFlags { flags: 0x30 })
+        );
+        pretty_assertions::assert_eq!(
+            Mb1Info::parse(&mbi(FLAG_MMAP, 0, 0, 0x100, 0xFFFF_FF80)),
+            Err(Mb1InfoError::MmapOutOfRange {
+                addr: 0xFFFF_FF80,
+                length: 0x100
+            })
+        );
+    }
+
+    #[test]
+    fn from_ptr_reads_in_place() {
+        let bytes = mbi(FLAG_MMAP, 0, 0, 8, 0x1000);
+        let info = unsafe { Mb1Info::from_ptr(bytes.as_ptr() as u64) }.unwrap();
+        pretty_assertions::assert_eq!(info.mmap, Some((0x1000, 8)));
+    }
+}
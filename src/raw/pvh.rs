@@ -0,0 +1,281 @@
+// pvh.rs
+//
+// Xen PVH direct boot (also used by QEMU/Firecracker "pvh" boot): the
+// kernel is entered with EBX pointing at an hvm_start_info.
+//
+//   +0   u32 magic            0x336ec578
+//   +4   u32 version          memmap fields exist from version 1
+//   ...
+//   +40  u64 memmap_paddr
+//   +48  u32 memmap_entries
+//
+// The memmap is an array of hvm_memmap_table_entry:
+//
+//   u64 addr   u64 size   u32 type   u32 reserved
+//
+// 24 bytes each. Types use the E820 numbering, so they pass through to
+// sanitize unchanged. A version 0 start_info has no memmap; the loader
+// then passes the map through an E820 table elsewhere.
+
+use crate::blob::TableBlob;
+use crate::raw::RawEntry;
+
+pub const PVH_START_MAGIC: u32 = 0x336E_C578;
+/// Bytes of hvm_start_info read here (version 1).
+pub const START_INFO_LEN: usize = 56;
+/// Bytes of hvm_start_info in version 0 (no memmap fields).
+pub const START_INFO_V0_LEN: usize = 40;
+pub const MEMMAP_ENTRY_SIZE: usize = 24;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PvhError {
+    TruncatedStartInfo {
+        needed: usize,
+        have: usize,
+    },
+    BadMagic {
+        magic: u32,
+    },
+    /// memmap_entries * 24 does not fit in usize.
+    MemmapTooLarge {
+        entries: u32,
+    },
+    TruncatedEntry {
+        needed: usize,
+        have: usize,
+    },
+}
+
+/// The parts of hvm_start_info this crate cares about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PvhStartInfo {
+    pub version: u32,
+    pub rsdp_paddr: u64,
+    /// (memmap_paddr, memmap_entries), if version >= 1 and entries > 0.
+    pub memmap: Option<(u64, u32)>,
+}
+
+impl PvhStartInfo {
+    pub fn parse(bytes: &[u8]) -> Result<Self, PvhError> {
+        let blob = TableBlob::new(bytes);
+        let truncated = |needed| PvhError::TruncatedStartInfo {
+            needed,
+            have: bytes.len(),
+        };
+        if bytes.len() < START_INFO_V0_LEN {
+            return Err(truncated(START_INFO_V0_LEN));
+        }
+        let magic = blob.u32_at(0).unwrap_or_default();
+        if magic != PVH_START_MAGIC {
+            return Err(PvhError::BadMagic { magic });
+        }
+        let version = blob.u32_at(4).unwrap_or_default();
+        let rsdp_paddr = blob.u64_at(32).unwrap_or_default();
+
+        let mut memmap = None;
+        if version >= 1 {
+            if bytes.len() < START_INFO_LEN {
+                return Err(truncated(START_INFO_LEN));
+            }
+            let paddr = blob.u64_at(40).unwrap_or_default();
+            let entries = blob.u32_at(48).unwrap_or_default();
+            if (entries as usize).checked_mul(MEMMAP_ENTRY_SIZE).is_none() {
+                return Err(PvhError::MemmapTooLarge { entries });
+            }
+            memmap = (entries > 0).then_some((paddr, entries));
+        }
+        Ok(PvhStartInfo {
+            version,
+            rsdp_paddr,
+            memmap,
+        })
+    }
+
+    /// Parse the start_info at physical address `addr` (the value of EBX).
+    ///
+    /// # Safety
+    /// `addr` must be identity-mapped and readable for START_INFO_LEN
+    /// bytes (START_INFO_V0_LEN if the loader only speaks version 0).
+    pub unsafe fn from_ptr(addr: u64) -> Result<Self, PvhError> {
+        let head = core::slice::from_raw_parts(addr as usize as *const u8, START_INFO_V0_LEN);
+        let version = TableBlob::new(head).u32_at(4).unwrap_or_default();
+        let len = if version >= 1 {
+            START_INFO_LEN
+        } else {
+            START_INFO_V0_LEN
+        };
+        Self::parse(core::slice::from_raw_parts(addr as usize as *const u8, len))
+    }
+
+    /// The memmap array as a slice, for PvhMemmapIter.
+    ///
+    /// # Safety
+    /// The memmap must be identity-mapped, readable, and stay untouched
+    /// for `'a`.
+    pub unsafe fn memmap_bytes<'a>(&self) -> Option<&'a [u8]> {
+        let (paddr, entries) = self.memmap?;
+        Some(core::slice::from_raw_parts(
+            paddr as usize as *const u8,
+            entries as usize * MEMMAP_ENTRY_SIZE,
+        ))
+    }
+}
+
+/// One hvm_memmap_table_entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PvhMemmapEntry {
+    pub addr: u64,
+    pub size: u64,
+    pub typ: u32,
+}
+
+impl From<PvhMemmapEntry> for RawEntry {
+    fn from(e: PvhMemmapEntry) -> Self {
+        crate::raw::raw(e.addr, e.size, e.typ)
+    }
+}
+
+/// Iterator over a PVH memmap array. Yields Err at most once, then stops.
+#[derive(Clone, Debug)]
+pub struct PvhMemmapIter<'a> {
+    entries: TableBlob<'a>,
+}
+
+impl<'a> PvhMemmapIter<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        PvhMemmapIter {
+            entries: TableBlob::new(buf),
+        }
+    }
+
+    /// The memmap of `info`, read in place.
+    ///
+    /// # Safety
+    /// As for [`PvhStartInfo::memmap_bytes`].
+    pub unsafe fn from_start_info(info: &PvhStartInfo) -> Self {
+        Self::new(info.memmap_bytes().unwrap_or_default())
+    }
+}
+
+impl<'a> Iterator for PvhMemmapIter<'a> {
+    type Item = Result<PvhMemmapEntry, PvhError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.entries.is_exhausted() {
+            return None;
+        }
+        let e = self.entries;
+        if e.window(0, MEMMAP_ENTRY_SIZE).is_none() {
+            self.entries.finish();
+            return Some(Err(PvhError::TruncatedEntry {
+                needed: MEMMAP_ENTRY_SIZE,
+                have: e.remaining().len(),
+            }));
+        }
+        let _ = self.entries.advance(MEMMAP_ENTRY_SIZE);
+        Some(Ok(PvhMemmapEntry {
+            addr: e.u64_at(0).unwrap_or_default(),
+            size: e.u64_at(8).unwrap_or_default(),
+            typ: e.u32_at(16).unwrap_or_default(),
+        }))
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::{sanitize, MemRegion};
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn start_info(version: u32, memmap_paddr: u64, entries: u32) -> Vec<u8> {
+        let mut b = vec![0u8; START_INFO_LEN];
+        b[0..4].copy_from_slice(&PVH_START_MAGIC.to_le_bytes());
+        b[4..8].copy_from_slice(&version.to_le_bytes());
+        b[32..40].copy_from_slice(&0xF_5A40u64.to_le_bytes());
+        b[40..48].copy_from_slice(&memmap_paddr.to_le_bytes());
+        b[48..52].copy_from_slice(&entries.to_le_bytes());
+        b
+    }
+
+    fn push_entry(buf: &mut Vec<u8>, addr: u64, size: u64, typ: u32) {
+        buf.extend_from_slice(&addr.to_le_bytes());
+        buf.extend_from_slice(&size.to_le_bytes());
+        buf.extend_from_slice(&typ.to_le_bytes());
+        buf.extend_from_slice(&0u32.to_le_bytes());
+    }
+
+    #[test]
+    fn start_info_and_memmap_into_sanitize() {
+        init();
+        let mut memmap = Vec::new();
+        push_entry(&mut memmap, 0, 0x9_FC00, 1);
+        push_entry(&mut memmap, 0x10_0000, 0x3FF0_0000, 1);
+        push_entry(&mut memmap, 0xFEFF_C000, 0x4000, 2);
+
+        let mut info_bytes = start_info(1, 0, 3);
+        info_bytes[40..48].copy_from_slice(&(memmap.as_ptr() as u64).to_le_bytes());
+        let info = unsafe { PvhStartInfo::from_ptr(info_bytes.as_ptr() as u64) }.unwrap();
+        pretty_assertions::assert_eq!(info.rsdp_paddr, 0xF_5A40);
+
+        let regions: Vec<MemRegion> = unsafe { PvhMemmapIter::from_start_info(&info) }
+            .map(|e| sanitize(e.unwrap().into()).unwrap())
+            .collect();
+        pretty_assertions::assert_eq!(
+            regions,
+            vec![
+                region(0, 0x9_FC00, 1),
+                region(0x10_0000, 0x3FF0_0000, 1),
+                region(0xFEFF_C000, 0x4000, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn version_0_has_no_memmap() {
+        let b = start_info(0, 0x1234, 5);
+        let info = PvhStartInfo::parse(&b[..START_INFO_V0_LEN]).unwrap();
+        pretty_assertions::assert_eq!(info.memmap, None);
+    }
+
+    #[test]
+    fn start_info_errors() {
+        let mut b = start_info(1, 0, 1);
+        pretty_assertions::assert_eq!(
+            PvhStartInfo::parse(&b[..48]),
+            Err(PvhError::TruncatedStartInfo {
+                needed: START_INFO_LEN,
+                have: 48
+            })
+        );
+        b[0] ^= 1;
+        assert!(matches!(
+            PvhStartInfo::parse(&b),
+            Err(PvhError::BadMagic { .. })
+        ));
+    }
+
+    #[test]
+    fn trailing_partial_entry_errors_once() {
+        let mut buf = Vec::new();
+        push_entry(&mut buf, 0, 0x1000, 1);
+        buf.extend_from_slice(&[0; 10]);
+        let items: Vec<_> = PvhMemmapIter::new(&buf).collect();
+        pretty_assertions::assert_eq!(items.len(), 2);
+        pretty_assertions::assert_eq!(
+            items[1],
+            Err(PvhError::TruncatedEntry {
+                needed: MEMMAP_ENTRY_SIZE,
+                have: 10
+            })
+        );
+    }
+}
@@ -0,0 +1,393 @@
+// srat.rs
+//
+// ACPI SRAT (System Resource Affinity Table): which NUMA node each range
+// of physical memory belongs to.
+//
+//   36 bytes  standard ACPI table header ("SRAT", length, checksum, ...)
+//   12 bytes  reserved
+//   subtables, each starting with u8 type, u8 length
+//
+// Only Memory Affinity subtables (type 1) matter here:
+//
+//   +2  u32 proximity domain
+//   +8  u64 base  (as two u32 halves, low first)
+//   +16 u64 length (same)
+//   +28 u32 flags (enabled, hot-pluggable, non-volatile)
+//
+// The memory map itself stays node-agnostic. Node ids live in a parallel
+// table (split_by_node), so nothing that already takes MemRegion changes.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::blob::TableBlob;
+use crate::kind;
+use crate::raw::MemRegion;
+#[cfg(feature = "alloc")]
+use crate::region::RegionSet;
+
+pub const SRAT_SIGNATURE: [u8; 4] = *b"SRAT";
+/// ACPI header plus the 12 reserved bytes before the first subtable.
+pub const SRAT_HEADER_LEN: usize = 48;
+
+const SUBTABLE_MEMORY_AFFINITY: u8 = 1;
+const MEMORY_AFFINITY_LEN: usize = 40;
+
+pub const MEM_AFFINITY_ENABLED: u32 = 1 << 0;
+pub const MEM_AFFINITY_HOT_PLUGGABLE: u32 = 1 << 1;
+pub const MEM_AFFINITY_NON_VOLATILE: u32 = 1 << 2;
+
+/// One Memory Affinity structure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryAffinity {
+    pub start: u64,
+    pub len: u64,
+    /// Proximity domain (the NUMA node id, as firmware numbers them).
+    pub node: u32,
+    pub flags: u32,
+}
+
+impl MemoryAffinity {
+    /// Disabled entries must be ignored (firmware leaves unused slots).
+    pub fn is_enabled(&self) -> bool {
+        self.flags & MEM_AFFINITY_ENABLED != 0
+    }
+
+    pub fn is_hot_pluggable(&self) -> bool {
+        self.flags & MEM_AFFINITY_HOT_PLUGGABLE != 0
+    }
+
+    pub fn is_non_volatile(&self) -> bool {
+        self.flags & MEM_AFFINITY_NON_VOLATILE != 0
+    }
+
+    pub fn end(&self) -> u64 {
+        self.start.saturating_add(self.len)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SratError {
+    TruncatedHeader {
+        have: usize,
+    },
+    BadSignature {
+        signature: [u8; 4],
+    },
+    /// Header length is shorter than the header or longer than the buffer.
+    BadLength {
+        length: u32,
+        have: usize,
+    },
+    /// Bytes of the table do not sum to zero.
+    BadChecksum,
+    /// Subtable length too small to make progress, or past the table end.
+    BadSubtable {
+        offset: usize,
+        length: u8,
+    },
+}
+
+/// Iterator over the Memory Affinity structures of one SRAT.
+/// Other subtable types are skipped. Yields Err at most once, then stops.
+#[derive(Clone, Debug)]
+pub struct SratIter<'a> {
+    subtables: TableBlob<'a>,
+}
+
+impl<'a> SratIter<'a> {
+    /// Validate signature, length and checksum. Bytes past the header's
+    /// length field are ignored.
+    pub fn new(table: &'a [u8]) -> Result<Self, SratError> {
+        let blob = TableBlob::new(table);
+        let (Some(signature), Some(length)) = (blob.window(0, 4), blob.u32_at(4)) else {
+            return Err(SratError::TruncatedHeader { have: table.len() });
+        };
+        if signature != SRAT_SIGNATURE {
+            return Err(SratError::BadSignature {
+                signature: signature.try_into().unwrap_or_default(),
+            });
+        }
+        let bad_length = SratError::BadLength {
+            length,
+            have: table.len(),
+        };
+        let whole = blob.window(0, length as usize).ok_or(bad_length.clone())?;
+        if whole.len() < SRAT_HEADER_LEN {
+            return Err(bad_length);
+        }
+        if whole.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)) != 0 {
+            return Err(SratError::BadChecksum);
+        }
+        let mut subtables = TableBlob::new(whole);
+        // Length checked above, so this cannot fail.
+        let _ = subtables.advance(SRAT_HEADER_LEN);
+        Ok(SratIter { subtables })
+    }
+}
+
+impl<'a> Iterator for SratIter<'a> {
+    type Item = Result<MemoryAffinity, SratError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.subtables.is_exhausted() {
+            let s = self.subtables;
+            let offset = s.offset();
+            let (typ, length) = match s.window(0, 2) {
+                Some(&[typ, length]) => (typ, length),
+                _ => (0, 0),
+            };
+            if (length as usize) < 2 || s.window(0, length as usize).is_none() {
+                self.subtables.finish();
+                return Some(Err(SratError::BadSubtable { offset, length }));
+            }
+            let _ = self.subtables.advance(length as usize);
+
+            if typ != SUBTABLE_MEMORY_AFFINITY || (length as usize) < MEMORY_AFFINITY_LEN {
+                continue;
+            }
+            let half = |at| s.u32_at(at).unwrap_or_default() as u64;
+            return Some(Ok(MemoryAffinity {
+                node: s.u32_at(2).unwrap_or_default(),
+                start: half(8) | half(12) << 32,
+                len: half(16) | half(20) << 32,
+                flags: s.u32_at(28).unwrap_or_default(),
+            }));
+        }
+        None
+    }
+}
+
+/// A piece of a region and the node it belongs to (`None` if no enabled
+/// affinity entry covers it).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeRegion {
+    pub region: MemRegion,
+    pub node: Option<u32>,
+}
+
+/// Split `regions` at affinity boundaries and tag each piece with its
+/// node. Disabled affinity entries are ignored; where entries overlap,
+/// the lower-addressed one wins. Output follows input order.
+#[cfg(feature = "alloc")]
+pub fn split_by_node(regions: &[MemRegion], affinities: &[MemoryAffinity]) -> Vec<NodeRegion> {
+    let mut aff: Vec<MemoryAffinity> = affinities
+        .iter()
+        .copied()
+        .filter(|a| a.is_enabled() && a.len > 0)
+        .collect();
+    aff.sort_by_key(|a| a.start);
+
+    let mut out = Vec::new();
+    let mut piece = |start: u64, end: u64, kind: u32, node: Option<u32>| {
+        if start < end {
+            out.push(NodeRegion {
+                region: MemRegion {
+                    start,
+                    len: end - start,
+                    kind,
+                },
+                node,
+            });
+        }
+    };
+
+    for r in regions {
+        let end = r.end();
+        let mut cursor = r.start;
+        for a in aff.iter().filter(|a| a.end() > r.start && a.start < end) {
+            if a.end() <= cursor {
+                continue;
+            }
+            piece(cursor, a.start.max(cursor), r.kind, None);
+            let stop = a.end().min(end);
+            piece(a.start.max(cursor), stop, r.kind, Some(a.node));
+            cursor = stop;
+        }
+        piece(cursor, end, r.kind, None);
+    }
+    out
+}
+
+/// Turn the usable parts of `regions` that an enabled, hot-pluggable
+/// affinity entry covers into `kind::HOT_PLUGGABLE`, so default
+/// allocation leaves them alone (memory that may be unplugged must not
+/// end up holding kernel data). Other kinds are left as they are.
+#[cfg(feature = "alloc")]
+pub fn mark_hot_pluggable(regions: &[MemRegion], affinities: &[MemoryAffinity]) -> Vec<MemRegion> {
+    let pluggable = RegionSet::from_ranges(
+        affinities
+            .iter()
+            .filter(|a| a.is_enabled() && a.is_hot_pluggable())
+            .map(|a| a.start..a.end()),
+    );
+    let mut out = Vec::with_capacity(regions.len());
+    for r in regions {
+        if r.kind != kind::USABLE {
+            out.push(*r);
+            continue;
+        }
+        let whole = RegionSet::from_regions(core::slice::from_ref(r));
+        let hot = whole.intersect(&pluggable);
+        let mut pieces: Vec<MemRegion> = whole
+            .difference(&hot)
+            .regions(kind::USABLE)
+            .chain(hot.regions(kind::HOT_PLUGGABLE))
+            .collect();
+        pieces.sort();
+        out.extend(pieces);
+    }
+    out
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn affinity(start: u64, len: u64, node: u32) -> MemoryAffinity {
+        MemoryAffinity {
+            start,
+            len,
+            node,
+            flags: MEM_AFFINITY_ENABLED,
+        }
+    }
+
+    fn push_mem_affinity(buf: &mut Vec<u8>, a: MemoryAffinity) {
+        buf.push(SUBTABLE_MEMORY_AFFINITY);
+        buf.push(MEMORY_AFFINITY_LEN as u8);
+        buf.extend_from_slice(&a.node.to_le_bytes());
+        buf.extend_from_slice(&[0; 2]);
+        buf.extend_from_slice(&(a.start as u32).to_le_bytes());
+        buf.extend_from_slice(&((a.start >> 32) as u32).to_le_bytes());
+        buf.extend_from_slice(&(a.len as u32).to_le_bytes());
+        buf.extend_from_slice(&((a.len >> 32) as u32).to_le_bytes());
+        buf.extend_from_slice(&[0; 4]);
+        buf.extend_from_slice(&a.flags.to_le_bytes());
+        buf.extend_from_slice(&[0; 8]);
+    }
+
+    fn srat(body: &[u8]) -> Vec<u8> {
+        let mut t = Vec::new();
+        t.extend_from_slice(&SRAT_SIGNATURE);
+        t.extend_from_slice(&((SRAT_HEADER_LEN + body.len()) as u32).to_le_bytes());
+        t.resize(SRAT_HEADER_LEN, 0);
+        t.extend_from_slice(body);
+        let sum = t.iter().fold(0u8, |s, b| s.wrapping_add(*b));
+        t[9] = 0u8.wrapping_sub(sum); // checksum byte
+        t
+    }
+
+    #[test]
+    fn parses_memory_affinity_and_skips_other_subtables() {
+        init();
+        let mut body = Vec::new();
+        // Processor Local APIC affinity (type 0, 16 bytes): skipped.
+        body.extend_from_slice(&[0, 16]);
+        body.extend_from_slice(&[0; 14]);
+        push_mem_affinity(&mut body, affinity(0, 0x8000_0000, 0));
+        push_mem_affinity(&mut body, affinity(0x1_0000_0000, 0x1_0000_0000, 1));
+        let table = srat(&body);
+
+        let got: Vec<MemoryAffinity> = SratIter::new(&table).unwrap().map(Result::unwrap).collect();
+        pretty_assertions::assert_eq!(
+            got,
+            vec![
+                affinity(0, 0x8000_0000, 0),
+                affinity(0x1_0000_0000, 0x1_0000_0000, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn header_errors() {
+        pretty_assertions::assert_eq!(
+            SratIter::new(&[0; 4]).err(),
+            Some(SratError::TruncatedHeader { have: 4 })
+        );
+        let mut table = srat(&[]);
+        table[0] = b'X';
+        pretty_assertions::assert_eq!(
+            SratIter::new(&table).err(),
+            Some(SratError::BadSignature {
+                signature: *b"XRAT"
+            })
+        );
+        let mut table = srat(&[]);
+        table[20] ^= 1;
+        pretty_assertions::assert_eq!(SratIter::new(&table).err(), Some(SratError::BadChecksum));
+        let mut table = srat(&[]);
+        table[4] = 100;
+        assert!(matches!(
+            SratIter::new(&table),
+            Err(SratError::BadLength { .. })
+        ));
+    }
+
+    #[test]
+    fn zero_length_subtable_stops_iteration() {
+        let table = srat(&[1, 0, 0, 0]);
+        let mut it = SratIter::new(&table).unwrap();
+        pretty_assertions::assert_eq!(
+            it.next(),
+            Some(Err(SratError::BadSubtable {
+                offset: SRAT_HEADER_LEN,
+                length: 0
+            }))
+        );
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn split_by_node_tags_pieces() {
+        let regions = [region(0, 0x3000, 1), region(0x5000, 0x1000, 2)];
+        let mut disabled = affinity(0x5000, 0x1000, 7);
+        disabled.flags = 0;
+        let aff = [
+            affinity(0x1000, 0x1000, 0),
+            affinity(0x2000, 0x2000, 1),
+            disabled,
+        ];
+
+        let got: Vec<(u64, u64, Option<u32>)> = split_by_node(&regions, &aff)
+            .iter()
+            .map(|p| (p.region.start, p.region.len, p.node))
+            .collect();
+        pretty_assertions::assert_eq!(
+            got,
+            vec![
+                (0, 0x1000, None),
+                (0x1000, 0x1000, Some(0)),
+                (0x2000, 0x1000, Some(1)),
+                (0x5000, 0x1000, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn hot_pluggable_usable_memory_is_set_apart() {
+        let regions = [region(0, 0x4000, 1), region(0x4000, 0x1000, 2)];
+        let mut hot = affinity(0x2000, 0x4000, 1);
+        hot.flags |= MEM_AFFINITY_HOT_PLUGGABLE;
+        let aff = [affinity(0, 0x2000, 0), hot];
+
+        pretty_assertions::assert_eq!(
+            mark_hot_pluggable(&regions, &aff),
+            vec![
+                region(0, 0x2000, 1),
+                region(0x2000, 0x2000, kind::HOT_PLUGGABLE),
+                region(0x4000, 0x1000, 2),
+            ]
+        );
+    }
+}
@@ -0,0 +1,393 @@
+// uefi.rs
+//
+// UEFI memory map, as returned by GetMemoryMap(): an array of
+// EFI_MEMORY_DESCRIPTOR.
+//
+//   u32 Type
+//   u32 (padding)
+//   u64 PhysicalStart
+//   u64 VirtualStart
+//   u64 NumberOfPages   (always 4 KiB pages)
+//   u64 Attribute
+//
+// That is 40 bytes, but firmware reports its own DescriptorSize (48 is
+// common) and the array MUST be walked with that stride. Code that steps
+// by size_of::<EFI_MEMORY_DESCRIPTOR>() works on some machines and reads
+// garbage on others.
+//
+// EFI types are richer than E820 types; `kind()` maps them the same way
+// Linux does once boot services have been exited.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::blob::TableBlob;
+use crate::kind;
+use crate::raw::{MemRegion, RawEntry};
+
+/// Size of the descriptor fields this crate reads.
+pub const DESCRIPTOR_SIZE: u32 = 40;
+/// UEFI pages are always 4 KiB, whatever the CPU page size.
+pub const EFI_PAGE_SIZE: u64 = 4096;
+
+pub const EFI_RESERVED_MEMORY_TYPE: u32 = 0;
+pub const EFI_LOADER_CODE: u32 = 1;
+pub const EFI_LOADER_DATA: u32 = 2;
+pub const EFI_BOOT_SERVICES_CODE: u32 = 3;
+pub const EFI_BOOT_SERVICES_DATA: u32 = 4;
+pub const EFI_RUNTIME_SERVICES_CODE: u32 = 5;
+pub const EFI_RUNTIME_SERVICES_DATA: u32 = 6;
+pub const EFI_CONVENTIONAL_MEMORY: u32 = 7;
+pub const EFI_UNUSABLE_MEMORY: u32 = 8;
+pub const EFI_ACPI_RECLAIM_MEMORY: u32 = 9;
+pub const EFI_ACPI_MEMORY_NVS: u32 = 10;
+pub const EFI_MEMORY_MAPPED_IO: u32 = 11;
+pub const EFI_MEMORY_MAPPED_IO_PORT_SPACE: u32 = 12;
+pub const EFI_PAL_CODE: u32 = 13;
+pub const EFI_PERSISTENT_MEMORY: u32 = 14;
+pub const EFI_UNACCEPTED_MEMORY: u32 = 15;
+
+/// Write-back cacheable.
+pub const EFI_MEMORY_WB: u64 = 0x8;
+/// Non-volatile.
+pub const EFI_MEMORY_NV: u64 = 0x8000;
+/// Higher reliability than other memory in the system.
+pub const EFI_MEMORY_MORE_RELIABLE: u64 = 0x1_0000;
+/// Must be mapped by the OS for runtime services.
+pub const EFI_MEMORY_RUNTIME: u64 = 1 << 63;
+
+/// One EFI_MEMORY_DESCRIPTOR. Attribute bits are kept untouched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UefiDescriptor {
+    pub typ: u32,
+    pub phys_start: u64,
+    pub virt_start: u64,
+    pub num_pages: u64,
+    pub attribute: u64,
+}
+
+impl UefiDescriptor {
+    /// The MB1/E820-style kind for this descriptor.
+    pub fn kind(&self) -> u32 {
+        efi_type_to_kind(self.typ, self.attribute)
+    }
+
+    /// Size in bytes, or None if `num_pages` overflows u64.
+    pub fn len(&self) -> Option<u64> {
+        self.num_pages.checked_mul(EFI_PAGE_SIZE)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_pages == 0
+    }
+
+    /// As a region, or None if the page count overflows.
+    pub fn region(&self) -> Option<MemRegion> {
+        Some(MemRegion {
+            start: self.phys_start,
+            len: self.len()?,
+            kind: self.kind(),
+        })
+    }
+
+    /// As a minimal MB1 entry for `sanitize`, or None if the page count
+    /// overflows (there is no honest length to report).
+    pub fn to_raw(&self) -> Option<RawEntry> {
+        Some(crate::raw::raw(self.phys_start, self.len()?, self.kind()))
+    }
+}
+
+/// Map an EFI memory type (plus attributes) to a region kind.
+///
+/// Loader and boot services memory count as usable: they are free once
+/// ExitBootServices() has been called, which is when you read this map.
+/// Conventional memory tagged EFI_MEMORY_SP is soft reserved.
+pub fn efi_type_to_kind(typ: u32, attribute: u64) -> u32 {
+    match typ {
+        EFI_CONVENTIONAL_MEMORY if attribute & kind::EFI_MEMORY_SP != 0 => kind::SOFT_RESERVED,
+        EFI_LOADER_CODE
+        | EFI_LOADER_DATA
+        | EFI_BOOT_SERVICES_CODE
+        | EFI_BOOT_SERVICES_DATA
+        | EFI_CONVENTIONAL_MEMORY => kind::USABLE,
+        EFI_UNUSABLE_MEMORY => kind::BAD_RAM,
+        EFI_ACPI_RECLAIM_MEMORY => kind::ACPI_RECLAIMABLE,
+        EFI_ACPI_MEMORY_NVS => kind::ACPI_NVS,
+        EFI_PERSISTENT_MEMORY => kind::PERSISTENT,
+        // Runtime services, MMIO, PAL code, unaccepted memory (must be
+        // accepted before use) and anything unknown.
+        _ => kind::RESERVED,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UefiError {
+    /// descriptor_size smaller than the fields we read.
+    DescriptorSizeTooSmall { descriptor_size: u32 },
+    /// Trailing bytes that do not make a whole descriptor.
+    TruncatedDescriptor { needed: usize, have: usize },
+}
+
+/// Parse ONE descriptor from the start of `buf`. Returns it and bytes
+/// consumed (always `descriptor_size`).
+pub fn read_one_uefi(
+    buf: &[u8],
+    descriptor_size: u32,
+) -> Result<(UefiDescriptor, usize), UefiError> {
+    if descriptor_size < DESCRIPTOR_SIZE {
+        return Err(UefiError::DescriptorSizeTooSmall { descriptor_size });
+    }
+    let needed = descriptor_size as usize;
+    let d = TableBlob::new(buf)
+        .sub(0, needed)
+        .ok_or(UefiError::TruncatedDescriptor {
+            needed,
+            have: buf.len(),
+        })?;
+    // The window is at least 40 bytes, so these reads cannot fail.
+    let desc = UefiDescriptor {
+        typ: d.u32_at(0).unwrap_or_default(),
+        phys_start: d.u64_at(8).unwrap_or_default(),
+        virt_start: d.u64_at(16).unwrap_or_default(),
+        num_pages: d.u64_at(24).unwrap_or_default(),
+        attribute: d.u64_at(32).unwrap_or_default(),
+    };
+    Ok((desc, needed))
+}
+
+/// Append one descriptor exactly as firmware lays it out, padded with
+/// zeros to `descriptor_size` bytes.
+///
+/// Panics if `descriptor_size` is smaller than [`DESCRIPTOR_SIZE`].
+#[cfg(feature = "alloc")]
+pub fn push_descriptor(buf: &mut Vec<u8>, desc: UefiDescriptor, descriptor_size: u32) {
+    assert!(
+        descriptor_size >= DESCRIPTOR_SIZE,
+        "descriptor_size {descriptor_size} < {DESCRIPTOR_SIZE}"
+    );
+    let at = buf.len();
+    buf.extend_from_slice(&desc.typ.to_le_bytes());
+    buf.extend_from_slice(&[0; 4]); // padding
+    buf.extend_from_slice(&desc.phys_start.to_le_bytes());
+    buf.extend_from_slice(&desc.virt_start.to_le_bytes());
+    buf.extend_from_slice(&desc.num_pages.to_le_bytes());
+    buf.extend_from_slice(&desc.attribute.to_le_bytes());
+    buf.resize(at + descriptor_size as usize, 0);
+}
+
+/// Iterator over a GetMemoryMap() buffer with the firmware's stride.
+/// Yields Err at most once, then stops.
+#[derive(Clone, Debug)]
+pub struct UefiMmapIter<'a> {
+    descriptors: TableBlob<'a>,
+    descriptor_size: u32,
+}
+
+impl<'a> UefiMmapIter<'a> {
+    /// `buf` is the map (MemoryMapSize bytes), `descriptor_size` what
+    /// GetMemoryMap() reported.
+    pub fn new(buf: &'a [u8], descriptor_size: u32) -> Result<Self, UefiError> {
+        if descriptor_size < DESCRIPTOR_SIZE {
+            return Err(UefiError::DescriptorSizeTooSmall { descriptor_size });
+        }
+        Ok(UefiMmapIter {
+            descriptors: TableBlob::new(buf),
+            descriptor_size,
+        })
+    }
+
+    /// [`new`](Self::new) over the MemoryMapSize bytes at `ptr`.
+    ///
+    /// # Safety
+    /// See [`TableBlob::from_raw_parts`].
+    pub unsafe fn from_raw_parts(
+        ptr: *const u8,
+        len: usize,
+        descriptor_size: u32,
+    ) -> Result<Self, UefiError> {
+        Self::new(
+            TableBlob::from_raw_parts(ptr, len).remaining(),
+            descriptor_size,
+        )
+    }
+}
+
+impl<'a> Iterator for UefiMmapIter<'a> {
+    type Item = Result<UefiDescriptor, UefiError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.descriptors.is_exhausted() {
+            return None;
+        }
+        match read_one_uefi(self.descriptors.remaining(), self.descriptor_size) {
+            Ok((desc, consumed)) => {
+                debug_assert!(consumed >= DESCRIPTOR_SIZE as usize);
+                // consumed fit in remaining(), so this cannot fail.
+                let _ = self.descriptors.advance(consumed);
+                Some(Ok(desc))
+            }
+            Err(e) => {
+                self.descriptors.finish();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::sanitize;
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn push_desc(buf: &mut Vec<u8>, stride: u32, typ: u32, start: u64, pages: u64, attr: u64) {
+        let at = buf.len();
+        buf.extend_from_slice(&typ.to_le_bytes());
+        buf.extend_from_slice(&[0; 4]);
+        buf.extend_from_slice(&start.to_le_bytes());
+        buf.extend_from_slice(&0u64.to_le_bytes());
+        buf.extend_from_slice(&pages.to_le_bytes());
+        buf.extend_from_slice(&attr.to_le_bytes());
+        buf.resize(at + stride as usize, 0xCC);
+    }
+
+    #[test]
+    fn walks_with_firmware_stride() {
+        init();
+        let mut buf = Vec::new();
+        push_desc(
+            &mut buf,
+            48,
+            EFI_CONVENTIONAL_MEMORY,
+            0,
+            0x9F,
+            EFI_MEMORY_WB,
+        );
+        push_desc(
+            &mut buf,
+            48,
+            EFI_RUNTIME_SERVICES_DATA,
+            0x9F000,
+            1,
+            EFI_MEMORY_RUNTIME,
+        );
+        push_desc(
+            &mut buf,
+            48,
+            EFI_BOOT_SERVICES_DATA,
+            0x10_0000,
+            0x100,
+            EFI_MEMORY_WB,
+        );
+
+        let descs: Vec<UefiDescriptor> = UefiMmapIter::new(&buf, 48)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        pretty_assertions::assert_eq!(descs.len(), 3);
+        pretty_assertions::assert_eq!(descs[1].attribute, EFI_MEMORY_RUNTIME);
+
+        let regions: Vec<MemRegion> = descs
+            .iter()
+            .map(|d| sanitize(d.to_raw().unwrap()).unwrap())
+            .collect();
+        pretty_assertions::assert_eq!(
+            regions,
+            vec![
+                MemRegion {
+                    start: 0,
+                    len: 0x9_F000,
+                    kind: kind::USABLE
+                },
+                MemRegion {
+                    start: 0x9_F000,
+                    len: 0x1000,
+                    kind: kind::RESERVED
+                },
+                MemRegion {
+                    start: 0x10_0000,
+                    len: 0x10_0000,
+                    kind: kind::USABLE
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn push_descriptor_roundtrips_at_any_stride() {
+        let d = UefiDescriptor {
+            typ: EFI_RUNTIME_SERVICES_CODE,
+            phys_start: 0x7F00_0000,
+            virt_start: 0xFFFF_FFFF_0000_0000,
+            num_pages: 16,
+            attribute: EFI_MEMORY_RUNTIME | EFI_MEMORY_WB,
+        };
+        for stride in [DESCRIPTOR_SIZE, 48, 56] {
+            let mut buf = Vec::new();
+            push_descriptor(&mut buf, d, stride);
+            push_descriptor(&mut buf, d, stride);
+            pretty_assertions::assert_eq!(buf.len(), 2 * stride as usize);
+            let got: Vec<UefiDescriptor> = UefiMmapIter::new(&buf, stride)
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+            pretty_assertions::assert_eq!(got, vec![d, d]);
+        }
+    }
+
+    #[test]
+    fn type_mapping() {
+        let k = |typ| efi_type_to_kind(typ, 0);
+        pretty_assertions::assert_eq!(k(EFI_LOADER_CODE), kind::USABLE);
+        pretty_assertions::assert_eq!(k(EFI_RUNTIME_SERVICES_CODE), kind::RESERVED);
+        pretty_assertions::assert_eq!(k(EFI_UNUSABLE_MEMORY), kind::BAD_RAM);
+        pretty_assertions::assert_eq!(k(EFI_ACPI_RECLAIM_MEMORY), kind::ACPI_RECLAIMABLE);
+        pretty_assertions::assert_eq!(k(EFI_ACPI_MEMORY_NVS), kind::ACPI_NVS);
+        pretty_assertions::assert_eq!(k(EFI_MEMORY_MAPPED_IO), kind::RESERVED);
+        pretty_assertions::assert_eq!(k(EFI_PERSISTENT_MEMORY), kind::PERSISTENT);
+        pretty_assertions::assert_eq!(k(EFI_UNACCEPTED_MEMORY), kind::RESERVED);
+        pretty_assertions::assert_eq!(k(0x7000_0000), kind::RESERVED);
+        pretty_assertions::assert_eq!(
+            efi_type_to_kind(EFI_CONVENTIONAL_MEMORY, kind::EFI_MEMORY_SP | EFI_MEMORY_WB),
+            kind::SOFT_RESERVED
+        );
+    }
+
+    #[test]
+    fn page_count_overflow_has_no_region() {
+        let d = UefiDescriptor {
+            typ: EFI_CONVENTIONAL_MEMORY,
+            phys_start: 0,
+            virt_start: 0,
+            num_pages: u64::MAX / 2,
+            attribute: 0,
+        };
+        pretty_assertions::assert_eq!(d.region(), None);
+        pretty_assertions::assert_eq!(d.to_raw(), None);
+    }
+
+    #[test]
+    fn stride_errors() {
+        pretty_assertions::assert_eq!(
+            UefiMmapIter::new(&[], 32).err(),
+            Some(UefiError::DescriptorSizeTooSmall {
+                descriptor_size: 32
+            })
+        );
+        let mut it = UefiMmapIter::new(&[0; 50], 48).unwrap();
+        assert!(it.next().unwrap().is_ok());
+        pretty_assertions::assert_eq!(
+            it.next(),
+            Some(Err(UefiError::TruncatedDescriptor {
+                needed: 48,
+                have: 2
+            }))
+        );
+        assert!(it.next().is_none());
+    }
+}
@@ -0,0 +1,1903 @@
+// region.rs
+//
+// Region algebra: turning whatever list of claims firmware handed you
+// into one canonical map.
+//
+// Canonical means:
+//   - sorted by start
+//   - no two regions overlap (the more restrictive kind wins)
+//   - adjacent regions of the same kind are merged
+//   - usable regions are frame-aligned (partial frames are not usable)
+//
+// A kernel should only ever build allocators from a canonical map.
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::kind;
+use crate::raw::MemRegion;
+#[cfg(feature = "alloc")]
+use crate::rejection::{RegionRejection, RejectionReason};
+
+/// Knobs for [`canonicalize_with`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CanonicalizeOptions {
+    /// Usable regions are shrunk inward to this alignment (power of two).
+    pub align: u64,
+    /// Usable regions smaller than this after alignment are dropped.
+    pub min_usable_size: u64,
+}
+
+impl Default for CanonicalizeOptions {
+    fn default() -> Self {
+        CanonicalizeOptions {
+            align: 4096,
+            min_usable_size: 0,
+        }
+    }
+}
+
+/// Where bytes went during canonicalization.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CanonicalStats {
+    /// Usable bytes lost shrinking regions to `align`.
+    pub alignment_trimmed_bytes: u64,
+    /// Usable regions dropped for being below `min_usable_size`.
+    pub slivers_dropped: usize,
+    /// Bytes in those dropped regions (after alignment).
+    pub sliver_bytes_dropped: u64,
+}
+
+/// Canonicalize with default options (4 KiB alignment, keep every sliver).
+#[cfg(feature = "alloc")]
+pub fn canonicalize(regions: &[MemRegion]) -> Vec<MemRegion> {
+    canonicalize_with(regions, &CanonicalizeOptions::default()).0
+}
+
+/// Canonicalize `regions` (see the top of this file for what that means).
+///
+/// Panics if `opts.align` is not a power of two.
+#[cfg(feature = "alloc")]
+pub fn canonicalize_with(
+    regions: &[MemRegion],
+    opts: &CanonicalizeOptions,
+) -> (Vec<MemRegion>, CanonicalStats) {
+    canonicalize_inner(regions, opts, None)
+}
+
+/// Like [`canonicalize_with`], but also report every piece of input that
+/// did not make it: overlap losers and dropped slivers.
+///
+/// Panics if `opts.align` is not a power of two.
+#[cfg(feature = "alloc")]
+pub fn canonicalize_with_rejections(
+    regions: &[MemRegion],
+    opts: &CanonicalizeOptions,
+) -> (Vec<MemRegion>, CanonicalStats, Vec<RegionRejection>) {
+    let mut rejected = Vec::new();
+    let (out, stats) = canonicalize_inner(regions, opts, Some(&mut rejected));
+    (out, stats, rejected)
+}
+
+#[cfg(feature = "alloc")]
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(regions = regions.len()))
+)]
+fn canonicalize_inner(
+    regions: &[MemRegion],
+    opts: &CanonicalizeOptions,
+    mut rejected: Option<&mut Vec<RegionRejection>>,
+) -> (Vec<MemRegion>, CanonicalStats) {
+    assert!(
+        opts.align.is_power_of_two(),
+        "canonicalize align must be a power of two"
+    );
+    let mut stats = CanonicalStats::default();
+
+    // Every start/end is a point where the winning kind can change.
+    let mut points: Vec<u64> = Vec::new();
+    for r in regions.iter().filter(|r| r.len > 0) {
+        points.push(r.start);
+        points.push(r.end());
+    }
+    points.sort_unstable();
+    points.dedup();
+
+    let mut merged: Vec<MemRegion> = Vec::new();
+    for w in points.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        let winner = regions
+            .iter()
+            .filter(|r| r.len > 0 && r.start <= a && r.end() >= b)
+            .max_by_key(|r| kind::precedence(r.kind));
+        let Some(winner) = winner else {
+            continue;
+        };
+
+        if let Some(rejected) = rejected.as_deref_mut() {
+            for loser in regions
+                .iter()
+                .filter(|r| r.len > 0 && r.start <= a && r.end() >= b && r.kind != winner.kind)
+            {
+                reject(
+                    rejected,
+                    a,
+                    b - a,
+                    loser.kind,
+                    RejectionReason::OverlapLoser,
+                );
+            }
+        }
+
+        match merged.last_mut() {
+            Some(last) if last.end() == a && last.kind == winner.kind => last.len += b - a,
+            _ => merged.push(MemRegion {
+                start: a,
+                len: b - a,
+                kind: winner.kind,
+            }),
+        }
+    }
+
+    let mut out = Vec::with_capacity(merged.len());
+    for r in merged {
+        if r.kind != kind::USABLE {
+            out.push(r);
+            continue;
+        }
+
+        let mask = opts.align - 1;
+        let start = match r.start.checked_add(mask) {
+            Some(v) => v & !mask,
+            None => r.end(),
+        };
+        let end = r.end() & !mask;
+        let aligned_len = end.saturating_sub(start);
+        stats.alignment_trimmed_bytes += r.len - aligned_len;
+
+        if aligned_len == 0 {
+            continue;
+        }
+        if aligned_len < opts.min_usable_size {
+            stats.slivers_dropped += 1;
+            stats.sliver_bytes_dropped += aligned_len;
+            if let Some(rejected) = rejected.as_deref_mut() {
+                reject(
+                    rejected,
+                    start,
+                    aligned_len,
+                    r.kind,
+                    RejectionReason::BelowMinSize,
+                );
+            }
+            continue;
+        }
+        out.push(MemRegion {
+            start,
+            len: aligned_len,
+            kind: kind::USABLE,
+        });
+    }
+
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        regions = out.len(),
+        trimmed = stats.alignment_trimmed_bytes,
+        slivers = stats.slivers_dropped,
+        "canonicalized"
+    );
+    (out, stats)
+}
+
+// ============================================================
+// IN-PLACE NORMALIZE
+// ============================================================
+//
+// canonicalize without the allocation and without the alignment: sort,
+// resolve overlaps by precedence, merge same-kind neighbours. This is
+// what a kernel can run on the firmware's own buffer before it has a
+// heap.
+//
+// Resolving an overlap can split a region in two (a reserved hole in the
+// middle of usable RAM), so the result can have more entries than the
+// input. Entries that get dropped (empty ones, merged ones) make room
+// for that. If there is none left, the piece that does not fit is left
+// out: a gap is never usable, so that alone only wastes memory. But the
+// piece still outranks whatever unread input overlaps it, so that input
+// is clipped back to outside the piece first. Otherwise a usable region
+// further on would surface inside what was reserved.
+
+/// Normalize `regions` in place; the result is the first `n` entries,
+/// where `n` is the return value. The rest of the slice is garbage.
+///
+/// Sorted by start, non-overlapping, adjacent same-kind regions merged.
+/// On overlap the higher precedence kind wins (bad RAM > reserved > ACPI
+/// NVS > ACPI reclaimable > usable); between different kinds of equal
+/// precedence the one that sorts first keeps the overlap. Regions that
+/// would run past the top of the address space are cut off there.
+pub fn normalize(regions: &mut [MemRegion]) -> usize {
+    // Pack the non-empty regions at the end, so the space freed by empty
+    // ones sits between the output (front) and the unread input.
+    let mut i = regions.len();
+    for j in (0..regions.len()).rev() {
+        let mut r = regions[j];
+        if r.len == 0 {
+            continue;
+        }
+        r.len = r.end() - r.start;
+        i -= 1;
+        regions[i] = r;
+    }
+    regions[i..].sort_unstable();
+
+    // Output is regions[..w], unread input regions[i..], w <= i.
+    let mut w = 0usize;
+    while i < regions.len() {
+        let r = regions[i];
+        i += 1;
+        if r.len == 0 {
+            continue;
+        }
+        let Some(last) = w.checked_sub(1).map(|l| regions[l]) else {
+            regions[0] = r;
+            w = 1;
+            continue;
+        };
+
+        if r.start >= last.end() || r.kind == last.kind {
+            push_merged(regions, &mut w, r);
+        } else if kind::precedence(r.kind) > kind::precedence(last.kind) {
+            // r takes the overlap; last keeps what is left on either side.
+            let tail = MemRegion {
+                start: r.end(),
+                len: last.end().saturating_sub(r.end()),
+                kind: last.kind,
+            };
+            w -= 1;
+            if r.start > last.start {
+                regions[w].len = r.start - last.start;
+                w += 1;
+            }
+            push_merged(regions, &mut w, r);
+            if tail.len > 0 && w < i {
+                i -= 1;
+                put_back(regions, i, tail);
+            } else if tail.len > 0 {
+                clip_under(regions, i, tail);
+            }
+        } else if r.end() > last.end() {
+            // last keeps the overlap; the rest of r goes back in line.
+            let rest = MemRegion {
+                start: last.end(),
+                len: r.end() - last.end(),
+                kind: r.kind,
+            };
+            i -= 1;
+            put_back(regions, i, rest);
+        }
+    }
+    w
+}
+
+// Append r to the output, merging with the last entry when they touch or
+// (same kind) overlap.
+fn push_merged(regions: &mut [MemRegion], w: &mut usize, r: MemRegion) {
+    if let Some(last) = w.checked_sub(1).map(|l| &mut regions[l]) {
+        if last.kind == r.kind && r.start <= last.end() {
+            last.len = last.end().max(r.end()) - last.start;
+            return;
+        }
+    }
+    regions[*w] = r;
+    *w += 1;
+}
+
+// Store r at `at` (a free slot just before the unread input) and move it
+// forward until the unread input is sorted again.
+fn put_back(regions: &mut [MemRegion], at: usize, r: MemRegion) {
+    let mut k = at;
+    while k + 1 < regions.len() && regions[k + 1].start < r.start {
+        regions[k] = regions[k + 1];
+        k += 1;
+    }
+    regions[k] = r;
+}
+
+// `dropped` did not fit back into the unread input regions[i..]. Cut
+// every unread region it outranks back to outside it, keeping the unread
+// input sorted. A region it covers entirely is left empty; a region
+// sticking out on both sides loses everything from `dropped` on, and
+// what it loses past `dropped` is dropped in turn, since it may have
+// been covering something of lower precedence still. Precedence goes
+// down with every level, so this stops.
+fn clip_under(regions: &mut [MemRegion], i: usize, dropped: MemRegion) {
+    for k in (i..regions.len()).rev() {
+        let u = regions[k];
+        if kind::precedence(u.kind) >= kind::precedence(dropped.kind)
+            || u.start >= dropped.end()
+            || u.end() <= dropped.start
+        {
+            continue;
+        }
+        if u.start < dropped.start {
+            regions[k].len = dropped.start - u.start;
+            if u.end() > dropped.end() {
+                let lost = MemRegion {
+                    start: dropped.end(),
+                    len: u.end() - dropped.end(),
+                    kind: u.kind,
+                };
+                clip_under(regions, i, lost);
+            }
+        } else {
+            let start = dropped.end();
+            let clipped = MemRegion {
+                start,
+                len: u.end().saturating_sub(start),
+                kind: u.kind,
+            };
+            put_back(regions, k, clipped);
+        }
+    }
+}
+
+/// [`normalize`] on a Vec, which always has room for every split.
+#[cfg(feature = "alloc")]
+pub fn normalize_vec(regions: &mut Vec<MemRegion>) {
+    // n regions have at most 2n boundaries, so at most 2n - 1 pieces.
+    let n = regions.len();
+    regions.resize(
+        2 * n,
+        MemRegion {
+            start: 0,
+            len: 0,
+            kind: 0,
+        },
+    );
+    let len = normalize(regions);
+    regions.truncate(len);
+}
+
+// ============================================================
+// CARVE-OUTS
+// ============================================================
+//
+// Before frames go to an allocator, the kernel image, the boot info and
+// the initrd have to come out of usable memory, or the allocator hands
+// them out as free. Only usable regions are touched: carving a range out
+// of reserved memory changes nothing.
+
+/// Error from [`carve_out`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CarveOutError {
+    /// Splitting regions needs `needed` entries but the slice has fewer.
+    NoRoom { needed: usize },
+}
+
+/// Remove `range` from every usable region in `regions[..len]`. Returns
+/// the new length.
+///
+/// A region the range lands in the middle of is split in two, which
+/// takes one of the spare entries in `regions[len..]`. Order is kept.
+/// On error nothing has been changed. An empty or inverted range
+/// (`start >= end`) carves nothing.
+pub fn carve_out(
+    regions: &mut [MemRegion],
+    len: usize,
+    range: Range<u64>,
+) -> Result<usize, CarveOutError> {
+    if range.is_empty() {
+        return Ok(len);
+    }
+    let hit =
+        |r: &MemRegion| r.kind == kind::USABLE && r.start < range.end && range.start < r.end();
+    let splits = regions[..len]
+        .iter()
+        .filter(|r| hit(r) && r.start < range.start && range.end < r.end())
+        .count();
+    let needed = len + splits;
+    if needed > regions.len() {
+        return Err(CarveOutError::NoRoom { needed });
+    }
+
+    // Walk backwards writing from the end, so a split never overwrites a
+    // region that has not been read yet.
+    let mut w = needed;
+    for i in (0..len).rev() {
+        let r = regions[i];
+        if !hit(&r) {
+            w -= 1;
+            regions[w] = r;
+            continue;
+        }
+        if range.end < r.end() {
+            w -= 1;
+            regions[w] = MemRegion {
+                start: range.end,
+                len: r.end() - range.end,
+                kind: r.kind,
+            };
+        }
+        if r.start < range.start {
+            w -= 1;
+            regions[w] = MemRegion {
+                start: r.start,
+                len: range.start - r.start,
+                kind: r.kind,
+            };
+        }
+    }
+    regions.copy_within(w..needed, 0);
+    Ok(needed - w)
+}
+
+/// [`carve_out`] on a Vec, growing it when a region has to be split.
+#[cfg(feature = "alloc")]
+pub fn carve_out_vec(regions: &mut Vec<MemRegion>, range: Range<u64>) {
+    if range.is_empty() {
+        return;
+    }
+    let len = regions.len();
+    // At most one split per region.
+    regions.resize(
+        2 * len,
+        MemRegion {
+            start: 0,
+            len: 0,
+            kind: 0,
+        },
+    );
+    let len = carve_out(regions, len, range).expect("room for every split");
+    regions.truncate(len);
+}
+
+/// Turn ACPI reclaimable memory into usable memory, in place. Call it
+/// once the ACPI tables have been parsed or copied out: until then that
+/// memory holds them. Returns how many regions changed; normalize
+/// afterwards to merge them with their usable neighbours.
+pub fn reclaim_acpi(regions: &mut [MemRegion]) -> usize {
+    let mut n = 0;
+    for r in regions
+        .iter_mut()
+        .filter(|r| r.kind == kind::ACPI_RECLAIMABLE)
+    {
+        r.kind = kind::USABLE;
+        n += 1;
+    }
+    n
+}
+
+/// End of real-mode memory: the IVT, BDA, EBDA, VGA memory and option
+/// ROMs all live below here.
+pub const LOW_MEMORY_END: u64 = 0x10_0000;
+
+/// Mark everything below 1 MiB reserved, whatever the firmware said about
+/// it. Firmware maps routinely call the EBDA or bits of the BIOS area
+/// usable; a young kernel is better off never touching any of it. Bad RAM
+/// down there stays bad RAM. `regions` comes back normalized.
+#[cfg(feature = "alloc")]
+pub fn reserve_low_memory(regions: &mut Vec<MemRegion>) {
+    regions.push(MemRegion {
+        start: 0,
+        len: LOW_MEMORY_END,
+        kind: kind::RESERVED,
+    });
+    normalize_vec(regions);
+}
+
+/// Like Linux's `mem=`: cut every region in `regions[..len]` off at
+/// `limit`, whatever its kind. Regions straddling it are truncated, ones
+/// entirely above it dropped. Returns the new length; order is kept.
+pub fn clamp_max_addr(regions: &mut [MemRegion], len: usize, limit: u64) -> usize {
+    let mut w = 0;
+    for i in 0..len {
+        let r = regions[i];
+        if r.start >= limit {
+            continue;
+        }
+        regions[w] = MemRegion {
+            len: r.end().min(limit) - r.start,
+            ..r
+        };
+        w += 1;
+    }
+    w
+}
+
+/// Special-purpose memory (soft reserved / EFI_SP, persistent, CXL) in
+/// `regions`. These never show up as usable, so frame iterators and
+/// allocators skip them; this is how a driver that owns them finds them.
+pub fn special_purpose_regions(regions: &[MemRegion]) -> impl Iterator<Item = &MemRegion> {
+    regions
+        .iter()
+        .filter(|r| r.len > 0 && kind::is_special_purpose(r.kind))
+}
+
+// ============================================================
+// PLACEMENT QUERIES
+// ============================================================
+//
+// "Where can I put a 64 KiB, 16-byte aligned buffer below 4 GiB?"
+// Every placement helper is some variation of that question, so ask it
+// once: describe the constraints, get candidate sub-regions back.
+
+/// What a candidate sub-region must satisfy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Constraints {
+    /// Candidate start must be a multiple of this (power of two).
+    pub align: u64,
+    /// Every byte of the candidate lies below this address (exclusive).
+    pub limit: u64,
+    /// Candidate must be at least this many bytes after alignment.
+    pub min_size: u64,
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Constraints {
+            align: 1,
+            limit: u64::MAX,
+            min_size: 0,
+        }
+    }
+}
+
+impl Constraints {
+    /// Candidates in address order over a canonical map.
+    ///
+    /// Panics if `align` is not a power of two.
+    pub fn candidates<'a>(&self, regions: &'a [MemRegion]) -> Candidates<'a> {
+        assert!(
+            self.align.is_power_of_two(),
+            "constraint align must be a power of two"
+        );
+        Candidates {
+            regions,
+            next: 0,
+            constraints: *self,
+        }
+    }
+
+    /// Lowest address satisfying the constraints, if any. Panics like
+    /// [`candidates`](Self::candidates).
+    pub fn first_fit(&self, regions: &[MemRegion]) -> Option<u64> {
+        self.candidates(regions).next().map(|r| r.start)
+    }
+}
+
+/// Iterator returned by [`Constraints::candidates`].
+///
+/// Yields the usable part of each usable region that satisfies the
+/// constraints: start aligned up, end clipped to `limit`. Expects a
+/// canonical map (sorted, non-overlapping), so output is in address order.
+#[derive(Clone, Debug)]
+pub struct Candidates<'a> {
+    regions: &'a [MemRegion],
+    next: usize,
+    constraints: Constraints,
+}
+
+impl<'a> Iterator for Candidates<'a> {
+    type Item = MemRegion;
+
+    fn next(&mut self) -> Option<MemRegion> {
+        let c = self.constraints;
+        while let Some(r) = self.regions.get(self.next) {
+            self.next += 1;
+            if r.kind != kind::USABLE || r.len == 0 {
+                continue;
+            }
+            let mask = c.align - 1;
+            let Some(start) = r.start.checked_add(mask).map(|v| v & !mask) else {
+                continue;
+            };
+            let end = r.end().min(c.limit);
+            if start >= end || end - start < c.min_size {
+                continue;
+            }
+            return Some(MemRegion {
+                start,
+                len: end - start,
+                kind: kind::USABLE,
+            });
+        }
+        None
+    }
+}
+
+// ============================================================
+// INTERSECTION / MMIO CONFLICTS
+// ============================================================
+
+/// Part of `region` inside `range`, keeping its kind.
+pub fn intersect(region: MemRegion, range: &Range<u64>) -> Option<MemRegion> {
+    let start = region.start.max(range.start);
+    let end = region.end().min(range.end);
+    if start >= end {
+        return None;
+    }
+    Some(MemRegion {
+        start,
+        len: end - start,
+        kind: region.kind,
+    })
+}
+
+/// A device window the firmware map claims is usable RAM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MmioConflict {
+    /// Index into the `mmio` slice passed to [`check_mmio_conflicts`].
+    pub mmio_index: usize,
+    /// The usable bytes that overlap the device window.
+    pub overlap: MemRegion,
+}
+
+/// Report every part of `mmio` that `map` marks usable.
+///
+/// This is a real firmware bug class: RAM entries that cover a PCI BAR
+/// or the local APIC page. Handing those frames out means "allocating"
+/// device registers. Feed the overlaps back in as reserved overrides
+/// (see `compose`) before building allocators.
+pub fn check_mmio_conflicts<'a>(
+    map: &'a [MemRegion],
+    mmio: &'a [Range<u64>],
+) -> impl Iterator<Item = MmioConflict> + 'a {
+    mmio.iter()
+        .enumerate()
+        .flat_map(move |(mmio_index, range)| {
+            map.iter()
+                .filter(|r| r.kind == kind::USABLE)
+                .filter_map(move |r| intersect(*r, range))
+                .map(move |overlap| MmioConflict {
+                    mmio_index,
+                    overlap,
+                })
+        })
+}
+
+// ============================================================
+// REGION SETS
+// ============================================================
+//
+// "Usable memory below 4 GiB", "RAM not covered by the kernel's
+// sections": set algebra over addresses. A RegionSet is just which bytes
+// are in, kinds are left behind; pick them with `of_kind` going in and
+// put one back with `regions` coming out.
+
+/// A set of physical addresses, kept as sorted, disjoint, non-touching
+/// ranges.
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegionSet {
+    ranges: Vec<Range<u64>>,
+}
+
+#[cfg(feature = "alloc")]
+impl RegionSet {
+    pub fn new() -> Self {
+        RegionSet::default()
+    }
+
+    /// Any ranges, in any order, overlapping or not.
+    pub fn from_ranges(ranges: impl IntoIterator<Item = Range<u64>>) -> Self {
+        let mut input: Vec<Range<u64>> = ranges.into_iter().filter(|r| r.start < r.end).collect();
+        input.sort_unstable_by_key(|r| r.start);
+        let mut ranges: Vec<Range<u64>> = Vec::with_capacity(input.len());
+        for r in input {
+            match ranges.last_mut() {
+                Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+                _ => ranges.push(r),
+            }
+        }
+        RegionSet { ranges }
+    }
+
+    /// Every byte of `regions`, whatever its kind.
+    pub fn from_regions(regions: &[MemRegion]) -> Self {
+        Self::from_ranges(regions.iter().map(|r| r.start..r.end()))
+    }
+
+    /// Bytes of `regions` that have kind `kind`.
+    pub fn of_kind(regions: &[MemRegion], kind: u32) -> Self {
+        Self::from_ranges(
+            regions
+                .iter()
+                .filter(|r| r.kind == kind)
+                .map(|r| r.start..r.end()),
+        )
+    }
+
+    pub fn ranges(&self) -> &[Range<u64>] {
+        &self.ranges
+    }
+
+    /// The set as regions of one kind.
+    pub fn regions(&self, kind: u32) -> impl Iterator<Item = MemRegion> + '_ {
+        self.ranges.iter().map(move |r| MemRegion {
+            start: r.start,
+            len: r.end - r.start,
+            kind,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Bytes in the set.
+    pub fn total_len(&self) -> u64 {
+        self.ranges.iter().map(|r| r.end - r.start).sum()
+    }
+
+    pub fn contains(&self, addr: u64) -> bool {
+        let i = self.ranges.partition_point(|r| r.end <= addr);
+        self.ranges.get(i).is_some_and(|r| r.start <= addr)
+    }
+
+    /// Bytes in either set.
+    pub fn union(&self, other: &RegionSet) -> RegionSet {
+        Self::from_ranges(self.ranges.iter().chain(&other.ranges).cloned())
+    }
+
+    /// Bytes in both sets.
+    pub fn intersect(&self, other: &RegionSet) -> RegionSet {
+        let (mut a, mut b) = (
+            self.ranges.iter().peekable(),
+            other.ranges.iter().peekable(),
+        );
+        let mut ranges = Vec::new();
+        while let (Some(x), Some(y)) = (a.peek(), b.peek()) {
+            let (start, end) = (x.start.max(y.start), x.end.min(y.end));
+            if start < end {
+                ranges.push(start..end);
+            }
+            // Drop whichever ends first; it cannot meet anything further on.
+            if x.end <= y.end {
+                a.next();
+            } else {
+                b.next();
+            }
+        }
+        RegionSet { ranges }
+    }
+
+    /// Bytes in `self` but not in `other`.
+    pub fn difference(&self, other: &RegionSet) -> RegionSet {
+        let mut ranges = Vec::new();
+        let mut cut = other.ranges.iter().peekable();
+        for r in &self.ranges {
+            let mut start = r.start;
+            while let Some(c) = cut.peek() {
+                if c.end <= start {
+                    cut.next();
+                    continue;
+                }
+                if c.start >= r.end {
+                    break;
+                }
+                if c.start > start {
+                    ranges.push(start..c.start);
+                }
+                start = c.end;
+                if c.end > r.end {
+                    // Still needed for the next range of self.
+                    break;
+                }
+                cut.next();
+            }
+            if start < r.end {
+                ranges.push(start..r.end);
+            }
+        }
+        RegionSet { ranges }
+    }
+}
+
+// ============================================================
+// COVERAGE
+// ============================================================
+//
+// "Is the memory my kernel was loaded into actually there?"
+//
+// Loading into a hole does not fault at load time; it faults much later,
+// somewhere unrelated, after reads came back as all-ones. Asking the map
+// up front turns that into one clear error.
+
+/// One piece of a queried range: backed by a region (`kind`) or a hole.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CoverageSpan {
+    pub start: u64,
+    pub len: u64,
+    /// Kind of the backing region, `None` for a hole.
+    pub kind: Option<u32>,
+}
+
+/// Walk `range` over a canonical map, in address order, splitting it
+/// into backed spans and holes. Together the spans cover `range` exactly.
+pub fn coverage<'a>(map: &'a [MemRegion], range: &Range<u64>) -> Coverage<'a> {
+    Coverage {
+        map,
+        next: 0,
+        cursor: range.start,
+        end: range.end,
+    }
+}
+
+/// `Ok` if every byte of `range` is backed by some region (usable or not),
+/// otherwise the first hole.
+pub fn assert_covered(map: &[MemRegion], range: &Range<u64>) -> Result<(), Range<u64>> {
+    match coverage(map, range).find(|s| s.kind.is_none()) {
+        Some(hole) => Err(hole.start..hole.start + hole.len),
+        None => Ok(()),
+    }
+}
+
+/// The region of the sorted, non-overlapping `map` that contains `addr`.
+/// Binary search, so fine to call per DMA buffer.
+pub fn region_containing(map: &[MemRegion], addr: u64) -> Option<&MemRegion> {
+    let i = map.partition_point(|r| r.end() <= addr);
+    map.get(i).filter(|r| r.start <= addr && r.len > 0)
+}
+
+/// Every byte of `start..start + len` is usable in the sorted,
+/// non-overlapping `map`, possibly across several touching regions. An
+/// empty or wrapping range is not: there is nothing to check it against.
+pub fn is_range_usable(map: &[MemRegion], start: u64, len: u64) -> bool {
+    let Some(end) = start.checked_add(len).filter(|_| len > 0) else {
+        return false;
+    };
+    let first = map.partition_point(|r| r.end() <= start);
+    let mut cursor = start;
+    for r in &map[first..] {
+        if r.start > cursor || r.kind != kind::USABLE {
+            return false;
+        }
+        cursor = r.end();
+        if cursor >= end {
+            return true;
+        }
+    }
+    false
+}
+
+/// Iterator returned by [`coverage`].
+#[derive(Clone, Debug)]
+pub struct Coverage<'a> {
+    map: &'a [MemRegion],
+    next: usize,
+    cursor: u64,
+    end: u64,
+}
+
+impl<'a> Iterator for Coverage<'a> {
+    type Item = CoverageSpan;
+
+    fn next(&mut self) -> Option<CoverageSpan> {
+        if self.cursor >= self.end {
+            return None;
+        }
+        // Skip regions entirely behind the cursor.
+        while let Some(r) = self.map.get(self.next) {
+            if r.len > 0 && r.end() > self.cursor {
+                break;
+            }
+            self.next += 1;
+        }
+
+        let (stop, kind) = match self.map.get(self.next) {
+            Some(r) if r.start <= self.cursor => (r.end().min(self.end), Some(r.kind)),
+            Some(r) => (r.start.min(self.end), None),
+            None => (self.end, None),
+        };
+        let span = CoverageSpan {
+            start: self.cursor,
+            len: stop - self.cursor,
+            kind,
+        };
+        self.cursor = stop;
+        Some(span)
+    }
+}
+
+/// Address ranges no region of the sorted, non-overlapping `map` covers,
+/// lowest first: firmware holes, the PCI hole below 4 GiB, other MMIO
+/// windows. Only holes below the end of the last region are reported;
+/// everything above it is implicitly a hole too.
+pub fn gaps(map: &[MemRegion]) -> Gaps<'_> {
+    let end = map.iter().map(|r| r.end()).max().unwrap_or(0);
+    Gaps(coverage(map, &(0..end)))
+}
+
+/// Iterator returned by [`gaps`].
+#[derive(Clone, Debug)]
+pub struct Gaps<'a>(Coverage<'a>);
+
+impl<'a> Iterator for Gaps<'a> {
+    type Item = Range<u64>;
+
+    fn next(&mut self) -> Option<Range<u64>> {
+        self.0
+            .by_ref()
+            .find(|s| s.kind.is_none())
+            .map(|s| s.start..s.start + s.len)
+    }
+}
+
+// ============================================================
+// STRIPING
+// ============================================================
+//
+// Splitting memory between N consumers (per-CPU pools, NUMA-ish tests)
+// works best when each one gets a slice of every region instead of one
+// consumer getting all of the first region:
+//
+//   regions:  A = [a0 a1 a2]   B = [b0]   C = [c0 c1]
+//   stripe:   a0 b0 c0 a1 c1 a2
+//
+// Chunks never straddle two regions, even adjacent ones; a tail shorter
+// than `chunk_size` is not yielded.
+
+/// Fixed-size chunks taken round-robin across `regions`, each keeping
+/// its region's kind. Filter the input first if only usable memory
+/// should be striped.
+///
+/// Panics if `chunk_size` is zero.
+pub fn stripe(regions: &[MemRegion], chunk_size: u64) -> Stripe<'_> {
+    assert!(chunk_size > 0, "stripe chunk size must be non-zero");
+    Stripe {
+        regions,
+        chunk_size,
+        round: 0,
+        next: 0,
+        yielded_this_round: false,
+    }
+}
+
+/// Iterator returned by [`stripe`].
+#[derive(Clone, Debug)]
+pub struct Stripe<'a> {
+    regions: &'a [MemRegion],
+    chunk_size: u64,
+    // Chunk index within each region for the current pass.
+    round: u64,
+    next: usize,
+    yielded_this_round: bool,
+}
+
+impl<'a> Iterator for Stripe<'a> {
+    type Item = MemRegion;
+
+    fn next(&mut self) -> Option<MemRegion> {
+        loop {
+            if self.next == self.regions.len() {
+                // A pass with nothing left in any region ends the stripe.
+                if !self.yielded_this_round {
+                    return None;
+                }
+                self.round += 1;
+                self.next = 0;
+                self.yielded_this_round = false;
+            }
+            let r = self.regions[self.next];
+            self.next += 1;
+            if r.len / self.chunk_size > self.round {
+                self.yielded_this_round = true;
+                return Some(MemRegion {
+                    start: r.start + self.round * self.chunk_size,
+                    len: self.chunk_size,
+                    kind: r.kind,
+                });
+            }
+        }
+    }
+}
+
+// ============================================================
+// DIFF
+// ============================================================
+//
+// "What did ExitBootServices change?" Compare two maps byte by byte and
+// report every range whose kind is not the same in both.
+
+/// One range that differs between two maps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegionChange {
+    /// Only in the new map.
+    Added(MemRegion),
+    /// Only in the old map.
+    Removed(MemRegion),
+    /// In both, with a different kind.
+    Retyped {
+        start: u64,
+        len: u64,
+        from: u32,
+        to: u32,
+    },
+}
+
+impl RegionChange {
+    /// The range this change covers.
+    pub fn range(&self) -> Range<u64> {
+        let (start, len) = match *self {
+            RegionChange::Added(r) | RegionChange::Removed(r) => (r.start, r.len),
+            RegionChange::Retyped { start, len, .. } => (start, len),
+        };
+        start..start + len
+    }
+}
+
+/// Changes from `old` to `new`, in address order. Both should be
+/// non-overlapping (normalized); where one is not, the first region
+/// covering an address counts. Adjacent ranges with the same change are
+/// reported as one.
+#[cfg(feature = "alloc")]
+pub fn diff(old: &[MemRegion], new: &[MemRegion]) -> impl Iterator<Item = RegionChange> {
+    let mut points: Vec<u64> = Vec::new();
+    for r in old.iter().chain(new).filter(|r| r.len > 0) {
+        points.push(r.start);
+        points.push(r.end());
+    }
+    points.sort_unstable();
+    points.dedup();
+
+    let kind_at = |map: &[MemRegion], a: u64, b: u64| {
+        map.iter()
+            .find(|r| r.len > 0 && r.start <= a && r.end() >= b)
+            .map(|r| r.kind)
+    };
+    let mut changes: Vec<RegionChange> = Vec::new();
+    for w in points.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        let change = match (kind_at(old, a, b), kind_at(new, a, b)) {
+            (None, Some(kind)) => RegionChange::Added(MemRegion {
+                start: a,
+                len: b - a,
+                kind,
+            }),
+            (Some(kind), None) => RegionChange::Removed(MemRegion {
+                start: a,
+                len: b - a,
+                kind,
+            }),
+            (Some(from), Some(to)) if from != to => RegionChange::Retyped {
+                start: a,
+                len: b - a,
+                from,
+                to,
+            },
+            _ => continue,
+        };
+        match (changes.last_mut(), change) {
+            (Some(RegionChange::Added(last)), RegionChange::Added(r))
+            | (Some(RegionChange::Removed(last)), RegionChange::Removed(r))
+                if last.kind == r.kind && last.end() == a =>
+            {
+                last.len += r.len
+            }
+            (
+                Some(RegionChange::Retyped {
+                    start,
+                    len,
+                    from,
+                    to,
+                }),
+                RegionChange::Retyped { from: f, to: t, .. },
+            ) if (*from, *to) == (f, t) && *start + *len == a => *len += b - a,
+            _ => changes.push(change),
+        }
+    }
+    changes.into_iter()
+}
+
+// Record a rejection, extending the previous one if it is the same
+// kind/reason and picks up exactly where it left off.
+#[cfg(feature = "alloc")]
+fn reject(
+    out: &mut Vec<RegionRejection>,
+    start: u64,
+    len: u64,
+    kind: u32,
+    reason: RejectionReason,
+) {
+    if let Some(last) = out.last_mut() {
+        if last.reason == reason && last.region.kind == kind && last.region.end() == start {
+            last.region.len += len;
+            return;
+        }
+    }
+    out.push(RegionRejection {
+        region: MemRegion { start, len, kind },
+        reason,
+    });
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    #[test]
+    fn sorts_and_merges_adjacent_same_kind() {
+        init();
+        let input = [
+            region(0x2000, 0x1000, 1),
+            region(0, 0x1000, 1),
+            region(0x1000, 0x1000, 1),
+        ];
+        pretty_assertions::assert_eq!(canonicalize(&input), vec![region(0, 0x3000, 1)]);
+    }
+
+    #[test]
+    fn normalize_in_place_splits_into_freed_slots() {
+        // The empty entry is the room the split needs.
+        let mut map = [
+            region(0x4000, 0x2000, 2),
+            region(0, 0x10000, 1),
+            region(0x20000, 0, 1),
+        ];
+        let n = normalize(&mut map);
+        pretty_assertions::assert_eq!(
+            map[..n],
+            [
+                region(0, 0x4000, 1),
+                region(0x4000, 0x2000, 2),
+                region(0x6000, 0xA000, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn normalize_without_room_drops_the_losing_tail() {
+        let mut map = [region(0, 0x10000, 1), region(0x4000, 0x2000, 2)];
+        let n = normalize(&mut map);
+        pretty_assertions::assert_eq!(map[..n], [region(0, 0x4000, 1), region(0x4000, 0x2000, 2)]);
+
+        let mut v = vec![region(0, 0x10000, 1), region(0x4000, 0x2000, 2)];
+        normalize_vec(&mut v);
+        pretty_assertions::assert_eq!(v.len(), 3);
+    }
+
+    #[test]
+    fn normalize_without_room_never_uncovers_usable() {
+        let mut map = [
+            region(0, 0x10000, 2),
+            region(0x1000, 0x1000, 5),
+            region(0x8000, 0x1000, 1),
+        ];
+        let n = normalize(&mut map);
+        pretty_assertions::assert_eq!(map[..n], [region(0, 0x1000, 2), region(0x1000, 0x1000, 5)]);
+
+        // Usable sticking out past the dropped reserved tail loses the rest.
+        let mut map = [
+            region(0, 0x10000, 2),
+            region(0x1000, 0x2000, 5),
+            region(0x2000, 0x20000, 1),
+        ];
+        let n = normalize(&mut map);
+        pretty_assertions::assert_eq!(map[..n], [region(0, 0x1000, 2), region(0x1000, 0x2000, 5)]);
+    }
+
+    #[test]
+    fn normalize_merges_and_clips_the_loser() {
+        let mut v = vec![
+            region(0x3000, 0x2000, 2),
+            region(0, 0x2000, 1),
+            region(0x1000, 0x3000, 1),
+            region(0x4800, 0x1000, 1),
+            region(u64::MAX - 0xFFF, 0x2000, 2),
+        ];
+        normalize_vec(&mut v);
+        pretty_assertions::assert_eq!(
+            v,
+            vec![
+                region(0, 0x3000, 1),
+                region(0x3000, 0x2000, 2),
+                region(0x5000, 0x800, 1),
+                region(u64::MAX - 0xFFF, 0xFFF, 2),
+            ]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn normalize_vec_matches_unaligned_canonicalize(
+            input in proptest::collection::vec((0u64..64, 0u64..16, 1u32..6), 0..24)
+        ) {
+            // Kinds 1..=5 all have distinct precedence, so ties cannot
+            // make the two disagree.
+            let input: Vec<MemRegion> = input
+                .iter()
+                .map(|&(s, l, k)| region(s * 0x1000, l * 0x1000, k))
+                .collect();
+            let opts = CanonicalizeOptions { align: 1, min_usable_size: 0 };
+            let mut v = input.clone();
+            normalize_vec(&mut v);
+            prop_assert_eq!(v, canonicalize_with(&input, &opts).0);
+        }
+
+        #[test]
+        fn normalize_without_room_only_loses_usable(
+            input in proptest::collection::vec((0u64..64, 0u64..16, 1u32..6), 0..24)
+        ) {
+            let input: Vec<MemRegion> = input
+                .iter()
+                .map(|&(s, l, k)| region(s * 0x1000, l * 0x1000, k))
+                .collect();
+            let opts = CanonicalizeOptions { align: 1, min_usable_size: 0 };
+            let canonical = canonicalize_with(&input, &opts).0;
+            let mut map = input.clone();
+            let n = normalize(&mut map);
+            let out = &map[..n];
+            prop_assert!(out.windows(2).all(|w| w[0].end() <= w[1].start));
+            for frame in 0..80u64 {
+                let addr = frame * 0x1000;
+                let got = region_containing(out, addr).map(|r| r.kind);
+                let want = region_containing(&canonical, addr).map(|r| r.kind);
+                prop_assert!(
+                    got.is_none() || got == want,
+                    "{:#x}: normalize says {:?}, canonicalize {:?}", addr, got, want
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn carve_out_splits_trims_and_removes() {
+        let mut map = [
+            region(0, 0x3000, 1),
+            region(0x3000, 0x1000, 2),
+            region(0x4000, 0x4000, 1),
+            region(0x8000, 0x1000, 1),
+            MemRegion {
+                start: 0,
+                len: 0,
+                kind: 0,
+            },
+        ];
+        // Kernel at 0x1000..0x5000: trims the first region, leaves the
+        // reserved one, trims the second usable one.
+        let n = carve_out(&mut map, 4, 0x1000..0x5000).unwrap();
+        pretty_assertions::assert_eq!(
+            map[..n],
+            [
+                region(0, 0x1000, 1),
+                region(0x3000, 0x1000, 2),
+                region(0x5000, 0x3000, 1),
+                region(0x8000, 0x1000, 1),
+            ]
+        );
+
+        // Initrd in the middle of a region takes the spare entry.
+        let n = carve_out(&mut map, n, 0x6000..0x7000).unwrap();
+        pretty_assertions::assert_eq!(
+            map[..n],
+            [
+                region(0, 0x1000, 1),
+                region(0x3000, 0x1000, 2),
+                region(0x5000, 0x1000, 1),
+                region(0x7000, 0x1000, 1),
+                region(0x8000, 0x1000, 1),
+            ]
+        );
+
+        // No spare left for another split; nothing changes.
+        let before = map;
+        pretty_assertions::assert_eq!(
+            carve_out(&mut map, n, 0x8400..0x8800),
+            Err(CarveOutError::NoRoom { needed: 6 })
+        );
+        pretty_assertions::assert_eq!(map, before);
+
+        // Covering a whole region removes it.
+        let n = carve_out(&mut map, n, 0..0x1000).unwrap();
+        pretty_assertions::assert_eq!(map[0], region(0x3000, 0x1000, 2));
+        pretty_assertions::assert_eq!(n, 4);
+    }
+
+    #[test]
+    fn carve_out_vec_grows() {
+        let mut v = vec![region(0, 0x10000, 1)];
+        carve_out_vec(&mut v, 0x4000..0x6000);
+        pretty_assertions::assert_eq!(v, vec![region(0, 0x4000, 1), region(0x6000, 0xA000, 1)]);
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn carve_out_of_empty_or_inverted_range_is_a_no_op() {
+        for range in [0x8000..0x2000, 0x4000..0x4000] {
+            let mut v = vec![region(0, 0x10000, 1)];
+            carve_out_vec(&mut v, range.clone());
+            pretty_assertions::assert_eq!(v, vec![region(0, 0x10000, 1)]);
+
+            let mut map = [region(0, 0x10000, 1)];
+            pretty_assertions::assert_eq!(carve_out(&mut map, 1, range), Ok(1));
+            pretty_assertions::assert_eq!(map, [region(0, 0x10000, 1)]);
+        }
+    }
+
+    #[test]
+    fn reclaim_acpi_retypes_only_reclaimable() {
+        let mut regions = [
+            region(0, 0x1000, 1),
+            region(0x1000, 0x1000, 3),
+            region(0x2000, 0x1000, 4),
+            region(0x3000, 0x1000, 3),
+        ];
+        pretty_assertions::assert_eq!(reclaim_acpi(&mut regions), 2);
+        pretty_assertions::assert_eq!(
+            regions,
+            [
+                region(0, 0x1000, 1),
+                region(0x1000, 0x1000, 1),
+                region(0x2000, 0x1000, 4),
+                region(0x3000, 0x1000, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn reserve_low_memory_covers_the_first_mib() {
+        let mut v = vec![
+            region(0, 0x9_FC00, 1),
+            region(0x9_FC00, 0x400, 2),
+            region(0x5000, 0x1000, 5),
+            region(0xF_0000, 0x20_0000, 1),
+        ];
+        reserve_low_memory(&mut v);
+        pretty_assertions::assert_eq!(
+            v,
+            vec![
+                region(0, 0x5000, 2),
+                region(0x5000, 0x1000, 5),
+                region(0x6000, 0xF_A000, 2),
+                region(0x10_0000, 0x1F_0000, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn clamp_max_addr_truncates_and_drops() {
+        let mut regions = [
+            region(0, 0x9F000, 1),
+            region(0xF_0000, 0x2_0000, 2),
+            region(0x10_0000, 0x1000, 1),
+            region(0x4000, 0x1000, 1),
+            region(0x20_0000, 0x1000, 2),
+        ];
+        let len = clamp_max_addr(&mut regions, 5, 0x10_0000);
+        pretty_assertions::assert_eq!(
+            &regions[..len],
+            &[
+                region(0, 0x9F000, 1),
+                region(0xF_0000, 0x1_0000, 2),
+                region(0x4000, 0x1000, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn region_set_usable_below_4g_minus_kernel() {
+        const GIB: u64 = 1 << 30;
+        let map = [
+            region(0, 0x9F000, 1),
+            region(0x9F000, 0x61000, 2),
+            region(0x10_0000, 6 * GIB, 1),
+        ];
+        let usable = RegionSet::of_kind(&map, 1);
+        let low = usable.intersect(&RegionSet::from_ranges(core::iter::once(0..4 * GIB)));
+        let kernel = RegionSet::from_ranges([0x20_0000..0x40_0000, 0x30_0000..0x50_0000]);
+        let free = low.difference(&kernel);
+
+        pretty_assertions::assert_eq!(
+            free.ranges(),
+            &[0..0x9F000, 0x10_0000..0x20_0000, 0x50_0000..4 * GIB]
+        );
+        assert!(free.contains(0x50_0000));
+        assert!(!free.contains(0x4F_FFFF));
+        pretty_assertions::assert_eq!(free.regions(1).next(), Some(region(0, 0x9F000, 1)));
+        let all = free.union(&kernel).union(&RegionSet::of_kind(&map, 2));
+        pretty_assertions::assert_eq!(all.ranges().len(), 1);
+        pretty_assertions::assert_eq!(all.ranges()[0], 0..4 * GIB);
+    }
+
+    proptest! {
+        #[test]
+        fn region_set_ops_match_per_address(
+            a in proptest::collection::vec((0u64..64, 0u64..16), 0..8),
+            b in proptest::collection::vec((0u64..64, 0u64..16), 0..8),
+        ) {
+            let a = RegionSet::from_ranges(a.iter().map(|&(s, l)| s..s + l));
+            let b = RegionSet::from_ranges(b.iter().map(|&(s, l)| s..s + l));
+            let (u, i, d) = (a.union(&b), a.intersect(&b), a.difference(&b));
+            for addr in 0..80 {
+                let (x, y) = (a.contains(addr), b.contains(addr));
+                prop_assert_eq!(u.contains(addr), x || y);
+                prop_assert_eq!(i.contains(addr), x && y);
+                prop_assert_eq!(d.contains(addr), x && !y);
+            }
+            // Results stay sorted, disjoint and non-touching.
+            for set in [&u, &i, &d] {
+                prop_assert!(set.ranges().windows(2).all(|w| w[0].end < w[1].start));
+                prop_assert!(set.ranges().iter().all(|r| r.start < r.end));
+            }
+        }
+    }
+
+    #[test]
+    fn reserved_beats_usable_on_overlap() {
+        let input = [region(0, 0x10000, 1), region(0x4000, 0x2000, 2)];
+        pretty_assertions::assert_eq!(
+            canonicalize(&input),
+            vec![
+                region(0, 0x4000, 1),
+                region(0x4000, 0x2000, 2),
+                region(0x6000, 0xA000, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_ram_beats_reserved() {
+        let input = [region(0, 0x2000, 5), region(0x1000, 0x2000, 2)];
+        pretty_assertions::assert_eq!(
+            canonicalize(&input),
+            vec![region(0, 0x2000, 5), region(0x2000, 0x1000, 2)]
+        );
+    }
+
+    #[test]
+    fn bad_ram_splits_usable_on_both_ends() {
+        // Interleaved type 1 / type 5, bad RAM clipping the head of one
+        // usable region, the tail of another, spanning the gap between two,
+        // and sitting in the middle of the last. Unaligned edges too.
+        let input = [
+            region(0x800, 0x1000, 5),
+            region(0, 0x4000, 1),
+            region(0x6000, 0x3000, 1),
+            region(0x8800, 0x2000, 5),
+            region(0xA000, 0x4000, 1),
+            region(0x10000, 0x4000, 1),
+            region(0x11000, 0x1000, 5),
+        ];
+        let want = vec![
+            region(0, 0x800, 1),
+            region(0x800, 0x1000, 5),
+            region(0x1800, 0x2800, 1),
+            region(0x6000, 0x2800, 1),
+            region(0x8800, 0x2000, 5),
+            region(0xA800, 0x3800, 1),
+            region(0x10000, 0x1000, 1),
+            region(0x11000, 0x1000, 5),
+            region(0x12000, 0x2000, 1),
+        ];
+        let mut v = input.to_vec();
+        normalize_vec(&mut v);
+        pretty_assertions::assert_eq!(v, want);
+
+        // canonicalize also shrinks the usable pieces to whole frames, so
+        // the partial frames next to bad RAM are lost as well.
+        pretty_assertions::assert_eq!(
+            canonicalize(&input),
+            vec![
+                region(0x800, 0x1000, 5),
+                region(0x2000, 0x2000, 1),
+                region(0x6000, 0x2000, 1),
+                region(0x8800, 0x2000, 5),
+                region(0xB000, 0x3000, 1),
+                region(0x10000, 0x1000, 1),
+                region(0x11000, 0x1000, 5),
+                region(0x12000, 0x2000, 1),
+            ]
+        );
+        let frames: Vec<u64> = crate::frames::UsableFrames::new(&want)
+            .map(|f| f.0)
+            .collect();
+        assert!(frames.iter().all(|&f| input
+            .iter()
+            .filter(|r| r.kind == 5)
+            .all(|b| f + 0x1000 <= b.start || f >= b.end())));
+    }
+
+    #[test]
+    fn usable_is_shrunk_to_alignment() {
+        let input = [region(0x800, 0x3000, 1)];
+        let (out, stats) = canonicalize_with(&input, &CanonicalizeOptions::default());
+        pretty_assertions::assert_eq!(out, vec![region(0x1000, 0x2000, 1)]);
+        pretty_assertions::assert_eq!(stats.alignment_trimmed_bytes, 0x1000);
+    }
+
+    #[test]
+    fn min_usable_size_drops_slivers_and_counts_them() {
+        let input = [
+            region(0, 0x4000, 1),
+            region(0x4000, 0x1000, 2),
+            region(0x5000, 0x100000, 1),
+        ];
+        let opts = CanonicalizeOptions {
+            min_usable_size: 0x10000,
+            ..Default::default()
+        };
+        let (out, stats) = canonicalize_with(&input, &opts);
+
+        pretty_assertions::assert_eq!(
+            out,
+            vec![region(0x4000, 0x1000, 2), region(0x5000, 0x100000, 1)]
+        );
+        pretty_assertions::assert_eq!(stats.slivers_dropped, 1);
+        pretty_assertions::assert_eq!(stats.sliver_bytes_dropped, 0x4000);
+    }
+
+    #[test]
+    #[should_panic(expected = "power of two")]
+    fn zero_align_is_rejected() {
+        let opts = CanonicalizeOptions {
+            align: 0,
+            ..Default::default()
+        };
+        canonicalize_with(&[region(0x1000, 0x1000, 1)], &opts);
+    }
+
+    #[test]
+    #[should_panic(expected = "power of two")]
+    fn non_power_of_two_align_is_rejected() {
+        let opts = CanonicalizeOptions {
+            align: 0x3000,
+            ..Default::default()
+        };
+        canonicalize_with(&[region(0x1000, 0x1000, 1)], &opts);
+    }
+
+    #[test]
+    fn zero_length_and_top_of_memory_are_harmless() {
+        let input = [region(0x1000, 0, 2), region(u64::MAX - 0x10, 0x10, 1)];
+        let (out, stats) = canonicalize_with(&input, &CanonicalizeOptions::default());
+        assert!(out.is_empty());
+        pretty_assertions::assert_eq!(stats.alignment_trimmed_bytes, 0x10);
+    }
+
+    #[test]
+    fn rejections_name_overlap_losers_and_slivers() {
+        let input = [
+            region(0, 0x10000, 1),
+            region(0x4000, 0x2000, 2),
+            region(0x20000, 0x1000, 1),
+        ];
+        let opts = CanonicalizeOptions {
+            min_usable_size: 0x2000,
+            ..Default::default()
+        };
+        let (_, _, rejected) = canonicalize_with_rejections(&input, &opts);
+
+        pretty_assertions::assert_eq!(
+            rejected,
+            vec![
+                RegionRejection {
+                    region: region(0x4000, 0x2000, 1),
+                    reason: RejectionReason::OverlapLoser,
+                },
+                RegionRejection {
+                    region: region(0x20000, 0x1000, 1),
+                    reason: RejectionReason::BelowMinSize,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn candidates_respect_align_limit_and_size() {
+        let map = [
+            region(0x1001, 0x2000, 1),      // aligns to 0x1010, 0x1ff1 left
+            region(0x4000, 0x1000, 2),      // not usable
+            region(0x8000, 0x100, 1),       // too small
+            region(0xFFFF_F000, 0x4000, 1), // clipped at 4 GiB
+        ];
+        let c = Constraints {
+            align: 16,
+            limit: 0x1_0000_0000,
+            min_size: 0x800,
+        };
+        let got: Vec<MemRegion> = c.candidates(&map).collect();
+        pretty_assertions::assert_eq!(
+            got,
+            vec![region(0x1010, 0x1FF1, 1), region(0xFFFF_F000, 0x1000, 1)]
+        );
+        pretty_assertions::assert_eq!(c.first_fit(&map), Some(0x1010));
+    }
+
+    #[test]
+    fn candidates_none_when_nothing_fits() {
+        let map = [region(0x1000_0000, 0x1000, 1)];
+        let c = Constraints {
+            limit: 0x100_0000, // 16 MiB ISA DMA
+            ..Default::default()
+        };
+        pretty_assertions::assert_eq!(c.first_fit(&map), None);
+
+        let c = Constraints {
+            align: 0x10_0000,
+            ..Default::default()
+        };
+        pretty_assertions::assert_eq!(c.first_fit(&[region(0x1000, 0x2000, 1)]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "power of two")]
+    fn candidates_reject_bad_align() {
+        let c = Constraints {
+            align: 0,
+            ..Default::default()
+        };
+        c.first_fit(&[region(0x1000, 0x1000, 1)]);
+    }
+
+    #[test]
+    fn candidates_near_top_of_address_space() {
+        let map = [region(u64::MAX - 0xFF, 0xFF, 1)];
+        let c = Constraints {
+            align: 0x1000,
+            ..Default::default()
+        };
+        pretty_assertions::assert_eq!(c.first_fit(&map), None);
+    }
+
+    #[test]
+    fn special_purpose_memory_is_found_and_never_usable() {
+        let map = [
+            region(0, 0x10_0000, 1),
+            region(0x1_0000_0000, 0x4000_0000, kind::SOFT_RESERVED),
+            region(0x2_0000_0000, 0x4000_0000, kind::PERSISTENT),
+            region(0x3_0000_0000, 0x1000, 2),
+        ];
+        let special: Vec<u64> = special_purpose_regions(&map).map(|r| r.start).collect();
+        pretty_assertions::assert_eq!(special, vec![0x1_0000_0000, 0x2_0000_0000]);
+
+        let canon = canonicalize(&map);
+        assert!(canon
+            .iter()
+            .filter(|r| r.kind == kind::USABLE)
+            .all(|r| r.end() <= 0x10_0000));
+        pretty_assertions::assert_eq!(Constraints::default().candidates(&map).count(), 1);
+    }
+
+    #[test]
+    fn intersect_clips_to_range() {
+        let r = region(0x1000, 0x3000, 1);
+        pretty_assertions::assert_eq!(
+            intersect(r, &(0x2000..0x8000)),
+            Some(region(0x2000, 0x2000, 1))
+        );
+        pretty_assertions::assert_eq!(intersect(r, &(0x4000..0x8000)), None);
+        pretty_assertions::assert_eq!(intersect(r, &(0x2000..0x2000)), None);
+    }
+
+    #[test]
+    fn mmio_conflicts_only_report_usable_overlap() {
+        let map = [
+            region(0, 0xFEE0_0000, 1),
+            region(0xFEE0_0000, 0x1000, 2),
+            region(0xFEE0_1000, 0x1_F000, 1),
+        ];
+        let mmio = [
+            0xFEC0_0000..0xFEC0_1000, // IOAPIC inside usable: conflict
+            0xFEE0_0000..0xFEE0_1000, // LAPIC correctly reserved: fine
+            0xFEE0_0800..0xFEE0_2000, // straddles reserved into usable
+            0x1_0000_0000..0x2_0000_0000,
+        ];
+        let got: Vec<MmioConflict> = check_mmio_conflicts(&map, &mmio).collect();
+        pretty_assertions::assert_eq!(
+            got,
+            vec![
+                MmioConflict {
+                    mmio_index: 0,
+                    overlap: region(0xFEC0_0000, 0x1000, 1),
+                },
+                MmioConflict {
+                    mmio_index: 2,
+                    overlap: region(0xFEE0_1000, 0x1000, 1),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn stripe_round_robins_across_regions() {
+        let regions = [
+            region(0x10000, 0x3000, 1),
+            region(0x20000, 0x1000, 1),
+            region(0x30000, 0x2000, 3),
+        ];
+        let starts: Vec<(u64, u32)> = stripe(&regions, 0x1000)
+            .map(|c| (c.start, c.kind))
+            .collect();
+        pretty_assertions::assert_eq!(
+            starts,
+            vec![
+                (0x10000, 1),
+                (0x20000, 1),
+                (0x30000, 3),
+                (0x11000, 1),
+                (0x31000, 3),
+                (0x12000, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn stripe_never_crosses_region_boundaries() {
+        // Adjacent regions whose sizes are not multiples of the chunk:
+        // the tails are dropped rather than glued together.
+        let regions = [region(0, 0x1800, 1), region(0x1800, 0x1800, 1)];
+        let chunks: Vec<MemRegion> = stripe(&regions, 0x1000).collect();
+        pretty_assertions::assert_eq!(
+            chunks,
+            vec![region(0, 0x1000, 1), region(0x1800, 0x1000, 1)]
+        );
+    }
+
+    #[test]
+    fn stripe_of_nothing_is_empty() {
+        pretty_assertions::assert_eq!(stripe(&[], 0x1000).count(), 0);
+        pretty_assertions::assert_eq!(stripe(&[region(0, 0xFFF, 1)], 0x1000).count(), 0);
+    }
+
+    #[test]
+    fn coverage_splits_range_into_backed_spans_and_holes() {
+        let map = [
+            region(0, 0x9_F000, 1),
+            region(0x9_F000, 0x1000, 2),
+            region(0x10_0000, 0x10_0000, 1),
+        ];
+        let spans: Vec<CoverageSpan> = coverage(&map, &(0x9_E000..0x10_1000)).collect();
+        let span = |start, len, kind| CoverageSpan { start, len, kind };
+        pretty_assertions::assert_eq!(
+            spans,
+            vec![
+                span(0x9_E000, 0x1000, Some(1)),
+                span(0x9_F000, 0x1000, Some(2)),
+                span(0xA_0000, 0x6_0000, None),
+                span(0x10_0000, 0x1000, Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn gaps_lists_holes_below_the_last_region() {
+        let map = [
+            region(0x1000, 0x9_E000, 1),
+            region(0xF_0000, 0x1_0000, 2),
+            region(0x10_0000, 0xBFF0_0000, 1),
+            region(0x1_0000_0000, 0x4000_0000, 1),
+        ];
+        pretty_assertions::assert_eq!(
+            gaps(&map).collect::<Vec<_>>(),
+            vec![0..0x1000, 0x9_F000..0xF_0000, 0xC000_0000..0x1_0000_0000]
+        );
+        pretty_assertions::assert_eq!(gaps(&[]).next(), None);
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_retyped() {
+        // Boot services memory goes back to usable, a runtime region
+        // appears in a hole, and a reserved stub disappears.
+        let old = [
+            region(0, 0x1000, 2),
+            region(0x1000, 0x3000, 1),
+            region(0x4000, 0x2000, 3),
+            region(0x6000, 0x2000, 3),
+        ];
+        let new = [
+            region(0x1000, 0x3000, 1),
+            region(0x4000, 0x4000, 1),
+            region(0x9000, 0x1000, 2),
+        ];
+        pretty_assertions::assert_eq!(
+            diff(&old, &new).collect::<Vec<_>>(),
+            vec![
+                RegionChange::Removed(region(0, 0x1000, 2)),
+                RegionChange::Retyped {
+                    start: 0x4000,
+                    len: 0x4000,
+                    from: 3,
+                    to: 1
+                },
+                RegionChange::Added(region(0x9000, 0x1000, 2)),
+            ]
+        );
+        pretty_assertions::assert_eq!(diff(&old, &old).next(), None);
+        pretty_assertions::assert_eq!(
+            diff(&[], &new).map(|c| c.range()).collect::<Vec<_>>(),
+            vec![0x1000..0x8000, 0x9000..0xA000]
+        );
+    }
+
+    #[test]
+    fn region_containing_and_is_range_usable() {
+        let map = [
+            region(0, 0x9_F000, 1),
+            region(0x9_F000, 0x6_1000, 2),
+            region(0x10_0000, 0x10_0000, 1),
+            region(0x20_0000, 0x10_0000, 1),
+            region(0x40_0000, 0x1000, 1),
+        ];
+        pretty_assertions::assert_eq!(region_containing(&map, 0xA_0000), Some(&map[1]));
+        pretty_assertions::assert_eq!(region_containing(&map, 0x20_0000), Some(&map[3]));
+        pretty_assertions::assert_eq!(region_containing(&map, 0x30_0000), None);
+        pretty_assertions::assert_eq!(region_containing(&map, u64::MAX), None);
+
+        assert!(is_range_usable(&map, 0x1000, 0x1000));
+        // Across two touching usable regions.
+        assert!(is_range_usable(&map, 0x1F_F000, 0x2000));
+        // Into reserved memory, across a hole, past the end.
+        assert!(!is_range_usable(&map, 0x9_E000, 0x2000));
+        assert!(!is_range_usable(&map, 0x2F_F000, 0x2000));
+        assert!(!is_range_usable(&map, 0x40_0000, 0x2000));
+        assert!(!is_range_usable(&map, 0x1000, 0));
+        assert!(!is_range_usable(&map, u64::MAX, 2));
+    }
+
+    #[test]
+    fn assert_covered_reports_first_hole() {
+        let map = [
+            region(0x10_0000, 0x10_0000, 1),
+            region(0x30_0000, 0x1000, 1),
+        ];
+        pretty_assertions::assert_eq!(assert_covered(&map, &(0x10_0000..0x18_0000)), Ok(()));
+        pretty_assertions::assert_eq!(
+            assert_covered(&map, &(0x18_0000..0x40_0000)),
+            Err(0x20_0000..0x30_0000)
+        );
+        // Past the end of the map is a hole too.
+        pretty_assertions::assert_eq!(
+            assert_covered(&map, &(0x30_0000..0x30_2000)),
+            Err(0x30_1000..0x30_2000)
+        );
+        pretty_assertions::assert_eq!(assert_covered(&[], &(5..5)), Ok(()));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn canonicalize_opens_a_span() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        static SPANS: AtomicUsize = AtomicUsize::new(0);
+        static EVENTS: AtomicUsize = AtomicUsize::new(0);
+
+        struct Counter;
+        impl Subscriber for Counter {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(SPANS.fetch_add(1, Ordering::SeqCst) as u64 + 1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event<'_>) {
+                EVENTS.fetch_add(1, Ordering::SeqCst);
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        tracing::subscriber::with_default(Counter, || {
+            canonicalize(&[region(0, 0x1000, 1)]);
+        });
+        pretty_assertions::assert_eq!(SPANS.load(Ordering::SeqCst), 1);
+        pretty_assertions::assert_eq!(EVENTS.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,39 @@
+// rejection.rs
+//
+// One vocabulary for "why did this memory disappear?".
+//
+// sanitize drops firmware entries, canonicalize drops slivers and the
+// losing side of overlaps, compose replaces firmware claims with
+// overrides. All of them answer with a RejectionReason, so a kernel can
+// log every lost byte the same way no matter which stage lost it.
+
+use crate::raw::MemRegion;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RejectionReason {
+    // Entry claimed zero bytes.
+    ZeroLength,
+
+    // start + len does not fit in u64.
+    Overflow,
+
+    // Kind the policy does not recognize.
+    UnknownKind,
+
+    // Usable region too small to be worth tracking (after alignment).
+    BelowMinSize,
+
+    // Region lies above the physical address limit in effect.
+    OutsidePhysLimit,
+
+    // Another claim over the same bytes won (more restrictive kind,
+    // or a later override).
+    OverlapLoser,
+}
+
+/// A (piece of a) region some stage threw away, and why.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegionRejection {
+    pub region: MemRegion,
+    pub reason: RejectionReason,
+}
@@ -0,0 +1,296 @@
+// relocate.rs
+//
+// A bootloader loads the kernel and initrd wherever it could, then has
+// to move them where the kernel wants them: above 1 MiB, aligned, out of
+// memory the firmware still uses. Where to, and in what order?
+//
+// Order matters because targets may land on top of another payload that
+// has not been moved yet. The plan only ever lets a target overlap the
+// source of a payload that is copied earlier, so running the moves in
+// order never overwrites data that is still needed. A target may also
+// overlap its own source; that move has to be a memmove, and
+// `Move::copy_backwards` says which direction.
+//
+// Nothing is copied here. The caller runs the plan with whatever copy
+// routine it has.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::kind;
+use crate::raw::MemRegion;
+use crate::region::RegionSet;
+
+/// One blob in memory and where it is allowed to end up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Payload {
+    pub start: u64,
+    pub len: u64,
+    /// Target alignment (power of two).
+    pub align: u64,
+    /// Target must start at or above this, e.g. 1 MiB.
+    pub min_addr: u64,
+}
+
+impl Payload {
+    fn range(&self) -> Range<u64> {
+        self.start..self.start.saturating_add(self.len)
+    }
+
+    fn overlaps(&self, other: &Payload) -> bool {
+        let (a, b) = (self.range(), other.range());
+        a.start < b.end && b.start < a.end
+    }
+
+    // Already somewhere acceptable: aligned, high enough, all usable.
+    fn fits_in_place(&self, usable: &RegionSet) -> bool {
+        self.start.is_multiple_of(self.align)
+            && self.start >= self.min_addr
+            && usable
+                .intersect(&RegionSet::from_ranges(core::iter::once(self.range())))
+                .total_len()
+                == self.len
+    }
+}
+
+/// Copy `len` bytes of payload `index` from `from` to `to`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Move {
+    pub index: usize,
+    pub from: u64,
+    pub to: u64,
+    pub len: u64,
+}
+
+impl Move {
+    /// Target and source overlap with the target above, so a forward
+    /// copy would overwrite bytes before reading them.
+    pub fn copy_backwards(&self) -> bool {
+        self.to > self.from && self.to < self.from + self.len
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RelocationError {
+    /// Two payloads claim the same bytes to begin with.
+    OverlappingSources { a: usize, b: usize },
+    /// No usable space satisfies this payload's constraints.
+    NoSpace { index: usize },
+}
+
+/// Plan moves that put every payload somewhere acceptable in the usable
+/// memory of the canonical `map`. Payloads already acceptable stay put
+/// and get no move. Returns the moves in a safe order.
+pub fn plan_relocation(
+    map: &[MemRegion],
+    payloads: &[Payload],
+) -> Result<Vec<Move>, RelocationError> {
+    for (a, pa) in payloads.iter().enumerate() {
+        for (b, pb) in payloads.iter().enumerate().skip(a + 1) {
+            if pa.overlaps(pb) {
+                return Err(RelocationError::OverlappingSources { a, b });
+            }
+        }
+    }
+
+    let usable = RegionSet::of_kind(map, kind::USABLE);
+    let moving: Vec<usize> = (0..payloads.len())
+        .filter(|&i| payloads[i].len > 0 && !payloads[i].fits_in_place(&usable))
+        .collect();
+    let staying = RegionSet::from_ranges(
+        (0..payloads.len())
+            .filter(|i| !moving.contains(i))
+            .map(|i| payloads[i].range()),
+    );
+
+    // First try letting targets land on other moving payloads (less
+    // memory needed), then fall back to keeping clear of every source.
+    let packed = assign(&usable, &staying, payloads, &moving)?;
+    if let Some(order) = copy_order(&packed) {
+        return Ok(order);
+    }
+    let all_sources = staying.union(&RegionSet::from_ranges(
+        moving.iter().map(|&i| payloads[i].range()),
+    ));
+    let spread = assign(&usable, &all_sources, payloads, &moving)?;
+    Ok(copy_order(&spread).expect("targets clear of every other source"))
+}
+
+// Lowest acceptable target for each moving payload, avoiding `blocked`
+// (its own source is always allowed) and earlier targets.
+fn assign(
+    usable: &RegionSet,
+    blocked: &RegionSet,
+    payloads: &[Payload],
+    moving: &[usize],
+) -> Result<Vec<Move>, RelocationError> {
+    let mut taken = RegionSet::new();
+    let mut moves = Vec::with_capacity(moving.len());
+    for &index in moving {
+        let p = payloads[index];
+        let own = RegionSet::from_ranges(core::iter::once(p.range()));
+        let free = usable
+            .intersect(&RegionSet::from_ranges(core::iter::once(
+                p.min_addr..u64::MAX,
+            )))
+            .difference(&blocked.difference(&own))
+            .difference(&taken);
+        let to = free
+            .ranges()
+            .iter()
+            .find_map(|r| {
+                let start = r.start.checked_next_multiple_of(p.align)?;
+                (start.checked_add(p.len)? <= r.end).then_some(start)
+            })
+            .ok_or(RelocationError::NoSpace { index })?;
+        taken = taken.union(&RegionSet::from_ranges(core::iter::once(to..to + p.len)));
+        moves.push(Move {
+            index,
+            from: p.start,
+            to,
+            len: p.len,
+        });
+    }
+    Ok(moves)
+}
+
+// Order `moves` so none overwrites a source that has not been copied yet.
+// None if they depend on each other in a cycle.
+fn copy_order(moves: &[Move]) -> Option<Vec<Move>> {
+    let overlaps = |a: &Move, b: &Move| a.to < b.from + b.len && b.from < a.to + a.len;
+    let mut pending: Vec<Move> = moves.to_vec();
+    let mut order = Vec::with_capacity(moves.len());
+    while !pending.is_empty() {
+        // Ready: its target covers no other pending source.
+        let ready = pending.iter().position(|m| {
+            pending
+                .iter()
+                .all(|o| o.index == m.index || !overlaps(m, o))
+        })?;
+        order.push(pending.remove(ready));
+    }
+    Some(order)
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    const MIB: u64 = 1 << 20;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn payload(start: u64, len: u64) -> Payload {
+        Payload {
+            start,
+            len,
+            align: 0x1000,
+            min_addr: MIB,
+        }
+    }
+
+    // Run a plan over fake memory with one byte per 4 KiB, checking every
+    // payload arrives intact.
+    fn execute(payloads: &[Payload], moves: &[Move]) {
+        let cell = |a: u64| (a / 0x1000) as usize;
+        let mut mem = [0u8; 64];
+        for (i, p) in payloads.iter().enumerate() {
+            mem[cell(p.start)..cell(p.start + p.len)].fill(i as u8 + 1);
+        }
+        for m in moves {
+            mem.copy_within(cell(m.from)..cell(m.from + m.len), cell(m.to));
+        }
+        for (i, p) in payloads.iter().enumerate() {
+            let at = moves
+                .iter()
+                .find(|m| m.index == i)
+                .map_or(p.start, |m| m.to);
+            assert!(mem[cell(at)..cell(at + p.len)]
+                .iter()
+                .all(|&b| b == i as u8 + 1));
+        }
+    }
+
+    #[test]
+    fn payloads_below_1mib_move_up() {
+        init();
+        let map = [region(0, 0x9F000, 1), region(MIB, 4 * MIB, 1)];
+        let payloads = [payload(0x10000, 0x3000), payload(2 * MIB, 0x2000)];
+        let moves = plan_relocation(&map, &payloads).unwrap();
+        pretty_assertions::assert_eq!(
+            moves,
+            vec![Move {
+                index: 0,
+                from: 0x10000,
+                to: MIB,
+                len: 0x3000
+            }]
+        );
+    }
+
+    #[test]
+    fn target_may_overlap_its_own_source() {
+        // Kernel sits at 1 MiB + 0x1000 but needs 64 KiB alignment; the
+        // only space is right where it is, shifted down.
+        let map = [region(MIB, 0x11000, 1)];
+        let p = Payload {
+            align: 0x10000,
+            ..payload(MIB + 0x1000, 0x10000)
+        };
+        let moves = plan_relocation(&map, &[p]).unwrap();
+        pretty_assertions::assert_eq!(moves[0].to, MIB);
+        assert!(!moves[0].copy_backwards());
+
+        let up = Move {
+            to: moves[0].from + 0x1000,
+            ..moves[0]
+        };
+        assert!(up.copy_backwards());
+    }
+
+    #[test]
+    fn copy_order_moves_the_occupant_out_first() {
+        // Both payloads sit below their floors. Payload 0's lowest target
+        // is where payload 1 lives now, so payload 1 must go first.
+        let map = [region(0, 0x40000, 1), region(0x40000, 0x4000, 2)];
+        let mut payloads = [
+            Payload {
+                min_addr: 0x10000,
+                ..payload(0x1000, 0x2000)
+            },
+            Payload {
+                min_addr: 0x20000,
+                align: 0x8000,
+                ..payload(0x11000, 0x2000)
+            },
+        ];
+        let moves = plan_relocation(&map, &payloads).unwrap();
+        pretty_assertions::assert_eq!(moves.len(), 2);
+        pretty_assertions::assert_eq!(moves[0].index, 1);
+        pretty_assertions::assert_eq!(moves[1].to, 0x10000);
+        execute(&payloads, &moves);
+
+        payloads[1].min_addr = 0x40000;
+        pretty_assertions::assert_eq!(
+            plan_relocation(&map, &payloads),
+            Err(RelocationError::NoSpace { index: 1 })
+        );
+    }
+
+    #[test]
+    fn overlapping_sources_are_rejected() {
+        let map = [region(0, 4 * MIB, 1)];
+        pretty_assertions::assert_eq!(
+            plan_relocation(&map, &[payload(MIB, 0x2000), payload(MIB + 0x1000, 0x2000)]),
+            Err(RelocationError::OverlappingSources { a: 0, b: 1 })
+        );
+    }
+}
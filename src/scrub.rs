@@ -0,0 +1,147 @@
+// scrub.rs
+//
+// Before memory leaves your hands (handed back to firmware, to the next
+// kernel via kexec, or to another guest), zero it. Otherwise whatever
+// secrets were there are now someone else's.
+//
+// Two details matter and are easy to get wrong:
+//
+//   - the writes must be volatile: the compiler sees memory that is never
+//     read again and is allowed to delete plain stores to it
+//   - the range can be huge, so it is mapped and zeroed one chunk at a time
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use crate::mapper::PhysMapper;
+use crate::raw::MemRegion;
+
+/// Bytes mapped and zeroed per step.
+pub const SCRUB_CHUNK: u64 = 64 * 1024;
+
+/// Zero every byte of `region` through `mapper`. Returns bytes written.
+///
+/// # Safety
+/// Nothing may be using `region` (no live Rust references, no DMA in
+/// flight), and `mapper` must map it writable.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(start = region.start, len = region.len))
+)]
+pub unsafe fn scrub<M: PhysMapper>(region: MemRegion, mapper: &mut M) -> u64 {
+    let end = region.end();
+    let mut phys = region.start;
+
+    while phys < end {
+        let len = (end - phys).min(SCRUB_CHUNK) as usize;
+        let virt = mapper.map(phys, len);
+        zero_volatile(virt, len);
+        mapper.unmap(virt, len);
+        phys += len as u64;
+    }
+
+    // Keep later code (the actual release) from being reordered before the zeroing.
+    compiler_fence(Ordering::SeqCst);
+    end - region.start
+}
+
+// Byte stores up to u64 alignment, word stores in the middle, bytes at the tail.
+unsafe fn zero_volatile(ptr: *mut u8, len: usize) {
+    let mut i = 0;
+    while i < len && !(ptr as usize + i).is_multiple_of(8) {
+        ptr.add(i).write_volatile(0);
+        i += 1;
+    }
+    while i + 8 <= len {
+        (ptr.add(i) as *mut u64).write_volatile(0);
+        i += 8;
+    }
+    while i < len {
+        ptr.add(i).write_volatile(0);
+        i += 1;
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    /// Fake physical memory: `base..base+mem.len()` backed by a Vec.
+    struct VecMapper {
+        base: u64,
+        mem: Vec<u8>,
+        maps: usize,
+        max_chunk: usize,
+    }
+
+    impl PhysMapper for VecMapper {
+        unsafe fn map(&mut self, phys: u64, len: usize) -> *mut u8 {
+            let off = (phys - self.base) as usize;
+            assert!(off + len <= self.mem.len(), "mapped outside fake RAM");
+            self.maps += 1;
+            self.max_chunk = self.max_chunk.max(len);
+            self.mem.as_mut_ptr().add(off)
+        }
+    }
+
+    fn mapper(base: u64, size: usize) -> VecMapper {
+        VecMapper {
+            base,
+            mem: vec![0xA5; size],
+            maps: 0,
+            max_chunk: 0,
+        }
+    }
+
+    #[test]
+    fn scrub_zeroes_exactly_the_region() {
+        init();
+        let mut m = mapper(0x10_0000, 0x3000);
+        let region = MemRegion {
+            start: 0x10_0003, // unaligned head
+            len: 0x1FF0,      // unaligned tail
+            kind: 1,
+        };
+
+        let written = unsafe { scrub(region, &mut m) };
+
+        pretty_assertions::assert_eq!(written, 0x1FF0);
+        assert!(m.mem[..3].iter().all(|&b| b == 0xA5));
+        assert!(m.mem[3..0x1FF3].iter().all(|&b| b == 0));
+        assert!(m.mem[0x1FF3..].iter().all(|&b| b == 0xA5));
+    }
+
+    #[test]
+    fn scrub_maps_in_chunks() {
+        let size = (SCRUB_CHUNK * 3 + 0x100) as usize;
+        let mut m = mapper(0, size);
+        let region = MemRegion {
+            start: 0,
+            len: size as u64,
+            kind: 1,
+        };
+
+        unsafe { scrub(region, &mut m) };
+
+        pretty_assertions::assert_eq!(m.maps, 4);
+        pretty_assertions::assert_eq!(m.max_chunk as u64, SCRUB_CHUNK);
+        assert!(m.mem.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn scrub_empty_region_maps_nothing() {
+        let mut m = mapper(0, 0x1000);
+        let region = MemRegion {
+            start: 0x800,
+            len: 0,
+            kind: 1,
+        };
+        pretty_assertions::assert_eq!(unsafe { scrub(region, &mut m) }, 0);
+        pretty_assertions::assert_eq!(m.maps, 0);
+    }
+}
@@ -0,0 +1,102 @@
+// source.rs
+//
+// Every boot protocol ends in the same place: a stream of sanitized
+// regions, some of which may be errors. Allocator init code only needs
+// that stream, so it can be written once against MemoryMapSource and the
+// boot protocol swapped underneath it.
+//
+// Each source keeps its own error type (MB1 framing errors are not E820
+// errors); generic code that only logs them can require `Error: Debug`.
+
+use core::convert::Infallible;
+
+use crate::raw::{sanitize, Mb1MmapIter, MemRegion, MmapError};
+
+/// Something that can list the regions of a memory map, as many times as
+/// asked. Entries `sanitize` drops (empty, overflowing) are skipped.
+pub trait MemoryMapSource {
+    type Error;
+
+    fn regions(&self) -> impl Iterator<Item = Result<MemRegion, Self::Error>> + '_;
+}
+
+impl MemoryMapSource for Mb1MmapIter<'_> {
+    type Error = MmapError;
+
+    /// Walks from wherever this iterator currently is; the iterator itself
+    /// is not advanced.
+    fn regions(&self) -> impl Iterator<Item = Result<MemRegion, MmapError>> + '_ {
+        self.clone()
+            .filter_map(|entry| entry.map(sanitize).transpose())
+    }
+}
+
+/// An already-parsed map (tests, or a map built by hand).
+impl MemoryMapSource for [MemRegion] {
+    type Error = Infallible;
+
+    fn regions(&self) -> impl Iterator<Item = Result<MemRegion, Infallible>> + '_ {
+        self.iter().copied().map(Ok)
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::{push_entry, raw};
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    /// The kind of code this trait is for: written once, any protocol.
+    fn usable_bytes<S: MemoryMapSource + ?Sized>(source: &S) -> Result<u64, S::Error> {
+        source.regions().try_fold(0, |total, r| {
+            let r = r?;
+            Ok(if r.region_kind().is_usable() { total + r.len } else { total })
+        })
+    }
+
+    #[test]
+    fn mb1_and_slice_sources_agree() {
+        init();
+        let mut buf = Vec::new();
+        push_entry(&mut buf, raw(0, 0x9_F000, 1));
+        push_entry(&mut buf, raw(0x9_F000, 0, 1)); // dropped by sanitize
+        push_entry(&mut buf, raw(0x10_0000, 0x100_0000, 1));
+        push_entry(&mut buf, raw(0xF000_0000, 0x1000, 2));
+
+        let mb1 = Mb1MmapIter::new(&buf);
+        let parsed: Vec<MemRegion> = mb1.regions().map(Result::unwrap).collect();
+        pretty_assertions::assert_eq!(
+            parsed,
+            vec![
+                region(0, 0x9_F000, 1),
+                region(0x10_0000, 0x100_0000, 1),
+                region(0xF000_0000, 0x1000, 2),
+            ]
+        );
+        // Sources can be walked repeatedly.
+        pretty_assertions::assert_eq!(mb1.regions().count(), 3);
+
+        pretty_assertions::assert_eq!(usable_bytes(&mb1), Ok(0x10_9F000));
+        pretty_assertions::assert_eq!(usable_bytes(parsed.as_slice()), Ok(0x10_9F000));
+    }
+
+    #[test]
+    fn framing_errors_surface_through_the_trait() {
+        let mut buf = Vec::new();
+        push_entry(&mut buf, raw(0, 0x1000, 1));
+        buf.extend_from_slice(&[0; 3]);
+        pretty_assertions::assert_eq!(
+            usable_bytes(&Mb1MmapIter::new(&buf)),
+            Err(MmapError::TruncatedHeader { have: 3 })
+        );
+    }
+}
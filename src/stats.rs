@@ -0,0 +1,231 @@
+// stats.rs
+//
+// The capacity numbers a kernel prints at boot. Soft reserved and
+// hot-pluggable memory get figures of their own: counting them as usable
+// promises memory the allocator will not hand out, counting them as
+// reserved hides RAM the machine really has.
+//
+//   println!("{}", map.stats());
+//
+//   usable         7.9 GiB in 3 regions, largest 5.0 GiB at 0x100000000
+//   reserved       1.1 GiB
+//   ...
+
+#[cfg(feature = "fmt")]
+use core::fmt;
+
+use crate::kind;
+use crate::raw::MemRegion;
+#[cfg(feature = "fmt")]
+use crate::table::{Size, SizeFormat};
+
+/// Number of regions of each kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KindCounts {
+    pub usable: usize,
+    pub reserved: usize,
+    pub acpi_reclaimable: usize,
+    pub acpi_nvs: usize,
+    pub bad_ram: usize,
+    pub soft_reserved: usize,
+    pub hot_pluggable: usize,
+    /// Persistent memory and anything this crate has no name for.
+    pub other: usize,
+}
+
+impl KindCounts {
+    pub fn total(&self) -> usize {
+        self.usable
+            + self.reserved
+            + self.acpi_reclaimable
+            + self.acpi_nvs
+            + self.bad_ram
+            + self.soft_reserved
+            + self.hot_pluggable
+            + self.other
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// General-purpose RAM, what default allocation draws from.
+    pub usable_bytes: u64,
+    /// Everything that is neither usable nor claimable below.
+    pub reserved_bytes: u64,
+    /// EFI_MEMORY_SP memory, claimable by its owner.
+    pub soft_reserved_bytes: u64,
+    /// RAM that may be unplugged, claimable for movable data.
+    pub hot_pluggable_bytes: u64,
+    /// The biggest usable region (the first one, on a tie).
+    pub largest_usable: Option<MemRegion>,
+    /// Last usable byte (inclusive, so a region ending at 2^64 fits).
+    pub highest_usable: Option<u64>,
+    pub counts: KindCounts,
+}
+
+impl MemoryStats {
+    pub fn from_regions(regions: &[MemRegion]) -> Self {
+        let mut stats = MemoryStats::default();
+        for r in regions {
+            let count = match r.kind {
+                kind::USABLE => &mut stats.counts.usable,
+                kind::RESERVED => &mut stats.counts.reserved,
+                kind::ACPI_RECLAIMABLE => &mut stats.counts.acpi_reclaimable,
+                kind::ACPI_NVS => &mut stats.counts.acpi_nvs,
+                kind::BAD_RAM => &mut stats.counts.bad_ram,
+                kind::SOFT_RESERVED => &mut stats.counts.soft_reserved,
+                kind::HOT_PLUGGABLE => &mut stats.counts.hot_pluggable,
+                _ => &mut stats.counts.other,
+            };
+            *count += 1;
+
+            if r.kind == kind::USABLE && r.len > 0 {
+                if stats.largest_usable.is_none_or(|l| r.len > l.len) {
+                    stats.largest_usable = Some(*r);
+                }
+                let last = r.end() - 1;
+                if stats.highest_usable.is_none_or(|h| last > h) {
+                    stats.highest_usable = Some(last);
+                }
+            }
+
+            let bucket = match r.kind {
+                kind::USABLE => &mut stats.usable_bytes,
+                kind::SOFT_RESERVED => &mut stats.soft_reserved_bytes,
+                kind::HOT_PLUGGABLE => &mut stats.hot_pluggable_bytes,
+                _ => &mut stats.reserved_bytes,
+            };
+            *bucket += r.len;
+        }
+        stats
+    }
+
+    /// Usable plus everything a caller could opt into.
+    pub fn claimable_bytes(&self) -> u64 {
+        self.usable_bytes + self.soft_reserved_bytes + self.hot_pluggable_bytes
+    }
+}
+
+#[cfg(feature = "fmt")]
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let human = |bytes| Size {
+            bytes,
+            format: SizeFormat::Human,
+        };
+        let c = &self.counts;
+        write!(
+            f,
+            "usable         {} in {} regions",
+            human(self.usable_bytes),
+            c.usable
+        )?;
+        if let Some(l) = self.largest_usable {
+            write!(f, ", largest {} at {:#x}", human(l.len), l.start)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "reserved       {}", human(self.reserved_bytes))?;
+        writeln!(f, "soft reserved  {}", human(self.soft_reserved_bytes))?;
+        writeln!(f, "hot pluggable  {}", human(self.hot_pluggable_bytes))?;
+        writeln!(
+            f,
+            "entries        {} ({} usable, {} reserved, {} ACPI reclaimable, {} ACPI NVS, {} bad RAM, {} other)",
+            c.total(),
+            c.usable,
+            c.reserved,
+            c.acpi_reclaimable,
+            c.acpi_nvs,
+            c.bad_ram,
+            c.soft_reserved + c.hot_pluggable + c.other,
+        )?;
+        match self.highest_usable {
+            Some(high) => writeln!(f, "highest usable {high:#x}"),
+            None => writeln!(f, "highest usable none"),
+        }
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    #[test]
+    fn special_purpose_memory_is_counted_apart() {
+        init();
+        let map = [
+            region(0, 0x8000, kind::USABLE),
+            region(0x8000, 0x1000, kind::RESERVED),
+            region(0x9000, 0x2000, kind::SOFT_RESERVED),
+            region(0xB000, 0x4000, kind::HOT_PLUGGABLE),
+        ];
+        let stats = MemoryStats::from_regions(&map);
+        pretty_assertions::assert_eq!(
+            stats,
+            MemoryStats {
+                usable_bytes: 0x8000,
+                reserved_bytes: 0x1000,
+                soft_reserved_bytes: 0x2000,
+                hot_pluggable_bytes: 0x4000,
+                largest_usable: Some(region(0, 0x8000, kind::USABLE)),
+                highest_usable: Some(0x7FFF),
+                counts: KindCounts {
+                    usable: 1,
+                    reserved: 1,
+                    soft_reserved: 1,
+                    hot_pluggable: 1,
+                    ..Default::default()
+                },
+            }
+        );
+        pretty_assertions::assert_eq!(stats.claimable_bytes(), 0xE000);
+    }
+
+    #[test]
+    fn largest_and_highest_usable() {
+        let map = [
+            region(0, 0x9_FC00, kind::USABLE),
+            region(0x9_FC00, 0x6_0400, kind::RESERVED),
+            region(0x10_0000, 0xBFF0_0000, kind::USABLE),
+            region(0xC000_0000, 0x1000, kind::ACPI_NVS),
+            region(0x1_0000_0000, 0x4000_0000, kind::USABLE),
+            region(0x2_0000_0000, 0x1000, 0xF00),
+        ];
+        let stats = MemoryStats::from_regions(&map);
+        pretty_assertions::assert_eq!(
+            stats.largest_usable,
+            Some(region(0x10_0000, 0xBFF0_0000, kind::USABLE))
+        );
+        pretty_assertions::assert_eq!(stats.highest_usable, Some(0x1_3FFF_FFFF));
+        pretty_assertions::assert_eq!(stats.counts.total(), map.len());
+        pretty_assertions::assert_eq!(MemoryStats::default().largest_usable, None);
+    }
+
+    #[cfg(feature = "fmt")]
+    #[test]
+    fn display_summary() {
+        let map = [
+            region(0, 0x9_FC00, kind::USABLE),
+            region(0x9_FC00, 0x6_0400, kind::RESERVED),
+            region(0x10_0000, 0x3FF0_0000, kind::USABLE),
+            region(0x4000_0000, 0x1000, kind::ACPI_RECLAIMABLE),
+        ];
+        insta::assert_snapshot!(MemoryStats::from_regions(&map).to_string(), @r"
+        usable         1023.6 MiB in 2 regions, largest 1023.0 MiB at 0x100000
+        reserved       389.0 KiB
+        soft reserved  0.0 B
+        hot pluggable  0.0 B
+        entries        4 (2 usable, 1 reserved, 1 ACPI reclaimable, 0 ACPI NVS, 0 bad RAM, 0 other)
+        highest usable 0x3fffffff
+        ");
+    }
+}
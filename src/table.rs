@@ -0,0 +1,320 @@
+// table.rs
+//
+// Printing the memory map is the first thing every kernel does with it,
+// and the first thing you look at when something goes wrong.
+//
+// Everything here is core::fmt only (no alloc), so it works on a serial
+// console before the heap exists:
+//
+//   println!("{}", MapTable::new(&regions));
+//
+//   start              end                size       kind
+//   0x0000000000000000 0x000000000009fc00 639.0 KiB  usable
+//   0x000000000009fc00 0x0000000000100000 385.0 KiB  reserved
+
+use core::fmt;
+
+use crate::kind;
+use crate::numfmt;
+use crate::raw::MemRegion;
+
+/// Human name for an MB1/E820 type value.
+pub fn kind_name(kind: u32) -> &'static str {
+    match kind {
+        kind::USABLE => "usable",
+        kind::RESERVED => "reserved",
+        kind::ACPI_RECLAIMABLE => "ACPI reclaimable",
+        kind::ACPI_NVS => "ACPI NVS",
+        kind::BAD_RAM => "bad RAM",
+        kind::PERSISTENT | kind::PERSISTENT_LEGACY => "persistent",
+        kind::SOFT_RESERVED => "soft reserved",
+        kind::HOT_PLUGGABLE => "hot pluggable",
+        _ => "unknown",
+    }
+}
+
+/// How the size column is rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeFormat {
+    /// `0x9fc00`
+    Hex,
+    /// `654336`
+    Decimal,
+    /// `639.0 KiB` (one decimal, rounded down so sizes are never overstated)
+    Human,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TableOptions {
+    /// Append the raw type value, e.g. `usable (1)`.
+    pub show_kind_value: bool,
+    pub size_format: SizeFormat,
+    /// Add a footer with total bytes per kind.
+    pub summary_footer: bool,
+    /// Hex digits in the start/end columns (16 fits any u64; 9 fits a PC map).
+    pub addr_width: usize,
+    /// Width of the size column.
+    pub size_width: usize,
+    /// Print the column header line.
+    pub header: bool,
+}
+
+impl Default for TableOptions {
+    fn default() -> Self {
+        TableOptions {
+            show_kind_value: false,
+            size_format: SizeFormat::Human,
+            summary_footer: false,
+            addr_width: 16,
+            size_width: 10,
+            header: true,
+        }
+    }
+}
+
+/// `Display` adapter printing regions as a table, one per line.
+pub struct MapTable<'a> {
+    regions: &'a [MemRegion],
+    opts: TableOptions,
+}
+
+impl<'a> MapTable<'a> {
+    pub fn new(regions: &'a [MemRegion]) -> Self {
+        Self::with_options(regions, TableOptions::default())
+    }
+
+    pub fn with_options(regions: &'a [MemRegion], opts: TableOptions) -> Self {
+        MapTable { regions, opts }
+    }
+
+    fn write_kind(&self, f: &mut fmt::Formatter<'_>, kind: u32) -> fmt::Result {
+        f.write_str(kind_name(kind))?;
+        if self.opts.show_kind_value {
+            write!(f, " ({kind})")?;
+        }
+        Ok(())
+    }
+}
+
+/// `Display` adapter for a byte count in the given format.
+pub struct Size {
+    pub bytes: u64,
+    pub format: SizeFormat,
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.format {
+            SizeFormat::Hex => f.pad(numfmt::hex(self.bytes, &mut [0; numfmt::HEX_BUF_LEN])),
+            SizeFormat::Decimal => fmt::Display::fmt(&self.bytes, f),
+            SizeFormat::Human => f.pad(numfmt::human_size(
+                self.bytes,
+                &mut [0; numfmt::SIZE_BUF_LEN],
+            )),
+        }
+    }
+}
+
+impl<'a> fmt::Display for MapTable<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let aw = self.opts.addr_width + 2; // "0x"
+        let sw = self.opts.size_width;
+
+        if self.opts.header {
+            writeln!(f, "{:<aw$} {:<aw$} {:<sw$} kind", "start", "end", "size")?;
+        }
+
+        for r in self.regions {
+            let size = Size {
+                bytes: r.len,
+                format: self.opts.size_format,
+            };
+            write!(f, "{:#0aw$x} {:#0aw$x} {:<sw$} ", r.start, r.end(), size)?;
+            self.write_kind(f, r.kind)?;
+            writeln!(f)?;
+        }
+
+        if self.opts.summary_footer {
+            writeln!(f, "--")?;
+            for (i, r) in self.regions.iter().enumerate() {
+                // Only the first region of each kind prints the group line.
+                if self.regions[..i].iter().any(|p| p.kind == r.kind) {
+                    continue;
+                }
+                let group = self.regions.iter().filter(|p| p.kind == r.kind);
+                let count = group.clone().count();
+                let total = Size {
+                    bytes: group.map(|p| p.len).sum(),
+                    format: self.opts.size_format,
+                };
+                write!(f, "{:<sw$} in {count:>3} region(s): ", total)?;
+                self.write_kind(f, r.kind)?;
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// One-line boot banner:
+///
+///   RAM: 8.0 GiB usable / 8.2 GiB total, 14 regions, high=0x23fffffff
+///
+/// `total` counts every region regardless of kind; `high` is the last
+/// usable byte (inclusive), or `none` if nothing is usable.
+pub struct MapSummary<'a> {
+    regions: &'a [MemRegion],
+}
+
+impl<'a> MapSummary<'a> {
+    pub fn new(regions: &'a [MemRegion]) -> Self {
+        MapSummary { regions }
+    }
+}
+
+impl<'a> fmt::Display for MapSummary<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let usable = self.regions.iter().filter(|r| r.region_kind().is_usable());
+        let human = |bytes| Size {
+            bytes,
+            format: SizeFormat::Human,
+        };
+
+        write!(
+            f,
+            "RAM: {} usable / {} total, {} regions, high=",
+            human(usable.clone().map(|r| r.len).sum()),
+            human(self.regions.iter().map(|r| r.len).sum()),
+            self.regions.len(),
+        )?;
+        match usable.filter(|r| r.len > 0).map(|r| r.end() - 1).max() {
+            Some(high) => write!(f, "{high:#x}"),
+            None => f.write_str("none"),
+        }
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn pc_map() -> [MemRegion; 3] {
+        [
+            region(0, 0x9_FC00, 1),
+            region(0x9_FC00, 0x6_0400, 2),
+            region(0x10_0000, 0x7FF0_0000, 1),
+        ]
+    }
+
+    #[test]
+    fn default_table() {
+        init();
+        let out = MapTable::new(&pc_map()).to_string();
+        insta::assert_snapshot!(out, @r"
+        start              end                size       kind
+        0x0000000000000000 0x000000000009fc00 639.0 KiB  usable
+        0x000000000009fc00 0x0000000000100000 385.0 KiB  reserved
+        0x0000000000100000 0x0000000080000000 1.9 GiB    usable
+        ");
+    }
+
+    #[test]
+    fn narrow_columns_hex_sizes_and_kind_values() {
+        let opts = TableOptions {
+            show_kind_value: true,
+            size_format: SizeFormat::Hex,
+            addr_width: 8,
+            size_width: 10,
+            header: false,
+            ..Default::default()
+        };
+        let out = MapTable::with_options(&pc_map(), opts).to_string();
+        insta::assert_snapshot!(out, @r"
+        0x00000000 0x0009fc00 0x9fc00    usable (1)
+        0x0009fc00 0x00100000 0x60400    reserved (2)
+        0x00100000 0x80000000 0x7ff00000 usable (1)
+        ");
+    }
+
+    #[test]
+    fn decimal_sizes_with_summary_footer() {
+        let opts = TableOptions {
+            size_format: SizeFormat::Decimal,
+            summary_footer: true,
+            addr_width: 8,
+            header: false,
+            ..Default::default()
+        };
+        let out = MapTable::with_options(&pc_map(), opts).to_string();
+        insta::assert_snapshot!(out, @r"
+        0x00000000 0x0009fc00 654336     usable
+        0x0009fc00 0x00100000 394240     reserved
+        0x00100000 0x80000000 2146435072 usable
+        --
+        2147089408 in   2 region(s): usable
+        394240     in   1 region(s): reserved
+        ");
+    }
+
+    #[test]
+    fn human_sizes_pick_largest_unit() {
+        let human = |bytes| {
+            Size {
+                bytes,
+                format: SizeFormat::Human,
+            }
+            .to_string()
+        };
+        pretty_assertions::assert_eq!(human(0), "0.0 B");
+        pretty_assertions::assert_eq!(human(1536), "1.5 KiB");
+        pretty_assertions::assert_eq!(human(8 << 30), "8.0 GiB");
+        pretty_assertions::assert_eq!(human(u64::MAX), "15.9 EiB");
+    }
+
+    #[test]
+    fn unknown_kinds_are_labelled() {
+        pretty_assertions::assert_eq!(kind_name(0xF00), "unknown");
+        pretty_assertions::assert_eq!(kind_name(kind::SOFT_RESERVED), "soft reserved");
+        let opts = TableOptions {
+            show_kind_value: true,
+            header: false,
+            ..Default::default()
+        };
+        let out = MapTable::with_options(&[region(0, 0x1000, 0xF00)], opts).to_string();
+        assert!(out.ends_with("unknown (3840)\n"));
+    }
+
+    #[test]
+    fn summary_line() {
+        let regions = [
+            region(0, 0x9_FC00, 1),
+            region(0x9_FC00, 0x6_0400, 2),
+            region(0x10_0000, 0xBFF0_0000, 1),
+            region(0xC000_0000, 0x4000_0000, 2),
+            region(0x1_0000_0000, 0x1_4000_0000, 1),
+        ];
+        pretty_assertions::assert_eq!(
+            MapSummary::new(&regions).to_string(),
+            "RAM: 7.9 GiB usable / 9.0 GiB total, 5 regions, high=0x23fffffff"
+        );
+    }
+
+    #[test]
+    fn summary_without_usable_memory() {
+        let regions = [region(0, 0x1000, 2)];
+        pretty_assertions::assert_eq!(
+            MapSummary::new(&regions).to_string(),
+            "RAM: 0.0 B usable / 4.0 KiB total, 1 regions, high=none"
+        );
+    }
+}
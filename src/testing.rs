@@ -0,0 +1,191 @@
+// testing.rs
+//
+// Synthetic memory maps for tests, ours and downstream kernels'. Describe
+// the layout, get both the MB1 wire bytes a bootloader would hand over
+// and the regions this crate should make of them:
+//
+//   let map = MapBuilder::new()
+//       .usable(0, 0x9F000)
+//       .reserved(0x9F000, 0x61000)
+//       .then_usable(0x7FF0_0000)
+//       .hole(0x1000_0000)
+//       .then_reserved(0x1000);
+//   let regions: Vec<MemRegion> = Mb1MmapIter::new(&map.bytes())...;
+//   assert_eq!(regions, map.expected());
+//
+// Nothing here checks the layout makes sense; building broken maps is
+// the point.
+
+use alloc::vec::Vec;
+
+use crate::kind;
+use crate::raw::{push_entry, raw, sanitize, MemRegion, RawEntry};
+use crate::region::canonicalize;
+
+/// Builds an MB1 mmap and the regions it should parse to.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MapBuilder {
+    entries: Vec<RawEntry>,
+    /// Where `then_*` places the next region.
+    cursor: u64,
+    /// Bytes cut off the end of the wire format.
+    truncate: usize,
+}
+
+impl MapBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An entry of any kind at `start`. The cursor moves to its end.
+    pub fn region(mut self, start: u64, len: u64, kind: u32) -> Self {
+        self.entries.push(raw(start, len, kind));
+        self.cursor = start.wrapping_add(len);
+        self
+    }
+
+    pub fn usable(self, start: u64, len: u64) -> Self {
+        self.region(start, len, kind::USABLE)
+    }
+
+    pub fn reserved(self, start: u64, len: u64) -> Self {
+        self.region(start, len, kind::RESERVED)
+    }
+
+    /// An entry right at the cursor (the end of the last one, or of a hole).
+    pub fn then(self, len: u64, kind: u32) -> Self {
+        let start = self.cursor;
+        self.region(start, len, kind)
+    }
+
+    pub fn then_usable(self, len: u64) -> Self {
+        self.then(len, kind::USABLE)
+    }
+
+    pub fn then_reserved(self, len: u64) -> Self {
+        self.then(len, kind::RESERVED)
+    }
+
+    /// Leave `len` bytes uncovered before the next `then_*` region.
+    pub fn hole(mut self, len: u64) -> Self {
+        self.cursor = self.cursor.wrapping_add(len);
+        self
+    }
+
+    /// An entry of `kind` over the last `len` bytes of the previous one,
+    /// as firmware that reports a reserved range inside RAM does. The
+    /// cursor does not move.
+    ///
+    /// # Panics
+    /// If there is no previous entry.
+    pub fn overlap(mut self, len: u64, kind: u32) -> Self {
+        let last = *self.entries.last().expect("overlap needs a previous entry");
+        let end = last.base_addr.wrapping_add(last.length);
+        self.entries.push(raw(end.wrapping_sub(len), len, kind));
+        self
+    }
+
+    /// Cut `n` bytes off the end of the wire format, as a bootloader with
+    /// a wrong `mmap_length` would.
+    pub fn truncate(mut self, n: usize) -> Self {
+        self.truncate = n;
+        self
+    }
+
+    /// The MB1 mmap buffer.
+    pub fn bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for &e in &self.entries {
+            push_entry(&mut buf, e);
+        }
+        buf.truncate(buf.len().saturating_sub(self.truncate));
+        buf
+    }
+
+    /// What parsing [`bytes`](Self::bytes) and sanitizing each entry
+    /// yields, in order: entries cut by truncation are missing, as are
+    /// the ones sanitize drops.
+    pub fn expected(&self) -> Vec<MemRegion> {
+        // Every entry is 24 bytes on the wire.
+        let whole = self.bytes().len() / 24;
+        self.entries[..whole]
+            .iter()
+            .filter_map(|&e| sanitize(e))
+            .collect()
+    }
+
+    /// [`canonicalize`] of [`expected`](Self::expected).
+    pub fn expected_canonical(&self) -> Vec<MemRegion> {
+        canonicalize(&self.expected())
+    }
+
+    /// Whether truncation leaves a partial entry, i.e. the parser should
+    /// end with an error.
+    pub fn expects_error(&self) -> bool {
+        !self.bytes().len().is_multiple_of(24)
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::{Mb1MmapIter, MmapError};
+    use crate::tests::common::init;
+    use crate::vectors::MB1_PC;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn parse(buf: &[u8]) -> (Vec<MemRegion>, Option<MmapError>) {
+        let mut regions = Vec::new();
+        for entry in Mb1MmapIter::new(buf) {
+            match entry {
+                Ok(e) => regions.extend(sanitize(e)),
+                Err(e) => return (regions, Some(e)),
+            }
+        }
+        (regions, None)
+    }
+
+    #[test]
+    fn builds_the_pc_vector() {
+        init();
+        let map = MapBuilder::new()
+            .usable(0, 0x9_FC00)
+            .then_reserved(0x6_0400)
+            .then_usable(0x7FF0_0000);
+        pretty_assertions::assert_eq!(map.bytes(), MB1_PC.bytes);
+        pretty_assertions::assert_eq!(map.expected(), MB1_PC.regions);
+        assert!(!map.expects_error());
+    }
+
+    #[test]
+    fn overlap_and_truncation() {
+        let map = MapBuilder::new()
+            .usable(0, 0x1_0000)
+            .overlap(0x1000, kind::RESERVED)
+            .then_usable(0)
+            .hole(0x1000)
+            .then_usable(0x1000)
+            .truncate(4);
+        pretty_assertions::assert_eq!(
+            map.expected(),
+            vec![region(0, 0x1_0000, 1), region(0xF000, 0x1000, 2)]
+        );
+        pretty_assertions::assert_eq!(
+            map.expected_canonical(),
+            vec![region(0, 0xF000, 1), region(0xF000, 0x1000, 2)]
+        );
+        assert!(map.expects_error());
+
+        let (regions, err) = parse(&map.bytes());
+        pretty_assertions::assert_eq!(regions, map.expected());
+        assert!(matches!(err, Some(MmapError::TruncatedEntry { .. })));
+    }
+}
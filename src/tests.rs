@@ -1,2 +1,6 @@
+pub mod allocators;
+pub mod auto_traits;
+#[cfg(test)]
 pub mod common;
 pub mod prelude;
+pub mod progress;
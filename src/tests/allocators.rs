@@ -0,0 +1,256 @@
+#![cfg(all(test, feature = "std"))]
+
+// allocators.rs
+//
+// Every frame allocator against the same naive model: a set of the
+// frames handed out. Random alloc / contiguous alloc / free sequences
+// must never hand a frame out twice, never hand out anything but usable
+// memory, and always account for every frame (free + held = total).
+//
+// The allocators that promise to catch bad frees (bitmap, buddy) are
+// also fed frees of frames they never handed out: holes, reserved
+// memory, frames already free. Each must panic before touching anything.
+
+use std::collections::BTreeSet;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use proptest::prelude::*;
+
+use crate::frames::buddy::BuddyAllocator;
+use crate::frames::{
+    usable_frame_count, BitmapAllocator, BumpAllocator, FrameAlloc, FreeListAllocator, PhysFrame,
+    FRAME_SIZE,
+};
+use crate::mapper::PhysMapper;
+use crate::raw::MemRegion;
+
+/// What the harness needs beyond [`FrameAlloc`].
+trait Subject: FrameAlloc {
+    /// Frees of frames not handed out panic instead of corrupting state.
+    const CHECKS_FREES: bool;
+
+    /// Frames free right now, out of `total`.
+    fn free(&self, total: u64) -> u64;
+
+    /// Give back a block of `2^order` frames; false if this allocator
+    /// never takes frames back.
+    fn give_back(&mut self, frame: PhysFrame, order: u32) -> bool;
+
+    /// A block of `2^order` frames, aligned to its size.
+    fn alloc_block(&mut self, order: u32) -> Option<PhysFrame> {
+        if order == 0 {
+            self.alloc_frame()
+        } else {
+            None
+        }
+    }
+}
+
+impl Subject for BumpAllocator<'_> {
+    const CHECKS_FREES: bool = false;
+
+    fn free(&self, total: u64) -> u64 {
+        total - self.allocated_count() as u64
+    }
+
+    fn give_back(&mut self, _: PhysFrame, _: u32) -> bool {
+        false
+    }
+}
+
+impl Subject for BitmapAllocator<'_> {
+    const CHECKS_FREES: bool = true;
+
+    fn free(&self, _: u64) -> u64 {
+        self.free_count()
+    }
+
+    fn give_back(&mut self, frame: PhysFrame, order: u32) -> bool {
+        for k in 0..1u64 << order {
+            self.deallocate(PhysFrame(frame.0 + k * FRAME_SIZE));
+        }
+        true
+    }
+}
+
+impl Subject for BuddyAllocator<'_> {
+    const CHECKS_FREES: bool = true;
+
+    fn free(&self, _: u64) -> u64 {
+        self.free_count()
+    }
+
+    fn give_back(&mut self, frame: PhysFrame, order: u32) -> bool {
+        self.deallocate(frame, order);
+        true
+    }
+
+    fn alloc_block(&mut self, order: u32) -> Option<PhysFrame> {
+        self.allocate(order)
+    }
+}
+
+impl Subject for FreeListAllocator<Ram> {
+    // A bad free just goes on the list; nothing to check here.
+    const CHECKS_FREES: bool = false;
+
+    fn free(&self, _: u64) -> u64 {
+        self.free_count()
+    }
+
+    fn give_back(&mut self, frame: PhysFrame, order: u32) -> bool {
+        assert_eq!(order, 0);
+        // SAFETY: the harness only gives back frames it holds.
+        unsafe { self.deallocate(frame) };
+        true
+    }
+}
+
+/// Fake RAM from physical 0, for the free list's links.
+struct Ram(Vec<u64>);
+
+impl PhysMapper for Ram {
+    unsafe fn map(&mut self, phys: u64, len: usize) -> *mut u8 {
+        assert!(
+            phys as usize + len <= self.0.len() * 8,
+            "mapped outside fake RAM"
+        );
+        self.0.as_mut_ptr().add(phys as usize / 8) as *mut u8
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    Alloc,
+    Block(u32),
+    Contiguous(usize, u32),
+    Free(usize),
+    /// Free a block of frames none of which is handed out.
+    FreeUnheld(u64, u32),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        4 => Just(Op::Alloc),
+        2 => (0u32..5).prop_map(Op::Block),
+        3 => (1usize..20, 0u32..5).prop_map(|(n, a)| Op::Contiguous(n, a)),
+        4 => any::<usize>().prop_map(Op::Free),
+        1 => (0u64..0x200, 0u32..3).prop_map(|(i, o)| Op::FreeUnheld(i, o)),
+    ]
+}
+
+/// Runs of usable and reserved memory with holes between them, all
+/// below 0x200 frames.
+fn map() -> impl Strategy<Value = Vec<MemRegion>> {
+    proptest::collection::vec((0u64..40, 1u64..40, any::<bool>()), 1..6).prop_map(|runs| {
+        let mut map = Vec::new();
+        let mut at = 0x1000;
+        for (gap, len, usable) in runs {
+            at += gap * FRAME_SIZE;
+            map.push(MemRegion {
+                start: at,
+                len: len * FRAME_SIZE,
+                kind: if usable { 1 } else { 2 },
+            });
+            at += len * FRAME_SIZE;
+        }
+        map
+    })
+}
+
+fn check<A: Subject>(map: &[MemRegion], alloc: &mut A, ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let usable = |f: u64| {
+        map.iter()
+            .any(|r| r.kind == 1 && r.start <= f && f < r.end())
+    };
+    let total = usable_frame_count(map);
+    prop_assert_eq!(alloc.free(total), total);
+
+    let mut used = BTreeSet::new();
+    let mut held: Vec<(PhysFrame, u32)> = Vec::new();
+    for op in ops {
+        let got: Vec<(PhysFrame, u32)> = match op {
+            Op::Alloc => alloc.alloc_frame().map(|f| (f, 0)).into_iter().collect(),
+            Op::Block(order) => {
+                let block = alloc.alloc_block(order);
+                if let Some(f) = block {
+                    prop_assert_eq!(f.0 % (FRAME_SIZE << order), 0);
+                }
+                block.map(|f| (f, order)).into_iter().collect()
+            }
+            Op::Contiguous(n, a) => {
+                let align = FRAME_SIZE << a;
+                let range = alloc.alloc_contiguous(n, align);
+                if let Some(r) = range {
+                    prop_assert_eq!((r.start.0 % align, r.len()), (0, n as u64));
+                }
+                range.into_iter().flatten().map(|f| (f, 0)).collect()
+            }
+            Op::Free(i) => {
+                if !held.is_empty() {
+                    let (f, order) = held.swap_remove(i % held.len());
+                    if alloc.give_back(f, order) {
+                        for k in 0..1u64 << order {
+                            used.remove(&(f.0 + k * FRAME_SIZE));
+                        }
+                    } else {
+                        held.push((f, order));
+                    }
+                }
+                Vec::new()
+            }
+            Op::FreeUnheld(i, order) => {
+                let f = PhysFrame((i << order) * FRAME_SIZE);
+                let frames = (0..1u64 << order).map(|k| f.0 + k * FRAME_SIZE);
+                if A::CHECKS_FREES && !frames.clone().any(|a| used.contains(&a)) {
+                    let before = alloc.free(total);
+                    let freed = catch_unwind(AssertUnwindSafe(|| alloc.give_back(f, order)));
+                    prop_assert!(
+                        freed.is_err(),
+                        "freeing {:?} at order {} was accepted",
+                        f,
+                        order
+                    );
+                    prop_assert_eq!(alloc.free(total), before);
+                }
+                Vec::new()
+            }
+        };
+        for (f, order) in got {
+            for k in 0..1u64 << order {
+                let a = f.0 + k * FRAME_SIZE;
+                prop_assert!(usable(a), "{:#x} is not usable", a);
+                prop_assert!(used.insert(a), "{:#x} handed out twice", a);
+            }
+            held.push((f, order));
+        }
+        prop_assert_eq!(alloc.free(total) + used.len() as u64, total);
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn bump_allocator(map in map(), ops in proptest::collection::vec(op(), 0..200)) {
+        check(&map, &mut BumpAllocator::new(&map), ops)?;
+    }
+
+    #[test]
+    fn bitmap_allocator(map in map(), ops in proptest::collection::vec(op(), 0..200)) {
+        let mut storage = vec![0; BitmapAllocator::storage_words(&map)];
+        check(&map, &mut BitmapAllocator::new(&map, &mut storage).unwrap(), ops)?;
+    }
+
+    #[test]
+    fn buddy_allocator(map in map(), ops in proptest::collection::vec(op(), 0..200)) {
+        let mut storage = vec![0; BuddyAllocator::storage_words(&map)];
+        check(&map, &mut BuddyAllocator::new(&map, &mut storage).unwrap(), ops)?;
+    }
+
+    #[test]
+    fn free_list_allocator(map in map(), ops in proptest::collection::vec(op(), 0..200)) {
+        let end = map.iter().map(|r| r.end()).max().unwrap_or(0);
+        let ram = Ram(vec![0; end as usize / 8]);
+        check(&map, &mut unsafe { FreeListAllocator::init(&map, ram) }, ops)?;
+    }
+}
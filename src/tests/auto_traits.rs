@@ -0,0 +1,53 @@
+#![cfg(all(test, feature = "std"))]
+
+// auto_traits.rs
+//
+// What lib.rs promises about Clone/Send/Sync, exercised: a cloned
+// iterator resumes where the original was, and one boot map can be read
+// from several threads at once (SMP bring-up: every AP walks the same
+// map the BSP parsed).
+
+use crate::frames::UsableFrames;
+use crate::raw::e820::E820Iter;
+use crate::raw::{Mb1MmapIter, MemRegion};
+use crate::vectors::{E820_PC, MB1_PC};
+
+#[test]
+fn cloned_iterators_are_checkpoints() {
+    let mut it = Mb1MmapIter::new(MB1_PC.bytes);
+    it.next();
+    let checkpoint = it.clone();
+    let rest: Vec<_> = it.collect();
+    pretty_assertions::assert_eq!(checkpoint.collect::<Vec<_>>(), rest);
+
+    let regions = [MemRegion {
+        start: 0,
+        len: 0x4000,
+        kind: 1,
+    }];
+    let mut frames = UsableFrames::new(&regions);
+    frames.next();
+    let saved = frames.clone();
+    pretty_assertions::assert_eq!(frames.count(), 3);
+    pretty_assertions::assert_eq!(saved.count(), 3);
+}
+
+#[test]
+fn one_map_many_readers() {
+    let parsed: Vec<_> = E820Iter::new(E820_PC.bytes, 20)
+        .unwrap()
+        .map(|e| e.unwrap())
+        .collect();
+    std::thread::scope(|s| {
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                // Each thread gets its own iterator over the same bytes.
+                let it = E820Iter::new(E820_PC.bytes, 20).unwrap();
+                s.spawn(move || it.map(|e| e.unwrap()).collect::<Vec<_>>())
+            })
+            .collect();
+        for r in readers {
+            pretty_assertions::assert_eq!(r.join().unwrap(), parsed);
+        }
+    });
+}
@@ -10,6 +10,7 @@ pub fn init() {
     });
 }
 
+#[allow(clippy::module_inception)]
 pub mod prelude {
     pub use crate::tests::common::init;
     pub use hex::{decode as hex_decode, encode as hex_encode};
@@ -0,0 +1,230 @@
+#![cfg(all(test, feature = "std"))]
+
+// progress.rs
+//
+// The one rule every iterator in this crate must keep: each `next()` either
+// moves forward or ends. Hostile firmware controls the size fields, the
+// lengths and the addresses, so none of them may be able to stall a loop
+// or wrap it around to the beginning.
+//
+// Every test here bounds the iterator with `take(limit + 1)`, so a
+// regression fails the assertion instead of hanging the test run.
+
+use proptest::prelude::*;
+
+use crate::frames::{AlignedChunks, RegionFrames, UsableRuns, FRAME_SIZE};
+use crate::raw::coreboot::{ip_checksum, CorebootTable, LB_HEADER_LEN, LB_SIGNATURE};
+use crate::raw::e820::E820Iter;
+use crate::raw::mb2::{Mb2MmapIter, ENTRY_SIZE, TAG_TYPE_MMAP};
+use crate::raw::srat::{SratIter, SRAT_HEADER_LEN, SRAT_SIGNATURE};
+use crate::raw::uefi::UefiMmapIter;
+use crate::raw::{Mb1MmapIter, MemRegion};
+
+/// Smallest possible MB1 entry: size field plus 20 payload bytes.
+const MIN_MB1_ENTRY: usize = 24;
+
+/// An MB1 walk over `buf` yields at most one item per minimal entry (plus
+/// one trailing error), and an error is always the last item.
+fn check_mb1(buf: &[u8]) -> Result<(), TestCaseError> {
+    let limit = buf.len() / MIN_MB1_ENTRY + 1;
+    let items: Vec<_> = Mb1MmapIter::new(buf).take(limit + 1).collect();
+    prop_assert!(
+        items.len() <= limit,
+        "{} items from {} bytes",
+        items.len(),
+        buf.len()
+    );
+    if let Some(pos) = items.iter().position(|r| r.is_err()) {
+        prop_assert_eq!(pos, items.len() - 1, "iteration continued after an error");
+    }
+    Ok(())
+}
+
+/// Same for one MB2 tag: at most one item per minimal entry plus an error.
+fn check_mb2(tag: &[u8]) -> Result<(), TestCaseError> {
+    let Ok(it) = Mb2MmapIter::new(tag) else {
+        return Ok(());
+    };
+    let limit = tag.len() / ENTRY_SIZE as usize + 1;
+    let items: Vec<_> = it.take(limit + 1).collect();
+    prop_assert!(items.len() <= limit);
+    if let Some(pos) = items.iter().position(|r| r.is_err()) {
+        prop_assert_eq!(pos, items.len() - 1, "iteration continued after an error");
+    }
+    Ok(())
+}
+
+/// Size fields a broken or malicious loader might write.
+fn hostile_size() -> impl Strategy<Value = u32> {
+    prop_oneof![
+        Just(0u32),
+        Just(19),
+        Just(20),
+        Just(21),
+        Just(u32::MAX),
+        Just(u32::MAX - 3),
+        any::<u32>(),
+    ]
+}
+
+/// Addresses clustered at the edges where arithmetic wraps.
+fn edge_addr() -> impl Strategy<Value = u64> {
+    prop_oneof![
+        Just(0u64),
+        (0u64..0x10_0000),
+        (u64::MAX - 0x10_0000..=u64::MAX),
+        any::<u64>(),
+    ]
+}
+
+fn hostile_region() -> impl Strategy<Value = MemRegion> {
+    (
+        edge_addr(),
+        edge_addr(),
+        prop_oneof![Just(1u32), any::<u32>()],
+    )
+        .prop_map(|(start, len, kind)| MemRegion { start, len, kind })
+}
+
+proptest! {
+    #[test]
+    fn mb1_arbitrary_bytes_terminate(buf in proptest::collection::vec(any::<u8>(), 0..512)) {
+        check_mb1(&buf)?;
+    }
+
+    #[test]
+    fn mb1_hostile_size_fields_terminate(
+        entries in proptest::collection::vec((hostile_size(), proptest::collection::vec(any::<u8>(), 0..40)), 0..16)
+    ) {
+        let mut buf = Vec::new();
+        for (size, payload) in entries {
+            buf.extend_from_slice(&size.to_le_bytes());
+            buf.extend_from_slice(&payload);
+        }
+        check_mb1(&buf)?;
+    }
+
+    #[test]
+    fn mb2_hostile_headers_terminate(
+        tag_size in hostile_size(),
+        entry_size in hostile_size(),
+        body in proptest::collection::vec(any::<u8>(), 0..256),
+    ) {
+        let mut tag = Vec::new();
+        tag.extend_from_slice(&TAG_TYPE_MMAP.to_le_bytes());
+        tag.extend_from_slice(&tag_size.to_le_bytes());
+        tag.extend_from_slice(&entry_size.to_le_bytes());
+        tag.extend_from_slice(&0u32.to_le_bytes());
+        tag.extend_from_slice(&body);
+        check_mb2(&tag)?;
+
+        // And once more with a tag size that matches the buffer.
+        let exact = tag.len() as u32;
+        tag[4..8].copy_from_slice(&exact.to_le_bytes());
+        check_mb2(&tag)?;
+    }
+
+    #[test]
+    fn e820_any_entry_size_terminates(
+        entry_size in hostile_size(),
+        buf in proptest::collection::vec(any::<u8>(), 0..256),
+    ) {
+        if let Ok(it) = E820Iter::new(&buf, entry_size) {
+            let limit = buf.len() / 20 + 1;
+            prop_assert!(it.take(limit + 1).count() <= limit);
+        }
+    }
+
+    #[test]
+    fn uefi_any_stride_terminates(
+        stride in hostile_size(),
+        buf in proptest::collection::vec(any::<u8>(), 0..512),
+    ) {
+        if let Ok(it) = UefiMmapIter::new(&buf, stride) {
+            let limit = buf.len() / 40 + 1;
+            prop_assert!(it.take(limit + 1).count() <= limit);
+        }
+    }
+
+    #[test]
+    fn srat_arbitrary_subtables_terminate(body in proptest::collection::vec(any::<u8>(), 0..256)) {
+        let mut table = Vec::new();
+        table.extend_from_slice(&SRAT_SIGNATURE);
+        table.extend_from_slice(&((SRAT_HEADER_LEN + body.len()) as u32).to_le_bytes());
+        table.resize(SRAT_HEADER_LEN, 0);
+        table.extend_from_slice(&body);
+        let sum = table.iter().fold(0u8, |s, b| s.wrapping_add(*b));
+        table[9] = 0u8.wrapping_sub(sum);
+
+        let it = SratIter::new(&table).unwrap();
+        // Every subtable is at least 2 bytes long.
+        let limit = body.len() / 2 + 1;
+        prop_assert!(it.take(limit + 1).count() <= limit);
+    }
+
+    #[test]
+    fn coreboot_arbitrary_records_terminate(
+        entries in hostile_size(),
+        body in proptest::collection::vec(any::<u8>(), 0..256),
+    ) {
+        let mut table = Vec::new();
+        table.extend_from_slice(&LB_SIGNATURE);
+        for v in [LB_HEADER_LEN as u32, 0, body.len() as u32, ip_checksum(&body) as u32, entries] {
+            table.extend_from_slice(&v.to_le_bytes());
+        }
+        let sum = ip_checksum(&table) as u32;
+        table[8..12].copy_from_slice(&sum.to_le_bytes());
+        table.extend_from_slice(&body);
+
+        let cb = CorebootTable::new(&table).unwrap();
+        // Every record is at least its 8-byte header.
+        let limit = body.len() / 8 + 1;
+        prop_assert!(cb.records().take(limit + 1).count() <= limit);
+        if let Ok(mem) = cb.memory() {
+            prop_assert!(mem.take(limit + 1).count() <= limit);
+        }
+    }
+
+    #[test]
+    fn usable_runs_yield_at_most_one_run_per_region(
+        regions in proptest::collection::vec(hostile_region(), 0..16)
+    ) {
+        let n = UsableRuns::new(&regions).take(regions.len() + 1).count();
+        prop_assert!(n <= regions.len());
+    }
+
+    #[test]
+    fn aligned_chunks_strictly_advance(
+        regions in proptest::collection::vec(hostile_region(), 0..16)
+    ) {
+        // At most one ramp up and one ramp down of orders per region.
+        let limit = regions.len() * 2 * 64;
+        let chunks: Vec<_> = AlignedChunks::new(&regions, 63).take(limit + 1).collect();
+        prop_assert!(chunks.len() <= limit);
+    }
+
+    #[test]
+    fn region_frames_strictly_increase(
+        start in edge_addr(),
+        shift in 12u32..63,
+        count in 0u64..64,
+    ) {
+        let size = 1u64 << shift;
+        let region = MemRegion { start, len: count.saturating_mul(size), kind: 1 };
+        let it = RegionFrames::new(region, size);
+        let expected = it.len();
+        let frames: Vec<u64> = it.take(expected + 1).map(|f| f.0).collect();
+        prop_assert_eq!(frames.len(), expected);
+        prop_assert!(frames.windows(2).all(|w| w[0] < w[1]));
+        prop_assert!(frames.iter().all(|f| f % FRAME_SIZE == 0));
+    }
+}
+
+#[test]
+fn mb1_zero_size_every_entry() {
+    // A whole buffer of size==0 headers must still end after one error.
+    let buf = [0u8; 4 * 64];
+    let items: Vec<_> = Mb1MmapIter::new(&buf).take(2).collect();
+    assert_eq!(items.len(), 1);
+    assert!(items[0].is_err());
+}
@@ -0,0 +1,222 @@
+// tier.rs
+//
+// Heterogeneous memory: HBM next to DDR, or DDR next to CXL-attached
+// memory. All of it is plain usable RAM in the memory map; what differs
+// is how fast it is, and the map has no field for that. ACPI HMAT does,
+// per NUMA node, but parsing HMAT and deciding what counts as "fast" is
+// policy, so the tier is caller-supplied.
+//
+// TieredMap keeps one region list per tier (lower tier number = faster),
+// TieredFrames one frame iterator per tier, and a Fallback says where to
+// look when the preferred tier runs dry.
+
+use alloc::vec::Vec;
+
+use crate::frames::{PhysFrame, UsableFrames};
+use crate::raw::srat::{split_by_node, MemoryAffinity, NodeRegion, MEM_AFFINITY_ENABLED};
+use crate::raw::MemRegion;
+
+/// Performance tier. Lower is faster; 0 is the best memory on the box.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tier(pub u8);
+
+/// A caller-supplied claim: `[start, start + len)` is tier `tier`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TierRange {
+    pub start: u64,
+    pub len: u64,
+    pub tier: Tier,
+}
+
+/// Where an allocation may go when its preferred tier is empty.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Fallback {
+    /// Only the preferred tier.
+    Strict,
+    /// The preferred tier, then slower ones in order. Never takes faster
+    /// memory someone else may be waiting for.
+    #[default]
+    Slower,
+    /// The preferred tier, then slower ones, then faster ones.
+    Any,
+}
+
+/// Regions grouped by tier, fastest first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TieredMap {
+    tiers: Vec<(Tier, Vec<MemRegion>)>,
+}
+
+impl TieredMap {
+    /// Split `regions` at `ranges` boundaries. Memory no range covers
+    /// gets `default`. Overlapping ranges resolve like SRAT entries (the
+    /// lower-addressed one wins).
+    pub fn new(regions: &[MemRegion], ranges: &[TierRange], default: Tier) -> Self {
+        let as_affinity: Vec<MemoryAffinity> = ranges
+            .iter()
+            .map(|r| MemoryAffinity {
+                start: r.start,
+                len: r.len,
+                node: u32::from(r.tier.0),
+                flags: MEM_AFFINITY_ENABLED,
+            })
+            .collect();
+        let pieces = split_by_node(regions, &as_affinity);
+        Self::from_node_regions(&pieces, |node| node.map_or(default, |n| Tier(n as u8)))
+    }
+
+    /// Group NUMA-tagged regions (see `split_by_node`) with a node-to-tier
+    /// mapping, typically built from HMAT latencies.
+    pub fn from_node_regions(pieces: &[NodeRegion], tier_of: impl Fn(Option<u32>) -> Tier) -> Self {
+        let mut map = TieredMap::default();
+        for p in pieces {
+            let tier = tier_of(p.node);
+            let at = match map.tiers.binary_search_by_key(&tier, |(t, _)| *t) {
+                Ok(at) => at,
+                Err(at) => {
+                    map.tiers.insert(at, (tier, Vec::new()));
+                    at
+                }
+            };
+            map.tiers[at].1.push(p.region);
+        }
+        for (_, regions) in &mut map.tiers {
+            regions.sort();
+        }
+        map
+    }
+
+    /// Tiers present, fastest first.
+    pub fn tiers(&self) -> impl Iterator<Item = Tier> + '_ {
+        self.tiers.iter().map(|(t, _)| *t)
+    }
+
+    /// Regions of `tier`, sorted by start. Empty if the tier is absent.
+    pub fn regions(&self, tier: Tier) -> &[MemRegion] {
+        self.tiers
+            .iter()
+            .find(|(t, _)| *t == tier)
+            .map_or(&[], |(_, r)| r)
+    }
+
+    /// One frame iterator per tier.
+    pub fn frames(&self) -> TieredFrames<'_> {
+        TieredFrames {
+            tiers: self
+                .tiers
+                .iter()
+                .map(|(t, r)| (*t, UsableFrames::new(r)))
+                .collect(),
+        }
+    }
+}
+
+/// Per-tier frame iterators, see [`TieredMap::frames`].
+#[derive(Clone, Debug)]
+pub struct TieredFrames<'a> {
+    tiers: Vec<(Tier, UsableFrames<'a>)>,
+}
+
+impl<'a> TieredFrames<'a> {
+    /// A frame from `preferred`, or from wherever `fallback` allows. Also
+    /// returns the tier it came from.
+    pub fn allocate(&mut self, preferred: Tier, fallback: Fallback) -> Option<(Tier, PhysFrame)> {
+        // Tiers are sorted, so "slower" is everything after the split.
+        let split = self.tiers.partition_point(|(t, _)| *t < preferred);
+        let (faster, rest) = self.tiers.split_at_mut(split);
+        let rest = rest
+            .iter_mut()
+            .filter(|(t, _)| fallback != Fallback::Strict || *t == preferred);
+        let faster = faster
+            .iter_mut()
+            .rev()
+            .filter(|_| fallback == Fallback::Any);
+        rest.chain(faster)
+            .find_map(|(t, frames)| frames.next().map(|f| (*t, f)))
+    }
+
+    /// A frame from exactly `tier`.
+    pub fn allocate_from(&mut self, tier: Tier) -> Option<PhysFrame> {
+        self.allocate(tier, Fallback::Strict).map(|(_, f)| f)
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    const HBM: Tier = Tier(0);
+    const DDR: Tier = Tier(1);
+    const CXL: Tier = Tier(2);
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn range(start: u64, len: u64, tier: Tier) -> TierRange {
+        TierRange { start, len, tier }
+    }
+
+    // 0x0000..0x2000 DDR, 0x2000..0x3000 HBM, 0x3000..0x4000 CXL.
+    fn map() -> TieredMap {
+        TieredMap::new(
+            &[region(0, 0x4000, 1)],
+            &[range(0x2000, 0x1000, HBM), range(0x3000, 0x1000, CXL)],
+            DDR,
+        )
+    }
+
+    #[test]
+    fn ranges_split_regions_into_tiers() {
+        init();
+        let m = map();
+        pretty_assertions::assert_eq!(m.tiers().collect::<Vec<_>>(), vec![HBM, DDR, CXL]);
+        pretty_assertions::assert_eq!(m.regions(HBM), &[region(0x2000, 0x1000, 1)]);
+        pretty_assertions::assert_eq!(m.regions(DDR), &[region(0, 0x2000, 1)]);
+        pretty_assertions::assert_eq!(m.regions(Tier(9)), &[]);
+    }
+
+    #[test]
+    fn slower_fallback_never_takes_faster_memory() {
+        let m = map();
+        let mut frames = m.frames();
+        let got: Vec<_> = core::iter::from_fn(|| frames.allocate(DDR, Fallback::Slower)).collect();
+        pretty_assertions::assert_eq!(
+            got,
+            vec![
+                (DDR, PhysFrame(0)),
+                (DDR, PhysFrame(0x1000)),
+                (CXL, PhysFrame(0x3000)),
+            ]
+        );
+        // HBM was left alone.
+        pretty_assertions::assert_eq!(frames.allocate_from(HBM), Some(PhysFrame(0x2000)));
+    }
+
+    #[test]
+    fn strict_and_any() {
+        let m = map();
+        let mut frames = m.frames();
+        pretty_assertions::assert_eq!(
+            frames.allocate(HBM, Fallback::Strict),
+            Some((HBM, PhysFrame(0x2000)))
+        );
+        pretty_assertions::assert_eq!(frames.allocate(HBM, Fallback::Strict), None);
+
+        // Any: CXL first, then the nearest faster tier.
+        pretty_assertions::assert_eq!(
+            frames.allocate(CXL, Fallback::Any),
+            Some((CXL, PhysFrame(0x3000)))
+        );
+        pretty_assertions::assert_eq!(
+            frames.allocate(CXL, Fallback::Any),
+            Some((DDR, PhysFrame(0)))
+        );
+    }
+}
@@ -0,0 +1,194 @@
+// tree.rs
+//
+// The flat sorted Vec<MemRegion> is the right shape at boot: built once,
+// walked in order, binary-searched. A kernel that keeps editing the map at
+// runtime (hotplug, ballooning, carving out and returning ranges) pays
+// O(n) for every insert into the middle of it.
+//
+// RegionTree keeps the same canonical map in an ordered tree keyed by
+// start. Because canonical regions never overlap, the classic interval
+// tree augmentation (max end per subtree) is unnecessary: the only region
+// that can contain `addr` is the one with the greatest start <= addr.
+// Lookups and edits are O(log n + regions touched).
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::raw::MemRegion;
+
+/// A canonical map (sorted, non-overlapping, same-kind neighbours merged)
+/// that stays canonical under edits.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RegionTree {
+    // start -> (end, kind); end is exclusive.
+    regions: BTreeMap<u64, (u64, u32)>,
+}
+
+impl RegionTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build from any list of regions. Later regions win where they
+    /// overlap earlier ones; pass a canonical map for the usual meaning.
+    pub fn from_regions(regions: &[MemRegion]) -> Self {
+        let mut tree = Self::new();
+        for &r in regions {
+            tree.set(r);
+        }
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// The region containing `addr`, if any.
+    pub fn get(&self, addr: u64) -> Option<MemRegion> {
+        let (&start, &(end, kind)) = self.regions.range(..=addr).next_back()?;
+        (addr < end).then_some(MemRegion {
+            start,
+            len: end - start,
+            kind,
+        })
+    }
+
+    /// Give `region` its kind, replacing whatever was there.
+    pub fn set(&mut self, region: MemRegion) {
+        if region.len == 0 {
+            return;
+        }
+        let (mut start, mut end) = (region.start, region.end());
+        self.clear(start..end);
+
+        // Merge with same-kind neighbours so the map stays canonical.
+        if let Some((&s, &(e, k))) = self.regions.range(..start).next_back() {
+            if e == start && k == region.kind {
+                self.regions.remove(&s);
+                start = s;
+            }
+        }
+        if let Some(&(e, k)) = self.regions.get(&end) {
+            if k == region.kind {
+                self.regions.remove(&end);
+                end = e;
+            }
+        }
+        self.regions.insert(start, (end, region.kind));
+    }
+
+    /// Forget everything inside `range`, splitting regions at its edges.
+    pub fn clear(&mut self, range: Range<u64>) {
+        if range.start >= range.end {
+            return;
+        }
+        // A region starting before the range may stick into it (or past it).
+        if let Some((&s, &(e, k))) = self.regions.range(..range.start).next_back() {
+            if e > range.start {
+                self.regions.insert(s, (range.start, k));
+                if e > range.end {
+                    self.regions.insert(range.end, (e, k));
+                }
+            }
+        }
+        let inside: Vec<u64> = self.regions.range(range.clone()).map(|(&s, _)| s).collect();
+        for s in inside {
+            if let Some((e, k)) = self.regions.remove(&s) {
+                if e > range.end {
+                    self.regions.insert(range.end, (e, k));
+                }
+            }
+        }
+    }
+
+    /// Regions in address order.
+    pub fn iter(&self) -> impl Iterator<Item = MemRegion> + '_ {
+        self.regions.iter().map(|(&start, &(end, kind))| MemRegion {
+            start,
+            len: end - start,
+            kind,
+        })
+    }
+
+    /// Back to the flat form.
+    pub fn to_vec(&self) -> Vec<MemRegion> {
+        self.iter().collect()
+    }
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::compose::{compose, MapSource, Override};
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    #[test]
+    fn set_splits_and_merges() {
+        init();
+        let mut t = RegionTree::from_regions(&[region(0, 0x10000, 1)]);
+        t.set(region(0x4000, 0x2000, 2));
+        pretty_assertions::assert_eq!(
+            t.to_vec(),
+            vec![
+                region(0, 0x4000, 1),
+                region(0x4000, 0x2000, 2),
+                region(0x6000, 0xA000, 1),
+            ]
+        );
+        pretty_assertions::assert_eq!(t.get(0x5FFF), Some(region(0x4000, 0x2000, 2)));
+        pretty_assertions::assert_eq!(t.get(0x10000), None);
+
+        // Returning the range merges everything back into one region.
+        t.set(region(0x4000, 0x2000, 1));
+        pretty_assertions::assert_eq!(t.to_vec(), vec![region(0, 0x10000, 1)]);
+    }
+
+    #[test]
+    fn clear_punches_holes() {
+        let mut t = RegionTree::from_regions(&[region(0, 0x3000, 1), region(0x3000, 0x3000, 2)]);
+        t.clear(0x2000..0x4000);
+        pretty_assertions::assert_eq!(
+            t.to_vec(),
+            vec![region(0, 0x2000, 1), region(0x4000, 0x2000, 2)]
+        );
+        pretty_assertions::assert_eq!(t.get(0x3000), None);
+    }
+
+    proptest! {
+        #[test]
+        fn edits_match_compose(
+            edits in proptest::collection::vec((0u64..64, 1u64..16, 1u32..4), 0..24)
+        ) {
+            let base = [region(0, 64 * 0x1000, 1)];
+            let overrides: Vec<Override> = edits
+                .iter()
+                .map(|&(s, l, k)| Override {
+                    region: region(s * 0x1000, l * 0x1000, k),
+                    source: MapSource::KernelCarveOut,
+                })
+                .collect();
+
+            let mut t = RegionTree::from_regions(&base);
+            for o in &overrides {
+                t.set(o.region);
+            }
+            prop_assert_eq!(t.to_vec(), compose(&base, &overrides));
+        }
+    }
+}
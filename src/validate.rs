@@ -0,0 +1,209 @@
+// validate.rs
+//
+// Everything wrong with an MB1 mmap blob, in one pass.
+//
+// The iterator stops at the first framing error, which is right for a
+// kernel but useless when staring at a corrupted dump: fix one problem,
+// rerun, find the next. validate() keeps going wherever the framing
+// allows, and also flags things that parse fine but are suspicious
+// (entries sanitize drops, overlaps, entries out of order).
+//
+// Errors mean entries were lost; warnings mean the map is usable but
+// odd. Either way `regions` holds every entry that could be read and
+// survived sanitize, in blob order.
+
+use alloc::vec::Vec;
+
+use crate::blob::Endian;
+use crate::raw::{read_one_with, sanitize_all, MemRegion, MmapError};
+use crate::rejection::RejectionReason;
+
+/// Where in the blob a finding is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Location {
+    /// Byte offset of the entry's size field.
+    pub offset: usize,
+    /// Entry number, counting every entry the walk reached.
+    pub index: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Warning {
+    /// Parsed, but sanitize drops it.
+    Dropped(RejectionReason),
+    /// Starts below the entry before it.
+    Unsorted { previous: usize },
+    /// Shares bytes with an earlier entry.
+    Overlap { with: usize },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Every entry that parsed and survived sanitize.
+    pub regions: Vec<MemRegion>,
+    pub errors: Vec<(Location, MmapError)>,
+    pub warnings: Vec<(Location, Warning)>,
+}
+
+impl ValidationReport {
+    /// No entry was lost to a framing error.
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Nothing to report at all.
+    pub fn is_clean(&self) -> bool {
+        self.errors.is_empty() && self.warnings.is_empty()
+    }
+}
+
+/// Walk the whole little-endian MB1 mmap `buf` and report every problem.
+///
+/// An entry whose size field is too small is skipped by the size it
+/// claims (as GRUB would) and the walk goes on; a truncated header or
+/// entry ends it, since nothing after it can be framed.
+pub fn validate(buf: &[u8]) -> ValidationReport {
+    let mut report = ValidationReport::default();
+    // Index of each kept region, for overlap / order warnings.
+    let mut kept: Vec<usize> = Vec::new();
+    let mut offset = 0usize;
+    let mut index = 0usize;
+
+    while offset < buf.len() {
+        let at = Location { offset, index };
+        index += 1;
+        let (entry, consumed) = match read_one_with(&buf[offset..], Endian::Little) {
+            Ok(ok) => ok,
+            Err(MmapError::SizeTooSmall { size }) => {
+                report.errors.push((at, MmapError::SizeTooSmall { size }));
+                offset += 4 + size as usize;
+                continue;
+            }
+            Err(e) => {
+                report.errors.push((at, e));
+                break;
+            }
+        };
+        offset += consumed;
+
+        let (regions, rejected) = sanitize_all([entry]);
+        if let Some(r) = rejected.first() {
+            report.warnings.push((at, Warning::Dropped(r.reason)));
+        }
+        let Some(&region) = regions.first() else {
+            continue;
+        };
+        if let (Some(&last), Some(&previous)) = (report.regions.last(), kept.last()) {
+            if region.start < last.start {
+                report.warnings.push((at, Warning::Unsorted { previous }));
+            }
+        }
+        let earlier = report
+            .regions
+            .iter()
+            .zip(&kept)
+            .find(|(r, _)| r.start < region.end() && region.start < r.end());
+        if let Some((_, &with)) = earlier {
+            report.warnings.push((at, Warning::Overlap { with }));
+        }
+        report.regions.push(region);
+        kept.push(at.index);
+    }
+    report
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::{push_entry, raw, RawEntry};
+    use crate::tests::common::init;
+    use crate::vectors::MB1_PC;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn at(offset: usize, index: usize) -> Location {
+        Location { offset, index }
+    }
+
+    #[test]
+    fn clean_map_has_nothing_to_report() {
+        init();
+        let report = validate(MB1_PC.bytes);
+        assert!(report.is_clean());
+        pretty_assertions::assert_eq!(report.regions, MB1_PC.regions);
+    }
+
+    #[test]
+    fn collects_every_problem_and_keeps_going() {
+        let mut buf = Vec::new();
+        push_entry(&mut buf, raw(0x10_0000, 0x1000, 1));
+        push_entry(&mut buf, raw(0x5000, 0, 1));
+        push_entry(&mut buf, raw(0, 0x10_0800, 2));
+        // A 12-byte entry: too small, skipped by its own size.
+        buf.extend_from_slice(&12u32.to_le_bytes());
+        buf.extend_from_slice(&[0; 12]);
+        push_entry(&mut buf, raw(0x20_0000, 0x1000, 1));
+        // Truncated: claims 20 bytes, has 8.
+        buf.extend_from_slice(&20u32.to_le_bytes());
+        buf.extend_from_slice(&[0; 8]);
+
+        let report = validate(&buf);
+        pretty_assertions::assert_eq!(
+            report.regions,
+            vec![
+                region(0x10_0000, 0x1000, 1),
+                region(0, 0x10_0800, 2),
+                region(0x20_0000, 0x1000, 1),
+            ]
+        );
+        pretty_assertions::assert_eq!(
+            report.errors,
+            vec![
+                (at(72, 3), MmapError::SizeTooSmall { size: 12 }),
+                (
+                    at(112, 5),
+                    MmapError::TruncatedEntry {
+                        needed: 24,
+                        have: 12
+                    }
+                ),
+            ]
+        );
+        pretty_assertions::assert_eq!(
+            report.warnings,
+            vec![
+                (at(24, 1), Warning::Dropped(RejectionReason::ZeroLength)),
+                (at(48, 2), Warning::Unsorted { previous: 0 }),
+                (at(48, 2), Warning::Overlap { with: 0 }),
+            ]
+        );
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn overflowing_entry_is_a_warning() {
+        let mut buf = Vec::new();
+        push_entry(
+            &mut buf,
+            RawEntry {
+                size: 20,
+                base_addr: u64::MAX,
+                length: 2,
+                typ: 1,
+            },
+        );
+        let report = validate(&buf);
+        assert!(report.is_ok());
+        pretty_assertions::assert_eq!(
+            report.warnings,
+            vec![(at(0, 0), Warning::Dropped(RejectionReason::Overflow))]
+        );
+    }
+}
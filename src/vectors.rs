@@ -0,0 +1,327 @@
+// vectors.rs
+//
+// Byte-exact test vectors for every wire format this crate reads or
+// writes, as a public API.
+//
+// Unit tests prove the crate agrees with itself. These prove it agrees
+// with you: a bootloader, VMM or kernel in another project can feed the
+// same bytes to its own encoder/decoder and compare. If both sides pass
+// against the same vectors, they interoperate.
+//
+// Each Vector is the raw bytes plus the regions they mean. Entries use
+// the classic PC layout (640 KiB low RAM, legacy hole, 2 GiB at 1 MiB).
+
+use crate::raw::MemRegion;
+
+/// One encoded blob and the regions it decodes to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Vector {
+    pub name: &'static str,
+    pub bytes: &'static [u8],
+    /// What a decoder should produce (after dropping ignored entries).
+    pub regions: &'static [MemRegion],
+}
+
+const fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+    MemRegion { start, len, kind }
+}
+
+const PC_REGIONS: [MemRegion; 3] = [
+    region(0, 0x9_FC00, 1),
+    region(0x9_FC00, 0x6_0400, 2),
+    region(0x10_0000, 0x7FF0_0000, 1),
+];
+
+const PC_REGIONS_WITHOUT_RESERVED: [MemRegion; 2] = [PC_REGIONS[0], PC_REGIONS[2]];
+
+const PC_CANONICAL: [MemRegion; 3] = [
+    region(0, 0x9_F000, 1),
+    region(0x9_F000, 0x6_1000, 2),
+    region(0x10_0000, 0x7FF0_0000, 1),
+];
+
+const EXTRA_PAYLOAD_REGIONS: [MemRegion; 1] = [region(0x1000, 0x2000, 1)];
+
+/// MB1 mmap, minimal (size = 20) entries.
+pub const MB1_PC: Vector = Vector {
+    name: "mb1_pc",
+    bytes: &MB1_PC_BYTES,
+    regions: &PC_REGIONS,
+};
+
+/// MB1 entry with size = 28; the 8 extra payload bytes must be skipped.
+pub const MB1_EXTRA_PAYLOAD: Vector = Vector {
+    name: "mb1_extra_payload",
+    bytes: &MB1_EXTRA_PAYLOAD_BYTES,
+    regions: &EXTRA_PAYLOAD_REGIONS,
+};
+
+/// Complete Multiboot2 mmap tag, header included.
+pub const MB2_PC: Vector = Vector {
+    name: "mb2_pc",
+    bytes: &MB2_PC_BYTES,
+    regions: &PC_REGIONS,
+};
+
+/// E820 array, 20-byte entries.
+pub const E820_PC: Vector = Vector {
+    name: "e820_pc",
+    bytes: &E820_PC_BYTES,
+    regions: &PC_REGIONS,
+};
+
+/// E820 array, 24-byte ACPI 3.0 entries. The middle entry has its
+/// "enabled" attribute clear and must be ignored.
+pub const E820_EXT_ATTRS: Vector = Vector {
+    name: "e820_ext_attrs",
+    bytes: &E820_EXT_ATTRS_BYTES,
+    regions: &PC_REGIONS_WITHOUT_RESERVED,
+};
+
+/// GetMemoryMap() output with a 48-byte descriptor stride: conventional,
+/// runtime services data, boot services data.
+pub const UEFI_PC: Vector = Vector {
+    name: "uefi_pc",
+    bytes: &UEFI_PC_BYTES,
+    regions: &PC_CANONICAL,
+};
+
+/// Measured-boot serialization of a canonical map (see `measure`).
+pub const MEASURE_PC: Vector = Vector {
+    name: "measure_pc",
+    bytes: &MEASURE_PC_BYTES,
+    regions: &PC_CANONICAL,
+};
+
+pub const MB1: &[Vector] = &[MB1_PC, MB1_EXTRA_PAYLOAD];
+pub const MB2: &[Vector] = &[MB2_PC];
+pub const E820: &[Vector] = &[E820_PC, E820_EXT_ATTRS];
+pub const UEFI: &[Vector] = &[UEFI_PC];
+pub const MEASURE: &[Vector] = &[MEASURE_PC];
+
+#[rustfmt::skip]
+const MB1_PC_BYTES: [u8; 72] = [
+    0x14, 0x00, 0x00, 0x00, // size = 20
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // base_addr = 0x0
+    0x00, 0xfc, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0x9fc00
+    0x01, 0x00, 0x00, 0x00, // type = 1
+    0x14, 0x00, 0x00, 0x00, // size = 20
+    0x00, 0xfc, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // base_addr = 0x9fc00
+    0x00, 0x04, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0x60400
+    0x02, 0x00, 0x00, 0x00, // type = 2
+    0x14, 0x00, 0x00, 0x00, // size = 20
+    0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, // base_addr = 0x100000
+    0x00, 0x00, 0xf0, 0x7f, 0x00, 0x00, 0x00, 0x00, // length = 0x7ff00000
+    0x01, 0x00, 0x00, 0x00, // type = 1
+];
+
+#[rustfmt::skip]
+const MB1_EXTRA_PAYLOAD_BYTES: [u8; 32] = [
+    0x1c, 0x00, 0x00, 0x00, // size = 28
+    0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // base_addr = 0x1000
+    0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0x2000
+    0x01, 0x00, 0x00, 0x00, // type = 1
+    0xee, 0xee, 0xee, 0xee, 0xee, 0xee, 0xee, 0xee, // extra payload
+];
+
+#[rustfmt::skip]
+const MB2_PC_BYTES: [u8; 88] = [
+    0x06, 0x00, 0x00, 0x00, // type = 6 (mmap)
+    0x58, 0x00, 0x00, 0x00, // size = 88
+    0x18, 0x00, 0x00, 0x00, // entry_size = 24
+    0x00, 0x00, 0x00, 0x00, // entry_version = 0
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // base_addr = 0x0
+    0x00, 0xfc, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0x9fc00
+    0x01, 0x00, 0x00, 0x00, // type = 1
+    0x00, 0x00, 0x00, 0x00, // reserved
+    0x00, 0xfc, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // base_addr = 0x9fc00
+    0x00, 0x04, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0x60400
+    0x02, 0x00, 0x00, 0x00, // type = 2
+    0x00, 0x00, 0x00, 0x00, // reserved
+    0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, // base_addr = 0x100000
+    0x00, 0x00, 0xf0, 0x7f, 0x00, 0x00, 0x00, 0x00, // length = 0x7ff00000
+    0x01, 0x00, 0x00, 0x00, // type = 1
+    0x00, 0x00, 0x00, 0x00, // reserved
+];
+
+#[rustfmt::skip]
+const E820_PC_BYTES: [u8; 60] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // base = 0x0
+    0x00, 0xfc, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0x9fc00
+    0x01, 0x00, 0x00, 0x00, // type = 1
+    0x00, 0xfc, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // base = 0x9fc00
+    0x00, 0x04, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0x60400
+    0x02, 0x00, 0x00, 0x00, // type = 2
+    0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, // base = 0x100000
+    0x00, 0x00, 0xf0, 0x7f, 0x00, 0x00, 0x00, 0x00, // length = 0x7ff00000
+    0x01, 0x00, 0x00, 0x00, // type = 1
+];
+
+#[rustfmt::skip]
+const E820_EXT_ATTRS_BYTES: [u8; 72] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // base = 0x0
+    0x00, 0xfc, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0x9fc00
+    0x01, 0x00, 0x00, 0x00, // type = 1
+    0x01, 0x00, 0x00, 0x00, // ext_attrs = 1
+    0x00, 0xfc, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // base = 0x9fc00
+    0x00, 0x04, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, // length = 0x60400
+    0x02, 0x00, 0x00, 0x00, // type = 2
+    0x00, 0x00, 0x00, 0x00, // ext_attrs = 0
+    0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, // base = 0x100000
+    0x00, 0x00, 0xf0, 0x7f, 0x00, 0x00, 0x00, 0x00, // length = 0x7ff00000
+    0x01, 0x00, 0x00, 0x00, // type = 1
+    0x01, 0x00, 0x00, 0x00, // ext_attrs = 1
+];
+
+#[rustfmt::skip]
+const MEASURE_PC_BYTES: [u8; 72] = [
+    0x4d, 0x4d, 0x41, 0x50, // magic "MMAP"
+    0x01, 0x00, 0x00, 0x00, // version = 1
+    0x03, 0x00, 0x00, 0x00, // count = 3
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // start = 0x0
+    0x00, 0xf0, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // len = 0x9f000
+    0x01, 0x00, 0x00, 0x00, // kind = 1
+    0x00, 0xf0, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // start = 0x9f000
+    0x00, 0x10, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, // len = 0x61000
+    0x02, 0x00, 0x00, 0x00, // kind = 2
+    0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, // start = 0x100000
+    0x00, 0x00, 0xf0, 0x7f, 0x00, 0x00, 0x00, 0x00, // len = 0x7ff00000
+    0x01, 0x00, 0x00, 0x00, // kind = 1
+];
+
+#[rustfmt::skip]
+const UEFI_PC_BYTES: [u8; 144] = [
+    0x07, 0x00, 0x00, 0x00, // type = 7
+    0x00, 0x00, 0x00, 0x00, // padding
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // physical_start = 0x0
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // virtual_start = 0
+    0x9f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // number_of_pages = 0x9f
+    0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // attribute = 0x8
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // stride padding (descriptor_size = 48)
+    0x06, 0x00, 0x00, 0x00, // type = 6
+    0x00, 0x00, 0x00, 0x00, // padding
+    0x00, 0xf0, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, // physical_start = 0x9f000
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // virtual_start = 0
+    0x61, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // number_of_pages = 0x61
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, // attribute = 0x8000000000000000
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // stride padding (descriptor_size = 48)
+    0x04, 0x00, 0x00, 0x00, // type = 4
+    0x00, 0x00, 0x00, 0x00, // padding
+    0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, // physical_start = 0x100000
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // virtual_start = 0
+    0x00, 0xff, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00, // number_of_pages = 0x7ff00
+    0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // attribute = 0x8
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // stride padding (descriptor_size = 48)
+];
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::measure::write_canonical;
+    use crate::raw::e820::{push_e820_entry, E820Entry, E820Iter, ENTRY_SIZE, ENTRY_SIZE_EXT};
+    use crate::raw::mb2::{push_mmap_tag, Mb2Entry, Mb2MmapIter};
+    use crate::raw::uefi::{push_descriptor, UefiMmapIter};
+    use crate::raw::{push_entry, sanitize, Mb1MmapIter, RawEntry};
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn decoded(entries: impl Iterator<Item = RawEntry>) -> Vec<MemRegion> {
+        entries.map(|e| sanitize(e).unwrap()).collect()
+    }
+
+    #[test]
+    fn mb1_vectors_decode() {
+        init();
+        for v in MB1 {
+            let got = decoded(Mb1MmapIter::new(v.bytes).map(Result::unwrap));
+            pretty_assertions::assert_eq!(got, v.regions, "{}", v.name);
+        }
+    }
+
+    #[test]
+    fn mb1_vectors_encode() {
+        for v in MB1 {
+            let size = (v.bytes.len() / v.regions.len()) as u32 - 4;
+            let mut buf = Vec::new();
+            for r in v.regions {
+                push_entry(
+                    &mut buf,
+                    RawEntry {
+                        size,
+                        base_addr: r.start,
+                        length: r.len,
+                        typ: r.kind,
+                    },
+                );
+            }
+            pretty_assertions::assert_eq!(buf, v.bytes, "{}", v.name);
+        }
+    }
+
+    #[test]
+    fn mb2_vectors_decode() {
+        for v in MB2 {
+            let it = Mb2MmapIter::new(v.bytes).unwrap();
+            let got = decoded(it.map(|e| e.unwrap().into()));
+            pretty_assertions::assert_eq!(got, v.regions, "{}", v.name);
+        }
+    }
+
+    #[test]
+    fn e820_vectors_decode() {
+        for (v, entry_size) in E820.iter().zip([ENTRY_SIZE, ENTRY_SIZE_EXT]) {
+            let it = E820Iter::new(v.bytes, entry_size).unwrap().enabled();
+            let got = decoded(it.map(|e| e.unwrap().into()));
+            pretty_assertions::assert_eq!(got, v.regions, "{}", v.name);
+        }
+    }
+
+    #[test]
+    fn e820_vector_encodes() {
+        let mut buf = Vec::new();
+        for &r in E820_PC.regions {
+            push_e820_entry(&mut buf, E820Entry::from(r));
+        }
+        pretty_assertions::assert_eq!(buf, E820_PC.bytes);
+    }
+
+    #[test]
+    fn uefi_vectors_decode() {
+        for v in UEFI {
+            let it = UefiMmapIter::new(v.bytes, 48).unwrap();
+            let got = decoded(it.map(|d| d.unwrap().to_raw().unwrap()));
+            pretty_assertions::assert_eq!(got, v.regions, "{}", v.name);
+        }
+    }
+
+    #[test]
+    fn uefi_vector_encodes() {
+        let mut buf = Vec::new();
+        for d in UefiMmapIter::new(UEFI_PC.bytes, 48).unwrap() {
+            push_descriptor(&mut buf, d.unwrap(), 48);
+        }
+        pretty_assertions::assert_eq!(buf, UEFI_PC.bytes);
+    }
+
+    #[test]
+    fn mb2_vector_encodes() {
+        let entries: Vec<Mb2Entry> = Mb2MmapIter::new(MB2_PC.bytes)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let mut buf = Vec::new();
+        push_mmap_tag(&mut buf, &entries, 24);
+        pretty_assertions::assert_eq!(buf, MB2_PC.bytes);
+    }
+
+    #[test]
+    fn measure_vector_encodes() {
+        let mut buf = Vec::new();
+        write_canonical(MEASURE_PC.regions, |b| buf.extend_from_slice(b));
+        pretty_assertions::assert_eq!(buf, MEASURE_PC.bytes);
+    }
+}
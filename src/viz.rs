@@ -0,0 +1,287 @@
+// viz.rs
+//
+// Pictures of the memory map, for bug reports and docs.
+//
+// A table tells you the numbers; a bar tells you the shape. Two firmware
+// versions that "only moved a reserved region" look obviously different
+// side by side:
+//
+//   |############RRRRRRRRRRRR####################...................RRRRRRRRR|
+//    # usable  R reserved  . hole
+//
+// Real maps span 640 KiB next to 60 GiB, so a linear bar hides all the
+// small regions. Log scale gives each segment width by order of
+// magnitude instead, which is usually what you want for firmware maps.
+//
+// Needs std (the output is a String); expects a canonical map.
+
+use std::fmt::Write;
+use std::string::String;
+use std::vec::Vec;
+
+use crate::kind;
+use crate::raw::MemRegion;
+use crate::table::kind_name;
+
+/// How segment widths relate to region sizes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scale {
+    /// Width proportional to bytes. Small regions may vanish.
+    Linear,
+    /// Width proportional to log2(bytes). Every segment stays visible.
+    Log,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VizOptions {
+    pub scale: Scale,
+    /// Characters in the ASCII bar, pixels in the SVG.
+    pub width: usize,
+    /// Draw gaps between regions as holes.
+    pub show_holes: bool,
+}
+
+impl Default for VizOptions {
+    fn default() -> Self {
+        VizOptions {
+            scale: Scale::Log,
+            width: 72,
+            show_holes: true,
+        }
+    }
+}
+
+// One span of the bar: a region, or a hole (kind None).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Segment {
+    start: u64,
+    end: u64,
+    kind: Option<u32>,
+}
+
+fn segments(regions: &[MemRegion], show_holes: bool) -> Vec<Segment> {
+    let mut out = Vec::new();
+    let mut cursor: Option<u64> = None;
+    for r in regions.iter().filter(|r| r.len > 0) {
+        if let Some(prev) = cursor {
+            if show_holes && r.start > prev {
+                out.push(Segment {
+                    start: prev,
+                    end: r.start,
+                    kind: None,
+                });
+            }
+        }
+        out.push(Segment {
+            start: r.start,
+            end: r.end(),
+            kind: Some(r.kind),
+        });
+        cursor = Some(cursor.map_or(r.end(), |c| c.max(r.end())));
+    }
+    out
+}
+
+fn weight(seg: &Segment, scale: Scale) -> u128 {
+    let len = seg.end - seg.start;
+    match scale {
+        Scale::Linear => len as u128,
+        // Bit length: 4 KiB -> 13, 4 GiB -> 33.
+        Scale::Log => (64 - len.leading_zeros()) as u128,
+    }
+}
+
+// Cell boundaries for each segment: [x_i, x_{i+1}) out of `width`.
+fn layout(segs: &[Segment], scale: Scale, width: usize) -> Vec<usize> {
+    let total: u128 = segs.iter().map(|s| weight(s, scale)).sum();
+    let mut xs = Vec::with_capacity(segs.len() + 1);
+    let mut acc = 0u128;
+    xs.push(0);
+    for s in segs {
+        acc += weight(s, scale);
+        xs.push((acc * width as u128 / total.max(1)) as usize);
+    }
+    xs
+}
+
+fn glyph(kind: Option<u32>) -> char {
+    match kind {
+        None => '.',
+        Some(kind::USABLE) => '#',
+        Some(kind::RESERVED) => 'R',
+        Some(kind::ACPI_RECLAIMABLE) => 'A',
+        Some(kind::ACPI_NVS) => 'N',
+        Some(kind::BAD_RAM) => 'X',
+        Some(kind::PERSISTENT | kind::PERSISTENT_LEGACY) => 'P',
+        Some(kind::SOFT_RESERVED) => 'S',
+        Some(kind::HOT_PLUGGABLE) => 'H',
+        Some(_) => '?',
+    }
+}
+
+fn color(kind: Option<u32>) -> &'static str {
+    match kind {
+        None => "#ffffff",
+        Some(kind::USABLE) => "#4caf50",
+        Some(kind::RESERVED) => "#9e9e9e",
+        Some(kind::ACPI_RECLAIMABLE) => "#2196f3",
+        Some(kind::ACPI_NVS) => "#3f51b5",
+        Some(kind::BAD_RAM) => "#f44336",
+        Some(kind::PERSISTENT | kind::PERSISTENT_LEGACY) => "#9c27b0",
+        Some(kind::SOFT_RESERVED) => "#ff9800",
+        Some(kind::HOT_PLUGGABLE) => "#cddc39",
+        Some(_) => "#795548",
+    }
+}
+
+fn label(kind: Option<u32>) -> &'static str {
+    kind.map_or("hole", kind_name)
+}
+
+// Kinds in order of first appearance, for the legend.
+fn legend_kinds(segs: &[Segment]) -> Vec<Option<u32>> {
+    let mut seen = Vec::new();
+    for s in segs {
+        if !seen.contains(&s.kind) {
+            seen.push(s.kind);
+        }
+    }
+    seen
+}
+
+/// Two-line ASCII bar plus legend.
+pub fn ascii_bar(regions: &[MemRegion], opts: &VizOptions) -> String {
+    let segs = segments(regions, opts.show_holes);
+    let xs = layout(&segs, opts.scale, opts.width);
+
+    let mut out = String::from("|");
+    for (i, s) in segs.iter().enumerate() {
+        out.extend(core::iter::repeat_n(glyph(s.kind), xs[i + 1] - xs[i]));
+    }
+    out.push_str("|\n");
+
+    let mut legend = String::new();
+    for k in legend_kinds(&segs) {
+        let _ = write!(legend, " {} {} ", glyph(k), label(k));
+    }
+    out.push_str(legend.trim_end());
+    out.push('\n');
+    out
+}
+
+/// Standalone SVG: one rect per segment (hover shows the range and kind),
+/// followed by a legend row.
+pub fn svg(regions: &[MemRegion], opts: &VizOptions) -> String {
+    const BAR_HEIGHT: usize = 32;
+    const LEGEND_ROW: usize = 20;
+
+    let segs = segments(regions, opts.show_holes);
+    let xs = layout(&segs, opts.scale, opts.width);
+    let legend = legend_kinds(&segs);
+    let height = BAR_HEIGHT + LEGEND_ROW * legend.len() + 8;
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{height}" font-family="monospace" font-size="12">"#,
+        opts.width
+    );
+    for (i, s) in segs.iter().enumerate() {
+        let _ = writeln!(
+            out,
+            r#"  <rect x="{}" y="0" width="{}" height="{BAR_HEIGHT}" fill="{}" stroke="black" stroke-width="0.5"><title>{:#x}-{:#x} {}</title></rect>"#,
+            xs[i],
+            xs[i + 1] - xs[i],
+            color(s.kind),
+            s.start,
+            s.end,
+            label(s.kind),
+        );
+    }
+    for (row, k) in legend.iter().enumerate() {
+        let y = BAR_HEIGHT + 8 + row * LEGEND_ROW;
+        let _ = writeln!(
+            out,
+            r#"  <rect x="0" y="{y}" width="12" height="12" fill="{}" stroke="black" stroke-width="0.5"/><text x="18" y="{}">{}</text>"#,
+            color(*k),
+            y + 10,
+            label(*k),
+        );
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+// -------------------------
+// Tests
+// -------------------------
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::common::init;
+
+    use super::*;
+
+    fn region(start: u64, len: u64, kind: u32) -> MemRegion {
+        MemRegion { start, len, kind }
+    }
+
+    fn pc_map() -> [MemRegion; 4] {
+        [
+            region(0, 0x9_F000, 1),
+            region(0x9_F000, 0x6_1000, 2),
+            region(0x10_0000, 0x7FF0_0000, 1),
+            region(0xFEC0_0000, 0x1000, 2),
+        ]
+    }
+
+    #[test]
+    fn log_scale_ascii_keeps_small_regions_visible() {
+        init();
+        let out = ascii_bar(&pc_map(), &VizOptions::default());
+        insta::assert_snapshot!(out, @r"
+        |############RRRRRRRRRRRR####################...................RRRRRRRRR|
+         # usable  R reserved  . hole
+        ");
+    }
+
+    #[test]
+    fn linear_scale_is_proportional() {
+        let opts = VizOptions {
+            scale: Scale::Linear,
+            width: 8,
+            show_holes: true,
+        };
+        let map = [region(0, 0x3000, 1), region(0x3000, 0x1000, 3)];
+        pretty_assertions::assert_eq!(
+            ascii_bar(&map, &opts),
+            "|######AA|\n # usable  A ACPI reclaimable\n"
+        );
+    }
+
+    #[test]
+    fn holes_can_be_hidden() {
+        let opts = VizOptions {
+            scale: Scale::Linear,
+            width: 4,
+            show_holes: false,
+        };
+        let map = [region(0, 0x1000, 1), region(0x10000, 0x1000, 2)];
+        pretty_assertions::assert_eq!(ascii_bar(&map, &opts), "|##RR|\n # usable  R reserved\n");
+    }
+
+    #[test]
+    fn svg_has_one_rect_per_segment_and_legend() {
+        let out = svg(&pc_map(), &VizOptions::default());
+        assert!(out.starts_with("<svg "));
+        assert!(out.ends_with("</svg>\n"));
+        // 5 segments (4 regions + 1 hole) and 3 legend swatches.
+        pretty_assertions::assert_eq!(out.matches("<rect").count(), 8);
+        assert!(out.contains("<title>0xfec00000-0xfec01000 reserved</title>"));
+    }
+
+    #[test]
+    fn empty_map_renders_empty_bar() {
+        pretty_assertions::assert_eq!(ascii_bar(&[], &VizOptions::default()), "||\n\n");
+    }
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "std")]
+
+// guest_layout.rs
+//
+// GuestLayout maps shaped like the ones QEMU's pc and q35 machines
+// report for each `-m`, written out in every format a kernel can
+// receive (MB1, fw_cfg E820, MB2) and parsed back.
+//
+// This checks that the writers and parsers agree with each other and
+// keep the RAM totals, not that the layout matches QEMU: nothing here
+// boots QEMU. That needs a freestanding example kernel to boot, and the
+// gates have neither.
+
+mod common;
+
+use mb1_memmap::guest::{GuestLayout, GuestLayoutConfig, EBDA_START, FOUR_GIB, HIGH_MEMORY_START};
+use mb1_memmap::raw::e820::E820Iter;
+use mb1_memmap::raw::mb2::Mb2MmapIter;
+use mb1_memmap::raw::{sanitize, Mb1MmapIter, MemRegion, RawEntry};
+
+use common::init;
+
+const MIB: u64 = 1 << 20;
+const GIB: u64 = 1 << 30;
+
+/// `-m` values: tiny, common, both machines' split points, and huge.
+const SIZES: [u64; 10] = [
+    128 * MIB,
+    512 * MIB,
+    2 * GIB,
+    0xB000_0000,
+    3 * GIB,
+    0xE000_0000,
+    4 * GIB,
+    8 * GIB,
+    64 * GIB,
+    1024 * GIB,
+];
+
+#[derive(Clone, Copy, Debug)]
+enum Machine {
+    /// i440FX: all RAM low up to 3.5 GiB, else 3 GiB low.
+    Pc,
+    /// Q35: all RAM low up to 2.75 GiB, else 2 GiB low.
+    Q35,
+}
+
+impl Machine {
+    /// RAM QEMU leaves below 4 GiB for `-m ram`, and where the PCI
+    /// window starts.
+    fn split(self, ram: u64) -> (u64, u64) {
+        let (threshold, split) = match self {
+            Machine::Pc => (0xE000_0000, 0xC000_0000),
+            Machine::Q35 => (0xB000_0000, 0x8000_0000),
+        };
+        if ram >= threshold {
+            (split, split)
+        } else {
+            (ram, threshold)
+        }
+    }
+
+    fn layout(self, ram: u64) -> GuestLayout {
+        let (_, hole) = self.split(ram);
+        GuestLayout::generate(&GuestLayoutConfig {
+            ram_size: ram,
+            pci_hole_base: hole,
+            pci_hole_size: FOUR_GIB - hole,
+            below_4g_split: hole,
+        })
+        .unwrap()
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Boot {
+    Mb1,
+    E820,
+    Mb2,
+}
+
+impl Boot {
+    /// What the bootloader hands over for `layout`.
+    fn encode(self, layout: &GuestLayout) -> Vec<u8> {
+        let mut buf = Vec::new();
+        match self {
+            Boot::Mb1 => layout.push_mb1(&mut buf),
+            Boot::E820 => layout.push_e820(&mut buf),
+            Boot::Mb2 => layout.push_mb2_tag(&mut buf),
+        }
+        buf
+    }
+
+    /// What the kernel reads back out of it.
+    fn parse(self, buf: &[u8]) -> Vec<MemRegion> {
+        let entries: Vec<RawEntry> = match self {
+            Boot::Mb1 => Mb1MmapIter::new(buf).map(Result::unwrap).collect(),
+            Boot::E820 => E820Iter::fw_cfg(buf)
+                .enabled()
+                .map(|e| e.unwrap().into())
+                .collect(),
+            Boot::Mb2 => Mb2MmapIter::new(buf)
+                .unwrap()
+                .map(|e| e.unwrap().into())
+                .collect(),
+        };
+        entries.into_iter().filter_map(sanitize).collect()
+    }
+}
+
+#[test]
+fn every_format_round_trips_the_configured_ram() {
+    init();
+    for machine in [Machine::Pc, Machine::Q35] {
+        for ram in SIZES {
+            let layout = machine.layout(ram);
+            let (low, _) = machine.split(ram);
+            for boot in [Boot::Mb1, Boot::E820, Boot::Mb2] {
+                let what = format!("{machine:?} -m {}M via {boot:?}", ram / MIB);
+                let regions = boot.parse(&boot.encode(&layout));
+                pretty_assertions::assert_eq!(regions.as_slice(), layout.regions(), "{}", what);
+
+                let usable = regions.iter().filter(|r| r.region_kind().is_usable());
+                // Only the EBDA and BIOS area below 1 MiB is not handed out.
+                pretty_assertions::assert_eq!(
+                    usable.clone().map(|r| r.len).sum::<u64>(),
+                    ram - (HIGH_MEMORY_START - EBDA_START),
+                    "{}",
+                    what
+                );
+                pretty_assertions::assert_eq!(
+                    usable
+                        .clone()
+                        .filter(|r| r.start >= FOUR_GIB)
+                        .map(|r| r.len)
+                        .sum::<u64>(),
+                    ram - low,
+                    "{}",
+                    what
+                );
+                pretty_assertions::assert_eq!(
+                    usable.map(|r| r.end()).max(),
+                    Some(if low < ram { FOUR_GIB + ram - low } else { ram }),
+                    "{}",
+                    what
+                );
+            }
+        }
+    }
+}